@@ -0,0 +1,191 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! CPU time of the elliptic curve work of a signing round.
+//!
+//! A round consists of the work of the threshold signers, which verify the VSSs of the block key
+//! from all the participants and compute the local signatures, and the work of the master, which
+//! verifies the local signatures and the aggregated signature. The round runs the functions of
+//! the signer, which use `DefaultBackend`. The verification of a share, which is the most of the
+//! work, is also measured for each backend.
+//!
+//! Run with `cargo bench --bench signing_round`.
+
+#[macro_use]
+extern crate criterion;
+
+use bitcoin::PublicKey;
+use criterion::{BenchmarkId, Criterion};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::ECScalar;
+use curv::FE;
+use std::collections::BTreeMap;
+use tapyrus_signer::blockdata::hash::SHA256Hash;
+use tapyrus_signer::crypto::backend::{self, Backend, CurvBackend, Secp256k1Backend};
+use tapyrus_signer::crypto::multi_party_schnorr::SharedKeys;
+use tapyrus_signer::crypto::vss::Vss;
+use tapyrus_signer::key::generate_key_pair;
+use tapyrus_signer::net::SignerID;
+use tapyrus_signer::sign::Sign;
+use tapyrus_signer::signer_node::{BidirectionalSharedSecretMap, SharedSecret, SharedSecretMap};
+
+const SIGNER_COUNTS: [usize; 3] = [5, 15, 30];
+const MESSAGE: [u8; 32] = [1u8; 32];
+
+/// The VSSs of the node keys and the block keys as the signers receive them. The first
+/// `threshold` signers are the participants, and the first participant is the master.
+struct Round {
+    signer_ids: Vec<SignerID>,
+    pubkey_list: Vec<PublicKey>,
+    /// The VSSs of the node keys which each signer received.
+    node_shared_secrets: Vec<SharedSecretMap>,
+    node_secret_shares: Vec<SharedKeys>,
+    /// The VSSs of the block keys which each participant received.
+    shared_block_secrets: Vec<BidirectionalSharedSecretMap>,
+}
+
+impl Round {
+    fn new(share_count: usize) -> Self {
+        let threshold = share_count / 2 + 1;
+        let signer_ids: Vec<SignerID> = (0..share_count)
+            .map(|_| SignerID::new(generate_key_pair().1))
+            .collect();
+        let pubkey_list = signer_ids
+            .iter()
+            .map(|signer_id| signer_id.pubkey)
+            .collect();
+
+        let node_vss: Vec<(VerifiableSS, Vec<FE>)> = (0..share_count)
+            .map(|_| Vss::create_shares(&ECScalar::new_random(), threshold, share_count))
+            .collect();
+        let node_shared_secrets: Vec<SharedSecretMap> = (0..share_count)
+            .map(|i| {
+                signer_ids
+                    .iter()
+                    .zip(&node_vss)
+                    .map(|(signer_id, (vss, shares))| {
+                        (
+                            *signer_id,
+                            SharedSecret {
+                                vss: vss.clone(),
+                                secret_share: shares[i].into(),
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        let node_secret_shares = node_shared_secrets
+            .iter()
+            .enumerate()
+            .map(|(i, secrets)| Sign::verify_vss_and_construct_key(secrets, &(i + 1)).unwrap())
+            .collect();
+
+        let block_vss: Vec<_> = (0..threshold)
+            .map(|i| Vss::create_block_shares(i + 1, threshold, share_count))
+            .collect();
+        let shared_block_secrets = (0..threshold)
+            .map(|i| {
+                signer_ids
+                    .iter()
+                    .zip(&block_vss)
+                    .map(|(signer_id, (_, vss_p, shares_p, vss_n, shares_n))| {
+                        (
+                            *signer_id,
+                            (
+                                SharedSecret {
+                                    vss: vss_p.clone(),
+                                    secret_share: shares_p[i].into(),
+                                },
+                                SharedSecret {
+                                    vss: vss_n.clone(),
+                                    secret_share: shares_n[i].into(),
+                                },
+                            ),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Round {
+            signer_ids,
+            pubkey_list,
+            node_shared_secrets,
+            node_secret_shares,
+            shared_block_secrets,
+        }
+    }
+
+    /// Run the round, and returns true if the signature is valid.
+    fn run(&self) -> bool {
+        let message = SHA256Hash::from_slice(&MESSAGE).unwrap();
+
+        // Each participant verifies the VSSs of the block keys and computes the local signature.
+        let mut signatures = BTreeMap::new();
+        let mut block_shared_keys = None;
+        for (i, shared_block_secrets) in self.shared_block_secrets.iter().enumerate() {
+            let (is_positive, shared_keys, local_sig) = Vss::create_local_sig_from_shares(
+                &self.node_secret_shares[i],
+                i + 1,
+                shared_block_secrets,
+                message,
+            )
+            .unwrap();
+            if i == 0 {
+                block_shared_keys = Some((is_positive, shared_keys.x_i, shared_keys.y));
+            }
+            signatures.insert(self.signer_ids[i], (local_sig.gamma_i, local_sig.e));
+        }
+
+        // The master verifies the local signatures and the aggregated signature.
+        Vss::aggregate_and_verify_signature(
+            message,
+            signatures,
+            &self.pubkey_list,
+            &self.node_shared_secrets[0],
+            &block_shared_keys,
+            &self.shared_block_secrets[0],
+            &self.node_secret_shares[0],
+        )
+        .is_ok()
+    }
+}
+
+fn bench_round(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signing_round");
+    group.sample_size(10);
+    for n in SIGNER_COUNTS.iter() {
+        let round = Round::new(*n);
+        assert!(round.run());
+        group.bench_with_input(BenchmarkId::from_parameter(n), &round, |b, round| {
+            b.iter(|| round.run())
+        });
+    }
+    group.finish();
+}
+
+fn bench_validate_share<B: Backend>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(format!("validate_share/{}", name));
+    for n in SIGNER_COUNTS.iter() {
+        let threshold = n / 2 + 1;
+        let (vss, shares) = Vss::create_shares(&ECScalar::new_random(), threshold, *n);
+        let commitments: Vec<B::Point> = vss.commitments.iter().map(B::point).collect();
+        let share = B::scalar(&shares[0]);
+        assert!(backend::validate_share::<B>(&commitments, &share, 1));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &share, |b, share| {
+            b.iter(|| backend::validate_share::<B>(&commitments, share, 1))
+        });
+    }
+    group.finish();
+}
+
+fn signing_round(c: &mut Criterion) {
+    bench_round(c);
+    bench_validate_share::<CurvBackend>(c, "curv");
+    bench_validate_share::<Secp256k1Backend>(c, "secp256k1");
+}
+
+criterion_group!(benches, signing_round);
+criterion_main!(benches);
@@ -2,12 +2,22 @@ extern crate tapyrus_signer;
 
 use clap::App;
 use tapyrus_signer::cli::setup::aggregate::AggregateCommand;
+use tapyrus_signer::cli::setup::audit_verify::AuditVerifyCommand;
 use tapyrus_signer::cli::setup::compute_sig::ComputeSigCommand;
 use tapyrus_signer::cli::setup::create_block_vss::CreateBlockVssCommand;
+use tapyrus_signer::cli::setup::create_genesis::CreateGenesisCommand;
 use tapyrus_signer::cli::setup::create_key::CreateKeyCommand;
 use tapyrus_signer::cli::setup::create_node_vss::CreateNodeVssCommand;
+use tapyrus_signer::cli::setup::decrypt::DecryptCommand;
+use tapyrus_signer::cli::setup::dkg::DkgCommand;
+use tapyrus_signer::cli::setup::encrypt::EncryptCommand;
+use tapyrus_signer::cli::setup::refresh::RefreshCommand;
+use tapyrus_signer::cli::setup::repair::RepairCommand;
+use tapyrus_signer::cli::setup::reshare::ReshareCommand;
 use tapyrus_signer::cli::setup::sign::SignCommand;
 use tapyrus_signer::cli::setup::traits::Response;
+use tapyrus_signer::cli::setup::verify_block::VerifyBlockCommand;
+use tapyrus_signer::cli::setup::verify_evidence::VerifyEvidenceCommand;
 use tapyrus_signer::errors::Error;
 
 fn main() {
@@ -15,9 +25,19 @@ fn main() {
         .subcommand(CreateKeyCommand::args())
         .subcommand(CreateNodeVssCommand::args())
         .subcommand(AggregateCommand::args())
+        .subcommand(CreateGenesisCommand::args())
         .subcommand(CreateBlockVssCommand::args())
         .subcommand(SignCommand::args())
         .subcommand(ComputeSigCommand::args())
+        .subcommand(DkgCommand::args())
+        .subcommand(RefreshCommand::args())
+        .subcommand(RepairCommand::args())
+        .subcommand(ReshareCommand::args())
+        .subcommand(EncryptCommand::args())
+        .subcommand(DecryptCommand::args())
+        .subcommand(AuditVerifyCommand::args())
+        .subcommand(VerifyEvidenceCommand::args())
+        .subcommand(VerifyBlockCommand::args())
         .get_matches();
     let result: Result<Box<dyn Response>, Error> = match matches.subcommand_name() {
         Some("createkey") => CreateKeyCommand::execute(
@@ -35,6 +55,11 @@ fn main() {
                 .subcommand_matches("aggregate")
                 .expect("invalid args"),
         ),
+        Some("creategenesis") => CreateGenesisCommand::execute(
+            matches
+                .subcommand_matches("creategenesis")
+                .expect("invalid args"),
+        ),
         Some("createblockvss") => CreateBlockVssCommand::execute(
             matches
                 .subcommand_matches("createblockvss")
@@ -48,6 +73,39 @@ fn main() {
                 .subcommand_matches("computesig")
                 .expect("invalid args"),
         ),
+        Some("dkg") => {
+            DkgCommand::execute(matches.subcommand_matches("dkg").expect("invalid args"))
+        }
+        Some("refresh") => {
+            RefreshCommand::execute(matches.subcommand_matches("refresh").expect("invalid args"))
+        }
+        Some("repair") => {
+            RepairCommand::execute(matches.subcommand_matches("repair").expect("invalid args"))
+        }
+        Some("reshare") => {
+            ReshareCommand::execute(matches.subcommand_matches("reshare").expect("invalid args"))
+        }
+        Some("encrypt") => {
+            EncryptCommand::execute(matches.subcommand_matches("encrypt").expect("invalid args"))
+        }
+        Some("decrypt") => {
+            DecryptCommand::execute(matches.subcommand_matches("decrypt").expect("invalid args"))
+        }
+        Some("auditverify") => AuditVerifyCommand::execute(
+            matches
+                .subcommand_matches("auditverify")
+                .expect("invalid args"),
+        ),
+        Some("verifyevidence") => VerifyEvidenceCommand::execute(
+            matches
+                .subcommand_matches("verifyevidence")
+                .expect("invalid args"),
+        ),
+        Some("verifyblock") => VerifyBlockCommand::execute(
+            matches
+                .subcommand_matches("verifyblock")
+                .expect("invalid args"),
+        ),
         None => return println!("No subcommand was used"),
         _ => unreachable!(),
     };
@@ -0,0 +1,71 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate bitcoin;
+extern crate clap;
+extern crate env_logger;
+extern crate log;
+extern crate tapyrus_signer;
+
+use bitcoin::PublicKey;
+use clap::{App, Arg};
+use std::path::Path;
+use std::str::FromStr;
+use tapyrus_signer::cli::setup::{passphrase_env_arg, passphrase_fd_arg, passphrase_of};
+use tapyrus_signer::crypto::share_signer::ShareSignerServer;
+use tapyrus_signer::federation::Federations;
+
+/// This command is for launch the share signer which keeps the node secret shares and creates the
+/// signature shares of FROST signing for tapyrus-signerd started with `--share-signer-socket`.
+fn main() {
+    let matches = App::new("tapyrus-share-signer")
+        .args(&[
+            Arg::with_name("public-key")
+                .long("public-key")
+                .required(true)
+                .takes_value(true)
+                .help("public key of the signer who host tapyrus-signerd"),
+            Arg::with_name("federations-file")
+                .long("federations-file")
+                .required(true)
+                .takes_value(true)
+                .help("path to TOML file of the federations of the chain"),
+            Arg::with_name("socket")
+                .long("socket")
+                .required(true)
+                .takes_value(true)
+                .help("path to the Unix domain socket to listen on"),
+            Arg::with_name("log")
+                .long("log")
+                .takes_value(true)
+                .possible_values(&["error", "warn", "info", "debug", "trace"])
+                .default_value("info")
+                .help("log level"),
+            passphrase_fd_arg(),
+            passphrase_env_arg(),
+        ])
+        .get_matches();
+
+    let log_level = matches.value_of("log").unwrap();
+    std::env::set_var("RUST_LOG", format!("tapyrus_signer={}", log_level));
+    env_logger::init();
+
+    let public_key = PublicKey::from_str(matches.value_of("public-key").unwrap())
+        .expect("public-key is invalid.");
+    let path = matches.value_of("federations-file").unwrap();
+    let federations_toml = std::fs::read_to_string(path).expect(&format!(
+        "Can't open federations_file. path: {:?} Error",
+        path
+    ));
+    let passphrase = passphrase_of(&matches).unwrap_or_else(|e| panic!("{}", e));
+    let federations =
+        Federations::from_pubkey_and_toml(&public_key, &federations_toml, &passphrase)
+            .unwrap_or_else(|e| panic!("federations_file: {}", e));
+
+    let socket = Path::new(matches.value_of("socket").unwrap());
+    let server = ShareSignerServer::bind(socket, federations)
+        .unwrap_or_else(|e| panic!("Can't listen on {:?}. {}", socket, e));
+    log::info!("Listening on {:?}", socket);
+    server.run();
+}
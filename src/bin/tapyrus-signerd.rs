@@ -10,17 +10,26 @@ extern crate log;
 extern crate redis;
 extern crate tapyrus_signer;
 
-use bitcoin::PublicKey;
+use bitcoin::{PrivateKey, PublicKey};
 
 use daemonize::Daemonize;
 use std::fs::OpenOptions;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::time::Duration;
-use tapyrus_signer::command_args::{CommandArgs, RedisConfig, RpcConfig};
-use tapyrus_signer::federation::Federations;
+use tapyrus_signer::command_args::{
+    AuditConfig, CommandArgs, PeerScoringConfig, RedisConfig, RpcConfig, RpcEndpoint,
+};
+use tapyrus_signer::crypto::secret::set_reveal_secrets;
+use tapyrus_signer::crypto::share_signer::UnixSocketShareSigner;
+use tapyrus_signer::federation::{Federations, SigningProtocol};
+use tapyrus_signer::keystore::Passphrase;
 use tapyrus_signer::net::{ConnectionManager, RedisManager};
 use tapyrus_signer::rpc::Rpc;
+use tapyrus_signer::rpc_pool::RpcPool;
+use tapyrus_signer::signer_node::admin::AdminServer;
+use tapyrus_signer::signer_node::audit_log::AuditLog;
+use tapyrus_signer::signer_node::peer_scores::PeerScores;
 use tapyrus_signer::signer_node::{NodeParameters, SignerNode};
 use tapyrus_signer::util::{set_stop_signal_handler, signal_to_string};
 
@@ -31,6 +40,19 @@ fn main() {
     let configs = CommandArgs::new().unwrap();
 
     let general_config = configs.general_config();
+    let signer_config = configs.signer_config();
+
+    // The federations are loaded before the process is daemonized, because the passphrase of the
+    // encrypted federations file may be asked on the terminal, which the daemon doesn't have.
+    // With the share signer, the federations file is never read by this process.
+    let federations = match signer_config.share_signer_socket() {
+        Some(socket) => fetch_federations(&signer_config.public_key(), socket),
+        None => load_federations(
+            &signer_config.public_key(),
+            signer_config.federations_file(),
+            &signer_config.passphrase(),
+        ),
+    };
 
     if general_config.daemon() {
         daemonize(general_config.pid(), general_config.log_file());
@@ -46,15 +68,16 @@ fn main() {
         env_logger::init();
     }
 
-    let signer_config = configs.signer_config();
-
-    let con = connect_signer_network(configs.redis_config());
-    let rpc = connect_rpc(configs.rpc_config());
+    if general_config.reveal_secrets() {
+        log::warn!("Secret values are printed in the logs.");
+        set_reveal_secrets(true);
+    }
 
-    let federations = load_federations(
-        &signer_config.public_key(),
-        signer_config.federations_file(),
+    let con = connect_signer_network(
+        configs.redis_config(),
+        load_message_key(signer_config.private_key(), &signer_config.public_key()),
     );
+    let rpc = connect_rpc(configs.rpc_config());
 
     let params = NodeParameters::new(
         signer_config.to_address(),
@@ -63,10 +86,32 @@ fn main() {
         round_duration,
         general_config.skip_waiting_ibd(),
         federations,
-    );
+    )
+    .with_share_signer_socket(signer_config.share_signer_socket().map(Path::to_path_buf))
+    .with_block_policy(configs.policy_config().block_policy())
+    .with_approval_hook(configs.approval_config().approval_hook())
+    .with_confirmation_timeout(configs.rpc_config().confirmation_timeout())
+    .with_audit_log(load_audit_log(
+        configs.audit_config(),
+        &signer_config.public_key(),
+    ))
+    .with_evidence_dir(signer_config.evidence_dir().map(Path::to_path_buf))
+    .with_peer_scores(load_peer_scores(
+        configs.peer_scoring_config(),
+        signer_config.private_key().is_some(),
+    ));
 
     let node = &mut SignerNode::new(con, params);
-    node.start();
+    if let Some(socket) = configs.admin_config().socket() {
+        let server = AdminServer::bind(socket)
+            .unwrap_or_else(|e| panic!("Can't listen on {:?}. {:?}", socket, e));
+        log::info!("Admin interface is listening on {:?}", socket);
+        node.admin_handler(server.start());
+    }
+    if let Err(e) = node.start() {
+        log::error!("Signer Node stops. {:?}", e);
+        std::process::exit(1);
+    }
 }
 
 fn daemonize(pid: &str, log_file: &str) {
@@ -91,21 +136,57 @@ fn daemonize(pid: &str, log_file: &str) {
     }
 }
 
-fn connect_rpc(rpc_config: RpcConfig) -> Rpc {
-    let url = format!("http://{}:{}", rpc_config.host(), rpc_config.port());
-    let user = rpc_config.user_name().map(str::to_string);
-    let pass = rpc_config.password().map(str::to_string);
-    let rpc = tapyrus_signer::rpc::Rpc::new(url.clone(), user.clone(), pass);
-    rpc.test_connection().expect(&format!(
-        "RPC connect failed. Please confirm RPC connection info. url: {}, user: '{}' ,",
-        url,
-        user.unwrap_or("".to_string())
-    ));
+/// Connects to the Tapyrus Core nodes. The backup endpoints are in the same pool, so that the
+/// signed block is submitted to them too and the calls fail over to them. Panics if no node is
+/// reachable.
+fn connect_rpc(rpc_config: RpcConfig) -> RpcPool<Rpc> {
+    let endpoints: Vec<RpcEndpoint> = rpc_config
+        .endpoints()
+        .into_iter()
+        .chain(rpc_config.backup_endpoints().iter().cloned())
+        .collect();
+    let rpc = RpcPool::new(
+        endpoints
+            .iter()
+            .map(|endpoint| new_rpc(endpoint, &rpc_config))
+            .collect(),
+    );
+    if rpc.check_health() == 0 {
+        let urls: Vec<String> = endpoints.iter().map(RpcEndpoint::url).collect();
+        panic!(
+            "RPC connect failed. Please confirm RPC connection info. url: {}, user: '{}' ,",
+            urls.join(", "),
+            rpc_config.user_name().unwrap_or("")
+        );
+    }
+    rpc
+}
+
+fn new_rpc(endpoint: &RpcEndpoint, rpc_config: &RpcConfig) -> Rpc {
+    let mut rpc = Rpc::new(endpoint.url(), endpoint.user.clone(), endpoint.pass.clone())
+        .with_timeouts(rpc_config.connect_timeout(), rpc_config.read_timeout())
+        .with_retry_policy(rpc_config.retry_policy());
+    if let Some(cookie_file) = &endpoint.cookie_file {
+        rpc = rpc.with_cookie_file(PathBuf::from(cookie_file));
+    }
+    if let Some(ca_file) = rpc_config.tls_ca_file() {
+        rpc = rpc.with_ca_file(ca_file).unwrap_or_else(|e| {
+            panic!(
+                "'{}' is invalid CA file. error msg: {:?}",
+                ca_file.display(),
+                e
+            )
+        });
+    }
     rpc
 }
 
-fn connect_signer_network(rc: RedisConfig) -> impl ConnectionManager {
-    let redis_manager = RedisManager::new(rc.host().to_string(), rc.port().to_string());
+fn connect_signer_network(
+    rc: RedisConfig,
+    private_key: Option<PrivateKey>,
+) -> impl ConnectionManager {
+    let redis_manager = RedisManager::new(rc.host().to_string(), rc.port().to_string())
+        .with_private_key(private_key);
     redis_manager
         .test_connection()
         .expect("Failed to connect redis. Please confirm redis connection info");
@@ -135,12 +216,45 @@ fn start_unix_signal_handling() {
     });
 }
 
-fn load_federations(pubkey: &PublicKey, path: &Path) -> Federations {
+/// The faults are scored only on the signed messages, so the signer who scores the others must
+/// sign its own messages too.
+fn load_peer_scores(peer_scoring_config: PeerScoringConfig, signs_messages: bool) -> PeerScores {
+    if !peer_scoring_config.is_enabled() {
+        return PeerScores::disabled();
+    }
+    if !signs_messages {
+        panic!(
+            "[peer-scoring] requires private-key-env, because only the signed messages are scored."
+        );
+    }
+    PeerScores::new(peer_scoring_config.scoring_config())
+}
+
+fn load_message_key(private_key: Option<PrivateKey>, pubkey: &PublicKey) -> Option<PrivateKey> {
+    let private_key = private_key?;
+    let secp = secp256k1::Secp256k1::signing_only();
+    if PublicKey::from_private_key(&secp, &private_key) != *pubkey {
+        panic!(
+            "The private key to sign the messages doesn't match with the public key of the signer."
+        );
+    }
+    Some(private_key)
+}
+
+fn load_audit_log(audit_config: AuditConfig, pubkey: &PublicKey) -> Option<AuditLog> {
+    let audit_log = audit_config.audit_log()?;
+    if audit_log.public_key() != *pubkey {
+        panic!("The private key of the audit log doesn't match with the public key of the signer.");
+    }
+    Some(audit_log)
+}
+
+fn load_federations(pubkey: &PublicKey, path: &Path, passphrase: &Passphrase) -> Federations {
     let federations_toml = std::fs::read_to_string(path).expect(&format!(
         "Can't open federations_file. path: {:?} Error",
         path
     ));
-    match Federations::from_pubkey_and_toml(pubkey, &federations_toml) {
+    match Federations::from_pubkey_and_toml(pubkey, &federations_toml, passphrase) {
         Ok(r) => r,
         Err(tapyrus_signer::errors::Error::InvalidTomlFormat(e)) => {
             panic!("federations_file: Invalid TOML format. {}", e);
@@ -154,12 +268,36 @@ fn load_federations(pubkey: &PublicKey, path: &Path) -> Federations {
         Err(tapyrus_signer::errors::Error::InvalidFederation(None, m)) => {
             panic!("federations_file: Invalid. message: {}", m);
         }
+        Err(tapyrus_signer::errors::Error::InvalidKeystore(m)) => {
+            panic!("federations_file: Can't decrypt. message: {}", m);
+        }
         Err(e) => {
             panic!("federations_file: {}", e);
         }
     }
 }
 
+/// Returns the federations without the node secret shares from the share signer. Panics if the
+/// signer is a member of the federation which doesn't use the frost signing protocol, because
+/// the share signer supports only it.
+fn fetch_federations(pubkey: &PublicKey, socket: &Path) -> Federations {
+    let federations = UnixSocketShareSigner::new(socket.to_path_buf())
+        .federations(pubkey)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Can't get the federations from the share signer. socket: {:?} {:?}",
+                socket, e
+            )
+        });
+    if federations
+        .iter()
+        .any(|f| f.threshold().is_some() && f.signing_protocol() != SigningProtocol::Frost)
+    {
+        panic!("The share signer supports only the frost signing protocol.");
+    }
+    federations
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{connect_rpc, connect_signer_network, load_federations};
@@ -167,6 +305,7 @@ mod tests {
     use std::path::Path;
     use std::str::FromStr;
     use tapyrus_signer::command_args::{RedisConfig, RpcConfig};
+    use tapyrus_signer::keystore::Passphrase;
 
     #[test]
     #[should_panic(
@@ -180,6 +319,7 @@ mod tests {
                 port: Some("9999"),
                 username: None,
                 password: None,
+                cookie_file: None,
             },
             toml_config: None,
         };
@@ -200,7 +340,7 @@ mod tests {
             toml_config: None,
         };
 
-        connect_signer_network(config);
+        connect_signer_network(config, None);
     }
 
     #[test]
@@ -211,7 +351,7 @@ mod tests {
         .unwrap();
 
         let path = Path::new("tests/resources/federations.toml");
-        let federations = load_federations(&pubkey, path);
+        let federations = load_federations(&pubkey, path, &Passphrase::Prompt);
 
         assert_eq!(federations.len(), 2);
     }
@@ -225,7 +365,7 @@ mod tests {
         .unwrap();
 
         let path = Path::new("/foo/bar/no_exist_file.toml");
-        load_federations(&pubkey, path);
+        load_federations(&pubkey, path, &Passphrase::Prompt);
     }
 
     #[test]
@@ -239,7 +379,7 @@ mod tests {
         .unwrap();
 
         let path = Path::new("tests/resources/federations_has_invalid_federation.toml");
-        load_federations(&pubkey, path);
+        load_federations(&pubkey, path, &Passphrase::Prompt);
     }
 
     #[test]
@@ -253,6 +393,21 @@ mod tests {
         .unwrap();
 
         let path = Path::new("tests/resources/federations_invalid_toml_format.toml");
-        load_federations(&pubkey, path);
+        load_federations(&pubkey, path, &Passphrase::Prompt);
+    }
+
+    #[test]
+    fn test_load_encrypted_federations() {
+        let pubkey = PublicKey::from_str(
+            "02472012cf49fca573ca1f63deafe59df842f0bbe77e9ac7e67b211bb074b72506",
+        )
+        .unwrap();
+
+        std::env::set_var("TAPYRUS_SIGNERD_TEST_PASSPHRASE", "passphrase");
+        let path = Path::new("tests/resources/federations_encrypted.toml");
+        let passphrase = Passphrase::Env("TAPYRUS_SIGNERD_TEST_PASSPHRASE".to_string());
+        let federations = load_federations(&pubkey, path, &passphrase);
+
+        assert_eq!(federations.len(), 2);
     }
 }
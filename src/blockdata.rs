@@ -4,13 +4,15 @@
 
 use crate::errors::Error;
 use crate::serialize::HexStrVisitor;
-use bitcoin::consensus::encode::serialize;
-use bitcoin::consensus::encode::Decodable;
+use bitcoin::consensus::encode::{self, serialize, Decodable, Encodable};
 use bitcoin::PublicKey;
+use bitcoin::Transaction;
 use bitcoin::VarInt;
 use bitcoin_hashes::{sha256d, Hash};
+use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::Debug;
+use std::io;
 
 pub mod hash {
     use crate::errors::Error;
@@ -20,7 +22,7 @@ pub mod hash {
 
     /// This is hash value container struct.
     /// This struct assumes porting value from sha256d::Hash.
-    #[derive(PartialEq, Clone, Copy)]
+    #[derive(PartialEq, Eq, Hash, Clone, Copy)]
     pub struct SHA256Hash([u8; 32]);
 
     impl SHA256Hash {
@@ -79,14 +81,144 @@ pub mod hash {
     }
 }
 
+/// The extra field of the block header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XField {
+    None,
+    AggregatePublicKey(PublicKey),
+    /// The max block size in bytes. It is serialized as 4 bytes without the length.
+    MaxBlockSize(u32),
+    /// The xfield type which the signer doesn't know. This assumes that the value of the new
+    /// xfield type added in the future leads with its length.
+    Unknown(u8, Vec<u8>),
+}
+
+impl XField {
+    pub fn field_type(&self) -> u8 {
+        match self {
+            XField::None => 0,
+            XField::AggregatePublicKey(_) => 1,
+            XField::MaxBlockSize(_) => 2,
+            XField::Unknown(field_type, _) => *field_type,
+        }
+    }
+}
+
+impl Encodable for XField {
+    fn consensus_encode<W: io::Write>(&self, mut w: W) -> Result<usize, encode::Error> {
+        let len = self.field_type().consensus_encode(&mut w)?;
+        let value_len = match self {
+            XField::None => 0,
+            XField::AggregatePublicKey(public_key) => {
+                public_key.to_bytes().consensus_encode(&mut w)?
+            }
+            XField::MaxBlockSize(max_block_size) => max_block_size.consensus_encode(&mut w)?,
+            XField::Unknown(_, value) => value.consensus_encode(&mut w)?,
+        };
+        Ok(len + value_len)
+    }
+}
+
+impl Decodable for XField {
+    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
+        match u8::consensus_decode(&mut d)? {
+            0 => Ok(XField::None),
+            1 => {
+                let bytes: Vec<u8> = Decodable::consensus_decode(&mut d)?;
+                PublicKey::from_slice(&bytes)
+                    .map(XField::AggregatePublicKey)
+                    .map_err(|_| encode::Error::ParseFailed("invalid aggregated public key"))
+            }
+            2 => Ok(XField::MaxBlockSize(Decodable::consensus_decode(&mut d)?)),
+            field_type => Ok(XField::Unknown(
+                field_type,
+                Decodable::consensus_decode(&mut d)?,
+            )),
+        }
+    }
+}
+
+/// Tapyrus block header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_blockhash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub im_merkle_root: [u8; 32],
+    pub time: u32,
+    pub xfield: XField,
+    pub proof: Vec<u8>,
+}
+
+impl BlockHeader {
+    fn encode_without_proof<W: io::Write>(&self, mut w: W) -> Result<usize, encode::Error> {
+        let mut len = self.version.consensus_encode(&mut w)?;
+        len += self.prev_blockhash.consensus_encode(&mut w)?;
+        len += self.merkle_root.consensus_encode(&mut w)?;
+        len += self.im_merkle_root.consensus_encode(&mut w)?;
+        len += self.time.consensus_encode(&mut w)?;
+        len += self.xfield.consensus_encode(&mut w)?;
+        Ok(len)
+    }
+}
+
+impl Encodable for BlockHeader {
+    fn consensus_encode<W: io::Write>(&self, mut w: W) -> Result<usize, encode::Error> {
+        let len = self.encode_without_proof(&mut w)?;
+        Ok(len + self.proof.consensus_encode(&mut w)?)
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
+        Ok(BlockHeader {
+            version: Decodable::consensus_decode(&mut d)?,
+            prev_blockhash: Decodable::consensus_decode(&mut d)?,
+            merkle_root: Decodable::consensus_decode(&mut d)?,
+            im_merkle_root: Decodable::consensus_decode(&mut d)?,
+            time: Decodable::consensus_decode(&mut d)?,
+            xfield: Decodable::consensus_decode(&mut d)?,
+            proof: Decodable::consensus_decode(&mut d)?,
+        })
+    }
+}
+
+/// The block which keeps the serialized payload as it is, so that the transactions are parsed
+/// only when they are needed.
 #[derive(PartialEq, Clone)]
-pub struct Block(Vec<u8>);
+pub struct Block {
+    header: BlockHeader,
+    /// The length of the serialized header in the payload.
+    header_len: usize,
+    payload: Vec<u8>,
+}
 
 impl Block {
-    const XFIELD_POSITION: usize = 105;
+    /// Returns `Error::InvalidBlock` if the header can not be parsed.
+    pub fn new(data: Vec<u8>) -> Result<Block, Error> {
+        let mut slice = &data[..];
+        let header = BlockHeader::consensus_decode(&mut slice).map_err(|_| Error::InvalidBlock)?;
+        let header_len = data.len() - slice.len();
+        Ok(Block {
+            header,
+            header_len,
+            payload: data,
+        })
+    }
 
-    pub fn new(data: Vec<u8>) -> Block {
-        Block(data)
+    pub fn from_parts(header: BlockHeader, transactions: Vec<Transaction>) -> Block {
+        let mut payload = serialize(&header);
+        let header_len = payload.len();
+        payload.extend(serialize(&transactions));
+        Block {
+            header,
+            header_len,
+            payload,
+        }
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
     }
 
     /// Length of block header without proof is 105 + len bytes.
@@ -98,16 +230,12 @@ impl Block {
     /// xfieldType: 1
     /// xfield: variable
     pub fn get_header_without_proof(&self) -> &[u8] {
-        let position = match self.get_xfield_length() {
-            Ok(None) => Self::XFIELD_POSITION,
-            Ok(Some(i)) => Self::XFIELD_POSITION + i.len() + i.0 as usize,
-            Err(_) => panic!("xField is unsupported"),
-        };
-        &self.0[..position]
+        let proof_len = VarInt(self.header.proof.len() as u64).len() + self.header.proof.len();
+        &self.payload[..self.header_len - proof_len]
     }
 
     pub fn hex(&self) -> String {
-        hex::encode(&self.0)
+        hex::encode(&self.payload)
     }
 
     /// Returns hash for signing. This hash value doesn't include proof field. Actual block hash
@@ -120,66 +248,88 @@ impl Block {
     }
 
     pub fn payload(&self) -> &[u8] {
-        &self.0
+        &self.payload
     }
 
-    pub fn add_proof(&self, proof: Vec<u8>) -> Block {
-        let position = match self.get_xfield_length() {
-            Ok(None) => Self::XFIELD_POSITION,
-            Ok(Some(i)) => Self::XFIELD_POSITION + i.len() + i.0 as usize,
-            Err(_) => panic!("xField is invalid"),
-        };
-        let (header, txs) = self.payload().split_at(position);
-        let new_payload = [header, &proof[..], &txs[1..]].concat();
-        Block(new_payload)
+    /// Returns the block which has the proof. The proof is serialized with its length like the
+    /// output of `Sign::format_signature`.
+    pub fn add_proof(&self, proof: Vec<u8>) -> Result<Block, Error> {
+        let mut slice = &proof[..];
+        let proof: Vec<u8> =
+            Decodable::consensus_decode(&mut slice).map_err(|_| Error::InvalidSig)?;
+        if !slice.is_empty() {
+            return Err(Error::InvalidSig);
+        }
+        Ok(self.with_header(BlockHeader {
+            proof,
+            ..self.header.clone()
+        }))
     }
 
     pub fn add_aggregated_public_key(&self, aggregated_public_key: PublicKey) -> Block {
-        let (header, rest) = self.payload().split_at(Self::XFIELD_POSITION - 1);
-        let bytes = aggregated_public_key.to_bytes();
-        let size = serialize(&VarInt(bytes.len() as u64));
-        let new_payload = [header, &[0x01], &size, &bytes[..], &rest[1..]].concat();
-        Block(new_payload)
+        self.with_header(BlockHeader {
+            xfield: XField::AggregatePublicKey(aggregated_public_key),
+            ..self.header.clone()
+        })
     }
 
     pub fn get_aggregated_public_key(&self) -> Option<PublicKey> {
-        match self.get_xfield_type() {
-            1 => {
-                let len = match self.get_xfield_length() {
-                    Ok(Some(i)) => i,
-                    _ => panic!("xField is invalid"),
-                };
-                let bytes = &self.payload()[Self::XFIELD_POSITION + len.len()
-                    ..Self::XFIELD_POSITION + len.len() + len.0 as usize];
-                PublicKey::from_slice(bytes).ok()
-            }
+        match &self.header.xfield {
+            XField::AggregatePublicKey(public_key) => Some(*public_key),
             _ => None,
         }
     }
 
+    pub fn add_max_block_size(&self, max_block_size: u32) -> Block {
+        self.with_header(BlockHeader {
+            xfield: XField::MaxBlockSize(max_block_size),
+            ..self.header.clone()
+        })
+    }
+
+    pub fn get_max_block_size(&self) -> Option<u32> {
+        match self.header.xfield {
+            XField::MaxBlockSize(max_block_size) => Some(max_block_size),
+            _ => None,
+        }
+    }
+
+    /// Returns the time field of the block header.
+    pub fn get_time(&self) -> u32 {
+        self.header.time
+    }
+
+    /// Returns the transactions which follow the header and the proof.
+    pub fn get_transactions(&self) -> Result<Vec<Transaction>, Error> {
+        let mut slice = &self.payload[self.header_len..];
+        let transactions: Vec<Transaction> =
+            Decodable::consensus_decode(&mut slice).map_err(|_| Error::InvalidBlock)?;
+        if !slice.is_empty() {
+            return Err(Error::InvalidBlock);
+        }
+        Ok(transactions)
+    }
+
     pub fn get_xfield_type(&self) -> u8 {
-        self.0[Self::XFIELD_POSITION - 1]
+        self.header.xfield.field_type()
     }
 
-    /// the length of xfield.
-    /// return 0  if xfieldType is None.
-    /// return 33 if xfieldType is AggregatePublicKey.
-    /// return value of the first byte if xfieldType is unknown,
-    /// this assumes that the leading of the new xfieldType added in the future is the length of the xfield value.
-    pub fn get_xfield_length(&self) -> Result<Option<VarInt>, Error> {
-        if self.get_xfield_type() == 0 {
-            return Ok(None);
+    /// Returns the block which has the header and the same transactions as this block.
+    fn with_header(&self, header: BlockHeader) -> Block {
+        let mut payload = serialize(&header);
+        let header_len = payload.len();
+        payload.extend_from_slice(&self.payload[self.header_len..]);
+        Block {
+            header,
+            header_len,
+            payload,
         }
-        let mut slice = &self.0[Self::XFIELD_POSITION..];
-        VarInt::consensus_decode(&mut slice)
-            .map(|i| Some(i))
-            .map_err(|_| Error::InvalidBlock)
     }
 }
 
 impl Debug for Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let h = hex::encode(&self.0);
+        let h = hex::encode(&self.payload);
         write!(f, "Block({})", h)
     }
 }
@@ -200,7 +350,7 @@ impl<'de> Deserialize<'de> for Block {
         D: Deserializer<'de>,
     {
         let vec = deserializer.deserialize_str(HexStrVisitor::new())?;
-        Ok(Block::new(vec))
+        Block::new(vec).map_err(|_| de::Error::custom("invalid block"))
     }
 }
 
@@ -218,32 +368,32 @@ mod tests {
 
     fn test_block() -> Block {
         let raw_block = hex::decode(TEST_BLOCK).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block2() -> Block {
         let raw_block = hex::decode(TEST_BLOCK2).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block_with_pubkey() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITH_PUBKEY).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block_without_pubkey() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITHOUT_PUBKEY).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block_without_proof() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITHOUT_PROOF).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block_with_unknown_xfield() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITH_UNKNOWN_XFIELD).unwrap();
-        Block(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     #[test]
@@ -267,13 +417,23 @@ mod tests {
         let block = test_block_without_proof();
         let sig_hex = "403a4c09253c7b583e5260074380c9b99b895f938e37799d326ded984fb707e91fa4df2e0524a4ccf5fe224945b4fb94784b411a760eb730d95402d3383dd7ffdc";
 
-        assert_eq!(block.add_proof(hex::decode(sig_hex).unwrap()), test_block());
+        assert_eq!(
+            block.add_proof(hex::decode(sig_hex).unwrap()).unwrap(),
+            test_block()
+        );
 
         let block = test_block_with_pubkey();
         assert_eq!(
-            block.add_proof(hex::decode(sig_hex).unwrap()),
+            block.add_proof(hex::decode(sig_hex).unwrap()).unwrap(),
             test_block2()
         );
+
+        // The length of the proof is wrong.
+        let block = test_block_without_proof();
+        match block.add_proof(hex::decode(&sig_hex[..126]).unwrap()) {
+            Err(Error::InvalidSig) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
     }
 
     #[test]
@@ -296,6 +456,23 @@ mod tests {
         assert_eq!(block.get_aggregated_public_key().unwrap(), public_key);
     }
 
+    #[test]
+    fn test_max_block_size() {
+        let block = test_block_without_pubkey().add_max_block_size(2_000_000);
+        assert_eq!(block.get_xfield_type(), 2);
+        assert_eq!(block.get_max_block_size(), Some(2_000_000));
+        assert_eq!(block.get_aggregated_public_key(), None);
+        assert_eq!(
+            hex::encode(&block.get_header_without_proof()[104..]),
+            "0280841e00"
+        );
+        assert_eq!(Block::new(block.payload().to_vec()).unwrap(), block);
+        assert_eq!(
+            block.get_transactions().unwrap(),
+            test_block_without_pubkey().get_transactions().unwrap()
+        );
+    }
+
     #[test]
     fn test_block_hash_debug_fmt() {
         let block = test_block();
@@ -324,13 +501,94 @@ mod tests {
     }
 
     #[test]
-    fn test_get_xfield_length() {
-        let block = test_block_with_pubkey();
-        let len = block.get_xfield_length().unwrap().unwrap();
-        assert_eq!(len, VarInt(33));
+    fn test_header() {
+        let header = test_block().header().clone();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.prev_blockhash, [0u8; 32]);
+        assert_eq!(
+            hex::encode(header.merkle_root),
+            "c1457ff3e5c527e69858108edf0ff1f49eea9c58d8d37300a164b3b4f8c8c7ce"
+        );
+        assert_eq!(
+            hex::encode(header.im_merkle_root),
+            "f1a2e72770d547feae29f2dd40123a97c580d44fd4493de072416d5333199761"
+        );
+        assert_eq!(header.time, 1576048251);
+        assert_eq!(header.xfield, XField::None);
+        assert_eq!(header.proof.len(), 64);
+
+        let public_key = PublicKey::from_str(
+            "025700236c2890233592fcef262f4520d22af9160e3d9705855140eb2aa06c35d3",
+        )
+        .unwrap();
+        let header = test_block_with_pubkey().header().clone();
+        assert_eq!(header.xfield, XField::AggregatePublicKey(public_key));
+        assert!(header.proof.is_empty());
 
-        let block = test_block_with_unknown_xfield();
-        let len = block.get_xfield_length().unwrap().unwrap();
-        assert_eq!(len, VarInt(550));
+        let header = test_block_with_unknown_xfield().header().clone();
+        assert_eq!(header.xfield, XField::Unknown(0xff, vec![0xff; 550]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for block in &[
+            test_block(),
+            test_block_with_pubkey(),
+            test_block_without_proof(),
+            test_block_with_unknown_xfield(),
+        ] {
+            let header = block.header().clone();
+            let transactions = block.get_transactions().unwrap();
+            assert_eq!(&Block::from_parts(header, transactions), block);
+        }
+    }
+
+    #[test]
+    fn test_invalid_block() {
+        // truncated header
+        match Block::new(test_block().payload()[..104].to_vec()) {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+
+        // truncated xfield
+        match Block::new(test_block_with_unknown_xfield().payload()[..200].to_vec()) {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+
+        // invalid prefix of the aggregated public key
+        let mut raw_block = test_block_with_pubkey().payload().to_vec();
+        raw_block[106] = 0x04;
+        match Block::new(raw_block) {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+
+        // invalid json
+        assert!(serde_json::from_str::<Block>("\"0100\"").is_err());
+    }
+
+    #[test]
+    fn test_get_time() {
+        assert_eq!(test_block_without_proof().get_time(), 1576048251);
+    }
+
+    #[test]
+    fn test_get_transactions() {
+        let transactions = test_block().get_transactions().unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert!(transactions[0].is_coin_base());
+        assert_eq!(
+            test_block_without_proof().get_transactions().unwrap(),
+            transactions
+        );
+
+        // truncated block
+        let block = Block::new(test_block().payload()[..200].to_vec()).unwrap();
+        match block.get_transactions() {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
     }
 }
@@ -77,7 +77,7 @@ impl<'a> AggregateCommand {
 
         Ok(Box::new(AggregateResponse::new(
             public_key,
-            shared_keys.x_i,
+            *shared_keys.x_i,
         )))
     }
 
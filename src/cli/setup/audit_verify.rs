@@ -0,0 +1,119 @@
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use crate::signer_node::audit_log;
+use bitcoin::PublicKey;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+pub struct AuditVerifyResponse {
+    count: usize,
+}
+
+impl AuditVerifyResponse {
+    fn new(count: usize) -> Self {
+        AuditVerifyResponse { count }
+    }
+}
+
+impl Response for AuditVerifyResponse {}
+
+impl fmt::Display for AuditVerifyResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The audit log is valid. records: {}", self.count)
+    }
+}
+
+pub struct AuditVerifyCommand {}
+
+impl<'a> AuditVerifyCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let file = matches
+            .value_of("file")
+            .ok_or(Error::InvalidArgs("file".to_string()))?;
+
+        let public_key: PublicKey = matches
+            .value_of("public-key")
+            .and_then(|key| PublicKey::from_str(key).ok())
+            .ok_or(Error::InvalidArgs("public-key".to_string()))?;
+
+        let count = audit_log::verify(Path::new(file), &public_key)?;
+        Ok(Box::new(AuditVerifyResponse::new(count)))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("auditverify").args(&[
+            Arg::with_name("file")
+                .long("file")
+                .required(true)
+                .takes_value(true)
+                .help("path to the audit log of tapyrus-signerd"),
+            Arg::with_name("public-key")
+                .long("public-key")
+                .required(true)
+                .takes_value(true)
+                .help("compressed public key of the signer who wrote the audit log"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::SignerID;
+    use crate::signer_node::audit_log::{AuditEntry, AuditEvent, AuditLog};
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    #[test]
+    fn test_execute() {
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-setup-test-auditverify-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let log = AuditLog::open(path.clone(), TEST_KEYS.key[0]).unwrap();
+        log.append(AuditEntry::new(
+            AuditEvent::LocalSig,
+            1,
+            get_block(0).sighash(),
+            SignerID::new(TEST_KEYS.pubkeys()[0]),
+            vec![SignerID::new(TEST_KEYS.pubkeys()[0])],
+            TEST_KEYS.aggregated(),
+            &[1u8; 32],
+        ))
+        .unwrap();
+
+        let public_key = TEST_KEYS.pubkeys()[0].to_string();
+        let matches = AuditVerifyCommand::args().get_matches_from(vec![
+            "auditverify",
+            "--file",
+            path.to_str().unwrap(),
+            "--public-key",
+            &public_key,
+        ]);
+        let response = AuditVerifyCommand::execute(&matches).unwrap();
+        assert_eq!(
+            format!("{}", response),
+            "The audit log is valid. records: 1"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_execute_invalid_public_key() {
+        let matches = AuditVerifyCommand::args().get_matches_from(vec![
+            "auditverify",
+            "--file",
+            "audit.log",
+            "--public-key",
+            "x",
+        ]);
+        let response = AuditVerifyCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"public-key\")"
+        );
+    }
+}
@@ -69,7 +69,7 @@ impl<'a> ComputeSigCommand {
         let block: Block = matches
             .value_of("block")
             .and_then(|s| hex::decode(s).ok())
-            .map(|hex| Block::new(hex))
+            .and_then(|hex| Block::new(hex).ok())
             .ok_or(Error::InvalidArgs("block".to_string()))?;
 
         let node_vss_vec: Vec<Vss> = matches
@@ -140,14 +140,14 @@ impl<'a> ComputeSigCommand {
         let point = GE::from_bytes(&bytes[1..]).expect("failed to convert to point");
         let priv_shared_keys = SharedKeys {
             y: point,
-            x_i: node_secret_share,
+            x_i: node_secret_share.into(),
         };
 
         let (is_positive, block_shared_keys, _local_sig) = Vss::create_local_sig_from_shares(
             &priv_shared_keys,
             index,
             &shared_block_secrets,
-            &block,
+            block.sighash(),
         )?;
 
         let shared_secrets = vss_to_shared_secret_map(&node_vss_vec, &params);
@@ -157,7 +157,7 @@ impl<'a> ComputeSigCommand {
             signatures.insert(SignerID { pubkey: public_key }, (sig.gamma_i, sig.e));
         }
         let signature = Vss::aggregate_and_verify_signature(
-            &block,
+            block.sighash(),
             signatures,
             &public_keys,
             &shared_secrets,
@@ -168,7 +168,7 @@ impl<'a> ComputeSigCommand {
         let hash = block.sighash().into_inner();
         signature.verify(&hash, &priv_shared_keys.y)?;
         let sig_hex = Sign::format_signature(&signature);
-        let new_block: Block = block.add_proof(hex::decode(sig_hex).unwrap());
+        let new_block: Block = block.add_proof(hex::decode(sig_hex).unwrap())?;
         Ok(Box::new(ComputeSigResponse::new(new_block)))
     }
 
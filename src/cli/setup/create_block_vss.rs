@@ -1,12 +1,17 @@
+use crate::blockdata::Block;
 use crate::cli::setup::index_of;
 use crate::cli::setup::traits::Response;
+use crate::crypto::block_key::BlockKeyDerivation;
 use crate::crypto::vss::{Commitment, Vss};
 use crate::errors::Error;
 use crate::rpc::Rpc;
+use crate::sign::Sign;
 use crate::signer_node::NodeParameters;
 
 use bitcoin::{PrivateKey, PublicKey};
 use clap::{App, Arg, ArgMatches, SubCommand};
+use curv::elliptic::curves::traits::ECScalar;
+use curv::FE;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
@@ -58,6 +63,27 @@ impl<'a> CreateBlockVssCommand {
                 "threshold should be integer.".to_string(),
             ))?;
 
+        let block: Option<Block> = match matches.value_of("block") {
+            Some(s) => Some(
+                hex::decode(s)
+                    .ok()
+                    .and_then(|hex| Block::new(hex).ok())
+                    .ok_or(Error::InvalidArgs("block".to_string()))?,
+            ),
+            None => None,
+        };
+
+        // The block key is derived from the private key and the block, so that it is not
+        // predictable even if the random number generator is weak. The round id is the height of
+        // the genesis block.
+        let secret: FE = ECScalar::from(
+            &Sign::private_key_to_big_int(private_key.key).ok_or(Error::InvalidKey)?,
+        );
+        let derivation = match &block {
+            Some(block) => BlockKeyDerivation::for_block(&secret, &block.sighash(), 0),
+            None => BlockKeyDerivation::without_block(&secret, 0),
+        };
+
         let index = index_of(&private_key, &public_keys);
         let (
             _key,
@@ -65,7 +91,12 @@ impl<'a> CreateBlockVssCommand {
             secret_shares_for_positive,
             vss_scheme_for_negative,
             secret_shares_for_negative,
-        ) = Vss::create_block_shares(index, threshold as usize, public_keys.len());
+        ) = Vss::create_block_shares_with(
+            &derivation,
+            index,
+            threshold as usize,
+            public_keys.len(),
+        );
         let mut vss_map = BTreeMap::new();
         let secp = secp256k1::Secp256k1::new();
         let sender_public_key = PublicKey::from_private_key(&secp, &private_key);
@@ -79,13 +110,13 @@ impl<'a> CreateBlockVssCommand {
                     .iter()
                     .map(|c| Commitment::from(c))
                     .collect(),
-                positive_secret: secret_shares_for_positive[j],
+                positive_secret: secret_shares_for_positive[j].into(),
                 negative_commitments: vss_scheme_for_negative
                     .commitments
                     .iter()
                     .map(|c| Commitment::from(c))
                     .collect(),
-                negative_secret: secret_shares_for_negative[j],
+                negative_secret: secret_shares_for_negative[j].into(),
             };
             vss_map.insert(public_keys[j].clone(), vss);
         }
@@ -110,6 +141,10 @@ impl<'a> CreateBlockVssCommand {
                 .required(true)
                 .takes_value(true)
                 .help("the minimum number of signers required to sign block"),
+            Arg::with_name("block")
+                .long("block")
+                .takes_value(true)
+                .help("block to be signed as a hex string format. The block key is bound to it if specified"),
         ])
     }
 }
@@ -136,6 +171,47 @@ mod tests {
         assert!(response.is_ok());
     }
 
+    #[test]
+    fn test_execute_with_block() {
+        let matches = CreateBlockVssCommand::args().get_matches_from(vec![
+            "createblockvss",
+            "--threshold",
+            "2",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--public-key",
+            "03e568e3a5641ac21930b51f92fb6dd201fb46faae560b108cf3a96380da08dee1",
+            "--public-key",
+            "02a1c8965ed06987fa6d7e0f552db707065352283ab3c1471510b12a76a5905287",
+            "--private-key",
+            "cQYYBMFS9dRR3Mt16gW4jixCqSiMhCwuDMHUBs6WeHMTxMnsq8Gh",
+            "--block",
+            "010000000000000000000000000000000000000000000000000000000000000000000000c0d6961ad2819f74eb6d085f04f9cceb0a9a6d5c153fd3c39fc47c3ca0bb548f85fbd09a5f7d8ac4c9552e52931ef6672984f64e52ad6d05d1cdb18907da8527db317c5e012103addb2555f37abf8f28f11f498bec7bd1460e7243c1813847c49a7ae326a97d1c00010100000001000000000000000000000000000000000000000000000000000000000000000000000000222103addb2555f37abf8f28f11f498bec7bd1460e7243c1813847c49a7ae326a97d1cffffffff0100f2052a010000001976a914a15f16ea2ba840d178e4c19781abca5f4fb1b4c288ac00000000",
+        ]);
+        let response = CreateBlockVssCommand::execute(&matches);
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    fn test_execute_invalid_block() {
+        let matches = CreateBlockVssCommand::args().get_matches_from(vec![
+            "createblockvss",
+            "--threshold",
+            "2",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--private-key",
+            "cQYYBMFS9dRR3Mt16gW4jixCqSiMhCwuDMHUBs6WeHMTxMnsq8Gh",
+            "--block",
+            "x",
+        ]);
+        let response = CreateBlockVssCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"block\")"
+        );
+    }
+
     #[test]
     fn test_execute_invalid_threshold() {
         let matches = CreateBlockVssCommand::args().get_matches_from(vec![
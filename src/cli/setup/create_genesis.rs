@@ -0,0 +1,207 @@
+use crate::blockdata::{Block, BlockHeader, XField};
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::{Address, OutPoint, PublicKey, Transaction, TxIn, TxOut, VarInt};
+use bitcoin_hashes::{sha256d, Hash};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+use std::str::FromStr;
+
+/// The reward of the coinbase transaction in the genesis block. It is 50 TPC as same as Tapyrus
+/// Core.
+const GENESIS_REWARD: u64 = 5_000_000_000;
+
+pub struct CreateGenesisResponse {
+    block: Block,
+}
+
+impl CreateGenesisResponse {
+    fn new(block: Block) -> Self {
+        CreateGenesisResponse { block }
+    }
+}
+
+impl Response for CreateGenesisResponse {}
+
+impl fmt::Display for CreateGenesisResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.block.hex())
+    }
+}
+
+pub struct CreateGenesisCommand {}
+
+impl<'a> CreateGenesisCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let aggregated_public_key: PublicKey = matches
+            .value_of("aggregated-public-key")
+            .and_then(|hex| PublicKey::from_str(hex).ok())
+            .filter(|pk| pk.compressed)
+            .ok_or(Error::InvalidArgs("aggregated-public-key".to_string()))?;
+
+        let address: Address = matches
+            .value_of("address")
+            .and_then(|s| Address::from_str(s).ok())
+            .ok_or(Error::InvalidArgs("address".to_string()))?;
+
+        let time: u32 = matches
+            .value_of("time")
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or(Error::InvalidArgs("time should be integer.".to_string()))?;
+
+        // The network id is not a part of the genesis block. It only decides the name of the
+        // genesis file, so that it is just validated here.
+        matches
+            .value_of("network-id")
+            .and_then(|s| s.parse::<u32>().ok())
+            .filter(|id| *id > 0)
+            .ok_or(Error::InvalidArgs(
+                "network-id should be positive integer.".to_string(),
+            ))?;
+
+        let block = create_genesis_block(&aggregated_public_key, &address, time);
+        Ok(Box::new(CreateGenesisResponse::new(block)))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("creategenesis").args(&[
+            Arg::with_name("aggregated-public-key")
+                .long("aggregated-public-key")
+                .required(true)
+                .takes_value(true)
+                .help("aggregated public key of the federation which signs the genesis block"),
+            Arg::with_name("address")
+                .long("address")
+                .required(true)
+                .takes_value(true)
+                .help("address to pay the reward of the coinbase transaction to"),
+            Arg::with_name("time")
+                .long("time")
+                .required(true)
+                .takes_value(true)
+                .help("time of the genesis block as unix timestamp"),
+            Arg::with_name("network-id")
+                .long("network-id")
+                .required(true)
+                .takes_value(true)
+                .help("network id of the Tapyrus network. The signed block should be saved as genesis.<network-id>"),
+        ])
+    }
+}
+
+/// Returns the genesis block without the proof. The coinbase transaction pushes the aggregated
+/// public key in its scriptSig and pays the reward to the address, and the header has the
+/// aggregated public key as the xfield.
+fn create_genesis_block(aggregated_public_key: &PublicKey, address: &Address, time: u32) -> Block {
+    let coinbase = Transaction {
+        version: 1,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Default::default(),
+                vout: 0,
+            },
+            script_sig: Builder::new()
+                .push_slice(&aggregated_public_key.to_bytes())
+                .into_script(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: GENESIS_REWARD,
+            script_pubkey: address.script_pubkey(),
+        }],
+    };
+
+    // The merkle roots of the block which has only one transaction are the hashes of it.
+    let header = BlockHeader {
+        version: 1,
+        prev_blockhash: [0u8; 32],
+        merkle_root: coinbase.txid().into_inner(),
+        im_merkle_root: malfix_txid(&coinbase),
+        time,
+        xfield: XField::AggregatePublicKey(*aggregated_public_key),
+        proof: vec![],
+    };
+    Block::from_parts(header, vec![coinbase])
+}
+
+/// Returns the malleability fixed id of the transaction which Tapyrus uses in the immutable merkle
+/// root. It is the hash of the transaction serialized without scriptSigs.
+fn malfix_txid(tx: &Transaction) -> [u8; 32] {
+    let mut data: Vec<u8> = serialize(&tx.version);
+    data.extend(serialize(&VarInt(tx.input.len() as u64)));
+    for input in &tx.input {
+        data.extend(serialize(&input.previous_output));
+        data.extend(serialize(&input.sequence));
+    }
+    data.extend(serialize(&tx.output));
+    data.extend(serialize(&tx.lock_time));
+    sha256d::Hash::hash(&data).into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The genesis block of a Tapyrus network without the proof.
+    const GENESIS_BLOCK: &str = "010000000000000000000000000000000000000000000000000000000000000000000000e7c526d0125538b13a50b06465fb8b72120be13fb1142e93aba2aabb2a4f369826c18219f76e4d0ebddbaa9b744837c2ac65b347673695a23c3cc1a2be4141e1427d735e0121025700236c2890233592fcef262f4520d22af9160e3d9705855140eb2aa06c35d3000101000000010000000000000000000000000000000000000000000000000000000000000000000000002221025700236c2890233592fcef262f4520d22af9160e3d9705855140eb2aa06c35d3ffffffff0100f2052a010000001976a914834e0737cdb9008db614cd95ec98824e952e3dc588ac00000000";
+    const AGGREGATED_PUBLIC_KEY: &str =
+        "025700236c2890233592fcef262f4520d22af9160e3d9705855140eb2aa06c35d3";
+    const ADDRESS: &str = "1BxFycAZqKCyY9veEwNugo9mKJWwgZJtgJ";
+
+    fn execute(
+        aggregated_public_key: &str,
+        address: &str,
+        time: &str,
+        network_id: &str,
+    ) -> Result<Box<dyn Response>, Error> {
+        let matches = CreateGenesisCommand::args().get_matches_from(vec![
+            "creategenesis",
+            "--aggregated-public-key",
+            aggregated_public_key,
+            "--address",
+            address,
+            "--time",
+            time,
+            "--network-id",
+            network_id,
+        ]);
+        CreateGenesisCommand::execute(&matches)
+    }
+
+    #[test]
+    fn test_execute() {
+        let response = execute(AGGREGATED_PUBLIC_KEY, ADDRESS, "1584627010", "1").unwrap();
+        assert_eq!(format!("{}", response), GENESIS_BLOCK);
+    }
+
+    #[test]
+    fn test_execute_invalid_args() {
+        let response = execute("x", ADDRESS, "1584627010", "1");
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"aggregated-public-key\")"
+        );
+
+        let response = execute(AGGREGATED_PUBLIC_KEY, "x", "1584627010", "1");
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"address\")"
+        );
+
+        let response = execute(AGGREGATED_PUBLIC_KEY, ADDRESS, "x", "1");
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"time should be integer.\")"
+        );
+
+        let response = execute(AGGREGATED_PUBLIC_KEY, ADDRESS, "1584627010", "0");
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"network-id should be positive integer.\")"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::cli::setup::encrypt::KeystoreResponse;
+use crate::cli::setup::traits::Response;
+use crate::cli::setup::{passphrase_env_arg, passphrase_fd_arg, passphrase_of};
+use crate::errors::Error;
+use crate::keystore;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+pub struct DecryptCommand {}
+
+impl<'a> DecryptCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let keystore_file = matches
+            .value_of("federations-file")
+            .ok_or(Error::InvalidArgs("federations-file".to_string()))?;
+        let keystore_toml = std::fs::read_to_string(keystore_file)?;
+        if !keystore::is_encrypted(&keystore_toml) {
+            return Err(Error::InvalidArgs(
+                "federations-file is not encrypted.".to_string(),
+            ));
+        }
+
+        let passphrase = passphrase_of(matches)?.read()?;
+        let content = keystore::decrypt(&keystore_toml, &passphrase)?;
+        // The content is copied out of the secret because this command prints it.
+        Ok(Box::new(KeystoreResponse::new((*content).clone())))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("decrypt").args(&[
+            Arg::with_name("federations-file")
+                .long("federations-file")
+                .required(true)
+                .takes_value(true)
+                .help("path to the encrypted federations.toml. The decrypted content is written to stdout"),
+            passphrase_fd_arg(),
+            passphrase_env_arg(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        std::env::set_var("TAPYRUS_SETUP_TEST_DECRYPT_PASSPHRASE", "passphrase");
+        let matches = DecryptCommand::args().get_matches_from(vec![
+            "decrypt",
+            "--federations-file",
+            "tests/resources/federations_encrypted.toml",
+            "--passphrase-env",
+            "TAPYRUS_SETUP_TEST_DECRYPT_PASSPHRASE",
+        ]);
+        let response = DecryptCommand::execute(&matches).unwrap();
+        assert_eq!(
+            format!("{}", response),
+            std::fs::read_to_string("tests/resources/federations.toml").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_execute_wrong_passphrase() {
+        std::env::set_var("TAPYRUS_SETUP_TEST_DECRYPT_WRONG_PASSPHRASE", "wrong");
+        let matches = DecryptCommand::args().get_matches_from(vec![
+            "decrypt",
+            "--federations-file",
+            "tests/resources/federations_encrypted.toml",
+            "--passphrase-env",
+            "TAPYRUS_SETUP_TEST_DECRYPT_WRONG_PASSPHRASE",
+        ]);
+        match DecryptCommand::execute(&matches) {
+            Err(Error::InvalidKeystore(_)) => assert!(true),
+            _ => assert!(false, "it should error"),
+        }
+    }
+
+    #[test]
+    fn test_execute_not_encrypted() {
+        let matches = DecryptCommand::args().get_matches_from(vec![
+            "decrypt",
+            "--federations-file",
+            "tests/resources/federations.toml",
+        ]);
+        let response = DecryptCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"federations-file is not encrypted.\")"
+        );
+    }
+}
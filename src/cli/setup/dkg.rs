@@ -0,0 +1,199 @@
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use crate::federation::{Federation, SerFederations};
+use crate::keygen::dkg::Dkg;
+use crate::keygen::run;
+use crate::net::RedisManager;
+use bitcoin::{PrivateKey, PublicKey};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The federation which is generated over the signer network, in the format of federations.toml.
+/// It is also the output of the other commands which generate a federation.
+pub struct DkgResponse {
+    federation: Federation,
+}
+
+impl DkgResponse {
+    pub fn new(federation: Federation) -> Self {
+        DkgResponse { federation }
+    }
+}
+
+impl Response for DkgResponse {}
+
+impl fmt::Display for DkgResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ser = SerFederations::new(vec![self.federation.clone().to_ser()]);
+        let toml = toml::to_string(&ser).map_err(|_| fmt::Error)?;
+        write!(f, "{}", toml)
+    }
+}
+
+pub struct DkgCommand {}
+
+impl<'a> DkgCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let private_key: PrivateKey = matches
+            .value_of("private-key")
+            .and_then(|key| PrivateKey::from_wif(key).ok())
+            .ok_or(Error::InvalidArgs("private-key".to_string()))?;
+
+        let public_keys: Vec<PublicKey> = matches
+            .values_of("public-key")
+            .ok_or(Error::InvalidArgs("public-key".to_string()))?
+            .map(|key| PublicKey::from_str(key).map_err(|_| Error::InvalidKey))
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        let threshold: u8 = matches
+            .value_of("threshold")
+            .and_then(|t| t.parse::<u8>().ok())
+            .ok_or(Error::InvalidArgs(
+                "threshold should be integer.".to_string(),
+            ))?;
+
+        let block_height: u64 = matches
+            .value_of("block-height")
+            .and_then(|h| h.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs(
+                "block-height should be integer.".to_string(),
+            ))?;
+
+        let timeout: u64 = matches
+            .value_of("timeout")
+            .and_then(|t| t.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs("timeout should be integer.".to_string()))?;
+
+        let mut dkg = Dkg::new(&private_key, public_keys, threshold, block_height)?;
+
+        let host = matches.value_of("redis-host").unwrap_or_default();
+        let port = matches.value_of("redis-port").unwrap_or_default();
+        let mut redis_manager = RedisManager::new(host.to_string(), port.to_string());
+        redis_manager.test_connection()?;
+
+        let federation = run(&mut dkg, &mut redis_manager, Duration::from_secs(timeout))?;
+        federation.validate()?;
+        Ok(Box::new(DkgResponse::new(federation)))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("dkg").args(&[
+            Arg::with_name("public-key")
+                .long("public-key")
+                .required(true)
+                .multiple(true)
+                .takes_value(true)
+                .help("compressed public key of the each signer with a hex format string"),
+            Arg::with_name("private-key")
+                .long("private-key")
+                .required(true)
+                .takes_value(true)
+                .help("private key of this signer with an extend WIF format"),
+            Arg::with_name("threshold")
+                .long("threshold")
+                .required(true)
+                .takes_value(true)
+                .help("the minimum number of signers required to sign block"),
+            Arg::with_name("block-height")
+                .long("block-height")
+                .required(true)
+                .takes_value(true)
+                .help("the block height where the federation gets started"),
+            Arg::with_name("redis-host")
+                .long("redis-host")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("host name of the redis server which signers are connected to"),
+            Arg::with_name("redis-port")
+                .long("redis-port")
+                .takes_value(true)
+                .default_value("6379")
+                .help("port number of the redis server which signers are connected to"),
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("300")
+                .help("seconds to wait for all signers to finish the key generation"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::federation::Federations;
+    use crate::keystore::Passphrase;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+
+    #[test]
+    fn test_dkg_response() {
+        let federation = Federation::new(
+            TEST_KEYS.pubkeys()[0],
+            100,
+            Some(3),
+            node_vss(0),
+            TEST_KEYS.aggregated(),
+        );
+        let response = DkgResponse::new(federation.clone());
+        let output = format!("{}", response);
+        assert!(output.starts_with("[[federation]]\nblock-height = 100\nthreshold = 3\n"));
+
+        let federations = Federations::from_pubkey_and_toml(
+            &TEST_KEYS.pubkeys()[0],
+            &output,
+            &Passphrase::Prompt,
+        )
+        .unwrap();
+        assert_eq!(federations.get_by_block_height(100), &federation);
+    }
+
+    #[test]
+    fn test_execute_invalid_private_key() {
+        let matches = DkgCommand::args().get_matches_from(vec![
+            "dkg",
+            "--threshold",
+            "2",
+            "--block-height",
+            "0",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--private-key",
+            "x",
+        ]);
+        let response = DkgCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"private-key\")"
+        );
+    }
+
+    #[test]
+    fn test_execute_not_member() {
+        let private_key = TEST_KEYS.key[0].to_wif();
+        let public_keys: Vec<String> = TEST_KEYS.pubkeys()[1..3]
+            .iter()
+            .map(|pk| pk.to_string())
+            .collect();
+        let matches = DkgCommand::args().get_matches_from(vec![
+            "dkg",
+            "--threshold",
+            "2",
+            "--block-height",
+            "0",
+            "--public-key",
+            public_keys[0].as_str(),
+            "--public-key",
+            public_keys[1].as_str(),
+            "--private-key",
+            private_key.as_str(),
+        ]);
+        let response = DkgCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"public-key should include the public key of private-key\")"
+        );
+    }
+}
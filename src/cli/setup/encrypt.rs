@@ -0,0 +1,126 @@
+use crate::cli::setup::traits::Response;
+use crate::cli::setup::{passphrase_env_arg, passphrase_fd_arg, passphrase_of};
+use crate::crypto::secret::Secret;
+use crate::errors::Error;
+use crate::federation::SerFederations;
+use crate::keystore;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+
+/// The content of the file which is encrypted or decrypted.
+pub struct KeystoreResponse {
+    content: String,
+}
+
+impl KeystoreResponse {
+    pub fn new(content: String) -> Self {
+        KeystoreResponse { content }
+    }
+}
+
+impl Response for KeystoreResponse {}
+
+impl fmt::Display for KeystoreResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.content)
+    }
+}
+
+pub struct EncryptCommand {}
+
+impl<'a> EncryptCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let federations_file = matches
+            .value_of("federations-file")
+            .ok_or(Error::InvalidArgs("federations-file".to_string()))?;
+        let federations_toml = Secret::new(std::fs::read_to_string(federations_file)?);
+        if keystore::is_encrypted(&federations_toml) {
+            return Err(Error::InvalidArgs(
+                "federations-file is already encrypted.".to_string(),
+            ));
+        }
+        // Check the format not to encrypt a wrong file.
+        let _: SerFederations = toml::from_str(&federations_toml)?;
+
+        let passphrase = passphrase_of(matches)?.read_new()?;
+        Ok(Box::new(KeystoreResponse::new(keystore::encrypt(
+            &federations_toml,
+            &passphrase,
+        ))))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("encrypt").args(&[
+            Arg::with_name("federations-file")
+                .long("federations-file")
+                .required(true)
+                .takes_value(true)
+                .help("path to federations.toml to be encrypted. The encrypted keystore is written to stdout"),
+            passphrase_fd_arg(),
+            passphrase_env_arg(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::setup::decrypt::DecryptCommand;
+
+    #[test]
+    fn test_execute() {
+        std::env::set_var("TAPYRUS_SETUP_TEST_ENCRYPT_PASSPHRASE", "passphrase");
+        let matches = EncryptCommand::args().get_matches_from(vec![
+            "encrypt",
+            "--federations-file",
+            "tests/resources/federations.toml",
+            "--passphrase-env",
+            "TAPYRUS_SETUP_TEST_ENCRYPT_PASSPHRASE",
+        ]);
+        let encrypted = format!("{}", EncryptCommand::execute(&matches).unwrap());
+        assert!(keystore::is_encrypted(&encrypted));
+
+        let path = std::env::temp_dir().join("tapyrus_setup_test_encrypt.toml");
+        std::fs::write(&path, &encrypted).unwrap();
+        let matches = DecryptCommand::args().get_matches_from(vec![
+            "decrypt",
+            "--federations-file",
+            path.to_str().unwrap(),
+            "--passphrase-env",
+            "TAPYRUS_SETUP_TEST_ENCRYPT_PASSPHRASE",
+        ]);
+        let decrypted = format!("{}", DecryptCommand::execute(&matches).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            decrypted,
+            std::fs::read_to_string("tests/resources/federations.toml").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_execute_already_encrypted() {
+        let matches = EncryptCommand::args().get_matches_from(vec![
+            "encrypt",
+            "--federations-file",
+            "tests/resources/federations_encrypted.toml",
+        ]);
+        let response = EncryptCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"federations-file is already encrypted.\")"
+        );
+    }
+
+    #[test]
+    fn test_execute_invalid_file() {
+        let matches = EncryptCommand::args().get_matches_from(vec![
+            "encrypt",
+            "--federations-file",
+            "tests/resources/invalid_format.toml",
+        ]);
+        match EncryptCommand::execute(&matches) {
+            Err(Error::InvalidTomlFormat(_)) => assert!(true),
+            _ => assert!(false, "it should error"),
+        }
+    }
+}
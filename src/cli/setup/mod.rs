@@ -1,20 +1,63 @@
 use crate::crypto::vss::Vss;
+use crate::errors::Error;
+use crate::keystore::Passphrase;
 use crate::net::SignerID;
 use crate::signer_node::BidirectionalSharedSecretMap;
 use crate::signer_node::SharedSecret;
 use crate::signer_node::SharedSecretMap;
 use bitcoin::{PrivateKey, PublicKey};
+use clap::{Arg, ArgMatches};
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
     ShamirSecretSharing, VerifiableSS,
 };
 
 pub mod aggregate;
+pub mod audit_verify;
 pub mod compute_sig;
 pub mod create_block_vss;
+pub mod create_genesis;
 pub mod create_key;
 pub mod create_node_vss;
+pub mod decrypt;
+pub mod dkg;
+pub mod encrypt;
+pub mod refresh;
+pub mod repair;
+pub mod reshare;
 pub mod sign;
 pub mod traits;
+pub mod verify_block;
+pub mod verify_evidence;
+
+pub fn passphrase_fd_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("passphrase-fd")
+        .long("passphrase-fd")
+        .takes_value(true)
+        .conflicts_with("passphrase-env")
+        .help("file descriptor to read the passphrase of the keystore from. If neither passphrase-fd nor passphrase-env is specified, the passphrase is asked on the terminal")
+}
+
+pub fn passphrase_env_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("passphrase-env")
+        .long("passphrase-env")
+        .takes_value(true)
+        .help("name of the environment variable which has the passphrase of the keystore")
+}
+
+/// Returns the source of the passphrase which is specified by `passphrase_fd_arg` or
+/// `passphrase_env_arg`.
+pub fn passphrase_of(matches: &ArgMatches) -> Result<Passphrase, Error> {
+    if let Some(fd) = matches.value_of("passphrase-fd") {
+        let fd = fd
+            .parse::<i32>()
+            .map_err(|_| Error::InvalidArgs("passphrase-fd should be integer.".to_string()))?;
+        Ok(Passphrase::Fd(fd))
+    } else if let Some(name) = matches.value_of("passphrase-env") {
+        Ok(Passphrase::Env(name.to_string()))
+    } else {
+        Ok(Passphrase::Prompt)
+    }
+}
 
 pub fn index_of(private_key: &PrivateKey, public_keys: &Vec<PublicKey>) -> usize {
     let secp = secp256k1::Secp256k1::new();
@@ -45,7 +88,7 @@ pub fn vss_to_shared_secret_map(
                         .map(|c| c.to_point())
                         .collect(),
                 },
-                secret_share: node_vss.positive_secret,
+                secret_share: node_vss.positive_secret.clone(),
             },
         );
     }
@@ -64,7 +107,7 @@ pub fn vss_to_bidirectional_shared_secret_map(
             },
             (
                 SharedSecret {
-                    secret_share: vss.positive_secret,
+                    secret_share: vss.positive_secret.clone(),
                     vss: VerifiableSS {
                         parameters: params.clone(),
                         commitments: vss
@@ -75,7 +118,7 @@ pub fn vss_to_bidirectional_shared_secret_map(
                     },
                 },
                 SharedSecret {
-                    secret_share: vss.negative_secret,
+                    secret_share: vss.negative_secret.clone(),
                     vss: VerifiableSS {
                         parameters: params.clone(),
                         commitments: vss
@@ -0,0 +1,135 @@
+use crate::cli::setup::dkg::DkgResponse;
+use crate::cli::setup::traits::Response;
+use crate::cli::setup::{passphrase_env_arg, passphrase_fd_arg, passphrase_of};
+use crate::errors::Error;
+use crate::federation::Federations;
+use crate::keygen::refresh::Refresh;
+use crate::keygen::run;
+use crate::net::RedisManager;
+use bitcoin::PublicKey;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub struct RefreshCommand {}
+
+impl<'a> RefreshCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let public_key: PublicKey = matches
+            .value_of("public-key")
+            .and_then(|key| PublicKey::from_str(key).ok())
+            .ok_or(Error::InvalidArgs("public-key".to_string()))?;
+
+        let federations_file = matches
+            .value_of("federations-file")
+            .ok_or(Error::InvalidArgs("federations-file".to_string()))?;
+        let federations_toml = std::fs::read_to_string(federations_file)?;
+        let federations = Federations::from_pubkey_and_toml(
+            &public_key,
+            &federations_toml,
+            &passphrase_of(matches)?,
+        )?;
+
+        let block_height: u64 = matches
+            .value_of("block-height")
+            .and_then(|h| h.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs(
+                "block-height should be integer.".to_string(),
+            ))?;
+
+        let timeout: u64 = matches
+            .value_of("timeout")
+            .and_then(|t| t.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs("timeout should be integer.".to_string()))?;
+
+        let mut refresh = Refresh::new(federations.last().clone(), block_height)?;
+
+        let host = matches.value_of("redis-host").unwrap_or_default();
+        let port = matches.value_of("redis-port").unwrap_or_default();
+        let mut redis_manager = RedisManager::new(host.to_string(), port.to_string());
+        redis_manager.test_connection()?;
+
+        let federation = run(
+            &mut refresh,
+            &mut redis_manager,
+            Duration::from_secs(timeout),
+        )?;
+        federation.validate()?;
+        Ok(Box::new(DkgResponse::new(federation)))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("refresh").args(&[
+            Arg::with_name("public-key")
+                .long("public-key")
+                .required(true)
+                .takes_value(true)
+                .help("compressed public key of this signer with a hex format string"),
+            Arg::with_name("federations-file")
+                .long("federations-file")
+                .required(true)
+                .takes_value(true)
+                .help("path to federations.toml. The shares of the last federation are refreshed"),
+            Arg::with_name("block-height")
+                .long("block-height")
+                .required(true)
+                .takes_value(true)
+                .help("the block height where the refreshed shares get started to be used"),
+            Arg::with_name("redis-host")
+                .long("redis-host")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("host name of the redis server which signers are connected to"),
+            Arg::with_name("redis-port")
+                .long("redis-port")
+                .takes_value(true)
+                .default_value("6379")
+                .help("port number of the redis server which signers are connected to"),
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("300")
+                .help("seconds to wait for all signers to finish the refresh"),
+            passphrase_fd_arg(),
+            passphrase_env_arg(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_invalid_public_key() {
+        let matches = RefreshCommand::args().get_matches_from(vec![
+            "refresh",
+            "--public-key",
+            "x",
+            "--federations-file",
+            "federations.toml",
+            "--block-height",
+            "100",
+        ]);
+        let response = RefreshCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"public-key\")"
+        );
+    }
+
+    #[test]
+    fn test_execute_federations_file_not_found() {
+        let matches = RefreshCommand::args().get_matches_from(vec![
+            "refresh",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--federations-file",
+            "/not/exist/federations.toml",
+            "--block-height",
+            "100",
+        ]);
+        let response = RefreshCommand::execute(&matches);
+        assert!(response.is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::cli::setup::dkg::DkgResponse;
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use crate::keygen::repair::Repair;
+use crate::keygen::run;
+use crate::net::RedisManager;
+use bitcoin::{PrivateKey, PublicKey};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub struct RepairCommand {}
+
+impl<'a> RepairCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let private_key: PrivateKey = matches
+            .value_of("private-key")
+            .and_then(|key| PrivateKey::from_wif(key).ok())
+            .ok_or(Error::InvalidArgs("private-key".to_string()))?;
+
+        let helpers: Vec<PublicKey> = matches
+            .values_of("helper")
+            .ok_or(Error::InvalidArgs("helper".to_string()))?
+            .map(|key| PublicKey::from_str(key).map_err(|_| Error::InvalidKey))
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        let block_height: u64 = matches
+            .value_of("block-height")
+            .and_then(|h| h.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs(
+                "block-height should be integer.".to_string(),
+            ))?;
+
+        let timeout: u64 = matches
+            .value_of("timeout")
+            .and_then(|t| t.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs("timeout should be integer.".to_string()))?;
+
+        let mut repair = Repair::new(&private_key, block_height, helpers)?;
+
+        let host = matches.value_of("redis-host").unwrap_or_default();
+        let port = matches.value_of("redis-port").unwrap_or_default();
+        let mut redis_manager = RedisManager::new(host.to_string(), port.to_string());
+        redis_manager.test_connection()?;
+
+        let federation = run(
+            &mut repair,
+            &mut redis_manager,
+            Duration::from_secs(timeout),
+        )?;
+        federation.validate()?;
+        Ok(Box::new(DkgResponse::new(federation)))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("repair").args(&[
+            Arg::with_name("private-key")
+                .long("private-key")
+                .required(true)
+                .takes_value(true)
+                .help("private key of this signer with an extend WIF format"),
+            Arg::with_name("helper")
+                .long("helper")
+                .required(true)
+                .multiple(true)
+                .takes_value(true)
+                .help("compressed public key of the signer who helps the repair. At least threshold signers are required"),
+            Arg::with_name("block-height")
+                .long("block-height")
+                .required(true)
+                .takes_value(true)
+                .help("the block height of the federation to repair"),
+            Arg::with_name("redis-host")
+                .long("redis-host")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("host name of the redis server which signers are connected to"),
+            Arg::with_name("redis-port")
+                .long("redis-port")
+                .takes_value(true)
+                .default_value("6379")
+                .help("port number of the redis server which signers are connected to"),
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("300")
+                .help("seconds to wait for all helpers to send the node VSS"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    #[test]
+    fn test_execute_invalid_private_key() {
+        let matches = RepairCommand::args().get_matches_from(vec![
+            "repair",
+            "--private-key",
+            "x",
+            "--helper",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--block-height",
+            "0",
+        ]);
+        let response = RepairCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"private-key\")"
+        );
+    }
+
+    #[test]
+    fn test_execute_self_as_helper() {
+        let private_key = TEST_KEYS.key[0].to_wif();
+        let secp = secp256k1::Secp256k1::new();
+        let public_key = PublicKey::from_private_key(&secp, &TEST_KEYS.key[0]).to_string();
+        let matches = RepairCommand::args().get_matches_from(vec![
+            "repair",
+            "--private-key",
+            private_key.as_str(),
+            "--helper",
+            public_key.as_str(),
+            "--block-height",
+            "0",
+        ]);
+        let response = RepairCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"helper should be other signers of the federation\")"
+        );
+    }
+}
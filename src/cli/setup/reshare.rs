@@ -0,0 +1,227 @@
+use crate::cli::setup::dkg::DkgResponse;
+use crate::cli::setup::traits::Response;
+use crate::cli::setup::{passphrase_env_arg, passphrase_fd_arg, passphrase_of};
+use crate::errors::Error;
+use crate::federation::{Federation, Federations};
+use crate::keygen::reshare::Reshare;
+use crate::keygen::run;
+use crate::net::{RedisManager, SignerID};
+use bitcoin::PublicKey;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The response for the holders who are not members of the new federation.
+pub struct LeaveResponse {}
+
+impl Response for LeaveResponse {}
+
+impl fmt::Display for LeaveResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Resharing completed. This signer is not a member of the new federation."
+        )
+    }
+}
+
+pub struct ReshareCommand {}
+
+impl<'a> ReshareCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let public_key: PublicKey = matches
+            .value_of("public-key")
+            .and_then(|key| PublicKey::from_str(key).ok())
+            .ok_or(Error::InvalidArgs("public-key".to_string()))?;
+
+        let federation: Option<Federation> = match matches.value_of("federations-file") {
+            Some(federations_file) => {
+                let federations_toml = std::fs::read_to_string(federations_file)?;
+                let federations = Federations::from_pubkey_and_toml(
+                    &public_key,
+                    &federations_toml,
+                    &passphrase_of(matches)?,
+                )?;
+                Some(federations.last().clone())
+            }
+            None => None,
+        };
+
+        let holders: Vec<PublicKey> = matches
+            .values_of("holder")
+            .ok_or(Error::InvalidArgs("holder".to_string()))?
+            .map(|key| PublicKey::from_str(key).map_err(|_| Error::InvalidKey))
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        let members: Vec<PublicKey> = matches
+            .values_of("new-public-key")
+            .ok_or(Error::InvalidArgs("new-public-key".to_string()))?
+            .map(|key| PublicKey::from_str(key).map_err(|_| Error::InvalidKey))
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        let threshold: u8 = matches
+            .value_of("threshold")
+            .and_then(|t| t.parse::<u8>().ok())
+            .ok_or(Error::InvalidArgs(
+                "threshold should be integer.".to_string(),
+            ))?;
+
+        let block_height: u64 = matches
+            .value_of("block-height")
+            .and_then(|h| h.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs(
+                "block-height should be integer.".to_string(),
+            ))?;
+
+        let aggregated_public_key: PublicKey = match matches.value_of("aggregated-public-key") {
+            Some(key) => PublicKey::from_str(key)
+                .map_err(|_| Error::InvalidArgs("aggregated-public-key".to_string()))?,
+            None => federation
+                .as_ref()
+                .map(|f| f.aggregated_public_key())
+                .ok_or(Error::InvalidArgs(
+                    "aggregated-public-key is required without federations-file".to_string(),
+                ))?,
+        };
+
+        let timeout: u64 = matches
+            .value_of("timeout")
+            .and_then(|t| t.parse::<u64>().ok())
+            .ok_or(Error::InvalidArgs("timeout should be integer.".to_string()))?;
+
+        let mut reshare = Reshare::new(
+            SignerID::new(public_key),
+            federation.as_ref(),
+            holders,
+            members,
+            threshold,
+            block_height,
+            aggregated_public_key,
+        )?;
+
+        let host = matches.value_of("redis-host").unwrap_or_default();
+        let port = matches.value_of("redis-port").unwrap_or_default();
+        let mut redis_manager = RedisManager::new(host.to_string(), port.to_string());
+        redis_manager.test_connection()?;
+
+        let federation = run(
+            &mut reshare,
+            &mut redis_manager,
+            Duration::from_secs(timeout),
+        )?;
+        match federation {
+            Some(federation) => {
+                federation.validate()?;
+                Ok(Box::new(DkgResponse::new(federation)))
+            }
+            None => Ok(Box::new(LeaveResponse {})),
+        }
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("reshare").args(&[
+            Arg::with_name("public-key")
+                .long("public-key")
+                .required(true)
+                .takes_value(true)
+                .help("compressed public key of this signer with a hex format string"),
+            Arg::with_name("federations-file")
+                .long("federations-file")
+                .takes_value(true)
+                .help("path to federations.toml. The shares of the last federation are reshared. Required for the holders"),
+            Arg::with_name("holder")
+                .long("holder")
+                .required(true)
+                .multiple(true)
+                .takes_value(true)
+                .help("compressed public key of the signer of the current federation who reshares the secret. At least threshold signers are required"),
+            Arg::with_name("new-public-key")
+                .long("new-public-key")
+                .required(true)
+                .multiple(true)
+                .takes_value(true)
+                .help("compressed public key of the signer of the new federation"),
+            Arg::with_name("threshold")
+                .long("threshold")
+                .required(true)
+                .takes_value(true)
+                .help("the threshold of the new federation"),
+            Arg::with_name("block-height")
+                .long("block-height")
+                .required(true)
+                .takes_value(true)
+                .help("the block height where the new federation gets started to be used"),
+            Arg::with_name("aggregated-public-key")
+                .long("aggregated-public-key")
+                .takes_value(true)
+                .help("the aggregated public key of the current federation. Required without federations-file"),
+            Arg::with_name("redis-host")
+                .long("redis-host")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("host name of the redis server which signers are connected to"),
+            Arg::with_name("redis-port")
+                .long("redis-port")
+                .takes_value(true)
+                .default_value("6379")
+                .help("port number of the redis server which signers are connected to"),
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("300")
+                .help("seconds to wait for all signers to finish the resharing"),
+            passphrase_fd_arg(),
+            passphrase_env_arg(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_without_aggregated_public_key() {
+        let matches = ReshareCommand::args().get_matches_from(vec![
+            "reshare",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--holder",
+            "02ce7edc292d7b747fab2f23584bbafaffde5c8ff17cf689969614441e0527b900",
+            "--new-public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--threshold",
+            "1",
+            "--block-height",
+            "100",
+        ]);
+        let response = ReshareCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"aggregated-public-key is required without federations-file\")"
+        );
+    }
+
+    #[test]
+    fn test_execute_invalid_threshold() {
+        let matches = ReshareCommand::args().get_matches_from(vec![
+            "reshare",
+            "--public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--holder",
+            "02ce7edc292d7b747fab2f23584bbafaffde5c8ff17cf689969614441e0527b900",
+            "--new-public-key",
+            "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca2",
+            "--threshold",
+            "x",
+            "--block-height",
+            "100",
+        ]);
+        let response = ReshareCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"threshold should be integer.\")"
+        );
+    }
+}
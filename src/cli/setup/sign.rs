@@ -63,7 +63,7 @@ impl<'a> SignCommand {
         let block: Block = matches
             .value_of("block")
             .and_then(|s| hex::decode(s).ok())
-            .map(|hex| Block::new(hex))
+            .and_then(|hex| Block::new(hex).ok())
             .ok_or(Error::InvalidArgs("block".to_string()))?;
 
         let node_secret_share: FE = matches
@@ -100,14 +100,14 @@ impl<'a> SignCommand {
         let point = GE::from_bytes(&bytes[1..]).expect("failed to convert to point");
         let priv_shared_keys = SharedKeys {
             y: point,
-            x_i: node_secret_share,
+            x_i: node_secret_share.into(),
         };
 
         let (_, _, local_sig) = Vss::create_local_sig_from_shares(
             &priv_shared_keys,
             index,
             &shared_block_secrets,
-            &block,
+            block.sighash(),
         )?;
 
         let secp = secp256k1::Secp256k1::new();
@@ -0,0 +1,94 @@
+use crate::blockdata::Block;
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use crate::sign::Sign;
+use bitcoin::PublicKey;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+use std::str::FromStr;
+
+pub struct VerifyBlockResponse {}
+
+impl Response for VerifyBlockResponse {}
+
+impl fmt::Display for VerifyBlockResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The proof of the block is valid.")
+    }
+}
+
+pub struct VerifyBlockCommand {}
+
+impl<'a> VerifyBlockCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let block: Block = matches
+            .value_of("block")
+            .and_then(|s| hex::decode(s).ok())
+            .and_then(|hex| Block::new(hex).ok())
+            .ok_or(Error::InvalidArgs("block".to_string()))?;
+
+        let aggregated_public_key: PublicKey = matches
+            .value_of("aggregated-public-key")
+            .and_then(|hex| PublicKey::from_str(hex).ok())
+            .ok_or(Error::InvalidArgs("aggregated-public-key".to_string()))?;
+
+        Sign::verify_block(&block, &aggregated_public_key)?;
+        Ok(Box::new(VerifyBlockResponse {}))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("verifyblock").args(&[
+            Arg::with_name("block")
+                .long("block")
+                .required(true)
+                .takes_value(true)
+                .help("block with the proof as a hex string format"),
+            Arg::with_name("aggregated-public-key")
+                .long("aggregated-public-key")
+                .required(true)
+                .takes_value(true)
+                .help("aggregated public key of the federation which signed the block"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    // The block signed by the key of TEST_KEYS.key[0].
+    const SIGNED_BLOCK: &str = "010000000000000000000000000000000000000000000000000000000000000000000000c1457ff3e5c527e69858108edf0ff1f49eea9c58d8d37300a164b3b4f8c8c7cef1a2e72770d547feae29f2dd40123a97c580d44fd4493de072416d53331997617b96f05d00403855a6ab34d740d8933b95a6ae020edbfd52b23e97ad9f1ca9f46169a473c6088ac16db0d07800341518586917e8933f2799ad032167d6d0c9ebfb9f7e406a2301010000000100000000000000000000000000000000000000000000000000000000000000000000000022210366262690cbdf648132ce0c088962c6361112582364ede120f3780ab73438fc4bffffffff0100f2052a010000002776a9226d70757956774d32596a454d755a4b72687463526b614a787062715447417346484688ac00000000";
+
+    fn execute(block: &str, aggregated_public_key: &str) -> Result<Box<dyn Response>, Error> {
+        let matches = VerifyBlockCommand::args().get_matches_from(vec![
+            "verifyblock",
+            "--block",
+            block,
+            "--aggregated-public-key",
+            aggregated_public_key,
+        ]);
+        VerifyBlockCommand::execute(&matches)
+    }
+
+    #[test]
+    fn test_execute() {
+        let response = execute(SIGNED_BLOCK, &TEST_KEYS.pubkeys()[0].to_string()).unwrap();
+        assert_eq!(format!("{}", response), "The proof of the block is valid.");
+    }
+
+    #[test]
+    fn test_execute_invalid_proof() {
+        let response = execute(SIGNED_BLOCK, &TEST_KEYS.pubkeys()[1].to_string());
+        assert_eq!(format!("{}", response.err().unwrap()), "InvalidSig");
+    }
+
+    #[test]
+    fn test_execute_invalid_aggregated_public_key() {
+        let response = execute(SIGNED_BLOCK, "x");
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidArgs(\"aggregated-public-key\")"
+        );
+    }
+}
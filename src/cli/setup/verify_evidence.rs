@@ -0,0 +1,139 @@
+use crate::cli::setup::traits::Response;
+use crate::errors::Error;
+use crate::net::SignerID;
+use crate::signer_node::equivocation::{EquivocationKind, Evidence};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::fmt;
+
+pub struct VerifyEvidenceResponse {
+    offender: SignerID,
+    kind: EquivocationKind,
+}
+
+impl VerifyEvidenceResponse {
+    fn new(offender: SignerID, kind: EquivocationKind) -> Self {
+        VerifyEvidenceResponse { offender, kind }
+    }
+}
+
+impl Response for VerifyEvidenceResponse {}
+
+impl fmt::Display for VerifyEvidenceResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            EquivocationKind::ConflictingCandidates => "conflicting candidate blocks",
+            EquivocationKind::ConflictingSignatures => "conflicting signatures",
+        };
+        write!(
+            f,
+            "The evidence is valid. offender: {}, kind: {}",
+            self.offender, kind
+        )
+    }
+}
+
+pub struct VerifyEvidenceCommand {}
+
+impl<'a> VerifyEvidenceCommand {
+    pub fn execute(matches: &ArgMatches) -> Result<Box<dyn Response>, Error> {
+        let file = matches
+            .value_of("file")
+            .ok_or(Error::InvalidArgs("file".to_string()))?;
+
+        let evidence: Evidence = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+        evidence.verify()?;
+        Ok(Box::new(VerifyEvidenceResponse::new(
+            evidence.offender,
+            evidence.kind,
+        )))
+    }
+
+    pub fn args<'b>() -> App<'a, 'b> {
+        SubCommand::with_name("verifyevidence").args(&[Arg::with_name("file")
+            .long("file")
+            .required(true)
+            .takes_value(true)
+            .help("path to the evidence of the equivocation written by tapyrus-signerd")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockdata::Block;
+    use crate::net::{Envelope, Message, MessageType};
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    fn candidate(block: Block) -> Envelope {
+        let message = Message {
+            message_type: MessageType::Candidateblock(block),
+            sender_id: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            receiver_id: None,
+        };
+        Envelope::new(&message, Some(&TEST_KEYS.key[0]))
+    }
+
+    fn write_evidence(name: &str, messages: Vec<Envelope>) -> std::path::PathBuf {
+        let evidence = Evidence {
+            kind: EquivocationKind::ConflictingCandidates,
+            block_height: 10,
+            offender: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            messages,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-setup-test-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, serde_json::to_string(&evidence).unwrap()).unwrap();
+        path
+    }
+
+    fn other_candidate() -> Block {
+        let mut header = get_block(0).header().clone();
+        header.time += 1;
+        Block::from_parts(header, get_block(0).get_transactions().unwrap())
+    }
+
+    #[test]
+    fn test_execute() {
+        let path = write_evidence(
+            "verifyevidence",
+            vec![candidate(get_block(0)), candidate(other_candidate())],
+        );
+        let matches = VerifyEvidenceCommand::args().get_matches_from(vec![
+            "verifyevidence",
+            "--file",
+            path.to_str().unwrap(),
+        ]);
+        let response = VerifyEvidenceCommand::execute(&matches).unwrap();
+        assert_eq!(
+            format!("{}", response),
+            format!(
+                "The evidence is valid. offender: {}, kind: conflicting candidate blocks",
+                SignerID::new(TEST_KEYS.pubkeys()[0])
+            )
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_execute_invalid_evidence() {
+        let path = write_evidence(
+            "verifyevidence-invalid",
+            vec![candidate(get_block(0)), candidate(get_block(0))],
+        );
+        let matches = VerifyEvidenceCommand::args().get_matches_from(vec![
+            "verifyevidence",
+            "--file",
+            path.to_str().unwrap(),
+        ]);
+        let response = VerifyEvidenceCommand::execute(&matches);
+        assert_eq!(
+            format!("{}", response.err().unwrap()),
+            "InvalidEvidence(\"messages don't conflict\")"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -4,12 +4,22 @@
 
 use std::str::FromStr;
 
+use crate::keystore::Passphrase;
+use crate::rpc::{
+    RetryPolicy, DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_MAX_RETRIES, DEFAULT_READ_TIMEOUT_SECS,
+};
+use crate::signer_node::approval::{ApprovalEndpoint, ApprovalHook, DEFAULT_APPROVAL_TIMEOUT_SECS};
+use crate::signer_node::audit_log::AuditLog;
+use crate::signer_node::block_policy::BlockPolicy;
+use crate::signer_node::confirmation::DEFAULT_CONFIRMATION_TIMEOUT_SECS;
+use crate::signer_node::peer_scores::ScoringConfig;
 use crate::signer_node::ROUND_INTERVAL_DEFAULT_SECS;
-use bitcoin::{Address, PublicKey};
+use bitcoin::{Address, PrivateKey, PublicKey, Script};
 use clap::{App, Arg};
 use log;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub const OPTION_NAME_CONFIG: &str = "config";
 
@@ -17,12 +27,16 @@ pub const OPTION_NAME_CONFIG: &str = "config";
 pub const OPTION_NAME_TO_ADDRESS: &str = "coinbase_pay_to_address";
 pub const OPTION_NAME_PUBLIC_KEY: &str = "publickey";
 pub const OPTION_NAME_FEDERATIONS_FILE: &str = "federations-file";
+pub const OPTION_NAME_PASSPHRASE_FD: &str = "passphrase-fd";
+pub const OPTION_NAME_PASSPHRASE_ENV: &str = "passphrase-env";
+pub const OPTION_NAME_SHARE_SIGNER_SOCKET: &str = "share-signer-socket";
 
 /// # RPC Config
 pub const OPTION_NAME_RPC_ENDPOINT_HOST: &str = "rpc_endpoint_host";
 pub const OPTION_NAME_RPC_ENDPOINT_PORT: &str = "rpc_endpoint_port";
 pub const OPTION_NAME_RPC_ENDPOINT_USER: &str = "rpc_endpoint_user";
 pub const OPTION_NAME_RPC_ENDPOINT_PASS: &str = "rpc_endpoint_pass";
+pub const OPTION_NAME_RPC_ENDPOINT_COOKIE_FILE: &str = "rpc_endpoint_cookie_file";
 
 /// # Redis Config
 pub const OPTION_NAME_REDIS_HOST: &str = "redis_host";
@@ -40,6 +54,7 @@ pub const OPTION_NAME_PID: &str = "pid";
 pub const OPTION_NAME_LOG_FILE: &str = "log_file";
 /// Others
 pub const OPTION_NAME_SKIP_WAITING_IBD: &str = "skip_waiting_ibd";
+pub const OPTION_NAME_REVEAL_SECRETS: &str = "reveal_secrets";
 
 /// # Default Values
 pub const DEFAULT_RPC_HOST: &str = "127.0.0.1";
@@ -74,6 +89,14 @@ struct SignerToml {
     publickey: Option<String>,
     #[serde(rename = "federations-file")]
     federations_file: Option<String>,
+    #[serde(rename = "passphrase-env")]
+    passphrase_env: Option<String>,
+    #[serde(rename = "share-signer-socket")]
+    share_signer_socket: Option<String>,
+    #[serde(rename = "private-key-env")]
+    private_key_env: Option<String>,
+    #[serde(rename = "evidence-dir")]
+    evidence_dir: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,6 +109,42 @@ pub struct RpcToml {
     rpc_endpoint_user: Option<String>,
     #[serde(rename = "rpc-endpoint-pass")]
     rpc_endpoint_pass: Option<String>,
+    #[serde(rename = "rpc-endpoint-cookie-file")]
+    rpc_endpoint_cookie_file: Option<String>,
+    #[serde(rename = "rpc-endpoint-tls")]
+    rpc_endpoint_tls: Option<bool>,
+    endpoints: Option<Vec<RpcEndpoint>>,
+    #[serde(rename = "backup-endpoints")]
+    backup_endpoints: Option<Vec<RpcEndpoint>>,
+    #[serde(rename = "confirmation-timeout")]
+    confirmation_timeout: Option<u64>,
+    #[serde(rename = "tls-ca-file")]
+    tls_ca_file: Option<String>,
+    #[serde(rename = "connect-timeout")]
+    connect_timeout: Option<u64>,
+    #[serde(rename = "read-timeout")]
+    read_timeout: Option<u64>,
+    retries: Option<u32>,
+}
+
+/// The endpoint of the Tapyrus Core RPC.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RpcEndpoint {
+    pub host: String,
+    pub port: u32,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    #[serde(rename = "cookie-file")]
+    pub cookie_file: Option<String>,
+    #[serde(default)]
+    pub tls: bool,
+}
+
+impl RpcEndpoint {
+    pub fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}:{}", scheme, self.host, self.port)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -106,18 +165,73 @@ pub struct GeneralToml {
     log_quiet: Option<bool>,
     #[serde(rename = "skip-waiting-ibd")]
     skip_waiting_ibd: Option<bool>,
+    #[serde(rename = "reveal-secrets")]
+    reveal_secrets: Option<bool>,
     daemon: Option<bool>,
     pid: Option<String>,
     #[serde(rename = "log-file")]
     log_file: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PolicyToml {
+    #[serde(rename = "max-time-drift")]
+    max_time_drift: Option<i64>,
+    #[serde(rename = "min-time-drift")]
+    min_time_drift: Option<i64>,
+    #[serde(rename = "coinbase-script")]
+    coinbase_script: Option<String>,
+    #[serde(rename = "max-tx-count")]
+    max_tx_count: Option<usize>,
+    #[serde(rename = "max-block-size")]
+    max_block_size: Option<usize>,
+    #[serde(rename = "denylisted-scripts")]
+    denylisted_scripts: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApprovalToml {
+    endpoint: Option<String>,
+    timeout: Option<u64>,
+    #[serde(rename = "fail-open")]
+    fail_open: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditToml {
+    #[serde(rename = "log-file")]
+    log_file: Option<String>,
+    #[serde(rename = "private-key-env")]
+    private_key_env: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminToml {
+    socket: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PeerScoringToml {
+    #[serde(rename = "ban-threshold")]
+    ban_threshold: Option<f64>,
+    #[serde(rename = "ban-rounds")]
+    ban_rounds: Option<u64>,
+    #[serde(rename = "decay-half-life")]
+    decay_half_life: Option<u64>,
+}
+
 #[derive(Debug, Deserialize, Default)]
 struct ConfigToml {
     signer: Option<SignerToml>,
     rpc: Option<RpcToml>,
     redis: Option<RedisToml>,
     general: Option<GeneralToml>,
+    policy: Option<PolicyToml>,
+    approval: Option<ApprovalToml>,
+    audit: Option<AuditToml>,
+    admin: Option<AdminToml>,
+    #[serde(rename = "peer-scoring")]
+    peer_scoring: Option<PeerScoringToml>,
 }
 
 pub struct CommandArgs<'a> {
@@ -129,6 +243,9 @@ pub struct SignerCommandArgs<'a> {
     to_address: Option<&'a str>,
     public_key: Option<&'a str>,
     federations_file: Option<&'a str>,
+    passphrase_fd: Option<&'a str>,
+    passphrase_env: Option<&'a str>,
+    share_signer_socket: Option<&'a str>,
 }
 
 pub struct SignerConfig<'a> {
@@ -178,6 +295,68 @@ impl<'a> SignerConfig<'a> {
             .and_then(|s| Some(Path::new(s)))
             .expect("Must be specified federations-file.")
     }
+
+    /// Returns the source of the passphrase of the encrypted federations file. The file
+    /// descriptor in the command line args takes precedence over the environment variable.
+    pub fn passphrase(&self) -> Passphrase {
+        if let Some(fd) = self.command_args.passphrase_fd {
+            return match fd.parse::<i32>() {
+                Ok(fd) => Passphrase::Fd(fd),
+                Err(e) => panic!(format!(
+                    "'{}' is invalid passphrase-fd. error msg: {:?}",
+                    fd, e
+                )),
+            };
+        }
+        let value_within_config: Option<&str> = self
+            .toml_config
+            .and_then(|config| config.passphrase_env.as_ref())
+            .map(|p| p as &str);
+        self.command_args
+            .passphrase_env
+            .or(value_within_config)
+            .map(|name| Passphrase::Env(name.to_string()))
+            .unwrap_or(Passphrase::Prompt)
+    }
+
+    pub fn share_signer_socket(&self) -> Option<&Path> {
+        let value_within_config: Option<&str> = self
+            .toml_config
+            .and_then(|config| config.share_signer_socket.as_ref())
+            .map(|p| p as &str);
+        self.command_args
+            .share_signer_socket
+            .or(value_within_config)
+            .map(|s| Path::new(s))
+    }
+
+    /// Returns the private key to sign the messages to the signer network. It is read from the
+    /// environment variable which is set only in the config file.
+    pub fn private_key(&self) -> Option<PrivateKey> {
+        self.toml_config
+            .and_then(|config| config.private_key_env.as_ref())
+            .map(|name| private_key_from_env(name))
+    }
+
+    /// The directory to write the evidence of the equivocation. This can be set only in the config
+    /// file.
+    pub fn evidence_dir(&self) -> Option<&Path> {
+        self.toml_config
+            .and_then(|config| config.evidence_dir.as_ref())
+            .map(|s| Path::new(s))
+    }
+}
+
+fn private_key_from_env(name: &str) -> PrivateKey {
+    std::env::var(name)
+        .ok()
+        .and_then(|wif| PrivateKey::from_wif(&wif).ok())
+        .unwrap_or_else(|| {
+            panic!(format!(
+                "Environment variable '{}' should have the private key with WIF format.",
+                name
+            ))
+        })
 }
 
 pub struct RpcCommandArgs<'a> {
@@ -185,6 +364,7 @@ pub struct RpcCommandArgs<'a> {
     pub port: Option<&'a str>,
     pub username: Option<&'a str>,
     pub password: Option<&'a str>,
+    pub cookie_file: Option<&'a str>,
 }
 
 pub struct RpcConfig<'a> {
@@ -225,6 +405,73 @@ impl<'a> RpcConfig<'a> {
             .map(|s| s as &str);
         self.command_args.password.or(toml_value)
     }
+    pub fn cookie_file(&'a self) -> Option<&'a str> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_endpoint_cookie_file.as_ref())
+            .map(|s| s as &str);
+        self.command_args.cookie_file.or(toml_value)
+    }
+    /// TLS can be enabled only in the config file.
+    pub fn tls(&'a self) -> bool {
+        self.toml_config
+            .and_then(|config| config.rpc_endpoint_tls)
+            .unwrap_or_default()
+    }
+    /// Returns the endpoint specified by the options above followed by the additional endpoints.
+    /// The additional endpoints can be set only in the config file.
+    pub fn endpoints(&'a self) -> Vec<RpcEndpoint> {
+        let main = RpcEndpoint {
+            host: self.host().to_string(),
+            port: self.port(),
+            user: self.user_name().map(str::to_string),
+            pass: self.password().map(str::to_string),
+            cookie_file: self.cookie_file().map(str::to_string),
+            tls: self.tls(),
+        };
+        let additional = self
+            .toml_config
+            .and_then(|config| config.endpoints.as_ref())
+            .into_iter()
+            .flatten()
+            .cloned();
+        std::iter::once(main).chain(additional).collect()
+    }
+    /// The backup endpoints can be set only in the config file.
+    pub fn backup_endpoints(&'a self) -> &'a [RpcEndpoint] {
+        self.toml_config
+            .and_then(|config| config.backup_endpoints.as_ref())
+            .map(|endpoints| &endpoints[..])
+            .unwrap_or(&[])
+    }
+    /// The confirmation timeout can be set only in the config file.
+    pub fn confirmation_timeout(&'a self) -> Duration {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.confirmation_timeout);
+        Duration::from_secs(toml_value.unwrap_or(DEFAULT_CONFIRMATION_TIMEOUT_SECS))
+    }
+    /// The settings below can be set only in the config file and apply to all endpoints.
+    pub fn tls_ca_file(&'a self) -> Option<&'a Path> {
+        self.toml_config
+            .and_then(|config| config.tls_ca_file.as_ref())
+            .map(|s| Path::new(s))
+    }
+    pub fn connect_timeout(&'a self) -> Duration {
+        let toml_value = self.toml_config.and_then(|config| config.connect_timeout);
+        Duration::from_secs(toml_value.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS))
+    }
+    pub fn read_timeout(&'a self) -> Duration {
+        let toml_value = self.toml_config.and_then(|config| config.read_timeout);
+        Duration::from_secs(toml_value.unwrap_or(DEFAULT_READ_TIMEOUT_SECS))
+    }
+    pub fn retry_policy(&'a self) -> RetryPolicy {
+        let toml_value = self.toml_config.and_then(|config| config.retries);
+        RetryPolicy {
+            max_retries: toml_value.unwrap_or(DEFAULT_MAX_RETRIES),
+            ..RetryPolicy::default()
+        }
+    }
 }
 
 pub struct RedisCommandArgs<'a> {
@@ -263,6 +510,7 @@ pub struct GeneralCommandArgs<'a> {
     log_quiet: bool,
     log_level: Option<&'a str>,
     skip_waiting_ibd: bool,
+    reveal_secrets: bool,
     daemon: bool,
     pid: Option<&'a str>,
     log_file: Option<&'a str>,
@@ -306,6 +554,13 @@ impl<'a> GeneralConfig<'a> {
             .unwrap_or_default();
         self.command_args.skip_waiting_ibd || toml_value
     }
+    pub fn reveal_secrets(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.reveal_secrets)
+            .unwrap_or_default();
+        self.command_args.reveal_secrets || toml_value
+    }
     pub fn daemon(&'a self) -> bool {
         let toml_value = self
             .toml_config
@@ -337,6 +592,148 @@ impl<'a> GeneralConfig<'a> {
     }
 }
 
+/// The block policy can be set only in the config file.
+pub struct PolicyConfig<'a> {
+    toml_config: Option<&'a PolicyToml>,
+}
+
+impl<'a> PolicyConfig<'a> {
+    pub fn block_policy(&self) -> BlockPolicy {
+        let config = match self.toml_config {
+            Some(config) => config,
+            None => return BlockPolicy::default(),
+        };
+        BlockPolicy {
+            max_time_drift: config.max_time_drift,
+            min_time_drift: config.min_time_drift,
+            coinbase_script: config
+                .coinbase_script
+                .as_ref()
+                .map(|s| to_script(s, "coinbase-script")),
+            max_tx_count: config.max_tx_count,
+            max_block_size: config.max_block_size,
+            denylisted_scripts: config
+                .denylisted_scripts
+                .iter()
+                .flatten()
+                .map(|s| to_script(s, "denylisted-scripts"))
+                .collect(),
+        }
+    }
+}
+
+fn to_script(hex: &str, name: &str) -> Script {
+    match hex::decode(hex) {
+        Ok(bytes) => Script::from(bytes),
+        Err(e) => panic!(format!(
+            "'{}' is invalid script in {}. error msg: {:?}",
+            hex, name, e
+        )),
+    }
+}
+
+/// The approval hook can be set only in the config file.
+pub struct ApprovalConfig<'a> {
+    toml_config: Option<&'a ApprovalToml>,
+}
+
+impl<'a> ApprovalConfig<'a> {
+    /// Returns None if the endpoint is not set.
+    pub fn approval_hook(&self) -> Option<ApprovalHook> {
+        let config = self.toml_config?;
+        let endpoint = config.endpoint.as_ref()?;
+        let endpoint = match ApprovalEndpoint::parse(endpoint) {
+            Ok(endpoint) => endpoint,
+            Err(e) => panic!(format!(
+                "'{}' is invalid approval endpoint. error msg: {:?}",
+                endpoint, e
+            )),
+        };
+        Some(ApprovalHook::new(
+            endpoint,
+            Duration::from_secs(config.timeout.unwrap_or(DEFAULT_APPROVAL_TIMEOUT_SECS)),
+            config.fail_open.unwrap_or_default(),
+        ))
+    }
+}
+
+/// The audit log can be set only in the config file.
+pub struct AuditConfig<'a> {
+    toml_config: Option<&'a AuditToml>,
+}
+
+impl<'a> AuditConfig<'a> {
+    /// Returns None if the log file is not set. The private key of the signer to sign the records
+    /// is read from the environment variable.
+    pub fn audit_log(&self) -> Option<AuditLog> {
+        let config = self.toml_config?;
+        let log_file = config.log_file.as_ref()?;
+        let private_key_env = config
+            .private_key_env
+            .as_ref()
+            .expect("private-key-env is required to sign the audit log.");
+        let private_key = private_key_from_env(private_key_env);
+        match AuditLog::open(PathBuf::from(log_file), private_key) {
+            Ok(audit_log) => Some(audit_log),
+            Err(e) => panic!(format!(
+                "Failed to open the audit log '{}'. error msg: {:?}",
+                log_file, e
+            )),
+        }
+    }
+}
+
+/// The admin interface can be set only in the config file.
+pub struct AdminConfig<'a> {
+    toml_config: Option<&'a AdminToml>,
+}
+
+impl<'a> AdminConfig<'a> {
+    /// The path to the Unix domain socket of the admin interface. Returns None if it is not set.
+    pub fn socket(&self) -> Option<&Path> {
+        self.toml_config
+            .and_then(|config| config.socket.as_ref())
+            .map(|s| Path::new(s))
+    }
+}
+
+/// The peer scoring can be set only in the config file.
+pub struct PeerScoringConfig<'a> {
+    toml_config: Option<&'a PeerScoringToml>,
+}
+
+impl<'a> PeerScoringConfig<'a> {
+    /// The peer scoring is enabled only if the `[peer-scoring]` section is set.
+    pub fn is_enabled(&self) -> bool {
+        self.toml_config.is_some()
+    }
+
+    pub fn scoring_config(&self) -> ScoringConfig {
+        let default = ScoringConfig::default();
+        let config = match self.toml_config {
+            Some(config) => config,
+            None => return default,
+        };
+        let ban_threshold = config.ban_threshold.unwrap_or(default.ban_threshold);
+        if !(ban_threshold > 0.0) {
+            panic!(format!(
+                "ban-threshold should be positive. value: {}",
+                ban_threshold
+            ));
+        }
+        let decay_half_life = match config.decay_half_life {
+            Some(0) => panic!("decay-half-life should be positive."),
+            Some(secs) => Duration::from_secs(secs),
+            None => default.decay_half_life,
+        };
+        ScoringConfig {
+            ban_threshold,
+            ban_rounds: config.ban_rounds.unwrap_or(default.ban_rounds),
+            decay_half_life,
+        }
+    }
+}
+
 impl<'a> CommandArgs<'a> {
     /// constructor.
     /// Basically, search config file as file name signer_config.toml in current dir.
@@ -374,6 +771,9 @@ impl<'a> CommandArgs<'a> {
                 to_address: self.matches.value_of(OPTION_NAME_TO_ADDRESS),
                 public_key: self.matches.value_of(OPTION_NAME_PUBLIC_KEY),
                 federations_file: self.matches.value_of(OPTION_NAME_FEDERATIONS_FILE),
+                passphrase_fd: self.matches.value_of(OPTION_NAME_PASSPHRASE_FD),
+                passphrase_env: self.matches.value_of(OPTION_NAME_PASSPHRASE_ENV),
+                share_signer_socket: self.matches.value_of(OPTION_NAME_SHARE_SIGNER_SOCKET),
             },
             toml_config: self.config.as_ref().and_then(|c| c.signer.as_ref()),
         }
@@ -386,6 +786,7 @@ impl<'a> CommandArgs<'a> {
                 port: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_PORT),
                 username: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_USER),
                 password: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_PASS),
+                cookie_file: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_COOKIE_FILE),
             },
             toml_config: self.config.as_ref().and_then(|c| c.rpc.as_ref()),
         }
@@ -407,6 +808,7 @@ impl<'a> CommandArgs<'a> {
                 log_level: self.matches.value_of(OPTION_NAME_LOG_LEVEL),
                 log_quiet: self.matches.is_present(OPTION_NAME_LOG_QUIET),
                 skip_waiting_ibd: self.matches.is_present(OPTION_NAME_SKIP_WAITING_IBD),
+                reveal_secrets: self.matches.is_present(OPTION_NAME_REVEAL_SECRETS),
                 daemon: self.matches.is_present(OPTION_NAME_DAEMON),
                 pid: self.matches.value_of(OPTION_NAME_PID),
                 log_file: self.matches.value_of(OPTION_NAME_LOG_FILE),
@@ -414,6 +816,36 @@ impl<'a> CommandArgs<'a> {
             toml_config: self.config.as_ref().and_then(|c| c.general.as_ref()),
         }
     }
+
+    pub fn policy_config(&self) -> PolicyConfig {
+        PolicyConfig {
+            toml_config: self.config.as_ref().and_then(|c| c.policy.as_ref()),
+        }
+    }
+
+    pub fn approval_config(&self) -> ApprovalConfig {
+        ApprovalConfig {
+            toml_config: self.config.as_ref().and_then(|c| c.approval.as_ref()),
+        }
+    }
+
+    pub fn audit_config(&self) -> AuditConfig {
+        AuditConfig {
+            toml_config: self.config.as_ref().and_then(|c| c.audit.as_ref()),
+        }
+    }
+
+    pub fn admin_config(&self) -> AdminConfig {
+        AdminConfig {
+            toml_config: self.config.as_ref().and_then(|c| c.admin.as_ref()),
+        }
+    }
+
+    pub fn peer_scoring_config(&self) -> PeerScoringConfig {
+        PeerScoringConfig {
+            toml_config: self.config.as_ref().and_then(|c| c.peer_scoring.as_ref()),
+        }
+    }
 }
 
 fn read_config(file_path: &str) -> Result<ConfigToml, crate::errors::Error> {
@@ -444,6 +876,18 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
             .long("federations-file")
             .value_name("FILE")
             .help("The path to TOML file of the federations of the chain."))
+        .arg(Arg::with_name(OPTION_NAME_PASSPHRASE_FD)
+            .long("passphrase-fd")
+            .value_name("FD")
+            .help("The file descriptor to read the passphrase of the encrypted federations file from."))
+        .arg(Arg::with_name(OPTION_NAME_PASSPHRASE_ENV)
+            .long("passphrase-env")
+            .value_name("NAME")
+            .help("The name of the environment variable which has the passphrase of the encrypted federations file. If neither --passphrase-fd nor --passphrase-env is specified, the passphrase is asked on the terminal."))
+        .arg(Arg::with_name(OPTION_NAME_SHARE_SIGNER_SOCKET)
+            .long("share-signer-socket")
+            .value_name("FILE")
+            .help("The path to the Unix domain socket of tapyrus-share-signer. If specified, the federations and the signature shares are got from it, and the federations file is not read."))
         .arg(Arg::with_name(OPTION_NAME_RPC_ENDPOINT_HOST)
             .long("rpc-host")
             .value_name("HOST_NAME or IP")
@@ -460,6 +904,10 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
             .long("rpc-pass")
             .value_name("PASS")
             .help("TapyrusCore RPC user password."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_ENDPOINT_COOKIE_FILE)
+            .long("rpc-cookie-file")
+            .value_name("FILE")
+            .help("The path to the .cookie file of TapyrusCore RPC. It is used instead of the user name and the password."))
         .arg(Arg::with_name(OPTION_NAME_REDIS_HOST)
             .long("redis-host")
             .value_name("HOST_NAME or IP")
@@ -488,6 +936,9 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
         .arg(Arg::with_name(OPTION_NAME_SKIP_WAITING_IBD)
             .long("skip-waiting-ibd")
             .help("This flag make signer node don't waiting connected Tapyrus full node finishes Initial Block Download when signer node started. When block creation stopped much time, The status of Tapyrus full node changes to progressing Initial Block Download. In this case, block creation is never resume, because signer node waits the status is back to non-IBD. So you can use this flag to start signer node with ignore tapyrus full node status."))
+        .arg(Arg::with_name(OPTION_NAME_REVEAL_SECRETS)
+            .long("reveal-secrets")
+            .help("Print the secret values such as the block keys and the secret shares in the logs. It is only for debugging. Never use this flag in production."))
         .arg(Arg::with_name(OPTION_NAME_DAEMON)
             .long("daemon")
             .help("Daemonize the Tapyrus Signer node process."))
@@ -628,6 +1079,10 @@ fn test_invalid_public_key() {
                 to_address: None,
                 publickey: Some("aabbccdd".to_string()),
                 federations_file: None,
+                passphrase_env: None,
+                share_signer_socket: None,
+                private_key_env: None,
+                evidence_dir: None,
             }),
             ..ConfigToml::default()
         }),
@@ -657,9 +1112,449 @@ fn test_invalid_to_address() {
                 to_address: Some("aabbccdd".to_string()),
                 publickey: None,
                 federations_file: None,
+                passphrase_env: None,
+                share_signer_socket: None,
+                private_key_env: None,
+                evidence_dir: None,
             }),
             ..ConfigToml::default()
         }),
     };
     let _to_address = args.signer_config().to_address();
 }
+
+#[test]
+fn test_passphrase() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.signer_config().passphrase(), Passphrase::Prompt);
+
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "-c=hoge.toml",
+        "--passphrase-env=SIGNER_PASSPHRASE",
+    ]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(
+        args.signer_config().passphrase(),
+        Passphrase::Env("SIGNER_PASSPHRASE".to_string())
+    );
+
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "-c=hoge.toml",
+        "--passphrase-fd=3",
+        "--passphrase-env=SIGNER_PASSPHRASE",
+    ]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.signer_config().passphrase(), Passphrase::Fd(3));
+}
+
+#[test]
+fn test_share_signer_socket() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.signer_config().share_signer_socket(), None);
+
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "-c=hoge.toml",
+        "--share-signer-socket=/tmp/tapyrus-share-signer.sock",
+    ]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(
+        args.signer_config().share_signer_socket(),
+        Some(Path::new("/tmp/tapyrus-share-signer.sock"))
+    );
+}
+
+#[test]
+fn test_block_policy() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.policy_config().block_policy(), BlockPolicy::default());
+
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[policy]
+max-time-drift = 60
+min-time-drift = -600
+coinbase-script = "76a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac"
+max-block-size = 1000000
+denylisted-scripts = ["6a", "a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b287"]
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let policy = args.policy_config().block_policy();
+    assert_eq!(policy.max_time_drift, Some(60));
+    assert_eq!(policy.min_time_drift, Some(-600));
+    assert_eq!(
+        policy.coinbase_script,
+        Some(Script::from(
+            hex::decode("76a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac").unwrap()
+        ))
+    );
+    assert_eq!(policy.max_tx_count, None);
+    assert_eq!(policy.max_block_size, Some(1000000));
+    assert_eq!(policy.denylisted_scripts.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "'zz' is invalid script in coinbase-script.")]
+fn test_invalid_coinbase_script() {
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str("[policy]\ncoinbase-script = \"zz\"\n").unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let _policy = args.policy_config().block_policy();
+}
+
+#[test]
+fn test_approval_hook() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.approval_config().approval_hook(), None);
+
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[approval]
+endpoint = "unix:/tmp/approval.sock"
+timeout = 3
+fail-open = true
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    assert_eq!(
+        args.approval_config().approval_hook(),
+        Some(ApprovalHook::new(
+            ApprovalEndpoint::Unix(PathBuf::from("/tmp/approval.sock")),
+            Duration::from_secs(3),
+            true
+        ))
+    );
+}
+
+#[test]
+#[should_panic(expected = "'ftp://127.0.0.1/' is invalid approval endpoint.")]
+fn test_invalid_approval_endpoint() {
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml =
+        toml::from_str("[approval]\nendpoint = \"ftp://127.0.0.1/\"\n").unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let _hook = args.approval_config().approval_hook();
+}
+
+#[test]
+fn test_rpc_backup_endpoints() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert!(args.rpc_config().backup_endpoints().is_empty());
+    assert_eq!(
+        args.rpc_config().confirmation_timeout(),
+        Duration::from_secs(DEFAULT_CONFIRMATION_TIMEOUT_SECS)
+    );
+
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[rpc]
+confirmation-timeout = 30
+
+[[rpc.backup-endpoints]]
+host = "192.168.0.2"
+port = 2377
+user = "user"
+pass = "pass"
+
+[[rpc.backup-endpoints]]
+host = "192.168.0.3"
+port = 12381
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    assert_eq!(
+        args.rpc_config().backup_endpoints(),
+        &[
+            RpcEndpoint {
+                host: "192.168.0.2".to_string(),
+                port: 2377,
+                user: Some("user".to_string()),
+                pass: Some("pass".to_string()),
+                cookie_file: None,
+                tls: false,
+            },
+            RpcEndpoint {
+                host: "192.168.0.3".to_string(),
+                port: 12381,
+                user: None,
+                pass: None,
+                cookie_file: None,
+                tls: false,
+            },
+        ]
+    );
+    assert_eq!(
+        args.rpc_config().confirmation_timeout(),
+        Duration::from_secs(30)
+    );
+}
+
+#[test]
+fn test_rpc_endpoints() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(
+        args.rpc_config().endpoints(),
+        vec![RpcEndpoint {
+            host: DEFAULT_RPC_HOST.to_string(),
+            port: DEFAULT_RPC_PORT.parse().unwrap(),
+            user: None,
+            pass: None,
+            cookie_file: None,
+            tls: false,
+        }]
+    );
+
+    let matches = get_options().get_matches_from(vec!["node", "--rpc-user=test"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[rpc]
+rpc-endpoint-host = "192.168.0.1"
+rpc-endpoint-port = 2377
+
+[[rpc.endpoints]]
+host = "192.168.0.2"
+port = 12381
+user = "user"
+pass = "pass"
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let endpoints = args.rpc_config().endpoints();
+    assert_eq!(endpoints.len(), 2);
+    assert_eq!(endpoints[0].url(), "http://192.168.0.1:2377");
+    assert_eq!(endpoints[0].user, Some("test".to_string()));
+    assert_eq!(endpoints[1].url(), "http://192.168.0.2:12381");
+    assert_eq!(endpoints[1].user, Some("user".to_string()));
+    assert_eq!(endpoints[1].pass, Some("pass".to_string()));
+}
+
+#[test]
+fn test_rpc_connection_settings() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.rpc_config().cookie_file(), None);
+    assert_eq!(args.rpc_config().tls(), false);
+    assert_eq!(args.rpc_config().tls_ca_file(), None);
+    assert_eq!(
+        args.rpc_config().connect_timeout(),
+        Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS)
+    );
+    assert_eq!(
+        args.rpc_config().read_timeout(),
+        Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS)
+    );
+    assert_eq!(args.rpc_config().retry_policy(), RetryPolicy::default());
+
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "--rpc-cookie-file=/var/lib/tapyrus/prod-1/.cookie",
+    ]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[rpc]
+rpc-endpoint-host = "tapyrus.example.com"
+rpc-endpoint-tls = true
+tls-ca-file = "/etc/tapyrus-signer/ca.pem"
+connect-timeout = 3
+read-timeout = 10
+retries = 0
+
+[[rpc.endpoints]]
+host = "192.168.0.2"
+port = 2377
+cookie-file = "/mnt/node2/.cookie"
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let endpoints = args.rpc_config().endpoints();
+    assert_eq!(endpoints[0].url(), "https://tapyrus.example.com:2377");
+    assert_eq!(
+        endpoints[0].cookie_file,
+        Some("/var/lib/tapyrus/prod-1/.cookie".to_string())
+    );
+    assert_eq!(endpoints[1].url(), "http://192.168.0.2:2377");
+    assert_eq!(
+        endpoints[1].cookie_file,
+        Some("/mnt/node2/.cookie".to_string())
+    );
+    assert_eq!(
+        args.rpc_config().tls_ca_file(),
+        Some(Path::new("/etc/tapyrus-signer/ca.pem"))
+    );
+    assert_eq!(args.rpc_config().connect_timeout(), Duration::from_secs(3));
+    assert_eq!(args.rpc_config().read_timeout(), Duration::from_secs(10));
+    assert_eq!(args.rpc_config().retry_policy().max_retries, 0);
+}
+
+#[test]
+fn test_audit_log() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert!(args.audit_config().audit_log().is_none());
+
+    std::env::set_var(
+        "TAPYRUS_SIGNER_TEST_AUDIT_PRIVATE_KEY",
+        "KwUKaA3KgtRhCsioNWZQfC6Nd6vPNTXwgmqcStewZ3KdonmP3k43",
+    );
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[audit]
+log-file = "/tmp/tapyrus-signer-test-audit.log"
+private-key-env = "TAPYRUS_SIGNER_TEST_AUDIT_PRIVATE_KEY"
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    assert!(args.audit_config().audit_log().is_some());
+}
+
+#[test]
+#[should_panic(
+    expected = "Environment variable 'TAPYRUS_SIGNER_TEST_AUDIT_NO_PRIVATE_KEY' should have the private key with WIF format."
+)]
+fn test_audit_log_without_private_key() {
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[audit]
+log-file = "/tmp/tapyrus-signer-test-audit.log"
+private-key-env = "TAPYRUS_SIGNER_TEST_AUDIT_NO_PRIVATE_KEY"
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let _audit_log = args.audit_config().audit_log();
+}
+
+#[test]
+fn test_signer_private_key_and_evidence_dir() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.signer_config().private_key(), None);
+    assert_eq!(args.signer_config().evidence_dir(), None);
+
+    std::env::set_var(
+        "TAPYRUS_SIGNER_TEST_SIGNER_PRIVATE_KEY",
+        "KwUKaA3KgtRhCsioNWZQfC6Nd6vPNTXwgmqcStewZ3KdonmP3k43",
+    );
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[signer]
+private-key-env = "TAPYRUS_SIGNER_TEST_SIGNER_PRIVATE_KEY"
+evidence-dir = "/var/lib/tapyrus-signer/evidence"
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    assert_eq!(
+        args.signer_config().private_key(),
+        Some(PrivateKey::from_wif("KwUKaA3KgtRhCsioNWZQfC6Nd6vPNTXwgmqcStewZ3KdonmP3k43").unwrap())
+    );
+    assert_eq!(
+        args.signer_config().evidence_dir(),
+        Some(Path::new("/var/lib/tapyrus-signer/evidence"))
+    );
+}
+
+#[test]
+fn test_admin_and_peer_scoring() {
+    let matches = get_options().get_matches_from(vec!["node", "-c=hoge.toml"]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert_eq!(args.admin_config().socket(), None);
+    assert!(!args.peer_scoring_config().is_enabled());
+    assert_eq!(
+        args.peer_scoring_config().scoring_config(),
+        ScoringConfig::default()
+    );
+
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str(
+        r#"
+[admin]
+socket = "/tmp/tapyrus-signer-admin.sock"
+
+[peer-scoring]
+ban-threshold = 200
+ban-rounds = 5
+"#,
+    )
+    .unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    assert_eq!(
+        args.admin_config().socket(),
+        Some(Path::new("/tmp/tapyrus-signer-admin.sock"))
+    );
+    assert!(args.peer_scoring_config().is_enabled());
+    assert_eq!(
+        args.peer_scoring_config().scoring_config(),
+        ScoringConfig {
+            ban_threshold: 200.0,
+            ban_rounds: 5,
+            decay_half_life: ScoringConfig::default().decay_half_life,
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "decay-half-life should be positive.")]
+fn test_invalid_decay_half_life() {
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let config: ConfigToml = toml::from_str("[peer-scoring]\ndecay-half-life = 0\n").unwrap();
+    let args = CommandArgs {
+        matches,
+        config: Some(config),
+    };
+    let _config = args.peer_scoring_config().scoring_config();
+}
@@ -0,0 +1,584 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Elliptic curve arithmetic of the signing hot path.
+//!
+//! The verification of the VSS shares and the local signatures are written over the `Backend`
+//! trait, so that they run on any implementation of the scalar and the point arithmetic. The
+//! signer uses `DefaultBackend`, which calls the secp256k1 library directly. `CurvBackend` is
+//! the arithmetic of curv, and is kept to compare the results and the performance.
+
+use crate::util::jacobi;
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
+use secp256k1::{All, PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+
+/// The backend which the signer uses.
+pub type DefaultBackend = Secp256k1Backend;
+
+lazy_static! {
+    static ref SECP: Secp256k1<All> = Secp256k1::new();
+    /// The order of the field of secp256k1.
+    static ref FIELD_ORDER: BigInt = BigInt::from_hex(
+        "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F"
+    );
+}
+
+pub trait Backend {
+    type Scalar: Clone + PartialEq + Debug;
+    type Point: Clone + PartialEq + Debug;
+
+    fn scalar(fe: &FE) -> Self::Scalar;
+    fn to_fe(scalar: &Self::Scalar) -> FE;
+    fn point(ge: &GE) -> Self::Point;
+    /// Returns None if the point is the point at infinity.
+    fn to_ge(point: &Self::Point) -> Option<GE>;
+
+    /// Returns the scalar of a positive integer, such as the index of the share.
+    fn scalar_from_u64(value: u64) -> Self::Scalar;
+    fn add(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    fn mul(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+
+    /// Returns `scalar * G`.
+    fn mul_base(scalar: &Self::Scalar) -> Self::Point;
+    fn mul_point(point: &Self::Point, scalar: &Self::Scalar) -> Self::Point;
+    fn add_point(a: &Self::Point, b: &Self::Point) -> Self::Point;
+    /// Returns the compressed form of the point, or None for the point at infinity.
+    fn serialize(point: &Self::Point) -> Option<[u8; 33]>;
+    /// Returns true if the y coordinate of the point is a quadratic residue.
+    fn has_square_y(point: &Self::Point) -> bool;
+}
+
+/// The arithmetic of curv.
+pub struct CurvBackend;
+
+impl Backend for CurvBackend {
+    type Scalar = FE;
+    type Point = GE;
+
+    fn scalar(fe: &FE) -> FE {
+        *fe
+    }
+
+    fn to_fe(scalar: &FE) -> FE {
+        *scalar
+    }
+
+    fn point(ge: &GE) -> GE {
+        *ge
+    }
+
+    fn to_ge(point: &GE) -> Option<GE> {
+        Some(*point)
+    }
+
+    fn scalar_from_u64(value: u64) -> FE {
+        ECScalar::from(&BigInt::from(value))
+    }
+
+    fn add(a: &FE, b: &FE) -> FE {
+        *a + *b
+    }
+
+    fn mul(a: &FE, b: &FE) -> FE {
+        *a * *b
+    }
+
+    fn mul_base(scalar: &FE) -> GE {
+        GE::generator() * scalar
+    }
+
+    fn mul_point(point: &GE, scalar: &FE) -> GE {
+        *point * scalar
+    }
+
+    fn add_point(a: &GE, b: &GE) -> GE {
+        *a + b
+    }
+
+    fn serialize(point: &GE) -> Option<[u8; 33]> {
+        Some(point.get_element().serialize())
+    }
+
+    fn has_square_y(point: &GE) -> bool {
+        match point.y_coor() {
+            Some(y) => jacobi(&y, &FIELD_ORDER) == 1,
+            None => false,
+        }
+    }
+}
+
+/// The arithmetic of the secp256k1 library. The zero scalar and the point at infinity are
+/// represented as None, because the library doesn't allow them.
+pub struct Secp256k1Backend;
+
+impl Backend for Secp256k1Backend {
+    type Scalar = Option<SecretKey>;
+    type Point = Option<PublicKey>;
+
+    fn scalar(fe: &FE) -> Option<SecretKey> {
+        let key = fe.get_element();
+        if key[..] == [0u8; 32][..] {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    fn to_fe(scalar: &Option<SecretKey>) -> FE {
+        match scalar {
+            Some(key) => ECScalar::from(&BigInt::from(&key[..])),
+            None => FE::zero(),
+        }
+    }
+
+    fn point(ge: &GE) -> Option<PublicKey> {
+        Some(ge.get_element())
+    }
+
+    fn to_ge(point: &Option<PublicKey>) -> Option<GE> {
+        let bytes = point.as_ref()?.serialize_uncompressed();
+        GE::from_bytes(&bytes[1..]).ok()
+    }
+
+    fn scalar_from_u64(value: u64) -> Option<SecretKey> {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&value.to_be_bytes());
+        SecretKey::from_slice(&bytes[..]).ok()
+    }
+
+    fn add(a: &Option<SecretKey>, b: &Option<SecretKey>) -> Option<SecretKey> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let mut sum = *a;
+                // It fails only if the sum is zero.
+                sum.add_assign(&b[..]).ok().map(|_| sum)
+            }
+            (Some(a), None) => Some(*a),
+            (None, b) => *b,
+        }
+    }
+
+    fn mul(a: &Option<SecretKey>, b: &Option<SecretKey>) -> Option<SecretKey> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let mut product = *a;
+                product.mul_assign(&b[..]).ok().map(|_| product)
+            }
+            _ => None,
+        }
+    }
+
+    fn mul_base(scalar: &Option<SecretKey>) -> Option<PublicKey> {
+        scalar.map(|key| PublicKey::from_secret_key(&*SECP, &key))
+    }
+
+    fn mul_point(point: &Option<PublicKey>, scalar: &Option<SecretKey>) -> Option<PublicKey> {
+        match (point, scalar) {
+            (Some(point), Some(scalar)) => {
+                let mut product = *point;
+                product
+                    .mul_assign(&*SECP, &scalar[..])
+                    .ok()
+                    .map(|_| product)
+            }
+            _ => None,
+        }
+    }
+
+    fn add_point(a: &Option<PublicKey>, b: &Option<PublicKey>) -> Option<PublicKey> {
+        match (a, b) {
+            // It fails only if the sum is the point at infinity.
+            (Some(a), Some(b)) => a.combine(b).ok(),
+            (Some(a), None) => Some(*a),
+            (None, b) => *b,
+        }
+    }
+
+    fn serialize(point: &Option<PublicKey>) -> Option<[u8; 33]> {
+        point.map(|point| point.serialize())
+    }
+
+    fn has_square_y(point: &Option<PublicKey>) -> bool {
+        match point {
+            Some(point) => is_quadratic_residue(&point.serialize_uncompressed()[33..]),
+            None => false,
+        }
+    }
+}
+
+/// The order of the field of secp256k1 as the 64 bits limbs from the least significant one.
+const FIELD_ORDER_LIMBS: [u64; 4] = [
+    0xFFFF_FFFE_FFFF_FC2F,
+    0xFFFF_FFFF_FFFF_FFFF,
+    0xFFFF_FFFF_FFFF_FFFF,
+    0xFFFF_FFFF_FFFF_FFFF,
+];
+
+/// Returns true if the field element, which is 32 bytes in big endian, is a quadratic residue.
+/// The Jacobi symbol is computed by the binary algorithm on the 64 bits limbs, so that the
+/// secp256k1 backend doesn't convert the coordinate to the big integer of curv.
+fn is_quadratic_residue(bytes: &[u8]) -> bool {
+    let mut a = to_limbs(bytes);
+    let mut n = FIELD_ORDER_LIMBS;
+    let mut positive = true;
+    while a != [0; 4] {
+        // (2/n) is -1 if n is 3 or 5 modulo 8.
+        while a[0] & 1 == 0 {
+            shift_right(&mut a);
+            if n[0] & 7 == 3 || n[0] & 7 == 5 {
+                positive = !positive;
+            }
+        }
+        // The quadratic reciprocity of the odd numbers, which flips the sign if both are 3
+        // modulo 4.
+        if a.iter().rev().lt(n.iter().rev()) {
+            std::mem::swap(&mut a, &mut n);
+            if a[0] & 3 == 3 && n[0] & 3 == 3 {
+                positive = !positive;
+            }
+        }
+        subtract(&mut a, &n);
+    }
+    n == [1, 0, 0, 0] && positive
+}
+
+fn to_limbs(bytes: &[u8]) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.rchunks(8)) {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(chunk);
+        *limb = u64::from_be_bytes(buf);
+    }
+    limbs
+}
+
+fn shift_right(a: &mut [u64; 4]) {
+    for i in 0..3 {
+        a[i] = (a[i] >> 1) | (a[i + 1] << 63);
+    }
+    a[3] >>= 1;
+}
+
+/// Subtracts `b` from `a`, which is not less than `b`.
+fn subtract(a: &mut [u64; 4], b: &[u64; 4]) {
+    let mut borrow = false;
+    for (x, y) in a.iter_mut().zip(b) {
+        let (d, borrow_x) = x.overflowing_sub(*y);
+        let (d, borrow_d) = d.overflowing_sub(borrow as u64);
+        *x = d;
+        borrow = borrow_x || borrow_d;
+    }
+}
+
+/// Returns the value at `index` of the polynomial whose coefficients are committed to
+/// `commitments`.
+fn evaluate_commitments<B: Backend>(commitments: &[B::Point], index: usize) -> B::Point {
+    let x = B::scalar_from_u64(index as u64);
+    let mut iter = commitments.iter().rev();
+    let head = iter.next().expect("commitments must not be empty").clone();
+    iter.fold(head, |acc, c| B::add_point(&B::mul_point(&acc, &x), c))
+}
+
+/// Verify the share at `index` against the commitments of the VSS.
+pub fn validate_share<B: Backend>(
+    commitments: &[B::Point],
+    share: &B::Scalar,
+    index: usize,
+) -> bool {
+    validate_share_public::<B>(commitments, &B::mul_base(share), index)
+}
+
+/// Verify the public share `share * G` at `index` against the commitments of the VSS.
+pub fn validate_share_public<B: Backend>(
+    commitments: &[B::Point],
+    share_g: &B::Point,
+    index: usize,
+) -> bool {
+    evaluate_commitments::<B>(commitments, index) == *share_g
+}
+
+/// Same as `validate_share` for the VSS of curv.
+pub fn validate_vss_share<B: Backend>(vss: &VerifiableSS, share: &FE, index: usize) -> bool {
+    let commitments: Vec<B::Point> = vss.commitments.iter().map(B::point).collect();
+    validate_share::<B>(&commitments, &B::scalar(share), index)
+}
+
+/// Returns the challenge of the Schnorr signature, which is the same as
+/// `multi_party_schnorr::compute_e`. Returns None if `r` or `y` is the point at infinity.
+pub fn compute_e<B: Backend>(r: &B::Point, y: &B::Point, message: &[u8]) -> Option<B::Scalar> {
+    let mut hasher = Sha256::new();
+    hasher.input(&B::serialize(r)?[1..33]);
+    hasher.input(&B::serialize(y)?[..]);
+    hasher.input(message);
+    let e: FE = ECScalar::from(&BigInt::from(&hasher.result()[..]));
+    Some(B::scalar(&e))
+}
+
+/// Verify that `sigma * G == v + e * y`.
+pub fn verify_signature<B: Backend>(
+    sigma: &B::Scalar,
+    v: &B::Point,
+    y: &B::Point,
+    message: &[u8],
+) -> bool {
+    let e = match compute_e::<B>(v, y, message) {
+        Some(e) => e,
+        None => return false,
+    };
+    B::add_point(&B::mul_point(y, &e), v) == B::mul_base(sigma)
+}
+
+/// Verify the local signatures `(gamma_i, e)` of the parties at `parties`, and returns the
+/// commitments of the VSS whose shares are the local signatures.
+///
+/// The commitment of the i-th coefficient is `e * sum(private) + sum(ephemeral)`, where `e` is
+/// the challenge of the i-th local signature, as in `LocalSig::verify_local_sigs`.
+pub fn verify_local_sigs<B: Backend>(
+    local_sigs: &[(B::Scalar, B::Scalar)],
+    parties: &[usize],
+    private_commitments: &[Vec<B::Point>],
+    ephemeral_commitments: &[Vec<B::Point>],
+) -> Option<Vec<B::Point>> {
+    let coefficient_count = ephemeral_commitments[0].len();
+    let commitments = (0..coefficient_count)
+        .map(|i| {
+            // Sum the commitments before the multiplication, which costs more than the addition.
+            let private = B::mul_point(
+                &sum_commitments::<B>(private_commitments, i),
+                &local_sigs[i].1,
+            );
+            B::add_point(&private, &sum_commitments::<B>(ephemeral_commitments, i))
+        })
+        .collect::<Vec<B::Point>>();
+
+    let valid = local_sigs.iter().zip(parties).all(|((gamma_i, _), index)| {
+        validate_share_public::<B>(&commitments, &B::mul_base(gamma_i), index + 1)
+    });
+    if valid {
+        Some(commitments)
+    } else {
+        None
+    }
+}
+
+/// Verify the local signature `gamma_i` of the party at `index` with the challenge `e`, so that
+/// the invalid one can be found when `verify_local_sigs` fails.
+pub fn verify_local_sig<B: Backend>(
+    gamma_i: &B::Scalar,
+    e: &B::Scalar,
+    index: usize,
+    private_commitments: &[Vec<B::Point>],
+    ephemeral_commitments: &[Vec<B::Point>],
+) -> bool {
+    let coefficient_count = ephemeral_commitments[0].len();
+    let commitments = (0..coefficient_count)
+        .map(|i| {
+            let private = B::mul_point(&sum_commitments::<B>(private_commitments, i), e);
+            B::add_point(&private, &sum_commitments::<B>(ephemeral_commitments, i))
+        })
+        .collect::<Vec<B::Point>>();
+    validate_share_public::<B>(&commitments, &B::mul_base(gamma_i), index + 1)
+}
+
+/// Returns the sum of the i-th commitments of the VSSs.
+fn sum_commitments<B: Backend>(commitments: &[Vec<B::Point>], i: usize) -> B::Point {
+    let mut iter = commitments.iter().map(|c| &c[i]);
+    let head = iter.next().expect("commitments must not be empty").clone();
+    iter.fold(head, |acc, c| B::add_point(&acc, c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::multi_party_schnorr::{LocalSig, SharedKeys};
+    use crate::crypto::vss::Vss;
+
+    fn random_scalar() -> FE {
+        ECScalar::new_random()
+    }
+
+    fn assert_same_arithmetic<B: Backend>() {
+        let a = random_scalar();
+        let b = random_scalar();
+        let g = GE::generator();
+        let p = g * &random_scalar();
+
+        assert_eq!(B::to_fe(&B::add(&B::scalar(&a), &B::scalar(&b))), a + b);
+        assert_eq!(B::to_fe(&B::mul(&B::scalar(&a), &B::scalar(&b))), a * b);
+        assert_eq!(B::to_ge(&B::mul_base(&B::scalar(&a))), Some(g * &a));
+        assert_eq!(
+            B::to_ge(&B::mul_point(&B::point(&p), &B::scalar(&a))),
+            Some(p * &a)
+        );
+        assert_eq!(
+            B::to_ge(&B::add_point(&B::point(&p), &B::point(&g))),
+            Some(p + &g)
+        );
+        assert_eq!(
+            B::has_square_y(&B::point(&p)),
+            CurvBackend::has_square_y(&p)
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_same_arithmetic::<CurvBackend>();
+        assert_same_arithmetic::<Secp256k1Backend>();
+    }
+
+    #[test]
+    fn test_is_quadratic_residue() {
+        for _ in 0..100 {
+            let y = random_scalar().to_big_int();
+            let mut bytes = [0u8; 32];
+            let y_bytes = BigInt::to_vec(&y);
+            bytes[32 - y_bytes.len()..].copy_from_slice(&y_bytes);
+            assert_eq!(is_quadratic_residue(&bytes), jacobi(&y, &FIELD_ORDER) == 1);
+        }
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert!(is_quadratic_residue(&one));
+        assert!(!is_quadratic_residue(&[0u8; 32]));
+        // -1 is not a quadratic residue, because the order of the field is 3 modulo 4.
+        let minus_one = BigInt::to_vec(&(FIELD_ORDER.clone() - BigInt::from(1)));
+        assert!(!is_quadratic_residue(&minus_one));
+    }
+
+    #[test]
+    fn test_secp256k1_zero_and_infinity() {
+        type B = Secp256k1Backend;
+        let a = random_scalar();
+        let minus_a = FE::zero().sub(&a.get_element());
+        assert_eq!(B::add(&B::scalar(&a), &B::scalar(&minus_a)), None);
+        assert_eq!(B::scalar(&FE::zero()), None);
+        assert_eq!(B::to_fe(&None), FE::zero());
+
+        let p = B::mul_base(&B::scalar(&a));
+        let minus_p = B::mul_base(&B::scalar(&minus_a));
+        assert_eq!(B::add_point(&p, &minus_p), None);
+        assert_eq!(B::add_point(&None, &p), p);
+        assert_eq!(B::to_ge(&None), None);
+    }
+
+    fn assert_validate_share<B: Backend>() {
+        let (vss, shares) = Vss::create_shares(&random_scalar(), 3, 5);
+        for (i, share) in shares.iter().enumerate() {
+            assert!(validate_vss_share::<B>(&vss, share, i + 1));
+        }
+        assert!(!validate_vss_share::<B>(&vss, &shares[0], 2));
+    }
+
+    #[test]
+    fn test_validate_share() {
+        assert_validate_share::<CurvBackend>();
+        assert_validate_share::<Secp256k1Backend>();
+    }
+
+    fn assert_verify_local_sigs<B: Backend>() {
+        let message = [1u8; 32];
+        let (private_vss, private_shares): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| Vss::create_shares(&random_scalar(), 2, 3))
+            .unzip();
+        let (ephemeral_vss, ephemeral_shares): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| Vss::create_shares(&random_scalar(), 2, 3))
+            .unzip();
+        let sum_of = |vss: &Vec<VerifiableSS>| {
+            vss.iter()
+                .skip(1)
+                .fold(vss[0].commitments[0], |acc, v| acc + &v.commitments[0])
+        };
+        let y = sum_of(&private_vss);
+        let v = sum_of(&ephemeral_vss);
+        let shared_keys = |shares: &Vec<Vec<FE>>, y: GE, i: usize| SharedKeys {
+            y,
+            x_i: shares.iter().fold(FE::zero(), |acc, s| acc + s[i]).into(),
+        };
+
+        let parties = [0, 2];
+        let local_sigs: Vec<LocalSig> = parties
+            .iter()
+            .map(|i| {
+                LocalSig::compute(
+                    &message,
+                    &shared_keys(&ephemeral_shares, v, *i),
+                    &shared_keys(&private_shares, y, *i),
+                )
+            })
+            .collect();
+
+        let commitments = |vss: &Vec<VerifiableSS>| {
+            vss.iter()
+                .map(|v| v.commitments.iter().map(B::point).collect())
+                .collect::<Vec<Vec<B::Point>>>()
+        };
+        let mut sigs: Vec<(B::Scalar, B::Scalar)> = local_sigs
+            .iter()
+            .map(|s| (B::scalar(&s.gamma_i), B::scalar(&s.e)))
+            .collect();
+        let expected =
+            LocalSig::verify_local_sigs(&local_sigs, &parties, &private_vss, &ephemeral_vss)
+                .unwrap();
+        let actual = verify_local_sigs::<B>(
+            &sigs,
+            &parties,
+            &commitments(&private_vss),
+            &commitments(&ephemeral_vss),
+        )
+        .unwrap();
+        let actual: Vec<GE> = actual.iter().map(|c| B::to_ge(c).unwrap()).collect();
+        assert_eq!(actual, expected.commitments);
+
+        // Signature of the secret which is reconstructed from the local signatures.
+        let sigma = expected.reconstruct(
+            &parties,
+            &local_sigs.iter().map(|s| s.gamma_i).collect::<Vec<FE>>(),
+        );
+        assert!(verify_signature::<B>(
+            &B::scalar(&sigma),
+            &B::point(&v),
+            &B::point(&y),
+            &message
+        ));
+        assert!(!verify_signature::<B>(
+            &B::scalar(&sigma),
+            &B::point(&v),
+            &B::point(&y),
+            &[2u8; 32]
+        ));
+
+        sigs[1].0 = B::add(&sigs[1].0, &B::scalar_from_u64(1));
+        assert!(verify_local_sigs::<B>(
+            &sigs,
+            &parties,
+            &commitments(&private_vss),
+            &commitments(&ephemeral_vss),
+        )
+        .is_none());
+
+        // The invalid local signature is found one by one.
+        let e = sigs[0].1.clone();
+        let is_valid = |sig: &(B::Scalar, B::Scalar), index: usize| {
+            verify_local_sig::<B>(
+                &sig.0,
+                &e,
+                index,
+                &commitments(&private_vss),
+                &commitments(&ephemeral_vss),
+            )
+        };
+        assert!(is_valid(&sigs[0], parties[0]));
+        assert!(!is_valid(&sigs[1], parties[1]));
+    }
+
+    #[test]
+    fn test_verify_local_sigs_and_signature() {
+        assert_verify_local_sigs::<CurvBackend>();
+        assert_verify_local_sigs::<Secp256k1Backend>();
+    }
+}
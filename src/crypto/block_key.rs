@@ -0,0 +1,197 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Hedged deterministic derivation of the block keys.
+//!
+//! The block key and the coefficients of its VSSs are derived from the node secret share, the
+//! sighash of the block, the round id and fresh randomness, in the style of RFC6979 with the
+//! additional randomness. If the random number generator of the signer is weak, the keys are
+//! still unpredictable for the other signers, because they don't know the node secret share. If
+//! the generator works, the keys are different even for the same block and round.
+//!
+//! A scalar is derived as below, where `tagged_hash(data)` is
+//! `SHA256(SHA256(TAG) || SHA256(TAG) || data)` and the integers are big endian.
+//!
+//! ```text
+//! seed = tagged_hash(secret(32) || len(message)(1) || message || round_id(8) || aux_rand(32))
+//! scalar(i) = tagged_hash(seed || i(4) || counter(4))
+//! ```
+//!
+//! `counter` starts from 0 and is incremented until the scalar is in the range of 1 to n - 1.
+
+use crate::blockdata::hash::SHA256Hash;
+use curv::arithmetic::traits::Converter;
+use curv::elliptic::curves::traits::ECScalar;
+use curv::{BigInt, FE};
+use secp256k1::rand::{thread_rng, RngCore};
+use sha2::{Digest, Sha256};
+
+const TAG: &[u8] = b"TapyrusSigner/BlockKey";
+
+pub struct BlockKeyDerivation {
+    seed: [u8; 32],
+}
+
+impl BlockKeyDerivation {
+    /// `message` is the sighash of the block. It can be empty only if the key is generated before
+    /// the block is known, such as the keys in the nonce pool.
+    pub fn new(secret: &FE, message: &[u8], round_id: u64, aux_rand: &[u8; 32]) -> Self {
+        assert!(message.len() <= 255, "message is too long");
+        let secret = hex::decode(format!("{:0>64}", secret.to_big_int().to_hex())).unwrap();
+        let mut data = Vec::with_capacity(32 + 1 + message.len() + 8 + 32);
+        data.extend_from_slice(&secret[..]);
+        data.push(message.len() as u8);
+        data.extend_from_slice(message);
+        data.extend_from_slice(&round_id.to_be_bytes());
+        data.extend_from_slice(aux_rand);
+        BlockKeyDerivation {
+            seed: tagged_hash(&data),
+        }
+    }
+
+    /// Returns the derivation for the block with the randomness from the system.
+    pub fn for_block(secret: &FE, sighash: &SHA256Hash, round_id: u64) -> Self {
+        Self::new(secret, sighash.borrow_inner(), round_id, &random_bytes())
+    }
+
+    /// Returns the derivation for the key which is generated before the block is known.
+    pub fn without_block(secret: &FE, round_id: u64) -> Self {
+        Self::new(secret, &[], round_id, &random_bytes())
+    }
+
+    /// Returns the scalar at `i`. The block key is at 0.
+    pub fn scalar(&self, i: u32) -> FE {
+        let order = FE::q();
+        (0u32..)
+            .map(|counter| {
+                let mut data = self.seed.to_vec();
+                data.extend_from_slice(&i.to_be_bytes());
+                data.extend_from_slice(&counter.to_be_bytes());
+                BigInt::from(&tagged_hash(&data)[..])
+            })
+            .find(|k| *k != BigInt::from(0) && *k < order)
+            .map(|k| ECScalar::from(&k))
+            .unwrap()
+    }
+}
+
+fn tagged_hash(data: &[u8]) -> [u8; 32] {
+    let tag = Sha256::digest(TAG);
+    let mut hasher = Sha256::new();
+    hasher.input(&tag[..]);
+    hasher.input(&tag[..]);
+    hasher.input(data);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.result()[..]);
+    hash
+}
+
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::elliptic::curves::traits::ECPoint;
+    use curv::GE;
+
+    fn scalar(hex: &str) -> FE {
+        ECScalar::from(&BigInt::from_hex(hex))
+    }
+
+    fn hex_of(fe: &FE) -> String {
+        format!("{:0>64}", fe.to_big_int().to_hex())
+    }
+
+    // Test vectors: (secret, message, round id, aux rand, scalar(0), scalar(1), scalar(0) * G)
+    const VECTORS: [(&str, &str, u64, &str, &str, &str, &str); 3] = [
+        (
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "c89277ec30d029c31d73565f25aea6109179c8e9970a80ce15243323cb946bb5",
+            "58404e252d829aee29ba5e791519e8b3106695a94e5f9832034f3206216d5503",
+            "03ed87fa58a8ba8fa58808ee3689ce56d41ff00af2e7a638173b4830b6f339227a",
+        ),
+        (
+            "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+            "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            100,
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "f41f0587dd568d7cb896615cc10a0d864c32ac6908cd4f29fff6b8598599eb0c",
+            "5217224e5046f32d31aa9eeb6a3be1b292f53eae410041c79469fbb5b31686f3",
+            "02c4598481578d2ca32e98595ca99b9bcc15f7d3221661207f1c847af01626ae32",
+        ),
+        (
+            "c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9",
+            "",
+            4294967296,
+            "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906",
+            "ca75c8ae11862afb89e696d9b83d1f5338b90519e44244790e1016095dbf6b0a",
+            "01794f8ece0757df934e36a8fb488bd494a1350e881bf1d37cead461daf0dd09",
+            "039d69cd0dd690c2b5068c0d27fb8ed5ed41e97ef76777a17bf0b057d5e2eaa2a5",
+        ),
+    ];
+
+    #[test]
+    fn test_vectors() {
+        for (secret, message, round_id, aux_rand, k0, k1, r) in VECTORS.iter() {
+            let mut aux = [0u8; 32];
+            aux.copy_from_slice(&hex::decode(aux_rand).unwrap()[..]);
+            let derivation = BlockKeyDerivation::new(
+                &scalar(secret),
+                &hex::decode(message).unwrap()[..],
+                *round_id,
+                &aux,
+            );
+            assert_eq!(hex_of(&derivation.scalar(0)), *k0);
+            assert_eq!(hex_of(&derivation.scalar(1)), *k1);
+            let g: GE = ECPoint::generator();
+            let point = g * &derivation.scalar(0);
+            assert_eq!(hex::encode(&point.get_element().serialize()[..]), *r);
+        }
+    }
+
+    #[test]
+    fn test_depends_on_every_input() {
+        let secret = scalar("01");
+        let sighash = SHA256Hash::from_slice(&[1u8; 32][..]).unwrap();
+        let aux = [2u8; 32];
+        let k = BlockKeyDerivation::new(&secret, sighash.borrow_inner(), 1, &aux).scalar(0);
+
+        assert_eq!(
+            BlockKeyDerivation::new(&secret, sighash.borrow_inner(), 1, &aux).scalar(0),
+            k
+        );
+        assert_ne!(
+            BlockKeyDerivation::new(&scalar("02"), sighash.borrow_inner(), 1, &aux).scalar(0),
+            k
+        );
+        assert_ne!(
+            BlockKeyDerivation::new(&secret, &[3u8; 32], 1, &aux).scalar(0),
+            k
+        );
+        assert_ne!(
+            BlockKeyDerivation::new(&secret, sighash.borrow_inner(), 2, &aux).scalar(0),
+            k
+        );
+        assert_ne!(
+            BlockKeyDerivation::new(&secret, sighash.borrow_inner(), 1, &[4u8; 32]).scalar(0),
+            k
+        );
+        assert_ne!(
+            BlockKeyDerivation::new(&secret, sighash.borrow_inner(), 1, &aux).scalar(1),
+            k
+        );
+        // Fresh randomness is used for the block.
+        assert_ne!(
+            BlockKeyDerivation::for_block(&secret, &sighash, 1).scalar(0),
+            BlockKeyDerivation::for_block(&secret, &sighash, 1).scalar(0)
+        );
+    }
+}
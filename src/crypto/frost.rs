@@ -0,0 +1,460 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Two-round threshold signing in the style of FROST.
+//!
+//! Each participant commits to a pair of nonces `(d_i, e_i)` as `(D_i, E_i)` in the first round.
+//! The nonce of participant i is `k_i = d_i + rho_i * e_i`, where the binding factor `rho_i` is the
+//! hash of the message and the commitments of all participants. The group commitment
+//! `R = sum(D_i + rho_i * E_i)` is negated when the jacobi symbol of its y coordinate is not 1,
+//! because Tapyrus requires it for the Schnorr signature. In the second round, each participant
+//! sends the signature share `z_i = k_i + lambda_i * c * x_i`, and `sum(z_i)` is the signature.
+//!
+//! The first round is preprocessed: each signer publishes the commitments of a batch of nonces
+//! ahead of the rounds, and the master selects one commitment of each participant for the
+//! candidate block. A nonce is removed from `Nonces` when it is used, so it is never used twice.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::backend::{Backend, DefaultBackend};
+use crate::crypto::multi_party_schnorr::{compute_e, Signature};
+use crate::crypto::secret::Secret;
+use crate::errors::Error;
+use crate::net::SignerID;
+use crate::signer_node::SharedSecretMap;
+use crate::util::{lagrange_coefficient, sum_point};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Nonce commitments `(D_i, E_i)` of each participant.
+pub type NonceCommitments = BTreeMap<SignerID, (GE, GE)>;
+
+/// Number of the nonces whose commitments each signer publishes ahead of the rounds.
+pub const NONCE_BATCH_SIZE: usize = 8;
+
+/// Returns a new pair of nonces and the commitments of them.
+pub fn create_nonce() -> ((FE, FE), (GE, GE)) {
+    let g: GE = ECPoint::generator();
+    let hiding: FE = ECScalar::new_random();
+    let binding: FE = ECScalar::new_random();
+    ((hiding, binding), (g * &hiding, g * &binding))
+}
+
+/// The secret nonces of this signer whose commitments are published ahead of the rounds.
+#[derive(Default)]
+pub struct Nonces {
+    nonces: Vec<((GE, GE), Secret<(FE, FE)>)>,
+}
+
+impl Nonces {
+    pub fn new() -> Self {
+        Nonces::default()
+    }
+
+    /// Returns the commitments of the unused nonces, after creating new nonces up to `count`.
+    pub fn commitments(&mut self, count: usize) -> Vec<(GE, GE)> {
+        while self.nonces.len() < count {
+            let (nonce, commitment) = create_nonce();
+            self.nonces.push((commitment, nonce.into()));
+        }
+        self.nonces
+            .iter()
+            .map(|(commitment, _)| *commitment)
+            .collect()
+    }
+
+    /// Removes the nonce of the commitment and returns it. It returns None if the nonce is not
+    /// created by this signer or is already used.
+    pub fn take(&mut self, commitment: &(GE, GE)) -> Option<Secret<(FE, FE)>> {
+        let position = self.nonces.iter().position(|(c, _)| c == commitment)?;
+        Some(self.nonces.remove(position).1)
+    }
+}
+
+/// The nonce commitments which the other signers published ahead of the rounds. The master
+/// selects the commitments of the participants from them.
+#[derive(Default)]
+pub struct PublishedCommitments {
+    commitments: BTreeMap<SignerID, Vec<(GE, GE)>>,
+}
+
+impl PublishedCommitments {
+    pub fn new() -> Self {
+        PublishedCommitments::default()
+    }
+
+    /// Replaces the commitments of the signer with the latest ones it published.
+    pub fn update(&mut self, signer_id: SignerID, mut commitments: Vec<(GE, GE)>) {
+        commitments.truncate(NONCE_BATCH_SIZE);
+        self.commitments.insert(signer_id, commitments);
+    }
+
+    /// Removes the commitments which are selected for a round.
+    pub fn remove(&mut self, selected: &NonceCommitments) {
+        for (signer_id, commitment) in selected {
+            if let Some(commitments) = self.commitments.get_mut(signer_id) {
+                commitments.retain(|c| c != commitment);
+            }
+        }
+    }
+
+    /// Takes a commitment of each of the first `count` candidates which published any. It takes
+    /// nothing and returns None if the candidates don't have enough commitments.
+    pub fn select(&mut self, candidates: &[SignerID], count: usize) -> Option<NonceCommitments> {
+        let selected: NonceCommitments = candidates
+            .iter()
+            .filter_map(|id| match self.commitments.get(id) {
+                Some(commitments) if !commitments.is_empty() => Some((*id, commitments[0])),
+                _ => None,
+            })
+            .take(count)
+            .collect();
+        if selected.len() < count {
+            return None;
+        }
+        self.remove(&selected);
+        Some(selected)
+    }
+}
+
+/// Returns the public share `G * x_i` of the signer at `index`, which is computed from the
+/// commitments of the node VSS.
+pub fn public_share(node_shared_secrets: &SharedSecretMap, index: usize) -> GE {
+    let points: Vec<GE> = node_shared_secrets
+        .values()
+        .map(|s| s.vss.get_point_commitment(index))
+        .collect();
+    sum_point(&points)
+}
+
+/// The inputs of a signing session which are common to all participants.
+pub struct SigningPackage {
+    message: SHA256Hash,
+    commitments: NonceCommitments,
+    /// Index of each participant, which is the x coordinate of its share.
+    indices: BTreeMap<SignerID, usize>,
+    aggregated_public_key: GE,
+    /// The group commitment whose y coordinate has jacobi symbol 1.
+    group_commitment: GE,
+    /// True if the group commitment is negated, so that the participants negate their nonces.
+    is_negated: bool,
+}
+
+impl SigningPackage {
+    /// `signers` are the signers of the federation which are sorted by SignerID.
+    pub fn new(
+        message: SHA256Hash,
+        commitments: NonceCommitments,
+        signers: &[SignerID],
+        aggregated_public_key: GE,
+    ) -> Result<Self, Error> {
+        let indices = commitments
+            .keys()
+            .map(|id| match signers.iter().position(|s| s == id) {
+                Some(i) => Ok((*id, i + 1)),
+                None => Err(Error::InvalidSS),
+            })
+            .collect::<Result<BTreeMap<SignerID, usize>, Error>>()?;
+        if indices.is_empty() {
+            return Err(Error::InvalidSS);
+        }
+
+        let mut package = SigningPackage {
+            message,
+            commitments,
+            indices,
+            aggregated_public_key,
+            group_commitment: ECPoint::generator(),
+            is_negated: false,
+        };
+        let points: Vec<GE> = package
+            .commitments
+            .keys()
+            .map(|id| package.nonce_commitment(id))
+            .collect();
+        let group_commitment = sum_point(&points);
+        if is_positive(&group_commitment) {
+            package.group_commitment = group_commitment;
+        } else {
+            package.group_commitment = negate_point(&group_commitment);
+            package.is_negated = true;
+        }
+        Ok(package)
+    }
+
+    pub fn participants(&self) -> Vec<SignerID> {
+        self.indices.keys().cloned().collect()
+    }
+
+    pub fn group_commitment(&self) -> GE {
+        self.group_commitment
+    }
+
+    /// Returns the binding factor of the participant, which binds its nonces to the message
+    /// and the commitments of all participants.
+    fn binding_factor(&self, signer_id: &SignerID) -> FE {
+        let mut hasher = Sha256::new();
+        hasher.input(&signer_id.pubkey.key.serialize()[..]);
+        hasher.input(&self.message.borrow_inner()[..]);
+        for (id, (hiding, binding)) in &self.commitments {
+            hasher.input(&id.pubkey.key.serialize()[..]);
+            hasher.input(&hiding.get_element().serialize()[..]);
+            hasher.input(&binding.get_element().serialize()[..]);
+        }
+        ECScalar::from(&BigInt::from(&hasher.result()[..]))
+    }
+
+    /// Returns `D_i + rho_i * E_i` of the participant.
+    fn nonce_commitment(&self, signer_id: &SignerID) -> GE {
+        let (hiding, binding) = self.commitments[signer_id];
+        hiding + &(binding * &self.binding_factor(signer_id))
+    }
+
+    /// Returns the challenge multiplied by the Lagrange coefficient of the participant.
+    fn weighted_challenge(&self, signer_id: &SignerID) -> FE {
+        let indices: Vec<usize> = self.indices.values().cloned().collect();
+        let lambda = lagrange_coefficient(self.indices[signer_id], &indices, 0);
+        let c = compute_e(
+            &self.group_commitment,
+            &self.aggregated_public_key,
+            self.message.borrow_inner(),
+        );
+        c * lambda
+    }
+
+    /// Returns the signature share of the participant. `nonce` must be used only once.
+    pub fn sign(
+        &self,
+        signer_id: &SignerID,
+        nonce: &(FE, FE),
+        secret_share: &FE,
+    ) -> Result<FE, Error> {
+        if !self.indices.contains_key(signer_id) {
+            return Err(Error::InvalidSS);
+        }
+        let k = nonce.0 + nonce.1 * self.binding_factor(signer_id);
+        let k = if self.is_negated {
+            negate_scalar(&k)
+        } else {
+            k
+        };
+        Ok(k + self.weighted_challenge(signer_id) * secret_share)
+    }
+
+    /// Verify the signature share of the participant with its public share.
+    pub fn verify_share(
+        &self,
+        signer_id: &SignerID,
+        share: &FE,
+        public_share: &GE,
+    ) -> Result<(), Error> {
+        if !self.indices.contains_key(signer_id) {
+            return Err(Error::InvalidSig);
+        }
+        let r = self.nonce_commitment(signer_id);
+        let r = if self.is_negated { negate_point(&r) } else { r };
+        let g: GE = ECPoint::generator();
+        if g * share == r + &(*public_share * &self.weighted_challenge(signer_id)) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSig)
+        }
+    }
+
+    /// Returns the signature from the shares of all participants.
+    pub fn aggregate(&self, shares: &BTreeMap<SignerID, FE>) -> Result<Signature, Error> {
+        if shares.keys().ne(self.indices.keys()) {
+            return Err(Error::InvalidSig);
+        }
+        let sigma = shares.values().fold(FE::zero(), |acc, z| acc + z);
+        let signature = Signature {
+            sigma,
+            v: self.group_commitment,
+        };
+        signature.verify(self.message.borrow_inner(), &self.aggregated_public_key)?;
+        Ok(signature)
+    }
+}
+
+fn is_positive(point: &GE) -> bool {
+    DefaultBackend::has_square_y(&DefaultBackend::point(point))
+}
+
+fn negate_scalar(scalar: &FE) -> FE {
+    ECScalar::from(&(FE::q() - scalar.to_big_int()))
+}
+
+fn negate_point(point: &GE) -> GE {
+    let minus_one: FE = ECScalar::from(&(FE::q() - BigInt::from(1)));
+    *point * &minus_one
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::federation::Federation;
+    use crate::sign::Sign;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+    }
+
+    fn message() -> SHA256Hash {
+        SHA256Hash::from_slice(&[1u8; 32][..]).unwrap()
+    }
+
+    fn create_package(participants: &[usize]) -> (SigningPackage, BTreeMap<SignerID, (FE, FE)>) {
+        let mut nonces = BTreeMap::new();
+        let mut commitments = NonceCommitments::new();
+        for i in participants {
+            let (nonce, commitment) = create_nonce();
+            nonces.insert(TEST_KEYS.signer_ids()[*i], nonce);
+            commitments.insert(TEST_KEYS.signer_ids()[*i], commitment);
+        }
+        let package = SigningPackage::new(
+            message(),
+            commitments,
+            &TEST_KEYS.signer_ids(),
+            federation(0).node_secret_share().y,
+        )
+        .unwrap();
+        (package, nonces)
+    }
+
+    #[test]
+    fn test_sign_and_aggregate() {
+        // The group commitment may or may not be negated, so try several times.
+        for _ in 0..8 {
+            let (package, nonces) = create_package(&[0, 2, 4]);
+            assert!(is_positive(&package.group_commitment()));
+
+            let node_shared_secrets = federation(0).node_shared_secrets();
+            let mut shares = BTreeMap::new();
+            for i in &[0, 2, 4] {
+                let signer_id = TEST_KEYS.signer_ids()[*i];
+                let secret_share = federation(*i).node_secret_share().x_i;
+                let share = package
+                    .sign(&signer_id, &nonces[&signer_id], &secret_share)
+                    .unwrap();
+                let public_share = public_share(&node_shared_secrets, *i + 1);
+                assert!(package
+                    .verify_share(&signer_id, &share, &public_share)
+                    .is_ok());
+                shares.insert(signer_id, share);
+            }
+
+            let signature = package.aggregate(&shares).unwrap();
+            assert_eq!(Sign::format_signature(&signature).len(), 130);
+        }
+    }
+
+    #[test]
+    fn test_verify_invalid_share() {
+        let (package, nonces) = create_package(&[1, 2, 3]);
+        let signer_id = TEST_KEYS.signer_ids()[1];
+        let secret_share = federation(1).node_secret_share().x_i;
+        let share = package
+            .sign(&signer_id, &nonces[&signer_id], &secret_share)
+            .unwrap();
+        let one: FE = ECScalar::from(&BigInt::from(1));
+        let invalid_share = share + one;
+
+        let public_share = public_share(&federation(1).node_shared_secrets(), 2);
+        assert!(package
+            .verify_share(&signer_id, &invalid_share, &public_share)
+            .is_err());
+        // The share of other participant is invalid for the signer.
+        let other = TEST_KEYS.signer_ids()[2];
+        assert!(package.verify_share(&other, &share, &public_share).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_with_missing_share() {
+        let (package, nonces) = create_package(&[0, 1, 2]);
+        let mut shares = BTreeMap::new();
+        for i in &[0, 1] {
+            let signer_id = TEST_KEYS.signer_ids()[*i];
+            let secret_share = federation(*i).node_secret_share().x_i;
+            let share = package
+                .sign(&signer_id, &nonces[&signer_id], &secret_share)
+                .unwrap();
+            shares.insert(signer_id, share);
+        }
+        assert!(package.aggregate(&shares).is_err());
+    }
+
+    #[test]
+    fn test_nonces_are_used_once() {
+        let mut nonces = Nonces::new();
+        let commitments = nonces.commitments(NONCE_BATCH_SIZE);
+        assert_eq!(commitments.len(), NONCE_BATCH_SIZE);
+        // The unused nonces are published again.
+        assert_eq!(nonces.commitments(NONCE_BATCH_SIZE), commitments);
+
+        let g: GE = ECPoint::generator();
+        let (hiding, binding) = *nonces.take(&commitments[0]).unwrap();
+        assert_eq!((g * &hiding, g * &binding), commitments[0]);
+        assert!(nonces.take(&commitments[0]).is_none());
+
+        // A new nonce takes the place of the used one.
+        let next = nonces.commitments(NONCE_BATCH_SIZE);
+        assert_eq!(next.len(), NONCE_BATCH_SIZE);
+        assert!(!next.contains(&commitments[0]));
+        assert_eq!(&next[..NONCE_BATCH_SIZE - 1], &commitments[1..]);
+    }
+
+    #[test]
+    fn test_select_published_commitments() {
+        let mut published = PublishedCommitments::new();
+        let signers = TEST_KEYS.signer_ids();
+        let mut nonces = Nonces::new();
+        published.update(signers[1], nonces.commitments(2));
+        published.update(signers[2], vec![]);
+        published.update(signers[3], nonces.commitments(3)[2..].to_vec());
+
+        // The signers 1 and 3 have the commitments, but 4 doesn't.
+        assert!(published.select(&signers[1..], 3).is_none());
+        let selected = published.select(&signers[1..], 2).unwrap();
+        assert_eq!(
+            selected.keys().cloned().collect::<Vec<SignerID>>(),
+            vec![signers[1], signers[3]]
+        );
+
+        // The selected commitments are never selected again.
+        let next = published.select(&signers[1..], 1).unwrap();
+        assert_ne!(next[&signers[1]], selected[&signers[1]]);
+        assert!(published.select(&signers[3..], 1).is_none());
+
+        // The latest commitments replace the previous ones.
+        published.update(signers[3], nonces.commitments(NONCE_BATCH_SIZE * 2));
+        let selected = published.select(&signers[3..], 1).unwrap();
+        assert_eq!(selected[&signers[3]], nonces.commitments(1)[0]);
+    }
+
+    #[test]
+    fn test_new_with_non_signer() {
+        let mut commitments = NonceCommitments::new();
+        let (_, commitment) = create_nonce();
+        commitments.insert(TEST_KEYS.signer_ids()[0], commitment);
+        assert!(
+            SigningPackage::new(message(), commitments.clone(), &[], ECPoint::generator()).is_err()
+        );
+        assert!(SigningPackage::new(
+            message(),
+            NonceCommitments::new(),
+            &TEST_KEYS.signer_ids(),
+            ECPoint::generator()
+        )
+        .is_err());
+    }
+}
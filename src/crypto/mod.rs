@@ -1,4 +1,9 @@
+pub mod backend;
+pub mod block_key;
+pub mod frost;
 pub mod multi_party_schnorr;
+pub mod secret;
+pub mod share_signer;
 pub mod vss;
 
 #[cfg(test)]
@@ -15,6 +15,8 @@
     @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
 */
 /// following the variant used in bip-schnorr: https://github.com/sipa/bips/blob/bip-schnorr/bip-schnorr.mediawiki
+use crate::crypto::backend::{self, Backend, DefaultBackend};
+use crate::crypto::secret::Secret;
 use crate::errors::Error::{self, InvalidKey, InvalidSS, InvalidSig};
 
 use curv::arithmetic::traits::*;
@@ -30,7 +32,7 @@ use sha2::{Digest, Sha256};
 const SECURITY: usize = 256;
 
 pub struct Keys {
-    pub u_i: FE,
+    pub u_i: Secret<FE>,
     pub y_i: GE,
     pub party_index: usize,
 }
@@ -52,7 +54,7 @@ pub struct Parameters {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SharedKeys {
     pub y: GE,
-    pub x_i: FE,
+    pub x_i: Secret<FE>,
 }
 
 impl PartialEq for SharedKeys {
@@ -70,7 +72,7 @@ impl Keys {
         let y = &ECPoint::generator() * &u;
 
         Keys {
-            u_i: u,
+            u_i: u.into(),
             y_i: y,
             party_index: index.clone(),
         }
@@ -155,7 +157,7 @@ impl Keys {
                 let y0 = y_vec_iter.next().unwrap();
                 let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
                 let x_i = secret_shares_vec.iter().fold(FE::zero(), |acc, x| acc + x);
-                Ok(SharedKeys { y, x_i })
+                Ok(SharedKeys { y, x_i: x_i.into() })
             }
             false => Err(InvalidSS),
         }
@@ -175,7 +177,7 @@ impl Keys {
         }
         SharedKeys {
             y: shared_key.y.clone(),
-            x_i: new_xi,
+            x_i: new_xi.into(),
         }
     }
 }
@@ -192,8 +194,8 @@ impl LocalSig {
         local_ephemaral_key: &SharedKeys,
         local_private_key: &SharedKeys,
     ) -> LocalSig {
-        let beta_i = local_ephemaral_key.x_i.clone();
-        let alpha_i = local_private_key.x_i.clone();
+        let beta_i = *local_ephemaral_key.x_i;
+        let alpha_i = *local_private_key.x_i;
 
         let e: FE = compute_e(&local_ephemaral_key.y, &local_private_key.y, message);
         let gamma_i = beta_i + e.clone() * alpha_i;
@@ -214,45 +216,34 @@ impl LocalSig {
         // test that enough parties are in this round
         assert!(parties_index_vec.len() > vss_private_keys[0].parameters.threshold);
 
-        // Vec of joint commitments:
-        // n' = num of signers, n - num of parties in keygen
-        // [com0_eph_0,... ,com0_eph_n', e*com0_kg_0, ..., e*com0_kg_n ;
-        // ...  ;
-        // comt_eph_0,... ,comt_eph_n', e*comt_kg_0, ..., e*comt_kg_n ]
-        let comm_vec = (0..vss_private_keys[0].parameters.threshold + 1)
-            .map(|i| {
-                let mut key_gen_comm_i_vec = (0..vss_private_keys.len())
-                    .map(|j| vss_private_keys[j].commitments[i].clone() * &gamma_vec[i].e)
-                    .collect::<Vec<GE>>();
-                let mut eph_comm_i_vec = (0..vss_ephemeral_keys.len())
-                    .map(|j| vss_ephemeral_keys[j].commitments[i].clone())
-                    .collect::<Vec<GE>>();
-                key_gen_comm_i_vec.append(&mut eph_comm_i_vec);
-                let mut comm_i_vec_iter = key_gen_comm_i_vec.iter();
-                let comm_i_0 = comm_i_vec_iter.next().unwrap();
-                comm_i_vec_iter.fold(comm_i_0.clone(), |acc, x| acc + x)
-            })
-            .collect::<Vec<GE>>();
-
-        let vss_sum = VerifiableSS {
+        // The arithmetic is done by the backend, because it is the hot path of the round.
+        type B = DefaultBackend;
+        let commitments = |vss_vec: &Vec<VerifiableSS>| {
+            vss_vec
+                .iter()
+                .map(|vss| vss.commitments.iter().map(B::point).collect())
+                .collect::<Vec<Vec<<B as Backend>::Point>>>()
+        };
+        let local_sigs = gamma_vec
+            .iter()
+            .map(|sig| (B::scalar(&sig.gamma_i), B::scalar(&sig.e)))
+            .collect::<Vec<_>>();
+        let comm_vec = backend::verify_local_sigs::<B>(
+            &local_sigs,
+            parties_index_vec,
+            &commitments(vss_private_keys),
+            &commitments(vss_ephemeral_keys),
+        )
+        .ok_or(InvalidSS)?
+        .iter()
+        .map(B::to_ge)
+        .collect::<Option<Vec<GE>>>()
+        .ok_or(InvalidSS)?;
+
+        Ok(VerifiableSS {
             parameters: vss_ephemeral_keys[0].parameters.clone(),
             commitments: comm_vec,
-        };
-
-        let g: GE = GE::generator();
-        let correct_ss_verify = (0..parties_index_vec.len())
-            .map(|i| {
-                let gamma_i_g = &g * &gamma_vec[i].gamma_i;
-                vss_sum
-                    .validate_share_public(&gamma_i_g, parties_index_vec[i] + 1)
-                    .is_ok()
-            })
-            .collect::<Vec<bool>>();
-
-        match correct_ss_verify.iter().all(|x| x.clone() == true) {
-            true => Ok(vss_sum),
-            false => Err(InvalidSS),
-        }
+        })
     }
 }
 
@@ -281,13 +272,13 @@ impl Signature {
     }
 
     pub fn verify(&self, message: &[u8], pubkey_y: &GE) -> Result<(), Error> {
-        let e: FE = compute_e(&self.v, pubkey_y, message);
-        let g: GE = GE::generator();
-        let sigma_g = g * &self.sigma;
-        let e_y = pubkey_y * &e;
-        let e_y_plus_v = e_y + &self.v;
-
-        if e_y_plus_v == sigma_g {
+        type B = DefaultBackend;
+        if backend::verify_signature::<B>(
+            &B::scalar(&self.sigma),
+            &B::point(&self.v),
+            &B::point(pubkey_y),
+            message,
+        ) {
             Ok(())
         } else {
             Err(InvalidSig)
@@ -295,7 +286,7 @@ impl Signature {
     }
 }
 
-fn compute_e(r: &GE, y: &GE, message: &[u8]) -> FE {
+pub fn compute_e(r: &GE, y: &GE, message: &[u8]) -> FE {
     let mut hasher = Sha256::new();
     hasher.input(&r.get_element().serialize()[1..33]);
     hasher.input(&y.get_element().serialize()[..]);
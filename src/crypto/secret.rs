@@ -0,0 +1,175 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Wrapper of the secret values.
+//!
+//! `Secret` overwrites the value with zero when it is dropped, and prints `<redacted>` for Debug
+//! and Display, so that the secrets don't end up in the log files. The full values are printed
+//! only after `set_reveal_secrets(true)` is called, which the signer does only if it is started
+//! with `--reveal-secrets`.
+//!
+//! Serialization always writes the full value, because it is used to store and send the secrets.
+//! Note that `FE` is `Copy`, so the copies which are taken out of the wrapper are not zeroized.
+
+use curv::elliptic::curves::traits::ECScalar;
+use curv::FE;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{compiler_fence, AtomicBool, Ordering};
+
+static REVEAL_SECRETS: AtomicBool = AtomicBool::new(false);
+
+/// Print the full values of the secrets in Debug and Display. It is only for debugging.
+pub fn set_reveal_secrets(reveal: bool) {
+    REVEAL_SECRETS.store(reveal, Ordering::SeqCst);
+}
+
+pub fn is_revealed() -> bool {
+    REVEAL_SECRETS.load(Ordering::SeqCst)
+}
+
+pub trait Zeroize {
+    fn zeroize(&mut self);
+}
+
+impl Zeroize for FE {
+    fn zeroize(&mut self) {
+        // The volatile write is not optimized out even though the value is never read again.
+        unsafe { std::ptr::write_volatile(self, FE::zero()) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl Zeroize for u8 {
+    fn zeroize(&mut self) {
+        unsafe { std::ptr::write_volatile(self, 0) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl Zeroize for String {
+    fn zeroize(&mut self) {
+        // Zero is a valid UTF-8, so the string is still valid while it is overwritten.
+        for byte in unsafe { self.as_bytes_mut() } {
+            byte.zeroize();
+        }
+        self.clear();
+    }
+}
+
+impl<A: Zeroize, B: Zeroize> Zeroize for (A, B) {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+        self.1.zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for Vec<T> {
+    fn zeroize(&mut self) {
+        for value in self.iter_mut() {
+            value.zeroize();
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize + fmt::Debug> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if is_revealed() {
+            f.debug_tuple("Secret").field(&self.0).finish()
+        } else {
+            write!(f, "Secret(<redacted>)")
+        }
+    }
+}
+
+impl<T: Zeroize + fmt::Debug> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if is_revealed() {
+            write!(f, "{:?}", self.0)
+        } else {
+            write!(f, "<redacted>")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::BigInt;
+
+    #[test]
+    fn test_redacted() {
+        let fe: FE = ECScalar::from(&BigInt::from(12345));
+        let secret = Secret::new(fe);
+        assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+        assert_eq!(format!("{}", secret), "<redacted>");
+        assert_eq!(
+            format!("{:?}", Some(secret.clone())),
+            "Some(Secret(<redacted>))"
+        );
+        assert_eq!(*secret, fe);
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut value: (FE, FE) = (ECScalar::new_random(), ECScalar::new_random());
+        value.zeroize();
+        assert_eq!(value.0.get_element()[..], [0u8; 32][..]);
+        assert_eq!(value.1.get_element()[..], [0u8; 32][..]);
+    }
+
+    #[test]
+    fn test_zeroize_string() {
+        let mut value = "passphrase".to_string();
+        let ptr = value.as_ptr();
+        value.zeroize();
+        assert!(value.is_empty());
+        // The buffer is still allocated because clear() doesn't release it.
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, "passphrase".len()) };
+        assert_eq!(bytes, &[0u8; 10][..]);
+    }
+
+    #[test]
+    fn test_serialize_full_value() {
+        let fe: FE = ECScalar::from(&BigInt::from(1));
+        let secret = Secret::new(fe);
+        assert_eq!(
+            serde_json::to_string(&secret).unwrap(),
+            serde_json::to_string(&fe).unwrap()
+        );
+        let deserialized: Secret<FE> =
+            serde_json::from_str(&serde_json::to_string(&secret).unwrap()).unwrap();
+        assert_eq!(deserialized, secret);
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Signers which create the signature shares of FROST signing with the node secret share.
+//!
+//! `ShareSigner` hides where the node secret share is kept. `LocalShareSigner` keeps it in the
+//! process, and `UnixSocketShareSigner` asks `ShareSignerServer` running in a separate local
+//! process (`tapyrus-share-signer`) over a Unix domain socket.
+//!
+//! With the socket, the network-facing signer never loads the node secret share. It gets the
+//! federations without the secret shares from the share signer, and the share signer keeps the
+//! nonces too, so the signature shares don't reveal the node secret share to the network-facing
+//! signer. It is supported only for the `frost` signing protocol. The `vss` protocol, the
+//! attestations and the repair of the node VSSs of the other signers are not supported, because
+//! they create the local signatures or the repair pieces from the values which the network-facing
+//! signer knows, and so they would reveal the node secret share.
+//!
+//! The protocol is a request and a response per connection. Both are a line of JSON:
+//!
+//! ```text
+//! request:  "Federations", {"NonceCommitments": 8}
+//!           or {"SignShare": {"block_height": 100, "message": "...", "commitments": [...]}}
+//! response: {"Federations": ...}, {"NonceCommitments": [...]}, {"SignatureShare": "..."}
+//!           or {"Error": "reason"}
+//! ```
+//!
+//! A nonce is used only once, but the share signer signs any message which is requested, so the
+//! socket is created to be accessible only by the owner and the group. The share signer can run as
+//! another user than the network-facing signer, which is the member of the group.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::frost::{NonceCommitments, Nonces, SigningPackage, NONCE_BATCH_SIZE};
+use crate::errors::Error;
+use crate::federation::{Federations, SerFederations};
+use crate::net::SignerID;
+use bitcoin::PublicKey;
+use curv::{FE, GE};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Timeout to wait for the response from the share signer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub trait ShareSigner {
+    /// Returns the commitments of the unused nonces, after creating new nonces up to `count`.
+    fn nonce_commitments(&self, count: usize) -> Result<Vec<(GE, GE)>, Error>;
+
+    /// Returns the signature share of the message with the nonce commitments of the participants
+    /// for the federation at the block height. The nonce of this signer is removed, so that it is
+    /// used only once.
+    fn sign_share(
+        &self,
+        block_height: u64,
+        message: SHA256Hash,
+        commitments: &NonceCommitments,
+    ) -> Result<FE, Error>;
+}
+
+/// Signs with the node secret shares of the federations in the process.
+pub struct LocalShareSigner {
+    federations: Federations,
+    nonces: Mutex<Nonces>,
+}
+
+impl LocalShareSigner {
+    pub fn new(federations: Federations) -> Self {
+        LocalShareSigner {
+            federations,
+            nonces: Mutex::new(Nonces::new()),
+        }
+    }
+}
+
+impl ShareSigner for LocalShareSigner {
+    fn nonce_commitments(&self, count: usize) -> Result<Vec<(GE, GE)>, Error> {
+        Ok(self.nonces.lock().unwrap().commitments(count))
+    }
+
+    fn sign_share(
+        &self,
+        block_height: u64,
+        message: SHA256Hash,
+        commitments: &NonceCommitments,
+    ) -> Result<FE, Error> {
+        let federation = self.federations.get_by_block_height(block_height);
+        if federation.threshold().is_none() {
+            return Err(Error::ShareSignerError(format!(
+                "the signer is not a member of the federation at {} height.",
+                block_height
+            )));
+        }
+        let signer_id = federation.signer_id();
+        let nonce = commitments
+            .get(&signer_id)
+            .and_then(|commitment| self.nonces.lock().unwrap().take(commitment))
+            .ok_or_else(|| {
+                Error::ShareSignerError(
+                    "the nonce of the commitment is unknown or already used.".to_string(),
+                )
+            })?;
+
+        let node_secret_share = federation.node_secret_share();
+        let package = SigningPackage::new(
+            message,
+            commitments.clone(),
+            &federation.signers(),
+            node_secret_share.y,
+        )?;
+        package.sign(&signer_id, &nonce, &node_secret_share.x_i)
+    }
+}
+
+/// Asks the share signer process listening on the Unix domain socket to sign with the node secret
+/// share.
+pub struct UnixSocketShareSigner {
+    path: PathBuf,
+}
+
+impl UnixSocketShareSigner {
+    pub fn new(path: PathBuf) -> Self {
+        UnixSocketShareSigner { path }
+    }
+
+    /// Returns the federations of the signer without the node secret shares.
+    pub fn federations(&self, pubkey: &PublicKey) -> Result<Federations, Error> {
+        match self.request(&Request::Federations)? {
+            Response::Federations(ser) => Federations::from_pubkey_and_public_ser(pubkey, ser),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    fn request(&self, request: &Request) -> Result<Response, Error> {
+        match self.exchange(request) {
+            Ok(Response::Error(reason)) => Err(Error::ShareSignerError(reason)),
+            Ok(response) => Ok(response),
+            Err(e) => Err(Error::ShareSignerError(format!(
+                "failed to request to {:?}. {:?}",
+                self.path, e
+            ))),
+        }
+    }
+
+    fn exchange(&self, request: &Request) -> Result<Response, Error> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        stream.write_all(&line)?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    fn unexpected_response(&self) -> Error {
+        Error::ShareSignerError(format!("unexpected response from {:?}.", self.path))
+    }
+}
+
+impl ShareSigner for UnixSocketShareSigner {
+    fn nonce_commitments(&self, count: usize) -> Result<Vec<(GE, GE)>, Error> {
+        match self.request(&Request::NonceCommitments(count))? {
+            Response::NonceCommitments(commitments) => Ok(commitments),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    fn sign_share(
+        &self,
+        block_height: u64,
+        message: SHA256Hash,
+        commitments: &NonceCommitments,
+    ) -> Result<FE, Error> {
+        let request = Request::SignShare {
+            block_height,
+            message,
+            commitments: commitments
+                .iter()
+                .map(|(id, commitment)| (*id, *commitment))
+                .collect(),
+        };
+        match self.request(&request)? {
+            Response::SignatureShare(share) => Ok(share),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// The federations without the node secret shares.
+    Federations,
+    NonceCommitments(usize),
+    SignShare {
+        block_height: u64,
+        message: SHA256Hash,
+        /// The keys of JSON objects must be strings, so the commitments are sent as a list.
+        commitments: Vec<(SignerID, (GE, GE))>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Federations(SerFederations),
+    NonceCommitments(Vec<(GE, GE)>),
+    SignatureShare(FE),
+    Error(String),
+}
+
+/// Serves `UnixSocketShareSigner` with the node secret shares of the federations.
+pub struct ShareSignerServer {
+    listener: UnixListener,
+    federations: Federations,
+    signer: LocalShareSigner,
+}
+
+impl ShareSignerServer {
+    /// Listens on the socket at the path. The stale socket left at the path is removed, but the
+    /// other kinds of files are not.
+    pub fn bind(path: &Path, federations: Federations) -> Result<Self, Error> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(Error::ShareSignerError(format!(
+                    "{:?} already exists and is not a socket.",
+                    path
+                )));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))?;
+        Ok(ShareSignerServer {
+            listener,
+            federations: federations.without_secret_shares(),
+            signer: LocalShareSigner::new(federations),
+        })
+    }
+
+    /// Handles the requests one by one until the process is stopped.
+    pub fn run(&self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.handle(stream) {
+                        log::warn!("Failed to handle the request. {:?}", e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to accept the connection. {:?}", e),
+            }
+        }
+    }
+
+    fn handle(&self, stream: UnixStream) -> Result<(), Error> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new((&stream).take(64 * 1024)).read_line(&mut line)?;
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match self.respond(request) {
+                Ok(response) => response,
+                Err(e) => Response::Error(format!("{:?}", e)),
+            },
+            Err(e) => Response::Error(format!("invalid request. {}", e)),
+        };
+
+        let mut line = serde_json::to_vec(&response)?;
+        line.push(b'\n');
+        (&stream).write_all(&line)?;
+        Ok(())
+    }
+
+    fn respond(&self, request: Request) -> Result<Response, Error> {
+        match request {
+            Request::Federations => Ok(Response::Federations(self.federations.to_ser())),
+            Request::NonceCommitments(count) if count <= NONCE_BATCH_SIZE => Ok(
+                Response::NonceCommitments(self.signer.nonce_commitments(count)?),
+            ),
+            Request::NonceCommitments(count) => Err(Error::ShareSignerError(format!(
+                "too many nonces are requested. count: {}",
+                count
+            ))),
+            Request::SignShare {
+                block_height,
+                message,
+                commitments,
+            } => {
+                log::info!(
+                    "Sign the block at {} height. sighash: {:?}",
+                    block_height,
+                    message
+                );
+                let share = self.signer.sign_share(
+                    block_height,
+                    message,
+                    &commitments.into_iter().collect(),
+                )?;
+                Ok(Response::SignatureShare(share))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::frost::public_share;
+    use crate::federation::{Federation, SigningProtocol};
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+        .with_signing_protocol(SigningProtocol::Frost)
+    }
+
+    fn message() -> SHA256Hash {
+        SHA256Hash::from_slice(&[1u8; 32][..]).unwrap()
+    }
+
+    /// Returns the commitments of the signer 0, 1 and 3, where the one of the signer 0 is created
+    /// by the share signer.
+    fn commitments<S: ShareSigner>(signer: &S) -> NonceCommitments {
+        let mut commitments = NonceCommitments::new();
+        commitments.insert(
+            TEST_KEYS.signer_ids()[0],
+            signer.nonce_commitments(NONCE_BATCH_SIZE).unwrap()[0],
+        );
+        for i in &[1, 3] {
+            let commitment = Nonces::new().commitments(1)[0];
+            commitments.insert(TEST_KEYS.signer_ids()[*i], commitment);
+        }
+        commitments
+    }
+
+    /// Asserts that the share is valid and the nonce of it can't be used again.
+    fn assert_share<S: ShareSigner>(signer: &S, commitments: &NonceCommitments, share: &FE) {
+        let package = SigningPackage::new(
+            message(),
+            commitments.clone(),
+            &TEST_KEYS.signer_ids(),
+            federation(0).node_secret_share().y,
+        )
+        .unwrap();
+        let public_share = public_share(&federation(0).node_shared_secrets(), 1);
+        assert!(package
+            .verify_share(&TEST_KEYS.signer_ids()[0], share, &public_share)
+            .is_ok());
+
+        match signer.sign_share(0, message(), commitments) {
+            Err(Error::ShareSignerError(_)) => {}
+            _ => panic!("the nonce should not be used twice"),
+        }
+    }
+
+    fn start_server(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let server = ShareSignerServer::bind(&path, Federations::new(vec![federation(0)])).unwrap();
+        std::thread::spawn(move || server.run());
+        path
+    }
+
+    #[test]
+    fn test_sign_in_process() {
+        let signer = LocalShareSigner::new(Federations::new(vec![federation(0)]));
+        let commitments = commitments(&signer);
+        let share = signer.sign_share(0, message(), &commitments).unwrap();
+        assert_share(&signer, &commitments, &share);
+    }
+
+    #[test]
+    fn test_sign_over_unix_socket() {
+        let path = start_server("tapyrus_signer_test_share_signer.sock");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        let signer = UnixSocketShareSigner::new(path);
+        let commitments = commitments(&signer);
+        let share = signer.sign_share(0, message(), &commitments).unwrap();
+        assert_share(&signer, &commitments, &share);
+    }
+
+    #[test]
+    fn test_federations_over_unix_socket() {
+        let path = start_server("tapyrus_signer_test_share_signer_federations.sock");
+        let signer = UnixSocketShareSigner::new(path);
+        let federations = signer.federations(&TEST_KEYS.pubkeys()[0]).unwrap();
+
+        // The federations are sent without the node secret shares.
+        let received = federations.last();
+        assert!(!received.has_secret_shares());
+        assert_eq!(received.signers(), TEST_KEYS.signer_ids());
+        assert_eq!(received.signing_protocol(), SigningProtocol::Frost);
+        assert_eq!(
+            public_share(&received.node_shared_secrets(), 1),
+            public_share(&federation(0).node_shared_secrets(), 1)
+        );
+    }
+
+    #[test]
+    fn test_sign_with_unknown_nonce() {
+        let signer = LocalShareSigner::new(Federations::new(vec![federation(0)]));
+        let mut commitments = commitments(&signer);
+        commitments.insert(TEST_KEYS.signer_ids()[0], Nonces::new().commitments(1)[0]);
+        match signer.sign_share(0, message(), &commitments) {
+            Err(Error::ShareSignerError(_)) => {}
+            _ => panic!("it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_sign_invalid_request() {
+        let path = start_server("tapyrus_signer_test_share_signer_invalid.sock");
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"{\"SignShare\": {\"block_height\": 0}}\n")
+            .unwrap();
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        match serde_json::from_str(&response).unwrap() {
+            Response::Error(reason) => assert!(reason.starts_with("invalid request.")),
+            _ => panic!("it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_too_many_nonces() {
+        let path = start_server("tapyrus_signer_test_share_signer_too_many.sock");
+        let signer = UnixSocketShareSigner::new(path);
+        match signer.nonce_commitments(NONCE_BATCH_SIZE + 1) {
+            Err(Error::ShareSignerError(_)) => {}
+            _ => panic!("it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_sign_without_server() {
+        let path = std::env::temp_dir().join("tapyrus_signer_test_share_signer_none.sock");
+        let signer = UnixSocketShareSigner::new(path);
+        match signer.nonce_commitments(1) {
+            Err(Error::ShareSignerError(_)) => {}
+            _ => panic!("it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_bind_not_socket() {
+        let path = std::env::temp_dir().join("tapyrus_signer_test_share_signer_file");
+        std::fs::write(&path, "").unwrap();
+        match ShareSignerServer::bind(&path, Federations::new(vec![federation(0)])) {
+            Err(Error::ShareSignerError(_)) => {}
+            _ => panic!("it should fail"),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -189,7 +189,7 @@ fn get_shared_keys(str_secret_key: &str) -> SharedKeys {
 
     SharedKeys {
         y: &ECPoint::generator() * &privkey,
-        x_i: privkey,
+        x_i: privkey.into(),
     }
 }
 
@@ -198,7 +198,7 @@ fn get_random_shared_keys() -> SharedKeys {
 
     SharedKeys {
         y: &ECPoint::generator() * &privkey,
-        x_i: privkey,
+        x_i: privkey.into(),
     }
 }
 
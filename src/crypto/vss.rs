@@ -1,8 +1,11 @@
-use crate::blockdata::Block;
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::backend::{self, Backend, DefaultBackend};
+use crate::crypto::block_key::BlockKeyDerivation;
 use crate::crypto::multi_party_schnorr::Keys;
 use crate::crypto::multi_party_schnorr::LocalSig;
 use crate::crypto::multi_party_schnorr::SharedKeys;
 use crate::crypto::multi_party_schnorr::Signature;
+use crate::crypto::secret::Secret;
 use crate::errors::Error;
 use crate::net::SignerID;
 use crate::serialize::HexStrVisitor;
@@ -12,11 +15,12 @@ use crate::signer_node::BidirectionalSharedSecretMap;
 use crate::signer_node::SharedSecretMap;
 use crate::signer_node::ToSharedSecretMap;
 use crate::signer_node::ToVerifiableSS;
-use crate::util::jacobi;
 use bitcoin::consensus::encode::{self, *};
 use bitcoin::{PrivateKey, PublicKey};
 use curv::arithmetic::traits::Converter;
-use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
 use curv::elliptic::curves::traits::ECScalar;
 use curv::elliptic::curves::traits::*;
 use curv::{BigInt, FE, GE};
@@ -39,9 +43,9 @@ pub struct Vss {
     pub sender_public_key: PublicKey,
     pub receiver_public_key: PublicKey,
     pub positive_commitments: Vec<Commitment>,
-    pub positive_secret: FE,
+    pub positive_secret: Secret<FE>,
     pub negative_commitments: Vec<Commitment>,
-    pub negative_secret: FE,
+    pub negative_secret: Secret<FE>,
 }
 
 impl Vss {
@@ -58,12 +62,33 @@ impl Vss {
             sender_public_key: sender_public_key,
             receiver_public_key: receiver_public_key,
             positive_commitments: positive_commitments,
-            positive_secret: positive_secret,
+            positive_secret: positive_secret.into(),
             negative_commitments: negative_commitments,
-            negative_secret: negative_secret,
+            negative_secret: negative_secret.into(),
         }
     }
 
+    /// Verify that the positive secret is a valid share for the receiver at `index` with the
+    /// positive commitments. `share_count` is the number of signers the secret is shared with.
+    pub fn verify_positive_share(&self, share_count: usize, index: usize) -> Result<(), Error> {
+        if self.positive_commitments.is_empty() {
+            return Err(Error::InvalidSS);
+        }
+        let vss = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: self.positive_commitments.len() - 1,
+                share_count,
+            },
+            commitments: self
+                .positive_commitments
+                .iter()
+                .map(|c| c.to_point())
+                .collect(),
+        };
+        vss.validate_share(&self.positive_secret, index)
+            .map_err(|_| Error::InvalidSS)
+    }
+
     pub fn create_node_shares(
         private_key: &PrivateKey,
         threshold: usize,
@@ -78,8 +103,92 @@ impl Vss {
         let key_as_int =
             Sign::private_key_to_big_int(private_key.key).expect("failed to parse private_key");
         let secret = ECScalar::from(&key_as_int);
+        Vss::create_shares(&secret, threshold, share_count)
+    }
+
+    /// Share the secret among the parties 1 to `share_count`.
+    pub fn create_shares(
+        secret: &FE,
+        threshold: usize,
+        share_count: usize,
+    ) -> (VerifiableSS, Vec<FE>) {
+        assert!(
+            share_count >= threshold,
+            "share count should be greater or equal to threshold. share_count: {}, threshold: {}",
+            share_count,
+            threshold
+        );
         let parties = (0..share_count).map(|i| i + 1).collect::<Vec<usize>>();
-        VerifiableSS::share_at_indices(threshold - 1, share_count, &secret, &parties)
+        VerifiableSS::share_at_indices(threshold - 1, share_count, secret, &parties)
+    }
+
+    /// Same as `create_block_shares`, but the block key and the coefficients of the VSSs are
+    /// derived by `derivation` instead of the random number generator.
+    pub fn create_block_shares_with(
+        derivation: &BlockKeyDerivation,
+        index: usize,
+        threshold: usize,
+        share_count: usize,
+    ) -> (Keys, VerifiableSS, Vec<FE>, VerifiableSS, Vec<FE>) {
+        assert!(
+            share_count >= threshold,
+            "share count should be greater or equal to threshold. share_count: {}, threshold: {}",
+            share_count,
+            threshold
+        );
+
+        let key = Sign::create_key(index, Some(derivation.scalar(0).to_big_int()));
+        let degree = (threshold - 1) as u32;
+        let coefficients_for_positive: Vec<FE> =
+            (1..=degree).map(|i| derivation.scalar(i)).collect();
+        let coefficients_for_negative: Vec<FE> = (1..=degree)
+            .map(|i| derivation.scalar(degree + i))
+            .collect();
+
+        let (vss_scheme_for_positive, secret_shares_for_positive) =
+            Vss::share_with_coefficients(&key.u_i, &coefficients_for_positive, share_count);
+        let (vss_scheme_for_negative, secret_shares_for_negative) = Vss::share_with_coefficients(
+            &ECScalar::from(&(FE::q() - key.u_i.to_big_int())),
+            &coefficients_for_negative,
+            share_count,
+        );
+        (
+            key,
+            vss_scheme_for_positive,
+            secret_shares_for_positive,
+            vss_scheme_for_negative,
+            secret_shares_for_negative,
+        )
+    }
+
+    /// Share the secret among the parties 1 to `share_count` with the polynomial whose
+    /// coefficients of degree 1 or more are `coefficients`.
+    fn share_with_coefficients(
+        secret: &FE,
+        coefficients: &[FE],
+        share_count: usize,
+    ) -> (VerifiableSS, Vec<FE>) {
+        let g: GE = ECPoint::generator();
+        let mut polynomial = vec![*secret];
+        polynomial.extend_from_slice(coefficients);
+        let commitments: Vec<GE> = polynomial.iter().map(|a| g * a).collect();
+        let shares: Vec<FE> = (1..=share_count)
+            .map(|index| {
+                let x: FE = ECScalar::from(&BigInt::from(index as u32));
+                // Horner's method
+                let mut iter = polynomial.iter().rev();
+                let head = *iter.next().expect("polynomial should not be empty");
+                iter.fold(head, |acc, a| acc * x + a)
+            })
+            .collect();
+        let vss = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: coefficients.len(),
+                share_count,
+            },
+            commitments,
+        };
+        (vss, shares)
     }
 
     pub fn create_block_shares(
@@ -117,34 +226,26 @@ impl Vss {
         )
     }
 
+    /// Returns the local signature of the message, such as the sighash of the block, which is
+    /// created with the node secret share.
     pub fn create_local_sig_from_shares(
         priv_shared_keys: &SharedKeys,
         index: usize,
         shared_block_secrets: &BidirectionalSharedSecretMap,
-        block: &Block,
+        message: SHA256Hash,
     ) -> Result<(bool, SharedKeys, LocalSig), Error> {
         let shared_keys_for_positive =
             Sign::verify_vss_and_construct_key(&shared_block_secrets.for_positive(), &index)?;
-        let local_sig_for_positive = Sign::sign(
-            &shared_keys_for_positive,
-            &priv_shared_keys,
-            block.sighash(),
-        );
+        let local_sig_for_positive =
+            Sign::sign(&shared_keys_for_positive, priv_shared_keys, message);
 
         let shared_keys_for_negative =
             Sign::verify_vss_and_construct_key(&shared_block_secrets.for_negative(), &index)?;
-        let local_sig_for_negative = Sign::sign(
-            &shared_keys_for_negative,
-            &priv_shared_keys,
-            block.sighash(),
-        );
+        let local_sig_for_negative =
+            Sign::sign(&shared_keys_for_negative, priv_shared_keys, message);
 
-        let p = BigInt::from_str_radix(
-            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F",
-            16,
-        )
-        .unwrap();
-        let is_positive = jacobi(&shared_keys_for_positive.y.y_coor().unwrap(), &p) == 1;
+        let is_positive =
+            DefaultBackend::has_square_y(&DefaultBackend::point(&shared_keys_for_positive.y));
         let (shared_keys, local_sig) = if is_positive {
             (shared_keys_for_positive, local_sig_for_positive)
         } else {
@@ -153,12 +254,14 @@ impl Vss {
         Ok((is_positive, shared_keys, local_sig))
     }
 
+    /// Aggregates the local signatures of the message, such as the sighash of the block, and
+    /// verifies the signature with the aggregated public key.
     pub fn aggregate_and_verify_signature(
-        block: &Block,
+        message: SHA256Hash,
         signatures: BTreeMap<SignerID, (FE, FE)>,
         pubkey_list: &Vec<PublicKey>,
         shared_secrets: &SharedSecretMap,
-        block_shared_keys: &Option<(bool, FE, GE)>,
+        block_shared_keys: &Option<(bool, Secret<FE>, GE)>,
         shared_block_secrets: &BidirectionalSharedSecretMap,
         priv_shared_keys: &SharedKeys,
     ) -> Result<Signature, Error> {
@@ -174,7 +277,7 @@ impl Vss {
                 e: s.1,
             })
             .collect();
-        let eph_vss_vec: Vec<VerifiableSS> = if block_shared_keys.unwrap().0 {
+        let eph_vss_vec: Vec<VerifiableSS> = if block_shared_keys.as_ref().unwrap().0 {
             shared_block_secrets.for_positive().to_vss()
         } else {
             shared_block_secrets.for_negative().to_vss()
@@ -186,13 +289,149 @@ impl Vss {
             &vss_sum,
             &local_sigs,
             &parties[..],
-            block_shared_keys.unwrap().2,
+            block_shared_keys.as_ref().unwrap().2,
         );
         let public_key = priv_shared_keys.y;
-        let hash = block.sighash().into_inner();
-        signature.verify(&hash, &public_key)?;
+        signature.verify(&message.into_inner(), &public_key)?;
         Ok(signature)
     }
+
+    /// Returns the signers whose local signatures are invalid, when
+    /// `aggregate_and_verify_signature` fails. The challenge in the local signature of `own` is
+    /// used to verify the others, because it is computed by this signer.
+    pub fn invalid_local_sig_senders(
+        own: &SignerID,
+        signatures: &BTreeMap<SignerID, (FE, FE)>,
+        pubkey_list: &Vec<PublicKey>,
+        shared_secrets: &SharedSecretMap,
+        block_shared_keys: &Option<(bool, Secret<FE>, GE)>,
+        shared_block_secrets: &BidirectionalSharedSecretMap,
+    ) -> Vec<SignerID> {
+        let (is_positive, e) = match (block_shared_keys, signatures.get(own)) {
+            (Some((is_positive, ..)), Some((_, e))) => (*is_positive, e),
+            _ => return vec![],
+        };
+
+        type B = DefaultBackend;
+        let commitments = |vss_vec: Vec<VerifiableSS>| {
+            vss_vec
+                .iter()
+                .map(|vss| vss.commitments.iter().map(B::point).collect())
+                .collect::<Vec<Vec<<B as Backend>::Point>>>()
+        };
+        let private_commitments = commitments(shared_secrets.to_vss());
+        let ephemeral_commitments = commitments(if is_positive {
+            shared_block_secrets.for_positive().to_vss()
+        } else {
+            shared_block_secrets.for_negative().to_vss()
+        });
+        signatures
+            .iter()
+            .filter(|(signer_id, (gamma_i, sig_e))| {
+                sig_e != e
+                    || !backend::verify_local_sig::<B>(
+                        &B::scalar(gamma_i),
+                        &B::scalar(e),
+                        sender_index(signer_id, pubkey_list),
+                        &private_commitments,
+                        &ephemeral_commitments,
+                    )
+            })
+            .map(|(signer_id, _)| *signer_id)
+            .collect()
+    }
+
+    /// Returns the node VSS which is re-randomized with the zero share from the same dealer.
+    /// The secret and the first commitment don't change, so the aggregated public key doesn't
+    /// change.
+    pub fn add_zero_share(&self, zero: &ZeroVss) -> Vss {
+        assert_eq!(self.positive_commitments.len(), zero.commitments.len() + 1);
+        let add = |commitments: &Vec<Commitment>| -> Vec<Commitment> {
+            commitments
+                .iter()
+                .enumerate()
+                .map(|(k, c)| {
+                    if k == 0 {
+                        c.clone()
+                    } else {
+                        Commitment::from(&(c.to_point() + &zero.commitments[k - 1]))
+                    }
+                })
+                .collect()
+        };
+        Vss::new(
+            self.sender_public_key,
+            self.receiver_public_key,
+            add(&self.positive_commitments),
+            *self.positive_secret + zero.secret,
+            add(&self.negative_commitments),
+            *self.negative_secret + zero.secret,
+        )
+    }
+}
+
+/// Returns the point of the share at `index` of the polynomial whose coefficients are committed
+/// by `commitments`. `commitments` should not be empty.
+pub fn share_point(commitments: &[GE], index: usize) -> GE {
+    let x: FE = ECScalar::from(&BigInt::from(index as u32));
+    let mut iter = commitments.iter().rev();
+    let head = *iter.next().expect("commitments should not be empty");
+    iter.fold(head, |acc, c| acc * &x + c)
+}
+
+/// VSS of zero which is used to refresh node VSS.
+/// `commitments` don't include the commitment for the constant term, because it is the point at
+/// infinity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZeroVss {
+    pub sender_public_key: PublicKey,
+    pub receiver_public_key: PublicKey,
+    pub commitments: Vec<GE>,
+    pub secret: FE,
+}
+
+impl ZeroVss {
+    /// Returns commitments for the coefficients of degree 1 to `threshold - 1` and shares of
+    /// zero for the parties 1 to `share_count`.
+    pub fn create_shares(threshold: usize, share_count: usize) -> (Vec<GE>, Vec<FE>) {
+        assert!(
+            threshold >= 2,
+            "threshold should be greater than 1 to share zero. threshold: {}",
+            threshold
+        );
+        assert!(
+            share_count >= threshold,
+            "share count should be greater or equal to threshold. share_count: {}, threshold: {}",
+            share_count,
+            threshold
+        );
+        let g: GE = ECPoint::generator();
+        let coefficients: Vec<FE> = (1..threshold).map(|_| ECScalar::new_random()).collect();
+        let commitments: Vec<GE> = coefficients.iter().map(|a| g * a).collect();
+        let shares: Vec<FE> = (1..=share_count)
+            .map(|index| {
+                let x: FE = ECScalar::from(&BigInt::from(index as u32));
+                // Horner's method without the constant term.
+                let mut iter = coefficients.iter().rev();
+                let head = *iter.next().expect("coefficients should not be empty");
+                iter.fold(head, |acc, a| acc * x + a) * x
+            })
+            .collect();
+        (commitments, shares)
+    }
+
+    /// Verify that the secret is a valid share of zero for the receiver at `index`.
+    pub fn verify(&self, index: usize) -> bool {
+        let x: FE = ECScalar::from(&BigInt::from(index as u32));
+        let mut iter = self.commitments.iter().rev();
+        let head = match iter.next() {
+            Some(c) => *c,
+            None => return false,
+        };
+        let expected = iter.fold(head, |acc, c| acc * &x + c) * &x;
+        let g: GE = ECPoint::generator();
+        expected == g * &self.secret
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -313,13 +552,13 @@ impl Decodable for Vss {
             .collect::<Vec<Commitment>>();
 
         let bytes: [u8; 32] = Decodable::consensus_decode(&mut d)?;
-        let positive_secret = ECScalar::from(&BigInt::from(&bytes[..]));
+        let positive_secret: FE = ECScalar::from(&BigInt::from(&bytes[..]));
         let negative_commitments: Vec<Commitment> = (0..length)
             .flat_map(|_| Decodable::consensus_decode(&mut d))
             .collect::<Vec<Commitment>>();
 
         let bytes: [u8; 32] = Decodable::consensus_decode(&mut d)?;
-        let negative_secret = ECScalar::from(&BigInt::from(&bytes[..]));
+        let negative_secret: FE = ECScalar::from(&BigInt::from(&bytes[..]));
         Ok(Vss::new(
             sender_public_key,
             receiver_public_key,
@@ -443,8 +682,8 @@ mod tests {
             .unwrap()
         );
 
-        assert_eq!(vss.positive_secret, ECScalar::from(&BigInt::from(1)));
-        assert_eq!(vss.negative_secret, ECScalar::from(&BigInt::from(2)));
+        assert_eq!(*vss.positive_secret, ECScalar::from(&BigInt::from(1)));
+        assert_eq!(*vss.negative_secret, ECScalar::from(&BigInt::from(2)));
     }
 
     #[test]
@@ -483,9 +722,9 @@ mod tests {
             )
             .unwrap(),
             positive_commitments: vec![p1],
-            positive_secret: ECScalar::from(&BigInt::from(1)),
+            positive_secret: Secret::new(ECScalar::from(&BigInt::from(1))),
             negative_commitments: vec![p2],
-            negative_secret: ECScalar::from(&BigInt::from(2)),
+            negative_secret: Secret::new(ECScalar::from(&BigInt::from(2))),
         };
         assert_eq!(format!("{}", vss), "03842d51608d08bee79587fb3b54ea68f5279e13fac7d72515a7205e6672858ca203e568e3a5641ac21930b51f92fb6dd201fb46faae560b108cf3a96380da08dee100014f8f2711cfcf76a4d3cb350b5cd59906685dc7fbb320541e7e1f7885b37163967359e69f3af7b7e1b3e3a294ab81a2c5b02658b8deee2008aa39eff6bf55742900000000000000000000000000000000000000000000000000000000000000014f8f2711cfcf76a4d3cb350b5cd59906685dc7fbb320541e7e1f7885b37163968ca61960c508481e4c1c5d6b547e5d3a4fd9a7472111dff755c6100840aa88060000000000000000000000000000000000000000000000000000000000000002")
     }
@@ -523,6 +762,33 @@ mod tests {
         Vss::create_block_shares(1, 4, 3);
     }
 
+    #[test]
+    fn test_create_block_shares_with() {
+        let secret: FE = ECScalar::from(&BigInt::from(1));
+        let derivation = BlockKeyDerivation::new(&secret, &[1u8; 32], 0, &[0u8; 32]);
+        let (key, vss_for_pos, shares_for_pos, vss_for_neg, shares_for_neg) =
+            Vss::create_block_shares_with(&derivation, 1, 3, 5);
+
+        assert_eq!(*key.u_i, derivation.scalar(0));
+        assert_eq!(vss_for_pos.commitments.len(), 3);
+        assert_eq!(vss_for_neg.commitments.len(), 3);
+        assert_eq!(vss_for_pos.commitments[0], key.y_i);
+        let negative_secret = vss_for_neg.reconstruct(&[0, 1, 2], &shares_for_neg[0..3]);
+        assert_eq!(negative_secret.to_big_int(), FE::q() - key.u_i.to_big_int());
+        for i in 0..5 {
+            assert!(vss_for_pos
+                .validate_share(&shares_for_pos[i], i + 1)
+                .is_ok());
+            assert!(vss_for_neg
+                .validate_share(&shares_for_neg[i], i + 1)
+                .is_ok());
+        }
+
+        // The same derivation produces the same shares.
+        let (_, _, shares, _, _) = Vss::create_block_shares_with(&derivation, 1, 3, 5);
+        assert_eq!(shares, shares_for_pos);
+    }
+
     #[test]
     fn test_create_local_sig_from_shares() {
         let contents = load_test_vector("./tests/resources/vss.json").unwrap();
@@ -544,9 +810,13 @@ mod tests {
             })
             .collect();
 
-        let (is_positive, key, local_sig) =
-            Vss::create_local_sig_from_shares(&priv_shared_keys, 1, &shared_block_secrets, &block)
-                .expect("error occurred in Vss::create_local_sig_from_shares");
+        let (is_positive, key, local_sig) = Vss::create_local_sig_from_shares(
+            &priv_shared_keys,
+            1,
+            &shared_block_secrets,
+            block.sighash(),
+        )
+        .expect("error occurred in Vss::create_local_sig_from_shares");
 
         let expected_localsig = to_local_sig(&v["expected_localsig"]).unwrap();
         let expected_block_shared_keys =
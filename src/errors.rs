@@ -2,6 +2,7 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
+use crate::net::SignerID;
 use redis::RedisError;
 
 #[derive(Debug)]
@@ -32,8 +33,40 @@ pub enum Error {
     InvalidFederation(Option<u64>, &'static str),
     /// Error when the aggregated public key included the candidate block is invalid.
     InvalidAggregatedPublicKey,
+    /// Error when the max block size included the candidate block is invalid.
+    InvalidMaxBlockSize,
     /// xField is not supported by signer.
     UnsupportedXField,
+    /// Error when the signer who is specified by the id behaved incorrectly in a key generation protocol.
+    DisqualifiedSigner(SignerID),
+    /// Error when signers received different commitments from same dealer in a key generation protocol.
+    InconsistentCommitments,
+    /// Error when a protocol over the signer network did not finish in time.
+    ProtocolTimeout,
+    /// Error when the connection to the signer network is lost.
+    ConnectionError(String),
+    /// Error when the keystore can not be decrypted. This error has the reason.
+    InvalidKeystore(&'static str),
+    /// Error when the passphrase of the keystore can not be read.
+    InvalidPassphrase(String),
+    /// Error when the share signer fails to create the signature share. This error has the reason.
+    ShareSignerError(String),
+    /// Error when the candidate block violates the block policy of the signer. This error has the reason.
+    PolicyViolation(String),
+    /// Error when the approval of the candidate block can not be got. This error has the reason.
+    ApprovalError(String),
+    /// Error when the signed block didn't become the tip of the chain in any Tapyrus Core node.
+    BlockNotAccepted,
+    /// Error when Tapyrus Core can not be reached or doesn't respond properly. This error has the reason.
+    RpcConnection(String),
+    /// Error when the audit log is broken. This error has the line number and the reason.
+    InvalidAuditLog(usize, &'static str),
+    /// Error when the evidence of the equivocation is invalid. This error has the reason.
+    InvalidEvidence(&'static str),
+    /// Error when the attestation to sign is invalid. This error has the reason.
+    InvalidAttestation(&'static str),
+    /// Error in the admin interface. This error has the reason.
+    AdminInterfaceError(String),
 }
 
 impl std::fmt::Display for Error {
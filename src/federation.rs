@@ -1,6 +1,7 @@
 use crate::crypto::multi_party_schnorr::SharedKeys;
 use crate::crypto::vss::Vss;
 use crate::errors::Error;
+use crate::keystore::{self, Passphrase};
 use crate::net::SignerID;
 use crate::sign::Sign;
 use crate::signer_node::{SharedSecret, SharedSecretMap};
@@ -8,6 +9,8 @@ use bitcoin::PublicKey;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
     ShamirSecretSharing, VerifiableSS,
 };
+use curv::elliptic::curves::traits::ECScalar;
+use curv::FE;
 use std::collections::HashSet;
 
 #[derive(Debug, Clone)]
@@ -43,6 +46,54 @@ impl Federations {
         self.federations.len()
     }
 
+    pub fn iter(&self) -> std::slice::Iter<'_, Federation> {
+        self.federations.iter()
+    }
+
+    /// Returns true if the federation which gets started at `block_height` has the aggregated
+    /// public key different from the previous federation. The first federation is always
+    /// treated as changed.
+    pub fn is_aggregated_public_key_changed_at(&self, block_height: u64) -> bool {
+        let position = match self
+            .federations
+            .iter()
+            .position(|f| f.block_height == block_height)
+        {
+            Some(position) => position,
+            None => return false,
+        };
+        if position == 0 {
+            return true;
+        }
+        self.federations[position - 1].aggregated_public_key
+            != self.federations[position].aggregated_public_key
+    }
+
+    /// Returns true if the federation which gets started at `block_height` schedules the max
+    /// block size different from the one in effect before it.
+    pub fn is_max_block_size_changed_at(&self, block_height: u64) -> bool {
+        let position = match self
+            .federations
+            .iter()
+            .position(|f| f.block_height == block_height)
+        {
+            Some(position) => position,
+            None => return false,
+        };
+        match self.federations[position].max_block_size {
+            Some(max_block_size) => self.max_block_size_before(position) != Some(max_block_size),
+            None => false,
+        }
+    }
+
+    /// Returns the max block size scheduled by the federations before the position.
+    fn max_block_size_before(&self, position: usize) -> Option<u32> {
+        self.federations[..position]
+            .iter()
+            .rev()
+            .find_map(|f| f.max_block_size)
+    }
+
     pub fn validate(&self) -> Result<(), Error> {
         if self.federations.len() == 0 {
             return Err(Error::InvalidFederation(
@@ -63,6 +114,16 @@ impl Federations {
 
         for federation in &self.federations {
             federation.validate()?;
+
+            // The block has only one xfield, so it can't carry both of them.
+            if self.is_aggregated_public_key_changed_at(federation.block_height)
+                && self.is_max_block_size_changed_at(federation.block_height)
+            {
+                return Err(Error::InvalidFederation(
+                    Some(federation.block_height),
+                    "The federation can not change both the aggregated public key and the max block size.",
+                ));
+            }
         }
 
         Ok(())
@@ -70,9 +131,18 @@ impl Federations {
 
     /// Create Federations instance from:
     ///   * `pubkey` The public key of a signer who runs this node.
-    ///   * `toml` toml string for federations.
-    pub fn from_pubkey_and_toml(pubkey: &PublicKey, toml: &str) -> Result<Self, Error> {
-        let ser: SerFederations = toml::from_str(toml)?;
+    ///   * `toml` toml string for federations, or the keystore which is encrypted it.
+    ///   * `passphrase` The source of the passphrase. It is read only if `toml` is the keystore.
+    pub fn from_pubkey_and_toml(
+        pubkey: &PublicKey,
+        toml: &str,
+        passphrase: &Passphrase,
+    ) -> Result<Self, Error> {
+        let ser: SerFederations = if keystore::is_encrypted(toml) {
+            toml::from_str(&keystore::decrypt(toml, &passphrase.read()?)?)?
+        } else {
+            toml::from_str(toml)?
+        };
 
         let vec: Vec<Federation> = ser
             .federation
@@ -85,6 +155,66 @@ impl Federations {
 
         Ok(r)
     }
+
+    /// Create Federations instance from the federations which the share signer sends without the
+    /// node secret shares.
+    pub fn from_pubkey_and_public_ser(
+        pubkey: &PublicKey,
+        ser: SerFederations,
+    ) -> Result<Self, Error> {
+        let vec: Vec<Federation> = ser
+            .federation
+            .into_iter()
+            .map(|i| Federation::from(*pubkey, i).without_secret_shares())
+            .collect();
+
+        let r = Federations::new(vec);
+        r.validate()?;
+
+        Ok(r)
+    }
+
+    /// Returns the federations whose node VSSs don't have the secret shares.
+    pub fn without_secret_shares(&self) -> Self {
+        Federations::new(
+            self.federations
+                .iter()
+                .map(Federation::without_secret_shares)
+                .collect(),
+        )
+    }
+
+    pub fn to_ser(&self) -> SerFederations {
+        SerFederations::new(
+            self.federations
+                .iter()
+                .cloned()
+                .map(Federation::to_ser)
+                .collect(),
+        )
+    }
+}
+
+/// The protocol which the signers of the federation use to produce block signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningProtocol {
+    /// Each signer deals VSS of the ephemeral key to all signers in every round.
+    Vss,
+    /// Each participant sends nonce commitments and a signature share in the style of FROST.
+    Frost,
+}
+
+impl SigningProtocol {
+    fn is_vss(&self) -> bool {
+        *self == SigningProtocol::Vss
+    }
+}
+
+impl Default for SigningProtocol {
+    fn default() -> Self {
+        SigningProtocol::Vss
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -104,6 +234,15 @@ pub struct Federation {
     nodevss: Vec<Vss>,
     /// The aggregated public key
     aggregated_public_key: PublicKey,
+    /// The protocol to produce block signatures while the federation is used.
+    signing_protocol: SigningProtocol,
+    /// The max block size which Tapyrus network would get started to use from the block height.
+    /// It is set at the previous block like the aggregated public key. None means that this
+    /// federation doesn't change it.
+    max_block_size: Option<u32>,
+    /// False if the secret shares are removed from `nodevss`, because the share signer keeps
+    /// them. The node secret share can't be constructed then.
+    has_secret_shares: bool,
 }
 
 impl Federation {
@@ -120,9 +259,36 @@ impl Federation {
             threshold,
             nodevss,
             aggregated_public_key,
+            signing_protocol: SigningProtocol::default(),
+            max_block_size: None,
+            has_secret_shares: true,
         }
     }
 
+    /// Returns the federation whose node VSSs don't have the secret shares. Only the public
+    /// values, such as the commitments of the node VSSs, are available in it.
+    pub fn without_secret_shares(&self) -> Self {
+        let mut federation = self.clone();
+        for vss in federation.nodevss.iter_mut() {
+            // The secret shares are replaced with random values instead of zero, because zero
+            // can't be decoded as a scalar.
+            vss.positive_secret = FE::new_random().into();
+            vss.negative_secret = FE::new_random().into();
+        }
+        federation.has_secret_shares = false;
+        federation
+    }
+
+    pub fn with_signing_protocol(mut self, signing_protocol: SigningProtocol) -> Self {
+        self.signing_protocol = signing_protocol;
+        self
+    }
+
+    pub fn with_max_block_size(mut self, max_block_size: Option<u32>) -> Self {
+        self.max_block_size = max_block_size;
+        self
+    }
+
     pub fn node_index(&self) -> usize {
         self.signers()
             .iter()
@@ -133,6 +299,10 @@ impl Federation {
             ))
     }
 
+    pub fn signer_id(&self) -> SignerID {
+        self.signer_id
+    }
+
     pub fn signers(&self) -> Vec<SignerID> {
         let mut signers: Vec<SignerID> = self
             .nodevss
@@ -155,6 +325,16 @@ impl Federation {
     pub fn aggregated_public_key(&self) -> PublicKey {
         self.aggregated_public_key
     }
+    pub fn signing_protocol(&self) -> SigningProtocol {
+        self.signing_protocol
+    }
+    pub fn has_secret_shares(&self) -> bool {
+        self.has_secret_shares
+    }
+
+    pub fn max_block_size(&self) -> Option<u32> {
+        self.max_block_size
+    }
 
     /// Returns Map collection of received shares from all each signers in Key Generation Protocol
     pub fn node_shared_secrets(&self) -> SharedSecretMap {
@@ -177,7 +357,7 @@ impl Federation {
                                 .map(|i| i.to_point())
                                 .collect(),
                         },
-                        secret_share: vss.positive_secret,
+                        secret_share: vss.positive_secret.clone(),
                     },
                 );
             }
@@ -187,6 +367,10 @@ impl Federation {
 
     /// Returns an aggregated share of the node.
     pub fn node_secret_share(&self) -> SharedKeys {
+        assert!(
+            self.has_secret_shares,
+            "The node secret share is kept by the share signer."
+        );
         let secret_shares = self.node_shared_secrets();
 
         let shared_keys =
@@ -230,8 +414,12 @@ impl Federation {
             }
         }
 
-        // verify each vss.
-        if Sign::verify_vss_and_construct_key(&self.node_shared_secrets(), &(self.node_index() + 1))
+        // verify each vss. The secret shares are verified by the share signer if it keeps them.
+        if self.has_secret_shares
+            && Sign::verify_vss_and_construct_key(
+                &self.node_shared_secrets(),
+                &(self.node_index() + 1),
+            )
             .is_err()
         {
             return Err(Error::InvalidFederation(
@@ -251,6 +439,8 @@ impl Federation {
             ser.nodevss,
             ser.aggregated_public_key,
         )
+        .with_signing_protocol(ser.signing_protocol)
+        .with_max_block_size(ser.max_block_size)
     }
 
     pub fn to_ser(self) -> SerFederation {
@@ -259,6 +449,8 @@ impl Federation {
             threshold: self.threshold,
             nodevss: self.nodevss,
             aggregated_public_key: self.aggregated_public_key,
+            signing_protocol: self.signing_protocol,
+            max_block_size: self.max_block_size,
         }
     }
 }
@@ -268,6 +460,12 @@ pub struct SerFederations {
     federation: Vec<SerFederation>,
 }
 
+impl SerFederations {
+    pub fn new(federation: Vec<SerFederation>) -> Self {
+        SerFederations { federation }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SerFederation {
     #[serde(rename = "block-height")]
@@ -277,12 +475,26 @@ pub struct SerFederation {
     nodevss: Vec<Vss>,
     #[serde(rename = "aggregated-public-key")]
     aggregated_public_key: PublicKey,
+    #[serde(
+        rename = "signing-protocol",
+        default,
+        skip_serializing_if = "SigningProtocol::is_vss"
+    )]
+    signing_protocol: SigningProtocol,
+    #[serde(
+        rename = "max-block-size",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    max_block_size: Option<u32>,
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::crypto::secret::Secret;
     use crate::errors::Error;
-    use crate::federation::{Federation, Federations};
+    use crate::federation::{Federation, Federations, SigningProtocol};
+    use crate::keystore::Passphrase;
     use crate::net::SignerID;
     use crate::tests::helper::keys::TEST_KEYS;
     use crate::tests::helper::node_vss::node_vss;
@@ -325,6 +537,68 @@ mod tests {
         assert_eq!(federations.get_by_block_height(101).clone(), federation100);
     }
 
+    #[test]
+    fn test_is_aggregated_public_key_changed_at() {
+        let another_key = PublicKey::from_str(
+            "030acd6af981c498ebf2ffd9a341d2a96bde5832c150e7d300fa3583eee0f964fe",
+        )
+        .unwrap();
+        let federations = Federations::new(vec![
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                0,
+                Some(3),
+                node_vss(0),
+                TEST_KEYS.aggregated(),
+            ),
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                100,
+                Some(3),
+                node_vss(1),
+                TEST_KEYS.aggregated(),
+            ),
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                200,
+                Some(4),
+                node_vss(2),
+                another_key,
+            ),
+        ]);
+        assert!(federations.is_aggregated_public_key_changed_at(0));
+        assert!(!federations.is_aggregated_public_key_changed_at(50));
+        assert!(!federations.is_aggregated_public_key_changed_at(100));
+        assert!(federations.is_aggregated_public_key_changed_at(200));
+    }
+
+    #[test]
+    fn test_is_max_block_size_changed_at() {
+        let federation = |block_height: u64, max_block_size: Option<u32>| {
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                block_height,
+                Some(3),
+                node_vss(0),
+                TEST_KEYS.aggregated(),
+            )
+            .with_max_block_size(max_block_size)
+        };
+        let federations = Federations::new(vec![
+            federation(0, None),
+            federation(100, Some(2_000_000)),
+            federation(200, None),
+            federation(300, Some(2_000_000)),
+            federation(400, Some(4_000_000)),
+        ]);
+        assert!(!federations.is_max_block_size_changed_at(0));
+        assert!(!federations.is_max_block_size_changed_at(50));
+        assert!(federations.is_max_block_size_changed_at(100));
+        assert!(!federations.is_max_block_size_changed_at(200));
+        assert!(!federations.is_max_block_size_changed_at(300));
+        assert!(federations.is_max_block_size_changed_at(400));
+    }
+
     #[test]
     fn test_signers() {
         let federation = Federation::new(
@@ -374,6 +648,27 @@ mod tests {
             }
             _ => assert!(false, "it should error"),
         }
+
+        let another_key = PublicKey::from_str(
+            "030acd6af981c498ebf2ffd9a341d2a96bde5832c150e7d300fa3583eee0f964fe",
+        )
+        .unwrap();
+        let federation = Federation::new(
+            TEST_KEYS.pubkeys()[0],
+            100,
+            Some(3),
+            node_vss(0),
+            another_key,
+        )
+        .with_max_block_size(Some(2_000_000));
+        let federations = Federations::new(vec![valid_federation(), federation]);
+        match federations.validate() {
+            Err(Error::InvalidFederation(Some(100), m)) => assert_eq!(
+                m,
+                "The federation can not change both the aggregated public key and the max block size."
+            ),
+            _ => assert!(false, "it should error"),
+        }
     }
 
     #[test]
@@ -418,9 +713,9 @@ mod tests {
         // the federation has invalid secret share
         let mut federation = valid_federation();
 
-        federation.nodevss[0].positive_secret = ECScalar::from(&BigInt::from_hex(
+        federation.nodevss[0].positive_secret = Secret::new(ECScalar::from(&BigInt::from_hex(
             "9b77b12bf0ec14c6094be7657a3a3d473077bc3c8b694ead6c1b6d8c5b4e816c",
-        ));
+        )));
         match federation.validate() {
             Err(Error::InvalidFederation(_, m)) => {
                 assert_eq!(m, "The nodevss includes invalid share.")
@@ -441,6 +736,36 @@ mod tests {
         assert_eq!(federation, deserialized);
     }
 
+    #[test]
+    fn test_serialize_deserialize_signing_protocol() {
+        let federation = valid_federation();
+        assert_eq!(federation.signing_protocol(), SigningProtocol::Vss);
+        let str = toml::to_string(&federation.clone().to_ser()).unwrap();
+        assert!(!str.contains("signing-protocol"));
+
+        let federation = federation.with_signing_protocol(SigningProtocol::Frost);
+        let str = toml::to_string(&federation.clone().to_ser()).unwrap();
+        assert!(str.contains("signing-protocol = \"frost\""));
+        let deserialized =
+            Federation::from(federation.signer_id.pubkey, toml::from_str(&str).unwrap());
+        assert_eq!(federation, deserialized);
+    }
+
+    #[test]
+    fn test_serialize_deserialize_max_block_size() {
+        let federation = valid_federation();
+        assert_eq!(federation.max_block_size(), None);
+        let str = toml::to_string(&federation.clone().to_ser()).unwrap();
+        assert!(!str.contains("max-block-size"));
+
+        let federation = federation.with_max_block_size(Some(2_000_000));
+        let str = toml::to_string(&federation.clone().to_ser()).unwrap();
+        assert!(str.contains("max-block-size = 2000000"));
+        let deserialized =
+            Federation::from(federation.signer_id.pubkey, toml::from_str(&str).unwrap());
+        assert_eq!(federation, deserialized);
+    }
+
     #[test]
     fn test_from_pubkey_and_toml() {
         let pubkey = PublicKey::from_str(
@@ -472,7 +797,8 @@ mod tests {
         ]
         "#;
 
-        let federations = Federations::from_pubkey_and_toml(&pubkey, toml).unwrap();
+        let federations =
+            Federations::from_pubkey_and_toml(&pubkey, toml, &Passphrase::Prompt).unwrap();
         assert_eq!(federations.len(), 2);
 
         // toml has federation item which dosen't have required item 'aggregated_public_key'.
@@ -498,7 +824,7 @@ mod tests {
         ]
         "#;
 
-        match Federations::from_pubkey_and_toml(&pubkey, toml) {
+        match Federations::from_pubkey_and_toml(&pubkey, toml, &Passphrase::Prompt) {
             Err(Error::InvalidTomlFormat(_)) => assert!(true),
             _ => assert!(false, "it should error"),
         }
@@ -527,9 +853,68 @@ mod tests {
         ]
         "#;
 
-        match Federations::from_pubkey_and_toml(&pubkey, toml) {
+        match Federations::from_pubkey_and_toml(&pubkey, toml, &Passphrase::Prompt) {
             Err(Error::InvalidFederation(_, _)) => assert!(true),
             _ => assert!(false, "it should error"),
         }
     }
+
+    #[test]
+    fn test_from_pubkey_and_encrypted_toml() {
+        let pubkey = PublicKey::from_str(
+            "02472012cf49fca573ca1f63deafe59df842f0bbe77e9ac7e67b211bb074b72506",
+        )
+        .unwrap();
+        let toml = std::fs::read_to_string("tests/resources/federations_encrypted.toml").unwrap();
+
+        std::env::set_var("TAPYRUS_SIGNER_TEST_FEDERATIONS_PASSPHRASE", "passphrase");
+        let passphrase = Passphrase::Env("TAPYRUS_SIGNER_TEST_FEDERATIONS_PASSPHRASE".to_string());
+        let federations = Federations::from_pubkey_and_toml(&pubkey, &toml, &passphrase).unwrap();
+        assert_eq!(federations.len(), 2);
+
+        std::env::set_var("TAPYRUS_SIGNER_TEST_FEDERATIONS_WRONG_PASSPHRASE", "wrong");
+        let passphrase =
+            Passphrase::Env("TAPYRUS_SIGNER_TEST_FEDERATIONS_WRONG_PASSPHRASE".to_string());
+        match Federations::from_pubkey_and_toml(&pubkey, &toml, &passphrase) {
+            Err(Error::InvalidKeystore(_)) => assert!(true),
+            _ => assert!(false, "it should error"),
+        }
+    }
+
+    #[test]
+    fn test_without_secret_shares() {
+        let federations = Federations::new(vec![valid_federation()]);
+        let public = Federations::from_pubkey_and_public_ser(
+            &TEST_KEYS.pubkeys()[0],
+            federations.without_secret_shares().to_ser(),
+        )
+        .unwrap();
+
+        let federation = public.get_by_block_height(0);
+        assert!(!federation.has_secret_shares());
+        let original = valid_federation();
+        assert!(federation
+            .nodevss()
+            .iter()
+            .zip(original.nodevss())
+            .all(
+                |(vss, original)| vss.positive_secret != original.positive_secret
+                    && vss.negative_secret != original.negative_secret
+            ));
+        // The public values are kept.
+        assert_eq!(federation.signers(), valid_federation().signers());
+        assert_eq!(
+            federation.aggregated_public_key(),
+            valid_federation().aggregated_public_key()
+        );
+        assert!(federation.validate().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "The node secret share is kept by the share signer.")]
+    fn test_node_secret_share_without_secret_shares() {
+        valid_federation()
+            .without_secret_shares()
+            .node_secret_share();
+    }
 }
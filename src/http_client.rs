@@ -0,0 +1,430 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Minimal HTTP/1.1 client which posts a JSON body, shared by the RPC client of Tapyrus Core and
+//! the approval hook.
+//!
+//! The timeout of the client is the deadline of the whole request: connecting, the TLS handshake,
+//! sending the request and reading the response. Each read and write on the socket waits only for
+//! the time left until the deadline, so a server which sends the response slowly can't hold the
+//! caller longer than the timeout. The host name is resolved in another thread for the same
+//! reason, because the resolver of the system has no timeout.
+//!
+//! The size of the response is limited, so that a broken or malicious server can't make the
+//! signer allocate the memory as much as it declares.
+
+use http::Uri;
+use native_tls::{Certificate, TlsConnector};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
+
+/// The default maximum size of the response including the headers. It is large enough for the
+/// RPC responses with a block.
+pub const MAX_RESPONSE_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpError {
+    /// The request couldn't be sent or the response couldn't be read in time, such as the
+    /// connection is refused or closed. Retrying the request may succeed.
+    Transport(String),
+    /// The url, the credentials or the response is invalid. Retrying the request doesn't help.
+    Invalid(String),
+}
+
+impl HttpError {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpError::Transport(_) => true,
+            HttpError::Invalid(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Transport(m) => write!(f, "{}", m),
+            HttpError::Invalid(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+fn transport<E: fmt::Display>(e: E) -> HttpError {
+    HttpError::Transport(e.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct HttpClient {
+    connect_timeout: Duration,
+    /// The deadline of the whole request.
+    timeout: Duration,
+    /// The CA certificate to verify the certificate of the server in addition to the system ones.
+    ca_certificate: Option<Certificate>,
+    /// The maximum size of the response including the headers.
+    max_response_size: usize,
+}
+
+impl HttpClient {
+    pub fn new(connect_timeout: Duration, timeout: Duration) -> Self {
+        HttpClient {
+            connect_timeout,
+            timeout,
+            ca_certificate: None,
+            max_response_size: MAX_RESPONSE_SIZE,
+        }
+    }
+
+    pub fn with_timeouts(mut self, connect_timeout: Duration, timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_ca_certificate(mut self, ca_certificate: Certificate) -> Self {
+        self.ca_certificate = Some(ca_certificate);
+        self
+    }
+
+    pub fn with_max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    /// Posts the JSON body to the url with the additional headers, and returns the response.
+    pub fn post(
+        &self,
+        uri: &Uri,
+        headers: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<HttpResponse, HttpError> {
+        let deadline = Instant::now() + self.timeout;
+        let https = match uri.scheme_str() {
+            Some("http") => false,
+            Some("https") => true,
+            _ => return Err(HttpError::Invalid(format!("invalid url {}", uri))),
+        };
+        let host = uri
+            .host()
+            .ok_or(HttpError::Invalid(format!("invalid url {}", uri)))?;
+        let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+        let address = resolve(host, port, deadline)?;
+        let connect_timeout = std::cmp::min(
+            self.connect_timeout,
+            remaining(deadline).map_err(transport)?,
+        );
+        let stream = DeadlineStream {
+            stream: TcpStream::connect_timeout(&address, connect_timeout).map_err(transport)?,
+            deadline,
+        };
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            uri.path_and_query().map(|p| p.as_str()).unwrap_or("/"),
+            host,
+            port,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        let mut request = request.into_bytes();
+        request.extend_from_slice(body);
+
+        if https {
+            let mut builder = TlsConnector::builder();
+            if let Some(certificate) = &self.ca_certificate {
+                builder.add_root_certificate(certificate.clone());
+            }
+            let connector = builder.build().map_err(transport)?;
+            let stream = connector.connect(host, stream).map_err(transport)?;
+            exchange(stream, &request, self.max_response_size)
+        } else {
+            exchange(stream, &request, self.max_response_size)
+        }
+    }
+}
+
+/// Resolves the host until the deadline. The lookup runs in another thread, which is left to
+/// finish by itself if it is timed out.
+fn resolve(host: &str, port: u16, deadline: Instant) -> Result<SocketAddr, HttpError> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+    let (sender, receiver) = channel();
+    let host_in_thread = host.to_string();
+    std::thread::Builder::new()
+        .name("HttpResolverThread".to_string())
+        .spawn(move || {
+            let address = (host_in_thread.as_str(), port)
+                .to_socket_addrs()
+                .map(|mut addresses| addresses.next());
+            // The receiver is dropped if the lookup is timed out.
+            let _ = sender.send(address);
+        })
+        .map_err(transport)?;
+    match receiver.recv_timeout(remaining(deadline).map_err(transport)?) {
+        Ok(Ok(Some(address))) => Ok(address),
+        Ok(Ok(None)) => Err(HttpError::Transport(format!("can not resolve {}", host))),
+        Ok(Err(e)) => Err(transport(e)),
+        Err(_) => Err(HttpError::Transport(format!(
+            "resolving {} is timed out",
+            host
+        ))),
+    }
+}
+
+/// Returns the time left until the deadline, or the timeout error if it is passed.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|d| *d > Duration::from_secs(0))
+        .ok_or(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the request is timed out",
+        ))
+}
+
+/// The TCP stream whose reads and writes wait only until the deadline.
+#[derive(Debug)]
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream
+            .set_read_timeout(Some(remaining(self.deadline)?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream
+            .set_write_timeout(Some(remaining(self.deadline)?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn exchange<S: Read + Write>(
+    mut stream: S,
+    request: &[u8],
+    max_response_size: usize,
+) -> Result<HttpResponse, HttpError> {
+    stream.write_all(request).map_err(transport)?;
+    stream.flush().map_err(transport)?;
+    read_response(stream, max_response_size)
+}
+
+fn too_large(max_response_size: usize) -> HttpError {
+    HttpError::Invalid(format!(
+        "HTTP response is larger than {} bytes",
+        max_response_size
+    ))
+}
+
+/// Reads the HTTP response and returns the status code and the body. The response larger than
+/// `max_response_size` is invalid.
+fn read_response<S: Read>(stream: S, max_response_size: usize) -> Result<HttpResponse, HttpError> {
+    // Read one more byte than the limit to know whether the response exceeds it.
+    let mut reader = BufReader::new(stream.take(max_response_size as u64 + 1));
+    let mut status_line = String::new();
+    if reader.read_line(&mut status_line).map_err(transport)? == 0 {
+        return Err(HttpError::Transport(
+            "connection is closed without the response".to_string(),
+        ));
+    }
+    let mut size = status_line.len();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(HttpError::Invalid("invalid HTTP response".to_string()))?;
+
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(transport)? == 0 {
+            return Err(HttpError::Transport(
+                "connection is closed in the HTTP headers".to_string(),
+            ));
+        }
+        size += line.len();
+        if size > max_response_size {
+            return Err(too_large(max_response_size));
+        }
+        if line == "\r\n" {
+            break;
+        }
+        let line = line.to_ascii_lowercase();
+        if line.starts_with("content-length:") {
+            content_length = line[15..].trim().parse::<usize>().ok();
+        } else if line.starts_with("transfer-encoding:") && line.contains("chunked") {
+            return Err(HttpError::Invalid(
+                "chunked HTTP response is not supported".to_string(),
+            ));
+        }
+    }
+
+    let mut body = Vec::new();
+    match content_length {
+        Some(len) => {
+            if size + len > max_response_size {
+                return Err(too_large(max_response_size));
+            }
+            body.resize(len, 0);
+            reader.read_exact(&mut body).map_err(transport)?;
+        }
+        None => {
+            reader.read_to_end(&mut body).map_err(transport)?;
+            if size + body.len() > max_response_size {
+                return Err(too_large(max_response_size));
+            }
+        }
+    }
+    Ok(HttpResponse { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+
+    fn client(timeout: Duration) -> HttpClient {
+        HttpClient::new(Duration::from_secs(1), timeout)
+    }
+
+    /// Starts the stub server which accepts a connection and returns the url and the receiver of
+    /// the request head. The response is written byte by byte at the interval.
+    fn start_stub(response: &'static str, interval: Duration) -> (Uri, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/path?query", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            sender.send(head).unwrap();
+            for byte in response.as_bytes() {
+                if (&stream).write_all(&[*byte]).is_err() {
+                    return;
+                }
+                std::thread::sleep(interval);
+            }
+        });
+        (uri, receiver)
+    }
+
+    #[test]
+    fn test_post() {
+        let (uri, receiver) = start_stub(
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 2\r\n\r\n{}",
+            Duration::from_secs(0),
+        );
+        let response = client(Duration::from_secs(1))
+            .post(&uri, &[("Authorization", "Basic xxx".to_string())], b"{}")
+            .unwrap();
+        assert_eq!(response.status, 401);
+        assert_eq!(response.body, b"{}");
+
+        let head = receiver.recv().unwrap();
+        assert!(head.starts_with("POST /path?query HTTP/1.1\r\n"));
+        assert!(head.contains("Content-Length: 2\r\n"));
+        assert!(head.contains("Authorization: Basic xxx\r\n"));
+    }
+
+    #[test]
+    fn test_deadline_of_whole_request() {
+        // Every byte arrives within the timeout, but the whole response doesn't.
+        let (uri, _receiver) = start_stub(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}",
+            Duration::from_millis(100),
+        );
+        let started_at = Instant::now();
+        match client(Duration::from_secs(1)).post(&uri, &[], b"{}") {
+            Err(e) => assert!(e.is_transient()),
+            r => panic!("it should be timed out, but {:?}", r),
+        }
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_response_too_large() {
+        let (uri, _receiver) = start_stub(
+            "HTTP/1.1 200 OK\r\nContent-Length: 1073741824\r\n\r\n{}",
+            Duration::from_secs(0),
+        );
+        match client(Duration::from_secs(1))
+            .with_max_response_size(64)
+            .post(&uri, &[], b"{}")
+        {
+            Err(HttpError::Invalid(_)) => {}
+            r => panic!("it should be too large, but {:?}", r),
+        }
+
+        // The response without Content-Length is read only up to the limit.
+        let (uri, _receiver) = start_stub(
+            "HTTP/1.1 200 OK\r\n\r\n{\"result\":\"0123456789012345678901234567890123456789\"}",
+            Duration::from_secs(0),
+        );
+        match client(Duration::from_secs(1))
+            .with_max_response_size(64)
+            .post(&uri, &[], b"{}")
+        {
+            Err(HttpError::Invalid(_)) => {}
+            r => panic!("it should be too large, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_resolve_until_deadline() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert_eq!(
+            resolve("127.0.0.1", 80, deadline),
+            Ok("127.0.0.1:80".parse().unwrap())
+        );
+        assert!(resolve("localhost", 80, deadline).is_ok());
+
+        // The lookup fails after the deadline.
+        match resolve("localhost", 80, Instant::now()) {
+            Err(e) => assert!(e.is_transient()),
+            r => panic!("it should be timed out, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        let uri = "ftp://127.0.0.1/".parse().unwrap();
+        match client(Duration::from_secs(1)).post(&uri, &[], b"{}") {
+            Err(HttpError::Invalid(_)) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Distributed Key Generation with Feldman VSS.
+//!
+//! Each signer deals node VSS of own private key to all signers through `Exchange`. The
+//! aggregated public key is the sum of the first commitments of all node VSS.
+
+use crate::crypto::vss::{Commitment, Vss};
+use crate::errors::Error;
+use crate::federation::Federation;
+use crate::keygen::exchange::{Dealing, Exchange};
+use crate::keygen::Protocol;
+use crate::net::{Message, MessageType, SignerID};
+use crate::util::sum_point;
+use bitcoin::{PrivateKey, PublicKey};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::ECPoint;
+use curv::{FE, GE};
+use std::collections::{BTreeMap, HashSet};
+
+impl Dealing for Vss {
+    fn sender(&self) -> SignerID {
+        SignerID::new(self.sender_public_key)
+    }
+
+    fn receiver(&self) -> SignerID {
+        SignerID::new(self.receiver_public_key)
+    }
+
+    fn commitments(&self) -> Vec<GE> {
+        self.positive_commitments
+            .iter()
+            .map(|c| c.to_point())
+            .collect()
+    }
+
+    fn verify(&self, share_count: usize, index: usize) -> bool {
+        self.verify_positive_share(share_count, index).is_ok()
+    }
+
+    fn to_message(&self) -> MessageType {
+        MessageType::Nodevss(self.clone())
+    }
+
+    fn to_reveal_message(&self) -> MessageType {
+        MessageType::Nodevssreveal(self.clone())
+    }
+}
+
+/// Validate the signer set and the threshold, and returns sorted signers.
+pub fn sorted_signers(
+    signer_id: &SignerID,
+    public_keys: Vec<PublicKey>,
+    threshold: u8,
+) -> Result<Vec<SignerID>, Error> {
+    let mut signers: Vec<SignerID> = public_keys.into_iter().map(SignerID::new).collect();
+    signers.sort();
+    let unique: HashSet<&SignerID> = signers.iter().collect();
+    if unique.len() != signers.len() {
+        return Err(Error::InvalidArgs(
+            "public-key should not be duplicated".to_string(),
+        ));
+    }
+    if !signers.contains(signer_id) {
+        return Err(Error::InvalidArgs(
+            "public-key should include the public key of private-key".to_string(),
+        ));
+    }
+    if threshold == 0 || threshold as usize > signers.len() {
+        return Err(Error::InvalidArgs(
+            "threshold should be between 1 and the number of signers".to_string(),
+        ));
+    }
+    Ok(signers)
+}
+
+/// Returns node VSS which shares `secret` among `signers`, for each receiver.
+pub fn deal_node_vss(
+    sender_id: &SignerID,
+    vss_scheme: &VerifiableSS,
+    secret_shares: &[FE],
+    signers: &[SignerID],
+) -> BTreeMap<SignerID, Vss> {
+    let commitments: Vec<Commitment> = vss_scheme
+        .commitments
+        .iter()
+        .map(|c| Commitment::from(c))
+        .collect();
+    signers
+        .iter()
+        .enumerate()
+        .map(|(i, receiver)| {
+            let vss = Vss::new(
+                sender_id.pubkey,
+                receiver.pubkey,
+                commitments.clone(),
+                secret_shares[i],
+                commitments.clone(),
+                secret_shares[i],
+            );
+            (*receiver, vss)
+        })
+        .collect()
+}
+
+/// Returns the aggregated public key of the node VSS.
+pub fn aggregated_public_key(nodevss: &[Vss]) -> PublicKey {
+    let points: Vec<GE> = nodevss
+        .iter()
+        .map(|vss| vss.positive_commitments[0].to_point())
+        .collect();
+    PublicKey {
+        compressed: true,
+        key: sum_point(&points).get_element(),
+    }
+}
+
+pub struct Dkg {
+    exchange: Exchange<Vss>,
+    threshold: u8,
+    /// The block height where the generated federation gets started.
+    block_height: u64,
+}
+
+impl Dkg {
+    pub fn new(
+        private_key: &PrivateKey,
+        public_keys: Vec<PublicKey>,
+        threshold: u8,
+        block_height: u64,
+    ) -> Result<Self, Error> {
+        let secp = secp256k1::Secp256k1::new();
+        let signer_id = SignerID::new(PublicKey::from_private_key(&secp, private_key));
+        let signers = sorted_signers(&signer_id, public_keys, threshold)?;
+
+        let (vss_scheme, secret_shares) =
+            Vss::create_node_shares(private_key, threshold as usize, signers.len());
+        let dealt = deal_node_vss(&signer_id, &vss_scheme, &secret_shares, &signers);
+
+        Ok(Dkg {
+            exchange: Exchange::new(signer_id, signers.clone(), signers, dealt),
+            threshold,
+            block_height,
+        })
+    }
+}
+
+impl Protocol for Dkg {
+    type Output = Federation;
+
+    fn signer_id(&self) -> SignerID {
+        self.exchange.signer_id()
+    }
+
+    fn outgoing(&self) -> Vec<Message> {
+        self.exchange.outgoing()
+    }
+
+    fn process(&mut self, message: Message) -> Result<(), Error> {
+        let sender_id = message.sender_id;
+        if sender_id == self.signer_id() {
+            return Ok(());
+        }
+
+        match message.message_type {
+            MessageType::Nodevss(vss) => self.exchange.receive_dealing(sender_id, vss),
+            MessageType::Nodevsscomplaints(digest, accused) => {
+                self.exchange.receive_complaints(sender_id, digest, accused)
+            }
+            MessageType::Nodevssreveal(vss) => self.exchange.receive_reveal(sender_id, vss),
+            _ => Ok(()),
+        }
+    }
+
+    fn output(&self) -> Option<Federation> {
+        let received = self.exchange.result()?;
+        let nodevss: Vec<Vss> = received.values().cloned().collect();
+        Some(Federation::new(
+            self.signer_id().pubkey,
+            self.block_height,
+            Some(self.threshold),
+            nodevss.clone(),
+            aggregated_public_key(&nodevss),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::secret::Secret;
+    use crate::keygen::tests::run_protocols;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use curv::elliptic::curves::traits::ECScalar;
+    use curv::BigInt;
+
+    fn create_dkgs() -> Vec<Dkg> {
+        TEST_KEYS
+            .key
+            .iter()
+            .map(|key| Dkg::new(key, TEST_KEYS.pubkeys(), 3, 0).unwrap())
+            .collect()
+    }
+
+    fn is_share_from(message: &Message, dealer: usize, receiver: usize) -> bool {
+        let vss = match &message.message_type {
+            MessageType::Nodevss(vss) => vss,
+            MessageType::Nodevssreveal(vss) => vss,
+            _ => return false,
+        };
+        vss.sender_public_key == TEST_KEYS.pubkeys()[dealer]
+            && vss.receiver_public_key == TEST_KEYS.pubkeys()[receiver]
+    }
+
+    fn tamper_share(message: &mut Message) {
+        let invalid_secret: FE = ECScalar::from(&BigInt::from(1));
+        match &mut message.message_type {
+            MessageType::Nodevss(vss) | MessageType::Nodevssreveal(vss) => {
+                vss.positive_secret = Secret::new(invalid_secret);
+                vss.negative_secret = Secret::new(invalid_secret);
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_dkg() {
+        let mut dkgs = create_dkgs();
+        let results = run_protocols(&mut dkgs, |_| {});
+
+        for (i, result) in results.into_iter().enumerate() {
+            let federation = result.unwrap();
+            assert!(federation.validate().is_ok());
+            assert_eq!(federation.node_index(), i);
+            assert_eq!(federation.aggregated_public_key(), TEST_KEYS.aggregated());
+            assert_eq!(federation.threshold(), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_dkg_with_complaint() {
+        // The share from signer 0 to signer 1 is broken on the way, but signer 0 reveals the
+        // valid share.
+        let mut dkgs = create_dkgs();
+        let results = run_protocols(&mut dkgs, |message| {
+            if let MessageType::Nodevss(_) = message.message_type {
+                if is_share_from(message, 0, 1) {
+                    tamper_share(message);
+                }
+            }
+        });
+
+        for result in results {
+            let federation = result.unwrap();
+            assert!(federation.validate().is_ok());
+            assert_eq!(federation.aggregated_public_key(), TEST_KEYS.aggregated());
+        }
+    }
+
+    #[test]
+    fn test_dkg_disqualifies_dealer() {
+        // Signer 0 deals and reveals an invalid share for signer 1.
+        let mut dkgs = create_dkgs();
+        let results = run_protocols(&mut dkgs, |message| {
+            if is_share_from(message, 0, 1) {
+                tamper_share(message);
+            }
+        });
+
+        let dealer = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        for (i, result) in results.into_iter().enumerate() {
+            if i == 0 {
+                // The dealer doesn't check own reveals.
+                continue;
+            }
+            match result {
+                Err(Error::DisqualifiedSigner(id)) => assert_eq!(id, dealer),
+                r => assert!(false, "it should fail but {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_with_invalid_args() {
+        let key = &TEST_KEYS.key[0];
+        assert!(Dkg::new(key, TEST_KEYS.pubkeys(), 0, 0).is_err());
+        assert!(Dkg::new(key, TEST_KEYS.pubkeys(), 6, 0).is_err());
+        assert!(Dkg::new(key, TEST_KEYS.pubkeys()[1..].to_vec(), 3, 0).is_err());
+
+        let mut duplicated = TEST_KEYS.pubkeys();
+        duplicated.push(TEST_KEYS.pubkeys()[0]);
+        assert!(Dkg::new(key, duplicated, 3, 0).is_err());
+    }
+}
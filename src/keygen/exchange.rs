@@ -0,0 +1,284 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Exchange of verifiable shares with complaints.
+//!
+//! 1. Each dealer sends a dealing to every receiver.
+//! 2. After receiving dealings from all dealers, each receiver verifies them and broadcasts
+//!    `Nodevsscomplaints` with the dealers whose dealings were invalid and the digest of all
+//!    commitments it received.
+//! 3. A dealer who got complaints reveals the dealings for the complainers. If a revealed
+//!    dealing is valid, the complainer uses it. Otherwise the dealer is disqualified and the
+//!    exchange fails.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::errors::Error;
+use crate::net::{Message, MessageType, SignerID};
+use curv::elliptic::curves::traits::ECPoint;
+use curv::GE;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Shares which a dealer sends to a receiver.
+pub trait Dealing: Clone {
+    fn sender(&self) -> SignerID;
+    fn receiver(&self) -> SignerID;
+    /// Commitments which must be same for all receivers of the dealer.
+    fn commitments(&self) -> Vec<GE>;
+    /// Verify the share for the receiver at `index` in `share_count` receivers.
+    fn verify(&self, share_count: usize, index: usize) -> bool;
+    /// Message to send the dealing to the receiver.
+    fn to_message(&self) -> MessageType;
+    /// Message to reveal the dealing to everyone.
+    fn to_reveal_message(&self) -> MessageType;
+}
+
+pub struct Exchange<D: Dealing> {
+    signer_id: SignerID,
+    /// Dealers which are sorted by SignerID.
+    dealers: Vec<SignerID>,
+    /// Receivers which are sorted by SignerID. The index of the receiver is the position in this
+    /// vector plus 1.
+    receivers: Vec<SignerID>,
+    /// Dealings which this signer dealt, for each receiver.
+    dealt: BTreeMap<SignerID, D>,
+    /// Dealings which this signer received, for each dealer.
+    received: BTreeMap<SignerID, D>,
+    /// Commitments digest and accused dealers, for each complainer.
+    complaints: BTreeMap<SignerID, (SHA256Hash, Vec<SignerID>)>,
+    /// Revealed dealings for each pair of the dealer and the receiver.
+    reveals: BTreeMap<(SignerID, SignerID), D>,
+}
+
+impl<D: Dealing> Exchange<D> {
+    /// `dealt` should be empty if the signer is not a dealer.
+    pub fn new(
+        signer_id: SignerID,
+        dealers: Vec<SignerID>,
+        receivers: Vec<SignerID>,
+        dealt: BTreeMap<SignerID, D>,
+    ) -> Self {
+        let mut dealers = dealers;
+        dealers.sort();
+        let mut receivers = receivers;
+        receivers.sort();
+
+        let mut received = BTreeMap::new();
+        if let Some(dealing) = dealt.get(&signer_id) {
+            received.insert(signer_id, dealing.clone());
+        }
+
+        Exchange {
+            signer_id,
+            dealers,
+            receivers,
+            dealt,
+            received,
+            complaints: BTreeMap::new(),
+            reveals: BTreeMap::new(),
+        }
+    }
+
+    pub fn signer_id(&self) -> SignerID {
+        self.signer_id
+    }
+
+    pub fn dealers(&self) -> &Vec<SignerID> {
+        &self.dealers
+    }
+
+    pub fn receivers(&self) -> &Vec<SignerID> {
+        &self.receivers
+    }
+
+    fn is_receiver(&self) -> bool {
+        self.receivers.contains(&self.signer_id)
+    }
+
+    /// Returns the index of the receiver which is used as x coordinate of the share.
+    pub fn index_of(&self, receiver: &SignerID) -> usize {
+        self.receivers
+            .iter()
+            .position(|s| s == receiver)
+            .expect("signer should be in the receivers")
+            + 1
+    }
+
+    fn is_valid(&self, dealing: &D, receiver: &SignerID) -> bool {
+        dealing.verify(self.receivers.len(), self.index_of(receiver))
+    }
+
+    fn commitments_digest(&self) -> SHA256Hash {
+        let mut hasher = Sha256::new();
+        for dealing in self.received.values() {
+            for commitment in dealing.commitments() {
+                hasher.input(&commitment.get_element().serialize_uncompressed()[..]);
+            }
+        }
+        SHA256Hash::from_slice(&hasher.result()[..]).expect("sha256 digest should be 32 bytes")
+    }
+
+    pub fn receive_dealing(&mut self, sender_id: SignerID, dealing: D) -> Result<(), Error> {
+        if self.dealers.contains(&sender_id)
+            && dealing.sender() == sender_id
+            && dealing.receiver() == self.signer_id
+            && !self.complaints.contains_key(&self.signer_id)
+        {
+            self.received.entry(sender_id).or_insert(dealing);
+        }
+        self.update()
+    }
+
+    pub fn receive_complaints(
+        &mut self,
+        sender_id: SignerID,
+        digest: SHA256Hash,
+        accused: Vec<SignerID>,
+    ) -> Result<(), Error> {
+        if self.receivers.contains(&sender_id) {
+            let accused: Vec<SignerID> = accused
+                .into_iter()
+                .filter(|dealer| self.dealers.contains(dealer))
+                .collect();
+            self.complaints
+                .entry(sender_id)
+                .or_insert((digest, accused));
+        }
+        self.update()
+    }
+
+    pub fn receive_reveal(&mut self, sender_id: SignerID, dealing: D) -> Result<(), Error> {
+        if self.dealers.contains(&sender_id)
+            && dealing.sender() == sender_id
+            && self.receivers.contains(&dealing.receiver())
+        {
+            self.reveals
+                .entry((sender_id, dealing.receiver()))
+                .or_insert(dealing);
+        }
+        self.update()
+    }
+
+    fn update(&mut self) -> Result<(), Error> {
+        self.complain_if_ready();
+        self.resolve_complaints()
+    }
+
+    /// Complain against invalid dealings once the dealings from all dealers are received.
+    fn complain_if_ready(&mut self) {
+        if !self.is_receiver()
+            || self.complaints.contains_key(&self.signer_id)
+            || self.received.len() != self.dealers.len()
+        {
+            return;
+        }
+        let accused: Vec<SignerID> = self
+            .received
+            .iter()
+            .filter(|(_, dealing)| !self.is_valid(dealing, &self.signer_id))
+            .map(|(dealer, _)| *dealer)
+            .collect();
+        if !accused.is_empty() {
+            log::warn!("Complain against dealers: {:?}", accused);
+        }
+        let digest = self.commitments_digest();
+        self.complaints.insert(self.signer_id, (digest, accused));
+    }
+
+    /// Check the revealed dealings for the complaints. The complainer replaces the invalid
+    /// dealing with the revealed one. Dealers who are not receivers don't check them.
+    fn resolve_complaints(&mut self) -> Result<(), Error> {
+        if !self.is_receiver() || self.complaints.len() != self.receivers.len() {
+            return Ok(());
+        }
+
+        let digest = self.complaints[&self.signer_id].0;
+        if self.complaints.values().any(|(d, _)| *d != digest) {
+            return Err(Error::InconsistentCommitments);
+        }
+
+        let mut accepted: Vec<(SignerID, D)> = Vec::new();
+        for (complainer, (_, accused)) in &self.complaints {
+            for dealer in accused {
+                if *dealer == self.signer_id {
+                    continue;
+                }
+                if let Some(dealing) = self.reveals.get(&(*dealer, *complainer)) {
+                    if dealing.commitments() != self.received[dealer].commitments()
+                        || !self.is_valid(dealing, complainer)
+                    {
+                        return Err(Error::DisqualifiedSigner(*dealer));
+                    }
+                    if *complainer == self.signer_id {
+                        accepted.push((*dealer, dealing.clone()));
+                    }
+                }
+            }
+        }
+        for (dealer, dealing) in accepted {
+            self.received.insert(dealer, dealing);
+        }
+        Ok(())
+    }
+
+    /// Returns the messages to send in the current state.
+    pub fn outgoing(&self) -> Vec<Message> {
+        let mut messages = Vec::new();
+
+        // Receivers who sent complaints have already received all dealings.
+        for (receiver, dealing) in &self.dealt {
+            if *receiver != self.signer_id && !self.complaints.contains_key(receiver) {
+                messages.push(Message {
+                    message_type: dealing.to_message(),
+                    sender_id: self.signer_id,
+                    receiver_id: Some(*receiver),
+                });
+            }
+        }
+
+        if let Some((digest, accused)) = self.complaints.get(&self.signer_id) {
+            messages.push(Message {
+                message_type: MessageType::Nodevsscomplaints(*digest, accused.clone()),
+                sender_id: self.signer_id,
+                receiver_id: None,
+            });
+        }
+
+        for (complainer, (_, accused)) in &self.complaints {
+            if !accused.contains(&self.signer_id) {
+                continue;
+            }
+            if let Some(dealing) = self.dealt.get(complainer) {
+                messages.push(Message {
+                    message_type: dealing.to_reveal_message(),
+                    sender_id: self.signer_id,
+                    receiver_id: None,
+                });
+            }
+        }
+        messages
+    }
+
+    /// Returns the received dealings for each dealer when the exchange has finished. It is empty
+    /// if the signer is not a receiver.
+    pub fn result(&self) -> Option<&BTreeMap<SignerID, D>> {
+        if self.complaints.len() != self.receivers.len() {
+            return None;
+        }
+        if !self.is_receiver() {
+            return Some(&self.received);
+        }
+        // All complaints against other dealers must be resolved with the revealed dealings.
+        let resolved = self.complaints.iter().all(|(complainer, (_, accused))| {
+            accused.iter().all(|dealer| {
+                *dealer == self.signer_id || self.reveals.contains_key(&(*dealer, *complainer))
+            })
+        });
+        if resolved {
+            Some(&self.received)
+        } else {
+            None
+        }
+    }
+}
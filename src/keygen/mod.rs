@@ -0,0 +1,189 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Key management protocols which signers run interactively over the signer network.
+
+pub mod dkg;
+pub mod exchange;
+pub mod refresh;
+pub mod repair;
+pub mod reshare;
+
+use crate::errors::Error;
+use crate::net::{ConnectionManager, Envelope, Message, SignerID};
+use redis::ControlFlow;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// Interval to send all messages of the current state again.
+/// Messages on the signer network are not persisted. The signers who join the protocol late can
+/// only receive the messages which are sent after they subscribed.
+pub static RESEND_INTERVAL_SECS: u64 = 5;
+
+/// A protocol which is driven by `run`.
+///
+/// Protocols are state based. `outgoing` returns all messages which should be delivered in the
+/// current state, so they are sent again periodically. Implementations should ignore messages
+/// which they already received.
+pub trait Protocol {
+    type Output;
+
+    /// The id of the signer who runs this protocol.
+    fn signer_id(&self) -> SignerID;
+
+    /// Returns the messages to send in the current state. A message which has `receiver_id` is
+    /// sent only to the receiver, otherwise it is broadcasted.
+    fn outgoing(&self) -> Vec<Message>;
+
+    /// Update the state with a received message. Returns Err when the protocol can not be
+    /// completed anymore.
+    fn process(&mut self, message: Message) -> Result<(), Error>;
+
+    /// Returns the result when the protocol has finished.
+    fn output(&self) -> Option<Self::Output>;
+}
+
+/// Run the protocol over the signer network until it finishes or `timeout` elapses.
+pub fn run<P, C>(
+    protocol: &mut P,
+    connection_manager: &mut C,
+    timeout: Duration,
+) -> Result<P::Output, Error>
+where
+    P: Protocol,
+    C: ConnectionManager,
+{
+    let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
+    let closure = move |envelope: Envelope| {
+        let message = match envelope.message() {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("Invalid message is received. {:?}", e);
+                return ControlFlow::Continue;
+            }
+        };
+        match sender.send(message) {
+            Ok(_) => ControlFlow::Continue,
+            Err(error) => {
+                log::warn!("Happened error!: {:?}", error);
+                ControlFlow::Break(())
+            }
+        }
+    };
+    let _handler = connection_manager.start(closure, protocol.signer_id());
+    let error_handler = connection_manager.error_handler();
+
+    let started_at = Instant::now();
+    let mut resent_at = Instant::now();
+    let mut sent = protocol.outgoing();
+    send_all(connection_manager, &sent);
+
+    loop {
+        if let Some(output) = protocol.output() {
+            // Send the final messages again, so that the signers who are behind can finish too.
+            send_all(connection_manager, &protocol.outgoing());
+            return Ok(output);
+        }
+
+        if started_at.elapsed() > timeout {
+            return Err(Error::ProtocolTimeout);
+        }
+
+        match receiver.recv_timeout(Duration::from_millis(300)) {
+            Ok(message) => {
+                log::debug!(
+                    "Got {} message from {:?}.",
+                    message.message_type,
+                    message.sender_id
+                );
+                protocol.process(message)?;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                // No new messages. Do nothing.
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::ConnectionError(
+                    "message receiver was disconnected".to_string(),
+                ));
+            }
+        }
+
+        // Send the messages which appeared with the state change immediately, and all of them
+        // periodically.
+        let outgoing = protocol.outgoing();
+        if resent_at.elapsed() >= Duration::from_secs(RESEND_INTERVAL_SECS) {
+            send_all(connection_manager, &outgoing);
+            resent_at = Instant::now();
+        } else {
+            let new_messages: Vec<Message> = outgoing
+                .iter()
+                .filter(|m| !sent.contains(m))
+                .cloned()
+                .collect();
+            send_all(connection_manager, &new_messages);
+        }
+        sent = outgoing;
+
+        if let Some(ref receiver) = error_handler {
+            match receiver.try_recv() {
+                Ok(e) => return Err(Error::ConnectionError(e.to_string())),
+                Err(TryRecvError::Empty) => {}
+                Err(e) => log::debug!("{:?}", e),
+            }
+        }
+    }
+}
+
+/// Send messages to the receivers, or broadcast them if they have no receiver.
+pub fn send_all<C: ConnectionManager>(connection_manager: &C, messages: &[Message]) {
+    for message in messages {
+        if message.receiver_id.is_some() {
+            connection_manager.send_message(message.clone());
+        } else {
+            connection_manager.broadcast_message(message.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::errors::Error;
+    use crate::keygen::Protocol;
+    use crate::net::Message;
+
+    /// Deliver messages between signers until all signers finish or fail. `tamper` can modify
+    /// messages on the way. The signers who can't finish get `ProtocolTimeout`.
+    pub fn run_protocols<P, F>(protocols: &mut Vec<P>, tamper: F) -> Vec<Result<P::Output, Error>>
+    where
+        P: Protocol,
+        F: Fn(&mut Message),
+    {
+        let mut results: Vec<Option<Result<P::Output, Error>>> =
+            (0..protocols.len()).map(|_| None).collect();
+        for _ in 0..10 {
+            let messages: Vec<Message> = protocols.iter().flat_map(|p| p.outgoing()).collect();
+            for mut message in messages {
+                tamper(&mut message);
+                for (i, protocol) in protocols.iter_mut().enumerate() {
+                    if results[i].is_some() {
+                        continue;
+                    }
+                    match message.receiver_id {
+                        Some(receiver) if receiver != protocol.signer_id() => continue,
+                        _ => {}
+                    }
+                    if let Err(e) = protocol.process(message.clone()) {
+                        results[i] = Some(Err(e));
+                    } else if let Some(output) = protocol.output() {
+                        results[i] = Some(Ok(output));
+                    }
+                }
+            }
+        }
+        results
+            .into_iter()
+            .map(|r| r.unwrap_or(Err(Error::ProtocolTimeout)))
+            .collect()
+    }
+}
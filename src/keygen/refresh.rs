@@ -0,0 +1,277 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Proactive refresh of node secret shares.
+//!
+//! Each signer deals VSS of zero to all signers of the federation through `Exchange`. Each
+//! receiver adds the shares of zero to the node VSS from the same dealer. The secret of every
+//! node VSS doesn't change, so the aggregated public key stays same, but the shares which are
+//! leaked before the refresh can not be combined with the shares after it.
+
+use crate::crypto::vss::{Vss, ZeroVss};
+use crate::errors::Error;
+use crate::federation::Federation;
+use crate::keygen::exchange::{Dealing, Exchange};
+use crate::keygen::Protocol;
+use crate::net::{Message, MessageType, SignerID};
+use curv::GE;
+use std::collections::BTreeMap;
+
+impl Dealing for ZeroVss {
+    fn sender(&self) -> SignerID {
+        SignerID::new(self.sender_public_key)
+    }
+
+    fn receiver(&self) -> SignerID {
+        SignerID::new(self.receiver_public_key)
+    }
+
+    fn commitments(&self) -> Vec<GE> {
+        self.commitments.clone()
+    }
+
+    fn verify(&self, _share_count: usize, index: usize) -> bool {
+        ZeroVss::verify(self, index)
+    }
+
+    fn to_message(&self) -> MessageType {
+        MessageType::Refreshvss(self.clone())
+    }
+
+    fn to_reveal_message(&self) -> MessageType {
+        MessageType::Refreshvssreveal(self.clone())
+    }
+}
+
+pub struct Refresh {
+    exchange: Exchange<ZeroVss>,
+    /// The federation whose node secret shares are refreshed.
+    federation: Federation,
+    /// The block height where the refreshed federation gets started.
+    block_height: u64,
+}
+
+impl Refresh {
+    pub fn new(federation: Federation, block_height: u64) -> Result<Self, Error> {
+        let threshold = federation.threshold().ok_or(Error::InvalidArgs(
+            "the signer should be a member of the federation".to_string(),
+        ))?;
+        if threshold < 2 {
+            return Err(Error::InvalidArgs(
+                "threshold should be greater than 1 to refresh shares".to_string(),
+            ));
+        }
+        if block_height <= federation.block_height() {
+            return Err(Error::InvalidArgs(
+                "block-height should be greater than the block height of the federation"
+                    .to_string(),
+            ));
+        }
+
+        let signer_id = federation.signer_id();
+        let signers = federation.signers();
+        let (commitments, secret_shares) =
+            ZeroVss::create_shares(threshold as usize, signers.len());
+        let dealt: BTreeMap<SignerID, ZeroVss> = signers
+            .iter()
+            .zip(secret_shares)
+            .map(|(receiver, secret)| {
+                let zero_vss = ZeroVss {
+                    sender_public_key: signer_id.pubkey,
+                    receiver_public_key: receiver.pubkey,
+                    commitments: commitments.clone(),
+                    secret,
+                };
+                (*receiver, zero_vss)
+            })
+            .collect();
+
+        Ok(Refresh {
+            exchange: Exchange::new(signer_id, signers.clone(), signers, dealt),
+            federation,
+            block_height,
+        })
+    }
+
+    /// Returns Err if the dealer sent VSS of zero which has wrong degree. It would break the
+    /// threshold of the refreshed shares even if all shares are consistent with the commitments.
+    fn check_degree(&self, sender_id: &SignerID, zero_vss: &ZeroVss) -> Result<(), Error> {
+        let threshold = self.federation.threshold().unwrap_or(0) as usize;
+        if zero_vss.commitments.len() + 1 != threshold {
+            return Err(Error::DisqualifiedSigner(*sender_id));
+        }
+        Ok(())
+    }
+}
+
+impl Protocol for Refresh {
+    type Output = Federation;
+
+    fn signer_id(&self) -> SignerID {
+        self.exchange.signer_id()
+    }
+
+    fn outgoing(&self) -> Vec<Message> {
+        self.exchange.outgoing()
+    }
+
+    fn process(&mut self, message: Message) -> Result<(), Error> {
+        let sender_id = message.sender_id;
+        if sender_id == self.signer_id() {
+            return Ok(());
+        }
+
+        match message.message_type {
+            MessageType::Refreshvss(zero_vss) => {
+                self.check_degree(&sender_id, &zero_vss)?;
+                self.exchange.receive_dealing(sender_id, zero_vss)
+            }
+            MessageType::Nodevsscomplaints(digest, accused) => {
+                self.exchange.receive_complaints(sender_id, digest, accused)
+            }
+            MessageType::Refreshvssreveal(zero_vss) => {
+                self.check_degree(&sender_id, &zero_vss)?;
+                self.exchange.receive_reveal(sender_id, zero_vss)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn output(&self) -> Option<Federation> {
+        let received = self.exchange.result()?;
+        let nodevss: Vec<Vss> = self
+            .federation
+            .nodevss()
+            .iter()
+            .map(|vss| {
+                let dealer = SignerID::new(vss.sender_public_key);
+                vss.add_zero_share(&received[&dealer])
+            })
+            .collect();
+        Some(
+            Federation::new(
+                self.signer_id().pubkey,
+                self.block_height,
+                self.federation.threshold(),
+                nodevss,
+                self.federation.aggregated_public_key(),
+            )
+            .with_signing_protocol(self.federation.signing_protocol()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keygen::tests::run_protocols;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+    use curv::{BigInt, FE};
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+    }
+
+    fn create_refreshes() -> Vec<Refresh> {
+        (0..5)
+            .map(|i| Refresh::new(federation(i), 100).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_refresh() {
+        let mut refreshes = create_refreshes();
+        let results = run_protocols(&mut refreshes, |_| {});
+
+        let federations: Vec<Federation> = results.into_iter().map(|r| r.unwrap()).collect();
+        for (i, refreshed) in federations.iter().enumerate() {
+            assert!(refreshed.validate().is_ok());
+            assert_eq!(refreshed.block_height(), 100);
+            assert_eq!(refreshed.threshold(), Some(3));
+            assert_eq!(refreshed.aggregated_public_key(), TEST_KEYS.aggregated());
+            assert!(refreshed.node_secret_share().x_i != federation(i).node_secret_share().x_i);
+        }
+
+        // The refreshed shares of any 3 signers still reconstruct the aggregated private key.
+        let shares: Vec<FE> = federations[2..5]
+            .iter()
+            .map(|f| *f.node_secret_share().x_i)
+            .collect();
+        let vss = federations[0]
+            .node_shared_secrets()
+            .values()
+            .next()
+            .unwrap()
+            .vss
+            .clone();
+        let secret = vss.reconstruct(&[2, 3, 4], &shares);
+        let g: GE = ECPoint::generator();
+        assert_eq!((g * &secret).get_element(), TEST_KEYS.aggregated().key);
+    }
+
+    #[test]
+    fn test_refresh_with_complaint() {
+        // The share from signer 0 to signer 1 is broken on the way, but signer 0 reveals the
+        // valid share.
+        let mut refreshes = create_refreshes();
+        let results = run_protocols(&mut refreshes, |message| {
+            if let MessageType::Refreshvss(zero_vss) = &mut message.message_type {
+                if zero_vss.sender_public_key == TEST_KEYS.pubkeys()[0]
+                    && zero_vss.receiver_public_key == TEST_KEYS.pubkeys()[1]
+                {
+                    zero_vss.secret = ECScalar::from(&BigInt::from(1));
+                }
+            }
+        });
+
+        for result in results {
+            let refreshed = result.unwrap();
+            assert!(refreshed.validate().is_ok());
+            assert_eq!(refreshed.aggregated_public_key(), TEST_KEYS.aggregated());
+        }
+    }
+
+    #[test]
+    fn test_refresh_disqualifies_dealer_with_wrong_degree() {
+        let mut refreshes = create_refreshes();
+        let results = run_protocols(&mut refreshes, |message| {
+            if let MessageType::Refreshvss(zero_vss) = &mut message.message_type {
+                if zero_vss.sender_public_key == TEST_KEYS.pubkeys()[0] {
+                    zero_vss.commitments.pop();
+                }
+            }
+        });
+
+        // The dealer doesn't check own dealings, and can't finish without complaints.
+        let dealer = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        for result in results.into_iter().skip(1) {
+            match result {
+                Err(Error::DisqualifiedSigner(id)) => assert_eq!(id, dealer),
+                r => assert!(false, "it should fail but {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_with_invalid_args() {
+        assert!(Refresh::new(federation(0), 0).is_err());
+
+        let non_member = Federation::new(
+            TEST_KEYS.pubkeys()[0],
+            0,
+            None,
+            vec![],
+            TEST_KEYS.aggregated(),
+        );
+        assert!(Refresh::new(non_member, 100).is_err());
+    }
+}
@@ -0,0 +1,741 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Repair of the node VSS of a signer who lost it.
+//!
+//! The node VSS of the target signer from a dealer is the share of the dealer's polynomial at the
+//! index of the target. It can be interpolated from the shares of any `threshold` helpers.
+//!
+//! 1. The target broadcasts `Repairrequest` with the helpers, a fresh nonce and the time of the
+//!    request, signed by its node private key. Helpers ignore the request after
+//!    `REPAIR_REQUEST_TIMEOUT_SECS`, so that a replayed request doesn't start the repair again.
+//! 2. Each helper multiplies its shares by the Lagrange coefficient for the index of the target,
+//!    splits them into random pieces and sends one piece to each helper with `Repairpiece`.
+//! 3. Each helper sums the pieces it received and sends the sum with the commitments to the
+//!    target with `Repairshare`. The message also has the points of the pieces the helper dealt.
+//! 4. The target verifies the contribution of each helper with the points and the commitments,
+//!    and sums the secrets from all helpers.
+//!
+//! The helpers only see random pieces, and the target only sees the sums, so nobody but the
+//! target learns the repaired shares. The points of the pieces reveal only the points of the
+//! contributions, which anyone can compute from the commitments.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::vss::{share_point, Commitment, Vss};
+use crate::errors::Error;
+use crate::federation::Federation;
+use crate::keygen::dkg::aggregated_public_key;
+use crate::keygen::Protocol;
+use crate::net::{Message, MessageType, Signature, SignerID};
+use crate::util::{lagrange_coefficient, split_secret};
+use bitcoin::{PrivateKey, PublicKey};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{FE, GE};
+use secp256k1::rand::{thread_rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds while helpers accept the repair request since it was created.
+pub static REPAIR_REQUEST_TIMEOUT_SECS: u64 = 300;
+
+/// Pieces of the contributions for each dealer. A tuple has the dealer, and the pieces for the
+/// positive and the negative secret.
+pub type RepairPieces = Vec<(SignerID, FE, FE)>;
+
+/// Points of the pieces which a helper dealt to each helper. A tuple has the helper who received
+/// the pieces, and the points of the positive and the negative piece for each dealer.
+pub type RepairPiecePoints = Vec<(SignerID, Vec<(GE, GE)>)>;
+
+/// Returns the hash which identifies the repair request.
+pub fn request_hash(
+    block_height: u64,
+    target: &SignerID,
+    helpers: &[SignerID],
+    nonce: u64,
+    requested_at: u64,
+) -> SHA256Hash {
+    let mut hasher = Sha256::new();
+    hasher.input(&block_height.to_le_bytes());
+    hasher.input(&target.pubkey.key.serialize()[..]);
+    for helper in helpers {
+        hasher.input(&helper.pubkey.key.serialize()[..]);
+    }
+    hasher.input(&nonce.to_le_bytes());
+    hasher.input(&requested_at.to_le_bytes());
+    SHA256Hash::from_slice(&hasher.result()[..]).expect("sha256 digest should be 32 bytes")
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after the unix epoch")
+        .as_secs()
+}
+
+/// Returns the point of the share at `index` which is committed by `commitments`.
+fn commitments_share_point(commitments: &[Commitment], index: usize) -> GE {
+    let points: Vec<GE> = commitments.iter().map(|c| c.to_point()).collect();
+    share_point(&points, index)
+}
+
+/// Returns the sum of the points. `points` should not be empty.
+fn sum_points<I: Iterator<Item = GE>>(mut points: I) -> GE {
+    let head = points.next().expect("points should not be empty");
+    points.fold(head, |acc, p| acc + &p)
+}
+
+fn to_secp_message(hash: &SHA256Hash) -> secp256k1::Message {
+    secp256k1::Message::from_slice(hash.borrow_inner()).expect("hash should be 32 bytes")
+}
+
+/// The signer who requests the repair of own node VSS.
+pub struct Repair {
+    signer_id: SignerID,
+    /// The block height of the federation to repair.
+    block_height: u64,
+    /// Helpers which are sorted by SignerID.
+    helpers: Vec<SignerID>,
+    /// Random number which makes the request unique.
+    nonce: u64,
+    /// Unix time when the request was created.
+    requested_at: u64,
+    signature: Signature,
+    /// Node VSS and the points of the dealt pieces which are received from each helper.
+    received: BTreeMap<SignerID, (Vec<Vss>, RepairPiecePoints)>,
+}
+
+impl Repair {
+    pub fn new(
+        private_key: &PrivateKey,
+        block_height: u64,
+        helpers: Vec<PublicKey>,
+    ) -> Result<Self, Error> {
+        let secp = secp256k1::Secp256k1::new();
+        let signer_id = SignerID::new(PublicKey::from_private_key(&secp, private_key));
+
+        let mut helpers: Vec<SignerID> = helpers.into_iter().map(SignerID::new).collect();
+        helpers.sort();
+        helpers.dedup();
+        if helpers.is_empty() || helpers.contains(&signer_id) {
+            return Err(Error::InvalidArgs(
+                "helper should be other signers of the federation".to_string(),
+            ));
+        }
+
+        let nonce = thread_rng().next_u64();
+        let requested_at = unix_time();
+        let hash = request_hash(block_height, &signer_id, &helpers, nonce, requested_at);
+        let signature = Signature(secp.sign(&to_secp_message(&hash), &private_key.key));
+
+        Ok(Repair {
+            signer_id,
+            block_height,
+            helpers,
+            nonce,
+            requested_at,
+            signature,
+            received: BTreeMap::new(),
+        })
+    }
+
+    fn hash(&self) -> SHA256Hash {
+        request_hash(
+            self.block_height,
+            &self.signer_id,
+            &self.helpers,
+            self.nonce,
+            self.requested_at,
+        )
+    }
+
+    /// Check that the node VSS from the helper has the same dealers and commitments as the ones
+    /// from the other helpers.
+    fn check_consistency(&self, nodevss: &[Vss]) -> Result<(), Error> {
+        if let Some((other, _)) = self.received.values().next() {
+            let is_consistent = other.len() == nodevss.len()
+                && other.iter().zip(nodevss).all(|(a, b)| {
+                    a.sender_public_key == b.sender_public_key
+                        && a.positive_commitments == b.positive_commitments
+                        && a.negative_commitments == b.negative_commitments
+                });
+            if !is_consistent {
+                return Err(Error::InconsistentCommitments);
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify the contributions of all helpers. Returns `Error::DisqualifiedSigner` with the
+    /// helper who dealt the pieces which don't sum to its contribution, or who sent the sum which
+    /// doesn't match the pieces it received.
+    fn verify_contributions(&self) -> Result<(), Error> {
+        let (nodevss, _) = self
+            .received
+            .values()
+            .next()
+            .expect("shares should be received");
+        let mut signers: Vec<SignerID> = nodevss
+            .iter()
+            .map(|vss| SignerID::new(vss.sender_public_key))
+            .collect();
+        signers.sort();
+        let index_of = |signer: &SignerID| {
+            signers
+                .iter()
+                .position(|s| s == signer)
+                .map(|i| i + 1)
+                .ok_or(Error::DisqualifiedSigner(*signer))
+        };
+        let helper_indices = self
+            .helpers
+            .iter()
+            .map(|h| index_of(h))
+            .collect::<Result<Vec<usize>, _>>()?;
+        let target_index = index_of(&self.signer_id).map_err(|_| Error::InvalidSS)?;
+
+        // The points of the pieces which each helper dealt, for each receiver.
+        let mut dealt: BTreeMap<SignerID, BTreeMap<SignerID, &Vec<(GE, GE)>>> = BTreeMap::new();
+        for (helper, (_, points)) in &self.received {
+            let by_receiver: BTreeMap<SignerID, &Vec<(GE, GE)>> =
+                points.iter().map(|(receiver, p)| (*receiver, p)).collect();
+            let is_complete = by_receiver.len() == self.helpers.len()
+                && self.helpers.iter().all(|h| {
+                    by_receiver
+                        .get(h)
+                        .map(|p| p.len() == nodevss.len())
+                        .unwrap_or(false)
+                });
+            if !is_complete {
+                return Err(Error::DisqualifiedSigner(*helper));
+            }
+            dealt.insert(*helper, by_receiver);
+        }
+
+        let g: GE = ECPoint::generator();
+        for (k, vss) in nodevss.iter().enumerate() {
+            // The pieces which each helper dealt should sum to its contribution, which is the
+            // share of the helper multiplied by the Lagrange coefficient.
+            for (helper, index) in self.helpers.iter().zip(&helper_indices) {
+                let coefficient = lagrange_coefficient(*index, &helper_indices, target_index);
+                let positive =
+                    commitments_share_point(&vss.positive_commitments, *index) * &coefficient;
+                let negative =
+                    commitments_share_point(&vss.negative_commitments, *index) * &coefficient;
+                let pieces = self.helpers.iter().map(|r| dealt[helper][r][k]);
+                if sum_points(pieces.clone().map(|p| p.0)) != positive
+                    || sum_points(pieces.map(|p| p.1)) != negative
+                {
+                    return Err(Error::DisqualifiedSigner(*helper));
+                }
+            }
+
+            // The secrets from each helper should be the sum of the pieces it received.
+            for (receiver, (shares, _)) in &self.received {
+                let pieces = self.helpers.iter().map(|h| dealt[h][receiver][k]);
+                if g * &*shares[k].positive_secret != sum_points(pieces.clone().map(|p| p.0))
+                    || g * &*shares[k].negative_secret != sum_points(pieces.map(|p| p.1))
+                {
+                    return Err(Error::DisqualifiedSigner(*receiver));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the node VSS which sums the secrets from all helpers.
+    fn sum_shares(&self) -> Option<Vec<Vss>> {
+        let mut iter = self.received.values().map(|(nodevss, _)| nodevss);
+        let head = iter.next()?.clone();
+        Some(iter.fold(head, |acc, nodevss| {
+            acc.iter()
+                .zip(nodevss)
+                .map(|(a, b)| {
+                    Vss::new(
+                        a.sender_public_key,
+                        a.receiver_public_key,
+                        a.positive_commitments.clone(),
+                        *a.positive_secret + *b.positive_secret,
+                        a.negative_commitments.clone(),
+                        *a.negative_secret + *b.negative_secret,
+                    )
+                })
+                .collect()
+        }))
+    }
+}
+
+impl Protocol for Repair {
+    type Output = Federation;
+
+    fn signer_id(&self) -> SignerID {
+        self.signer_id
+    }
+
+    fn outgoing(&self) -> Vec<Message> {
+        vec![Message {
+            message_type: MessageType::Repairrequest(
+                self.block_height,
+                self.helpers.clone(),
+                self.nonce,
+                self.requested_at,
+                self.signature.clone(),
+            ),
+            sender_id: self.signer_id,
+            receiver_id: None,
+        }]
+    }
+
+    fn process(&mut self, message: Message) -> Result<(), Error> {
+        let sender_id = message.sender_id;
+        match message.message_type {
+            MessageType::Repairshare(hash, nodevss, points) => {
+                if hash != self.hash()
+                    || !self.helpers.contains(&sender_id)
+                    || self.received.contains_key(&sender_id)
+                    || nodevss
+                        .iter()
+                        .any(|vss| vss.receiver_public_key != self.signer_id.pubkey)
+                {
+                    return Ok(());
+                }
+                self.check_consistency(&nodevss)?;
+                self.received.insert(sender_id, (nodevss, points));
+                if self.received.len() == self.helpers.len() {
+                    self.verify_contributions()?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The contributions of the helpers are verified in `process` when the last one is received,
+    /// so that the helper who cheated is reported. The summed shares are verified again here.
+    fn output(&self) -> Option<Federation> {
+        if self.received.len() != self.helpers.len() || self.verify_contributions().is_err() {
+            return None;
+        }
+        let nodevss = self.sum_shares()?;
+        let mut signers: Vec<PublicKey> = nodevss.iter().map(|v| v.sender_public_key).collect();
+        signers.sort_by_key(|pk| SignerID::new(*pk));
+        let index = signers.iter().position(|pk| *pk == self.signer_id.pubkey)? + 1;
+        if nodevss
+            .iter()
+            .any(|vss| vss.verify_positive_share(signers.len(), index).is_err())
+        {
+            return None;
+        }
+        let threshold = nodevss.first()?.positive_commitments.len() as u8;
+        Some(Federation::new(
+            self.signer_id.pubkey,
+            self.block_height,
+            Some(threshold),
+            nodevss.clone(),
+            aggregated_public_key(&nodevss),
+        ))
+    }
+}
+
+/// A signer who helps to repair the node VSS of the target. `tapyrus-signerd` runs it when it
+/// receives `Repairrequest`.
+pub struct RepairHelper {
+    signer_id: SignerID,
+    target: SignerID,
+    hash: SHA256Hash,
+    /// Unix time when the target created the request.
+    requested_at: u64,
+    /// Helpers which are sorted by SignerID.
+    helpers: Vec<SignerID>,
+    /// Node VSS of this signer, which provides the commitments for the target.
+    nodevss: Vec<Vss>,
+    /// Pieces which this signer sends to each helper.
+    dealt: BTreeMap<SignerID, RepairPieces>,
+    /// Pieces which this signer received from each helper.
+    received: BTreeMap<SignerID, RepairPieces>,
+}
+
+impl RepairHelper {
+    /// Verify the request for the federation which this signer belongs to, and prepare the pieces.
+    /// The request is rejected after `REPAIR_REQUEST_TIMEOUT_SECS` since `requested_at`.
+    pub fn new(
+        federation: &Federation,
+        target: SignerID,
+        block_height: u64,
+        helpers: Vec<SignerID>,
+        nonce: u64,
+        requested_at: u64,
+        signature: &Signature,
+    ) -> Result<Self, Error> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let hash = request_hash(block_height, &target, &helpers, nonce, requested_at);
+        if secp
+            .verify(&to_secp_message(&hash), &signature.0, &target.pubkey.key)
+            .is_err()
+        {
+            return Err(Error::InvalidSig);
+        }
+
+        let now = unix_time();
+        if requested_at + REPAIR_REQUEST_TIMEOUT_SECS < now
+            || requested_at > now + REPAIR_REQUEST_TIMEOUT_SECS
+        {
+            return Err(Error::InvalidArgs(
+                "the repair request is expired".to_string(),
+            ));
+        }
+
+        let signer_id = federation.signer_id();
+        let threshold = federation.threshold().ok_or(Error::InvalidArgs(
+            "the signer should be a member of the federation".to_string(),
+        ))?;
+        let signers = federation.signers();
+        let index_of = |signer: &SignerID| signers.iter().position(|s| s == signer).map(|i| i + 1);
+
+        let mut sorted = helpers.clone();
+        sorted.sort();
+        sorted.dedup();
+        if federation.block_height() != block_height
+            || sorted != helpers
+            || helpers.len() < threshold as usize
+            || !helpers.contains(&signer_id)
+            || helpers.contains(&target)
+            || index_of(&target).is_none()
+            || helpers.iter().any(|h| index_of(h).is_none())
+        {
+            return Err(Error::InvalidArgs("invalid repair request".to_string()));
+        }
+
+        let helper_indices: Vec<usize> = helpers.iter().filter_map(index_of).collect();
+        let coefficient = lagrange_coefficient(
+            index_of(&signer_id).expect("signer should be in the federation"),
+            &helper_indices,
+            index_of(&target).expect("target should be in the federation"),
+        );
+
+        let mut dealt: BTreeMap<SignerID, RepairPieces> =
+            helpers.iter().map(|h| (*h, Vec::new())).collect();
+        for vss in federation.nodevss() {
+            let dealer = SignerID::new(vss.sender_public_key);
+            let positive = split_secret(*vss.positive_secret * coefficient, helpers.len());
+            let negative = split_secret(*vss.negative_secret * coefficient, helpers.len());
+            for (i, helper) in helpers.iter().enumerate() {
+                dealt
+                    .get_mut(helper)
+                    .expect("helper should have pieces")
+                    .push((dealer, positive[i], negative[i]));
+            }
+        }
+
+        let mut received = BTreeMap::new();
+        received.insert(signer_id, dealt[&signer_id].clone());
+
+        Ok(RepairHelper {
+            signer_id,
+            target,
+            hash,
+            requested_at,
+            helpers,
+            nodevss: federation.nodevss().clone(),
+            dealt,
+            received,
+        })
+    }
+
+    pub fn hash(&self) -> SHA256Hash {
+        self.hash
+    }
+
+    pub fn requested_at(&self) -> u64 {
+        self.requested_at
+    }
+
+    pub fn receive_pieces(&mut self, sender_id: SignerID, pieces: RepairPieces) {
+        let is_valid = pieces.len() == self.nodevss.len()
+            && pieces
+                .iter()
+                .zip(&self.nodevss)
+                .all(|((dealer, _, _), vss)| dealer.pubkey == vss.sender_public_key);
+        if self.helpers.contains(&sender_id) && is_valid {
+            self.received.entry(sender_id).or_insert(pieces);
+        } else {
+            log::warn!("Invalid repair pieces from {}", sender_id);
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.received.len() == self.helpers.len()
+    }
+
+    /// Returns the messages to send in the current state.
+    pub fn outgoing(&self) -> Vec<Message> {
+        let mut messages: Vec<Message> = self
+            .dealt
+            .iter()
+            .filter(|(helper, _)| **helper != self.signer_id)
+            .map(|(helper, pieces)| Message {
+                message_type: MessageType::Repairpiece(self.hash, pieces.clone()),
+                sender_id: self.signer_id,
+                receiver_id: Some(*helper),
+            })
+            .collect();
+
+        if self.is_completed() {
+            let nodevss: Vec<Vss> = self
+                .nodevss
+                .iter()
+                .enumerate()
+                .map(|(k, vss)| {
+                    let sum = |f: fn(&(SignerID, FE, FE)) -> FE| {
+                        self.received
+                            .values()
+                            .fold(FE::zero(), |acc, pieces| acc + &f(&pieces[k]))
+                    };
+                    Vss::new(
+                        vss.sender_public_key,
+                        self.target.pubkey,
+                        vss.positive_commitments.clone(),
+                        sum(|piece| piece.1),
+                        vss.negative_commitments.clone(),
+                        sum(|piece| piece.2),
+                    )
+                })
+                .collect();
+            let g: GE = ECPoint::generator();
+            let points: RepairPiecePoints = self
+                .dealt
+                .iter()
+                .map(|(helper, pieces)| {
+                    let points = pieces
+                        .iter()
+                        .map(|(_, positive, negative)| (g * positive, g * negative))
+                        .collect();
+                    (*helper, points)
+                })
+                .collect();
+            messages.push(Message {
+                message_type: MessageType::Repairshare(self.hash, nodevss, points),
+                sender_id: self.signer_id,
+                receiver_id: Some(self.target),
+            });
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+    use curv::BigInt;
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+    }
+
+    /// Returns the private key of the signer at the index of the sorted public keys.
+    fn private_key(index: usize) -> PrivateKey {
+        let secp = secp256k1::Secp256k1::new();
+        TEST_KEYS
+            .key
+            .iter()
+            .find(|key| PublicKey::from_private_key(&secp, key) == TEST_KEYS.pubkeys()[index])
+            .unwrap()
+            .clone()
+    }
+
+    fn helper_ids(indices: &[usize]) -> Vec<PublicKey> {
+        indices.iter().map(|i| TEST_KEYS.pubkeys()[*i]).collect()
+    }
+
+    /// Run the repair of the target with the helpers in memory.
+    fn run_repair(target: usize, helpers: &[usize]) -> Result<Option<Federation>, Error> {
+        run_repair_with(target, helpers, |_| {})
+    }
+
+    /// Run the repair. `tamper` can modify the messages to the target.
+    fn run_repair_with<F>(
+        target: usize,
+        helpers: &[usize],
+        tamper: F,
+    ) -> Result<Option<Federation>, Error>
+    where
+        F: Fn(&mut Message),
+    {
+        let mut repair = Repair::new(&private_key(target), 0, helper_ids(helpers))?;
+        let request = repair.outgoing().remove(0);
+        let (helpers_sorted, nonce, requested_at, signature) = match request.message_type {
+            MessageType::Repairrequest(_, helpers, nonce, requested_at, signature) => {
+                (helpers, nonce, requested_at, signature)
+            }
+            _ => panic!("unexpected message"),
+        };
+
+        let mut repair_helpers: Vec<RepairHelper> = helpers
+            .iter()
+            .map(|i| {
+                RepairHelper::new(
+                    &federation(*i),
+                    request.sender_id,
+                    0,
+                    helpers_sorted.clone(),
+                    nonce,
+                    requested_at,
+                    &signature,
+                )
+            })
+            .collect::<Result<Vec<RepairHelper>, Error>>()?;
+
+        let pieces: Vec<Message> = repair_helpers.iter().flat_map(|h| h.outgoing()).collect();
+        for message in pieces {
+            if let MessageType::Repairpiece(_, pieces) = message.message_type {
+                for helper in repair_helpers.iter_mut() {
+                    if Some(helper.signer_id) == message.receiver_id {
+                        helper.receive_pieces(message.sender_id, pieces.clone());
+                    }
+                }
+            }
+        }
+
+        for helper in &repair_helpers {
+            assert!(helper.is_completed());
+            for mut message in helper.outgoing() {
+                if message.receiver_id == Some(request.sender_id) {
+                    tamper(&mut message);
+                    repair.process(message)?;
+                }
+            }
+        }
+        Ok(repair.output())
+    }
+
+    #[test]
+    fn test_repair() {
+        let repaired = run_repair(1, &[0, 2, 4]).unwrap().unwrap();
+        assert_eq!(repaired, federation(1));
+        assert!(repaired.validate().is_ok());
+
+        let repaired = run_repair(0, &[1, 2, 3, 4]).unwrap().unwrap();
+        assert_eq!(repaired, federation(0));
+    }
+
+    #[test]
+    fn test_repair_with_insufficient_helpers() {
+        match run_repair(1, &[0, 2]) {
+            Err(Error::InvalidArgs(_)) => {}
+            r => assert!(false, "it should fail but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_repair_helper_rejects_invalid_signature() {
+        let repair = Repair::new(&private_key(1), 0, helper_ids(&[0, 2, 4])).unwrap();
+        let signature = repair.signature.clone();
+        // The signature is not for block height 100.
+        let result = RepairHelper::new(
+            &federation(0),
+            repair.signer_id,
+            100,
+            repair.helpers.clone(),
+            repair.nonce,
+            repair.requested_at,
+            &signature,
+        );
+        match result {
+            Err(Error::InvalidSig) => {}
+            _ => assert!(false, "it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_repair_helper_rejects_expired_request() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut repair = Repair::new(&private_key(1), 0, helper_ids(&[0, 2, 4])).unwrap();
+        repair.requested_at -= REPAIR_REQUEST_TIMEOUT_SECS + 1;
+        let signature = Signature(secp.sign(&to_secp_message(&repair.hash()), &private_key(1).key));
+        let result = RepairHelper::new(
+            &federation(0),
+            repair.signer_id,
+            0,
+            repair.helpers.clone(),
+            repair.nonce,
+            repair.requested_at,
+            &signature,
+        );
+        match result {
+            Err(Error::InvalidArgs(_)) => {}
+            _ => assert!(false, "it should fail"),
+        }
+    }
+
+    #[test]
+    fn test_repair_disqualifies_invalid_contribution() {
+        let cheater = SignerID::new(TEST_KEYS.pubkeys()[2]);
+        let one: FE = ECScalar::from(&BigInt::from(1));
+        let result = run_repair_with(1, &[0, 2, 4], |message| {
+            if message.sender_id != cheater {
+                return;
+            }
+            if let MessageType::Repairshare(_, nodevss, _) = &mut message.message_type {
+                let vss = &mut nodevss[0];
+                *vss = Vss::new(
+                    vss.sender_public_key,
+                    vss.receiver_public_key,
+                    vss.positive_commitments.clone(),
+                    *vss.positive_secret + one,
+                    vss.negative_commitments.clone(),
+                    *vss.negative_secret,
+                );
+            }
+        });
+        match result {
+            Err(Error::DisqualifiedSigner(signer)) => assert_eq!(signer, cheater),
+            r => assert!(false, "it should fail but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_repair_rejects_inconsistent_commitments() {
+        let mut repair = Repair::new(&private_key(1), 0, helper_ids(&[0, 2, 4])).unwrap();
+        let hash = repair.hash();
+        let target = repair.signer_id;
+        let receiver = TEST_KEYS.pubkeys()[1];
+        let share = |helper: usize, dealer: usize| {
+            let vss = &node_vss(helper)[dealer];
+            Vss::new(
+                vss.sender_public_key,
+                receiver,
+                vss.positive_commitments.clone(),
+                *vss.positive_secret,
+                vss.negative_commitments.clone(),
+                *vss.negative_secret,
+            )
+        };
+        let message = move |helper: usize, nodevss: Vec<Vss>| Message {
+            message_type: MessageType::Repairshare(hash, nodevss, vec![]),
+            sender_id: SignerID::new(TEST_KEYS.pubkeys()[helper]),
+            receiver_id: Some(target),
+        };
+
+        let first = message(0, (0..5).map(|d| share(0, d)).collect());
+        assert!(repair.process(first).is_ok());
+
+        // The commitments of dealer 0 and dealer 1 are swapped.
+        let mut nodevss: Vec<Vss> = (0..5).map(|d| share(2, d)).collect();
+        let commitments = nodevss[0].positive_commitments.clone();
+        nodevss[0].positive_commitments = nodevss[1].positive_commitments.clone();
+        nodevss[1].positive_commitments = commitments;
+        match repair.process(message(2, nodevss)) {
+            Err(Error::InconsistentCommitments) => {}
+            _ => assert!(false, "it should fail"),
+        }
+    }
+}
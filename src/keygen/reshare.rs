@@ -0,0 +1,671 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Resharing of the aggregated private key to a new federation.
+//!
+//! The holders are at least `threshold` signers of the current federation. The members are the
+//! signers of the new federation, which may have a different threshold.
+//!
+//! 1. Each holder multiplies its node secret share by the Lagrange coefficient at zero for the
+//!    holders, splits it into random pieces and sends one piece to each member with
+//!    `Resharepiece`. The sum of all pieces is the aggregated private key. The message has the
+//!    points of all pieces of the holder, and the commitments of the current node VSS.
+//! 2. Each member verifies that the points of the pieces sum to the public share of the holder,
+//!    which is computed from the commitments, multiplied by the Lagrange coefficient.
+//! 3. Each member sums the pieces it received and deals node VSS of the sum to all members
+//!    through `Exchange`, in the same way as Distributed Key Generation.
+//! 4. Each member verifies that the aggregated public key of the new node VSS is the same as the
+//!    current one.
+//!
+//! The members who are not signers of the current federation take the commitments from the
+//! holders. They accept them only if the commitments of all holders are the same and they commit
+//! to the aggregated public key.
+//!
+//! The holders who are not members finish when all members have sent the complaints, which
+//! means all members have received the pieces.
+
+use crate::crypto::vss::{share_point, Commitment, Vss};
+use crate::errors::Error;
+use crate::federation::Federation;
+use crate::keygen::dkg::{aggregated_public_key, deal_node_vss, sorted_signers};
+use crate::keygen::exchange::Exchange;
+use crate::keygen::Protocol;
+use crate::net::{Message, MessageType, SignerID};
+use crate::util::{lagrange_coefficient, split_secret};
+use bitcoin::PublicKey;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{FE, GE};
+use std::collections::{BTreeMap, HashSet};
+
+/// Positive commitments of the node VSS of the current federation for each dealer.
+pub type CurrentCommitments = BTreeMap<SignerID, Vec<GE>>;
+
+pub struct Reshare {
+    signer_id: SignerID,
+    /// Holders of the current federation which are sorted by SignerID.
+    holders: Vec<SignerID>,
+    /// Members of the new federation which are sorted by SignerID.
+    members: Vec<SignerID>,
+    /// The threshold of the new federation.
+    threshold: u8,
+    /// The block height where the new federation gets started.
+    block_height: u64,
+    /// The aggregated public key of the current federation, which the new federation keeps.
+    aggregated_public_key: PublicKey,
+    /// Pieces which this signer sends to each member if it is a holder.
+    pieces: BTreeMap<SignerID, Vss>,
+    /// Pieces which this signer received from each holder if it is a member.
+    received_pieces: BTreeMap<SignerID, Vss>,
+    /// Commitments of the node VSS of the current federation for each dealer. It is None until
+    /// the first piece is received if this signer is not a signer of the current federation.
+    current_commitments: Option<CurrentCommitments>,
+    /// Exchange of the new node VSS. A member starts it after receiving the pieces from all
+    /// holders. A holder who is not a member only observes the complaints.
+    exchange: Option<Exchange<Vss>>,
+}
+
+impl Reshare {
+    /// `federation` is the current federation of this signer. It is required if this signer is a
+    /// holder.
+    pub fn new(
+        signer_id: SignerID,
+        federation: Option<&Federation>,
+        holders: Vec<PublicKey>,
+        members: Vec<PublicKey>,
+        threshold: u8,
+        block_height: u64,
+        aggregated_public_key: PublicKey,
+    ) -> Result<Self, Error> {
+        let mut holders: Vec<SignerID> = holders.into_iter().map(SignerID::new).collect();
+        holders.sort();
+        let unique: HashSet<&SignerID> = holders.iter().collect();
+        if holders.is_empty() || unique.len() != holders.len() {
+            return Err(Error::InvalidArgs(
+                "holder should not be empty nor duplicated".to_string(),
+            ));
+        }
+
+        let is_holder = holders.contains(&signer_id);
+        let is_member = members.iter().any(|key| SignerID::new(*key) == signer_id);
+        if !is_holder && !is_member {
+            return Err(Error::InvalidArgs(
+                "the signer should be a holder or a member of the new federation".to_string(),
+            ));
+        }
+        let members = if is_member {
+            sorted_signers(&signer_id, members, threshold)?
+        } else {
+            let member = SignerID::new(*members.first().ok_or(Error::InvalidArgs(
+                "new-public-key should not be empty".to_string(),
+            ))?);
+            sorted_signers(&member, members, threshold)?
+        };
+
+        let pieces = if is_holder {
+            let federation = federation.ok_or(Error::InvalidArgs(
+                "the holder should have the current federation".to_string(),
+            ))?;
+            Self::create_pieces(
+                federation,
+                &holders,
+                &members,
+                block_height,
+                aggregated_public_key,
+            )?
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut received_pieces = BTreeMap::new();
+        if let Some(piece) = pieces.get(&signer_id) {
+            received_pieces.insert(signer_id, piece.clone());
+        }
+        let current_commitments = federation.map(|federation| {
+            federation
+                .nodevss()
+                .iter()
+                .map(|vss| {
+                    let points = vss
+                        .positive_commitments
+                        .iter()
+                        .map(|c| c.to_point())
+                        .collect();
+                    (SignerID::new(vss.sender_public_key), points)
+                })
+                .collect()
+        });
+
+        let mut reshare = Reshare {
+            signer_id,
+            holders,
+            members,
+            threshold,
+            block_height,
+            aggregated_public_key,
+            pieces,
+            received_pieces,
+            current_commitments,
+            exchange: None,
+        };
+        if is_member {
+            reshare.start_exchange_if_ready();
+        } else {
+            reshare.exchange = Some(Exchange::new(
+                signer_id,
+                reshare.members.clone(),
+                reshare.members.clone(),
+                BTreeMap::new(),
+            ));
+        }
+        Ok(reshare)
+    }
+
+    /// Returns the pieces of the Lagrange-weighted node secret share of this holder for each
+    /// member. The commitments of each piece are the points of all pieces in the order of the
+    /// members, so that the members can verify that they sum to the public share of the holder.
+    fn create_pieces(
+        federation: &Federation,
+        holders: &[SignerID],
+        members: &[SignerID],
+        block_height: u64,
+        aggregated_public_key: PublicKey,
+    ) -> Result<BTreeMap<SignerID, Vss>, Error> {
+        let threshold = federation.threshold().ok_or(Error::InvalidArgs(
+            "the holder should be a member of the current federation".to_string(),
+        ))?;
+        if federation.aggregated_public_key() != aggregated_public_key {
+            return Err(Error::InvalidArgs(
+                "aggregated-public-key should be same as the current federation".to_string(),
+            ));
+        }
+        if block_height <= federation.block_height() {
+            return Err(Error::InvalidArgs(
+                "block-height should be greater than the block height of the federation"
+                    .to_string(),
+            ));
+        }
+        if holders.len() < threshold as usize {
+            return Err(Error::InvalidArgs(
+                "the number of holders should be greater or equal to the threshold".to_string(),
+            ));
+        }
+
+        let signers = federation.signers();
+        let index_of = |id: &SignerID| signers.iter().position(|s| s == id).map(|i| i + 1);
+        let holder_indices: Vec<usize> = holders
+            .iter()
+            .map(|holder| {
+                index_of(holder).ok_or(Error::InvalidArgs(
+                    "holder should be a signer of the current federation".to_string(),
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let signer_id = federation.signer_id();
+        let coefficient = lagrange_coefficient(federation.node_index() + 1, &holder_indices, 0);
+        let weighted = *federation.node_secret_share().x_i * coefficient;
+
+        let g: GE = ECPoint::generator();
+        let pieces = split_secret(weighted, members.len());
+        let commitments: Vec<Commitment> = pieces
+            .iter()
+            .map(|piece| Commitment::from(&(g * piece)))
+            .collect();
+        Ok(members
+            .iter()
+            .zip(pieces)
+            .map(|(member, piece)| {
+                let vss = Vss::new(
+                    signer_id.pubkey,
+                    member.pubkey,
+                    commitments.clone(),
+                    piece,
+                    commitments.clone(),
+                    piece,
+                );
+                (*member, vss)
+            })
+            .collect())
+    }
+
+    fn is_member(&self) -> bool {
+        self.members.contains(&self.signer_id)
+    }
+
+    /// Deal node VSS of the sum of the pieces once the pieces from all holders are received.
+    fn start_exchange_if_ready(&mut self) {
+        if self.exchange.is_some() || self.received_pieces.len() != self.holders.len() {
+            return;
+        }
+        let secret = self
+            .received_pieces
+            .values()
+            .fold(FE::zero(), |acc, piece| acc + *piece.positive_secret);
+        let (vss_scheme, secret_shares) =
+            Vss::create_shares(&secret, self.threshold as usize, self.members.len());
+        let dealt = deal_node_vss(&self.signer_id, &vss_scheme, &secret_shares, &self.members);
+        self.exchange = Some(Exchange::new(
+            self.signer_id,
+            self.members.clone(),
+            self.members.clone(),
+            dealt,
+        ));
+    }
+
+    fn receive_piece(
+        &mut self,
+        sender_id: SignerID,
+        piece: Vss,
+        commitments: Vec<(SignerID, Vec<GE>)>,
+    ) -> Result<(), Error> {
+        if !self.is_member()
+            || !self.holders.contains(&sender_id)
+            || SignerID::new(piece.sender_public_key) != sender_id
+            || SignerID::new(piece.receiver_public_key) != self.signer_id
+            || self.received_pieces.contains_key(&sender_id)
+        {
+            return Ok(());
+        }
+        let commitments: CurrentCommitments = commitments.into_iter().collect();
+        match &self.current_commitments {
+            Some(current) if *current != commitments => {
+                return Err(Error::DisqualifiedSigner(sender_id))
+            }
+            Some(_) => {}
+            None => {
+                if !self.commits_to_aggregated_public_key(&commitments) {
+                    return Err(Error::DisqualifiedSigner(sender_id));
+                }
+                self.current_commitments = Some(commitments);
+            }
+        }
+        if !self.verify_piece(&sender_id, &piece) {
+            return Err(Error::DisqualifiedSigner(sender_id));
+        }
+        self.received_pieces.insert(sender_id, piece);
+        self.start_exchange_if_ready();
+        Ok(())
+    }
+
+    /// Returns true if the constant terms of the commitments sum to the aggregated public key.
+    fn commits_to_aggregated_public_key(&self, commitments: &CurrentCommitments) -> bool {
+        let mut constants = commitments.values().map(|c| c.first());
+        let head = match constants.next() {
+            Some(Some(head)) => *head,
+            _ => return false,
+        };
+        let sum = constants.fold(Some(head), |acc, c| match (acc, c) {
+            (Some(acc), Some(c)) => Some(acc + c),
+            _ => None,
+        });
+        match sum {
+            Some(sum) => sum.get_element() == self.aggregated_public_key.key,
+            None => false,
+        }
+    }
+
+    /// Returns true if the piece matches the point of it, and the points of all pieces of the
+    /// holder sum to the public share of the holder multiplied by the Lagrange coefficient.
+    fn verify_piece(&self, holder: &SignerID, piece: &Vss) -> bool {
+        let commitments = match &self.current_commitments {
+            Some(commitments) => commitments,
+            None => return false,
+        };
+        // BTreeMap is sorted by SignerID, in the same order as `Federation::signers`.
+        let index_of = |id: &SignerID| commitments.keys().position(|s| s == id).map(|i| i + 1);
+        let holder_indices: Option<Vec<usize>> = self.holders.iter().map(index_of).collect();
+        let (holder_indices, holder_index) = match (holder_indices, index_of(holder)) {
+            (Some(indices), Some(index)) => (indices, index),
+            _ => return false,
+        };
+        let member_index = match self.members.iter().position(|m| *m == self.signer_id) {
+            Some(index) => index,
+            None => return false,
+        };
+        if piece.positive_commitments.len() != self.members.len()
+            || commitments.values().any(|c| c.is_empty())
+        {
+            return false;
+        }
+
+        let g: GE = ECPoint::generator();
+        let points: Vec<GE> = piece
+            .positive_commitments
+            .iter()
+            .map(|c| c.to_point())
+            .collect();
+        if g * &*piece.positive_secret != points[member_index] {
+            return false;
+        }
+
+        let public_share = commitments
+            .values()
+            .map(|c| share_point(c, holder_index))
+            .fold(None, |acc: Option<GE>, p| match acc {
+                Some(acc) => Some(acc + &p),
+                None => Some(p),
+            })
+            .expect("commitments should not be empty");
+        let coefficient = lagrange_coefficient(holder_index, &holder_indices, 0);
+        let sum = points[1..].iter().fold(points[0], |acc, p| acc + p);
+        sum == public_share * &coefficient
+    }
+
+    /// Returns Err if the new node VSS doesn't keep the aggregated public key.
+    fn check_aggregated_public_key(&self) -> Result<(), Error> {
+        match self.new_nodevss() {
+            Some(nodevss) if aggregated_public_key(&nodevss) != self.aggregated_public_key => {
+                Err(Error::InvalidAggregatedPublicKey)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn new_nodevss(&self) -> Option<Vec<Vss>> {
+        let received = self.exchange.as_ref()?.result()?;
+        if !self.is_member() {
+            return None;
+        }
+        Some(received.values().cloned().collect())
+    }
+}
+
+impl Protocol for Reshare {
+    /// The new federation, or None if this signer is not a member of it.
+    type Output = Option<Federation>;
+
+    fn signer_id(&self) -> SignerID {
+        self.signer_id
+    }
+
+    fn outgoing(&self) -> Vec<Message> {
+        let mut messages: Vec<Message> = self
+            .pieces
+            .iter()
+            .filter(|(member, _)| **member != self.signer_id)
+            .map(|(member, piece)| Message {
+                message_type: MessageType::Resharepiece(
+                    piece.clone(),
+                    self.current_commitments
+                        .as_ref()
+                        .expect("the holder should have the current federation")
+                        .iter()
+                        .map(|(dealer, commitments)| (*dealer, commitments.clone()))
+                        .collect(),
+                ),
+                sender_id: self.signer_id,
+                receiver_id: Some(*member),
+            })
+            .collect();
+        if let Some(exchange) = &self.exchange {
+            messages.extend(exchange.outgoing());
+        }
+        messages
+    }
+
+    fn process(&mut self, message: Message) -> Result<(), Error> {
+        let sender_id = message.sender_id;
+        if sender_id == self.signer_id {
+            return Ok(());
+        }
+
+        if let MessageType::Resharepiece(piece, commitments) = message.message_type {
+            return self.receive_piece(sender_id, piece, commitments);
+        }
+
+        // Messages of the exchange which arrive before it starts are sent again later.
+        let exchange = match &mut self.exchange {
+            Some(exchange) => exchange,
+            None => return Ok(()),
+        };
+        match message.message_type {
+            MessageType::Nodevss(vss) => exchange.receive_dealing(sender_id, vss)?,
+            MessageType::Nodevsscomplaints(digest, accused) => {
+                exchange.receive_complaints(sender_id, digest, accused)?
+            }
+            MessageType::Nodevssreveal(vss) => exchange.receive_reveal(sender_id, vss)?,
+            _ => {}
+        }
+        self.check_aggregated_public_key()
+    }
+
+    fn output(&self) -> Option<Option<Federation>> {
+        let exchange = self.exchange.as_ref()?;
+        exchange.result()?;
+        if !self.is_member() {
+            return Some(None);
+        }
+        let nodevss = self.new_nodevss()?;
+        if aggregated_public_key(&nodevss) != self.aggregated_public_key {
+            return None;
+        }
+        Some(Some(Federation::new(
+            self.signer_id.pubkey,
+            self.block_height,
+            Some(self.threshold),
+            nodevss,
+            self.aggregated_public_key,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::secret::Secret;
+    use crate::keygen::tests::run_protocols;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_vss::node_vss;
+    use curv::BigInt;
+    use std::str::FromStr;
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+    }
+
+    fn new_signer() -> PublicKey {
+        PublicKey::from_str("02a1c8965ed06987fa6d7e0f552db707065352283ab3c1471510b12a76a5905287")
+            .unwrap()
+    }
+
+    /// Signers 0, 1 and 2 are the holders. The new federation has signers 2, 3, 4 and a new
+    /// signer with threshold 2, so signers 0 and 1 leave.
+    fn create_reshares() -> Vec<Reshare> {
+        let holders: Vec<PublicKey> = TEST_KEYS.pubkeys()[0..3].to_vec();
+        let mut members: Vec<PublicKey> = TEST_KEYS.pubkeys()[2..5].to_vec();
+        members.push(new_signer());
+
+        let mut reshares: Vec<Reshare> = (0..5)
+            .map(|i| {
+                let federation = federation(i);
+                Reshare::new(
+                    SignerID::new(TEST_KEYS.pubkeys()[i]),
+                    Some(&federation),
+                    holders.clone(),
+                    members.clone(),
+                    2,
+                    100,
+                    TEST_KEYS.aggregated(),
+                )
+                .unwrap()
+            })
+            .collect();
+        reshares.push(
+            Reshare::new(
+                SignerID::new(new_signer()),
+                None,
+                holders,
+                members,
+                2,
+                100,
+                TEST_KEYS.aggregated(),
+            )
+            .unwrap(),
+        );
+        reshares
+    }
+
+    #[test]
+    fn test_reshare() {
+        let mut reshares = create_reshares();
+        let results: Vec<Option<Federation>> = run_protocols(&mut reshares, |_| {})
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert!(results[0].is_none());
+        assert!(results[1].is_none());
+        let federations: Vec<Federation> = results.into_iter().filter_map(|r| r).collect();
+        assert_eq!(federations.len(), 4);
+        for federation in &federations {
+            assert!(federation.validate().is_ok());
+            assert_eq!(federation.block_height(), 100);
+            assert_eq!(federation.threshold(), Some(2));
+            assert_eq!(federation.signers().len(), 4);
+            assert_eq!(federation.aggregated_public_key(), TEST_KEYS.aggregated());
+        }
+
+        // The shares of any 2 members of the new federation reconstruct the aggregated private
+        // key.
+        let pair = [&federations[1], &federations[3]];
+        let indices: Vec<usize> = pair.iter().map(|f| f.node_index()).collect();
+        let shares: Vec<FE> = pair.iter().map(|f| *f.node_secret_share().x_i).collect();
+        let vss = federations[0]
+            .node_shared_secrets()
+            .values()
+            .next()
+            .unwrap()
+            .vss
+            .clone();
+        let secret = vss.reconstruct(&indices, &shares);
+        let g: GE = ECPoint::generator();
+        assert_eq!((g * &secret).get_element(), TEST_KEYS.aggregated().key);
+    }
+
+    #[test]
+    fn test_reshare_with_invalid_piece() {
+        let mut reshares = create_reshares();
+        let results = run_protocols(&mut reshares, |message| {
+            if let MessageType::Resharepiece(piece, _) = &mut message.message_type {
+                if piece.sender_public_key == TEST_KEYS.pubkeys()[0] {
+                    piece.positive_secret = Secret::new(ECScalar::from(&BigInt::from(1)));
+                }
+            }
+        });
+
+        let holder = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        for result in results.into_iter().skip(2) {
+            match result {
+                Err(Error::DisqualifiedSigner(id)) => assert_eq!(id, holder),
+                r => assert!(false, "it should fail but {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reshare_with_wrong_secret() {
+        // Holder 0 sends pieces which match their points, but the points don't sum to the public
+        // share of the holder.
+        let mut reshares = create_reshares();
+        let g: GE = ECPoint::generator();
+        let results = run_protocols(&mut reshares, |message| {
+            if let MessageType::Resharepiece(piece, _) = &mut message.message_type {
+                if piece.sender_public_key == TEST_KEYS.pubkeys()[0] {
+                    let secret: FE = ECScalar::from(&BigInt::from(1));
+                    let commitments = vec![Commitment::from(&(g * &secret)); 4];
+                    piece.positive_commitments = commitments.clone();
+                    piece.positive_secret = Secret::new(secret);
+                    piece.negative_commitments = commitments;
+                    piece.negative_secret = Secret::new(secret);
+                }
+            }
+        });
+
+        let holder = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        for result in results.into_iter().skip(2) {
+            match result {
+                Err(Error::DisqualifiedSigner(id)) => assert_eq!(id, holder),
+                r => assert!(false, "it should fail but {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reshare_with_wrong_commitments() {
+        // Holder 0 sends the commitments of the current node VSS which are not the ones the other
+        // holders send.
+        let mut reshares = create_reshares();
+        let g: GE = ECPoint::generator();
+        let results = run_protocols(&mut reshares, |message| {
+            if let MessageType::Resharepiece(piece, commitments) = &mut message.message_type {
+                if piece.sender_public_key == TEST_KEYS.pubkeys()[0] {
+                    commitments[0].1[1] = g;
+                }
+            }
+        });
+
+        let holder = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        for result in results.into_iter().skip(2) {
+            match result {
+                Err(Error::DisqualifiedSigner(id)) => assert_eq!(id, holder),
+                r => assert!(false, "it should fail but {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_with_invalid_args() {
+        let signer_id = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        let new = |federation: Option<&Federation>,
+                   holders: Vec<PublicKey>,
+                   members: Vec<PublicKey>,
+                   threshold: u8,
+                   block_height: u64| {
+            Reshare::new(
+                signer_id,
+                federation,
+                holders,
+                members,
+                threshold,
+                block_height,
+                TEST_KEYS.aggregated(),
+            )
+        };
+        let federation = federation(0);
+        let holders = TEST_KEYS.pubkeys()[0..3].to_vec();
+        let members = TEST_KEYS.pubkeys()[1..5].to_vec();
+
+        assert!(new(Some(&federation), holders.clone(), members.clone(), 2, 100).is_ok());
+        // The holder doesn't have the federation.
+        assert!(new(None, holders.clone(), members.clone(), 2, 100).is_err());
+        // Too few holders.
+        assert!(new(
+            Some(&federation),
+            TEST_KEYS.pubkeys()[0..2].to_vec(),
+            members.clone(),
+            2,
+            100
+        )
+        .is_err());
+        // Invalid threshold.
+        assert!(new(Some(&federation), holders.clone(), members.clone(), 5, 100).is_err());
+        // Invalid block height.
+        assert!(new(Some(&federation), holders.clone(), members.clone(), 2, 0).is_err());
+        // The signer is neither a holder nor a member.
+        assert!(new(
+            Some(&federation),
+            TEST_KEYS.pubkeys()[1..4].to_vec(),
+            members,
+            2,
+            100
+        )
+        .is_err());
+    }
+}
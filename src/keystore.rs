@@ -0,0 +1,267 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Passphrase-encrypted keystore for the files which hold secrets, such as federations.toml.
+//!
+//! The keystore is a TOML file which has a `[keystore]` table:
+//!
+//! ```toml
+//! [keystore]
+//! version = 1
+//! cipher = "aes-256-gcm"
+//! kdf = "pbkdf2-hmac-sha256"
+//! iterations = 100000
+//! salt = "<32 bytes in hex>"
+//! nonce = "<12 bytes in hex>"
+//! ciphertext = "<encrypted content and tag in hex>"
+//! ```
+//!
+//! The key of AES-256-GCM is derived from the passphrase and the salt with PBKDF2-HMAC-SHA256.
+//! The salt and the nonce are generated for every encryption.
+
+use crate::crypto::secret::{Secret, Zeroize};
+use crate::errors::Error;
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::Aes256Gcm;
+use hmac::Hmac;
+use secp256k1::rand::{thread_rng, RngCore};
+use sha2::Sha256;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::FromRawFd;
+
+const VERSION: u32 = 1;
+const CIPHER: &str = "aes-256-gcm";
+const KDF: &str = "pbkdf2-hmac-sha256";
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+/// The source of the passphrase of the keystore.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Passphrase {
+    /// Reads the first line from the file descriptor.
+    Fd(i32),
+    /// Reads the value of the environment variable.
+    Env(String),
+    /// Asks on the terminal.
+    Prompt,
+}
+
+impl Passphrase {
+    /// Returns the passphrase to decrypt the keystore.
+    pub fn read(&self) -> Result<Secret<String>, Error> {
+        let passphrase = Secret::new(match self {
+            Passphrase::Fd(fd) => {
+                let file = unsafe { File::from_raw_fd(*fd) };
+                let mut line = String::new();
+                if let Err(e) = BufReader::new(file).read_line(&mut line) {
+                    line.zeroize();
+                    return Err(e.into());
+                }
+                // Trim in place, so that no copy of the passphrase is left.
+                let len = line.trim_end_matches(|c| c == '\n' || c == '\r').len();
+                line.truncate(len);
+                line
+            }
+            Passphrase::Env(name) => std::env::var(name).map_err(|_| {
+                Error::InvalidPassphrase(format!("environment variable {} is not set", name))
+            })?,
+            Passphrase::Prompt => rpassword::read_password_from_tty(Some("Passphrase: "))?,
+        });
+        if passphrase.is_empty() {
+            return Err(Error::InvalidPassphrase("passphrase is empty".to_string()));
+        }
+        Ok(passphrase)
+    }
+
+    /// Returns the passphrase to encrypt the keystore. The prompt asks it twice to avoid typos.
+    pub fn read_new(&self) -> Result<Secret<String>, Error> {
+        let passphrase = self.read()?;
+        if let Passphrase::Prompt = self {
+            let confirmation = Secret::new(rpassword::read_password_from_tty(Some(
+                "Confirm passphrase: ",
+            ))?);
+            if passphrase != confirmation {
+                return Err(Error::InvalidPassphrase(
+                    "passphrases do not match".to_string(),
+                ));
+            }
+        }
+        Ok(passphrase)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SerKeystore {
+    keystore: Keystore,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Keystore {
+    version: u32,
+    cipher: String,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Returns true if the content is a keystore.
+pub fn is_encrypted(content: &str) -> bool {
+    match toml::from_str::<toml::Value>(content) {
+        Ok(value) => value.get("keystore").is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Encrypts the content with the passphrase and returns the keystore in TOML.
+pub fn encrypt(content: &str, passphrase: &str) -> String {
+    encrypt_with_iterations(content, passphrase, DEFAULT_ITERATIONS)
+}
+
+pub fn encrypt_with_iterations(content: &str, passphrase: &str, iterations: u32) -> String {
+    let mut salt = [0u8; SALT_SIZE];
+    let mut nonce = [0u8; NONCE_SIZE];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = cipher(passphrase, &salt, iterations);
+    let ciphertext = cipher
+        .encrypt(GenericArray::from_slice(&nonce), content.as_bytes())
+        .expect("encryption failure");
+
+    let keystore = SerKeystore {
+        keystore: Keystore {
+            version: VERSION,
+            cipher: CIPHER.to_string(),
+            kdf: KDF.to_string(),
+            iterations,
+            salt: hex::encode(&salt),
+            nonce: hex::encode(&nonce),
+            ciphertext: hex::encode(&ciphertext),
+        },
+    };
+    toml::to_string(&keystore).expect("keystore should be serialized")
+}
+
+/// Decrypts the keystore with the passphrase and returns the content, which is zeroized when it
+/// is dropped.
+pub fn decrypt(keystore: &str, passphrase: &str) -> Result<Secret<String>, Error> {
+    let SerKeystore { keystore } = toml::from_str(keystore)?;
+    if keystore.version != VERSION || keystore.cipher != CIPHER || keystore.kdf != KDF {
+        return Err(Error::InvalidKeystore("unsupported keystore format"));
+    }
+    if keystore.iterations == 0 {
+        return Err(Error::InvalidKeystore("iterations should be positive"));
+    }
+    let salt = hex::decode(&keystore.salt)
+        .map_err(|_| Error::InvalidKeystore("salt should be a hex string"))?;
+    let nonce = hex::decode(&keystore.nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == NONCE_SIZE)
+        .ok_or(Error::InvalidKeystore(
+            "nonce should be 12 bytes hex string",
+        ))?;
+    let ciphertext = hex::decode(&keystore.ciphertext)
+        .map_err(|_| Error::InvalidKeystore("ciphertext should be a hex string"))?;
+
+    let cipher = cipher(passphrase, &salt, keystore.iterations);
+    let content = cipher
+        .decrypt(GenericArray::from_slice(&nonce), &ciphertext[..])
+        .map_err(|_| Error::InvalidKeystore("passphrase is wrong or keystore is corrupted"))?;
+    match String::from_utf8(content) {
+        Ok(content) => Ok(Secret::new(content)),
+        Err(e) => {
+            e.into_bytes().zeroize();
+            Err(Error::InvalidKeystore("content is not UTF-8"))
+        }
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8], iterations: u32) -> Aes256Gcm {
+    let mut key = [0u8; KEY_SIZE];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, iterations as usize, &mut key);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    unsafe { std::ptr::write_volatile(&mut key, [0u8; KEY_SIZE]) };
+    cipher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "[[federation]]\nblock-height = 0\n";
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let keystore = encrypt_with_iterations(CONTENT, "passphrase", 1000);
+        assert!(is_encrypted(&keystore));
+        assert!(!is_encrypted(CONTENT));
+        assert!(!keystore.contains("federation"));
+        assert_eq!(*decrypt(&keystore, "passphrase").unwrap(), CONTENT);
+
+        // The salt and the nonce are different in every encryption.
+        assert_ne!(
+            encrypt_with_iterations(CONTENT, "passphrase", 1000),
+            keystore
+        );
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_passphrase() {
+        let keystore = encrypt_with_iterations(CONTENT, "passphrase", 1000);
+        match decrypt(&keystore, "wrong") {
+            Err(Error::InvalidKeystore(_)) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_decrypt_resource() {
+        // Encrypted with the passphrase "passphrase" by another implementation.
+        let keystore =
+            std::fs::read_to_string("tests/resources/federations_encrypted.toml").unwrap();
+        let content = std::fs::read_to_string("tests/resources/federations.toml").unwrap();
+        assert_eq!(*decrypt(&keystore, "passphrase").unwrap(), content);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let keystore = encrypt_with_iterations(CONTENT, "passphrase", 1000)
+            .replace("aes-256-gcm", "chacha20-poly1305");
+        match decrypt(&keystore, "passphrase") {
+            Err(Error::InvalidKeystore("unsupported keystore format")) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_passphrase_from_env() {
+        std::env::set_var("TAPYRUS_SIGNER_TEST_PASSPHRASE", "secret");
+        assert_eq!(
+            *Passphrase::Env("TAPYRUS_SIGNER_TEST_PASSPHRASE".to_string())
+                .read()
+                .unwrap(),
+            "secret"
+        );
+        match Passphrase::Env("TAPYRUS_SIGNER_TEST_NO_PASSPHRASE".to_string()).read() {
+            Err(Error::InvalidPassphrase(_)) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_passphrase_from_fd() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let line = b"secret\nignored\n";
+        unsafe { libc::write(fds[1], line.as_ptr() as *const libc::c_void, line.len()) };
+        unsafe { libc::close(fds[1]) };
+        assert_eq!(*Passphrase::Fd(fds[0]).read().unwrap(), "secret");
+    }
+}
@@ -24,9 +24,13 @@ pub mod command_args;
 pub mod crypto;
 pub mod errors;
 pub mod federation;
+pub mod http_client;
 pub mod key;
+pub mod keygen;
+pub mod keystore;
 pub mod net;
 pub mod rpc;
+pub mod rpc_pool;
 pub mod serialize;
 pub mod sign;
 pub mod signer_node;
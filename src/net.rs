@@ -4,11 +4,15 @@
 
 use crate::blockdata::hash::SHA256Hash;
 use crate::blockdata::Block;
+use crate::crypto::frost::NonceCommitments;
+use crate::crypto::vss::{Vss, ZeroVss};
 use crate::errors;
 use crate::serialize::{ByteBufVisitor, HexStrVisitor};
-use bitcoin::PublicKey;
+use crate::signer_node::attestation::Attestation;
+use bitcoin::{PrivateKey, PublicKey};
 use redis::{Client, Commands, ControlFlow, PubSubCommands, RedisError};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::sync::mpsc::{channel, Receiver, Sender};
@@ -21,7 +25,7 @@ use std::thread::JoinHandle;
 use std::time::Duration;
 
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::FE;
+use curv::{FE, GE};
 use serde::export::fmt::Error;
 use serde::export::Formatter;
 use std::collections::HashSet;
@@ -97,13 +101,64 @@ impl<'de> Deserialize<'de> for SignerID {
 }
 
 /// Messages which are sent to and received from other signer nodes
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum MessageType {
     Candidateblock(Block),
     Completedblock(Block),
     Blockvss(SHA256Hash, VerifiableSS, FE, VerifiableSS, FE),
     Blockparticipants(SHA256Hash, HashSet<SignerID>),
     Blocksig(SHA256Hash, FE, FE),
+    /// Node VSS which is sent from the dealer to the receiver in Distributed Key Generation.
+    Nodevss(Vss),
+    /// Complaints against the dealers who sent invalid node VSS, with the digest of all
+    /// commitments the sender received. Empty complaints mean the sender accepts all node VSS.
+    Nodevsscomplaints(SHA256Hash, Vec<SignerID>),
+    /// Node VSS which the dealer reveals to everyone in response to a complaint.
+    Nodevssreveal(Vss),
+    /// VSS of zero which is sent from the dealer to the receiver in share refresh. Complaints
+    /// are sent with `Nodevsscomplaints`.
+    Refreshvss(ZeroVss),
+    /// VSS of zero which the dealer reveals to everyone in response to a complaint.
+    Refreshvssreveal(ZeroVss),
+    /// Request to repair the node VSS of the sender in the federation at the block height, with
+    /// the helpers, a random nonce, the unix time of the request and the signature by the node
+    /// private key of the sender.
+    Repairrequest(u64, Vec<SignerID>, u64, u64, Signature),
+    /// Additive pieces of the repair contributions for each dealer, which are sent between
+    /// helpers. The hash identifies the repair request.
+    Repairpiece(SHA256Hash, Vec<(SignerID, FE, FE)>),
+    /// Node VSS which a helper sends to the signer who is repaired. The secrets are the sums of
+    /// the pieces the helper received, so they are valid only when summed over all helpers. The
+    /// points of the pieces the helper dealt to each helper let the receiver verify them.
+    Repairshare(SHA256Hash, Vec<Vss>, Vec<(SignerID, Vec<(GE, GE)>)>),
+    /// Additive piece of the node secret share of an old holder, which is sent to a member of
+    /// the new federation in resharing. The commitments of the piece are the points of all pieces
+    /// of the holder. It also has the commitments of the node VSS of the current federation for
+    /// each dealer.
+    Resharepiece(Vss, Vec<(SignerID, Vec<GE>)>),
+    /// Nonce commitments of the sender for the future rounds of FROST signing, which are
+    /// broadcast ahead of the rounds. Each list replaces the previous one from the sender.
+    Blockcommitments(Vec<(GE, GE)>),
+    /// Nonce commitments of the participants for FROST signing, which are selected and broadcast
+    /// by the master.
+    Blocknonces(SHA256Hash, NonceCommitments),
+    /// Signature share of the participant for FROST signing with the nonce commitments it used,
+    /// which is sent to the master.
+    Blocksigshare(SHA256Hash, (GE, GE), FE),
+    /// Request of the coordinator to sign the attestation with the threshold key.
+    Attestationrequest(Attestation),
+    /// VSS of the ephemeral key for the attestation, which is sent to each signer like
+    /// `Blockvss`. The hash is the message to sign.
+    Attestationvss(SHA256Hash, VerifiableSS, FE, VerifiableSS, FE),
+    /// Participants of the attestation signing, which are selected and broadcast by the
+    /// coordinator.
+    Attestationparticipants(SHA256Hash, HashSet<SignerID>),
+    /// Local signature of the participant for the attestation, which is sent to the coordinator.
+    Attestationsig(SHA256Hash, FE, FE),
+    /// Candidate block which the member received from the master, in the envelope signed by the
+    /// master. The members relay it to each other to detect the master sending different
+    /// candidate blocks to them.
+    Candidaterelay(Envelope),
 }
 
 impl Display for MessageType {
@@ -114,18 +169,35 @@ impl Display for MessageType {
             MessageType::Blockvss(_, _, _, _, _) => write!(f, "Blockvss"),
             MessageType::Blockparticipants(_, _) => write!(f, "Blockparticipants"),
             MessageType::Blocksig(_, _, _) => write!(f, "Blocksig"),
+            MessageType::Nodevss(_) => write!(f, "Nodevss"),
+            MessageType::Nodevsscomplaints(_, _) => write!(f, "Nodevsscomplaints"),
+            MessageType::Nodevssreveal(_) => write!(f, "Nodevssreveal"),
+            MessageType::Refreshvss(_) => write!(f, "Refreshvss"),
+            MessageType::Refreshvssreveal(_) => write!(f, "Refreshvssreveal"),
+            MessageType::Repairrequest(_, _, _, _, _) => write!(f, "Repairrequest"),
+            MessageType::Repairpiece(_, _) => write!(f, "Repairpiece"),
+            MessageType::Repairshare(_, _, _) => write!(f, "Repairshare"),
+            MessageType::Resharepiece(_, _) => write!(f, "Resharepiece"),
+            MessageType::Blockcommitments(_) => write!(f, "Blockcommitments"),
+            MessageType::Blocknonces(_, _) => write!(f, "Blocknonces"),
+            MessageType::Blocksigshare(_, _, _) => write!(f, "Blocksigshare"),
+            MessageType::Attestationrequest(_) => write!(f, "Attestationrequest"),
+            MessageType::Attestationvss(_, _, _, _, _) => write!(f, "Attestationvss"),
+            MessageType::Attestationparticipants(_, _) => write!(f, "Attestationparticipants"),
+            MessageType::Attestationsig(_, _, _) => write!(f, "Attestationsig"),
+            MessageType::Candidaterelay(_) => write!(f, "Candidaterelay"),
         }
     }
 }
 
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Message {
     pub message_type: MessageType,
     pub sender_id: SignerID,
     pub receiver_id: Option<SignerID>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Signature(pub secp256k1::Signature);
 
 impl Serialize for Signature {
@@ -151,13 +223,78 @@ impl<'de> Deserialize<'de> for Signature {
     }
 }
 
+/// The message as it is sent over the signer network. The sender signs the message with its
+/// private key if the key is given to the connection manager.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The message serialized in JSON. The signature is over these bytes as they are, because the
+    /// JSON serialization of the message is not canonical.
+    pub payload: String,
+    pub signature: Option<Signature>,
+}
+
+impl Envelope {
+    pub fn new(message: &Message, private_key: Option<&PrivateKey>) -> Self {
+        let payload = serde_json::to_string(message).expect("message should be serialized");
+        let signature = private_key.map(|key| {
+            let secp = secp256k1::Secp256k1::signing_only();
+            Signature(secp.sign(&Self::digest(&payload), &key.key))
+        });
+        Envelope { payload, signature }
+    }
+
+    /// Parses the data on the wire. The message of the signer who doesn't sign the messages is
+    /// sent as it is.
+    pub fn from_wire(data: &str) -> Result<Self, errors::Error> {
+        if let Ok(envelope) = serde_json::from_str::<Envelope>(data) {
+            return Ok(envelope);
+        }
+        serde_json::from_str::<Message>(data)?;
+        Ok(Envelope {
+            payload: data.to_string(),
+            signature: None,
+        })
+    }
+
+    pub fn to_wire(&self) -> String {
+        match self.signature {
+            Some(_) => serde_json::to_string(self).expect("envelope should be serialized"),
+            None => self.payload.clone(),
+        }
+    }
+
+    pub fn message(&self) -> Result<Message, errors::Error> {
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+
+    /// Returns the message if it is signed by the sender.
+    pub fn verify(&self) -> Result<Message, errors::Error> {
+        let message = self.message()?;
+        let signature = self.signature.as_ref().ok_or(errors::Error::InvalidSig)?;
+        let secp = secp256k1::Secp256k1::verification_only();
+        secp.verify(
+            &Self::digest(&self.payload),
+            &signature.0,
+            &message.sender_id.pubkey.key,
+        )
+        .map_err(|_| errors::Error::InvalidSig)?;
+        Ok(message)
+    }
+
+    fn digest(payload: &str) -> secp256k1::Message {
+        let mut hasher = Sha256::new();
+        hasher.input(payload.as_bytes());
+        secp256k1::Message::from_slice(&hasher.result()[..]).expect("hash should be 32 bytes")
+    }
+}
+
 pub trait ConnectionManager {
     type ERROR: std::error::Error;
     fn broadcast_message(&self, message: Message);
     fn send_message(&self, message: Message);
     fn start(
         &self,
-        message_processor: impl FnMut(Message) -> ControlFlow<()> + Send + 'static,
+        message_processor: impl FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
         id: SignerID,
     ) -> JoinHandle<()>;
     fn error_handler(&mut self) -> Option<Receiver<ConnectionManagerError<Self::ERROR>>>;
@@ -201,6 +338,9 @@ pub struct RedisManager {
     pub client: Arc<Client>,
     error_sender: Sender<ConnectionManagerError<RedisError>>,
     pub error_receiver: Option<Receiver<ConnectionManagerError<RedisError>>>,
+    /// The private key to sign the messages. The messages are sent without the signature if it is
+    /// None.
+    private_key: Option<PrivateKey>,
 }
 
 impl RedisManager {
@@ -215,9 +355,15 @@ impl RedisManager {
             client,
             error_sender: s,
             error_receiver: Some(r),
+            private_key: None,
         }
     }
 
+    pub fn with_private_key(mut self, private_key: Option<PrivateKey>) -> Self {
+        self.private_key = private_key;
+        self
+    }
+
     pub fn test_connection(&self) -> Result<(), errors::Error> {
         match self.client.get_connection() {
             Ok(_) => Ok(()),
@@ -227,7 +373,7 @@ impl RedisManager {
 
     fn subscribe<F>(&self, message_processor: F, id: SignerID) -> thread::JoinHandle<()>
     where
-        F: FnMut(Message) -> ControlFlow<()> + Send + 'static,
+        F: FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
     {
         let client = Arc::clone(&self.client);
         let error_sender = self.error_sender.clone();
@@ -241,7 +387,7 @@ impl RedisManager {
                     channel_name: &str,
                 ) -> Result<(), ConnectionManagerError<RedisError>>
                 where
-                    F2: FnMut(Message) -> ControlFlow<()> + Send + 'static,
+                    F2: FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
                 {
                     let mut conn = client.get_connection()?;
                     conn.subscribe(&["tapyrus-signer", channel_name], |msg| {
@@ -249,8 +395,19 @@ impl RedisManager {
                         let payload: String = msg.get_payload().unwrap();
                         log::trace!("receive message. payload: {}", payload);
 
-                        let message: Message = serde_json::from_str(&payload).unwrap();
-                        message_processor(message)
+                        let envelope = match Envelope::from_wire(&payload) {
+                            Ok(envelope) => envelope,
+                            Err(e) => {
+                                log::warn!("Invalid message is received. {:?}", e);
+                                return ControlFlow::Continue;
+                            }
+                        };
+                        // The message which has a signature of other than the sender is forged.
+                        if envelope.signature.is_some() && envelope.verify().is_err() {
+                            log::warn!("Message with invalid signature is received.");
+                            return ControlFlow::Continue;
+                        }
+                        message_processor(envelope)
                     })?;
                     Ok(())
                 }
@@ -267,7 +424,7 @@ impl RedisManager {
     fn process_message(&self, message: Message, to: String) {
         let client = Arc::clone(&self.client);
         let error_sender = self.error_sender.clone();
-        let message_in_thread = serde_json::to_string(&message).unwrap();
+        let message_in_thread = Envelope::new(&message, self.private_key.as_ref()).to_wire();
 
         thread::Builder::new()
             .name("RedisBroadcastThread".to_string())
@@ -330,7 +487,7 @@ impl ConnectionManager for RedisManager {
 
     fn start(
         &self,
-        message_processor: impl FnMut(Message) -> ControlFlow<()> + Send + 'static,
+        message_processor: impl FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
         id: SignerID,
     ) -> JoinHandle<()> {
         self.subscribe(message_processor, id)
@@ -388,7 +545,8 @@ mod test {
             pubkey: TEST_KEYS.pubkeys()[4],
         };
 
-        let message_processor = move |message: Message| {
+        let message_processor = move |envelope: Envelope| {
+            let message = envelope.message().unwrap();
             let block = get_block(0);
             assert_eq!(message.message_type, MessageType::Candidateblock(block));
             ControlFlow::Break(())
@@ -428,6 +586,30 @@ mod test {
         assert_eq!(expected, signer_id);
     }
 
+    #[test]
+    fn test_envelope() {
+        let message = Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            receiver_id: None,
+        };
+
+        // The message without the signature is sent as it is.
+        let envelope = Envelope::new(&message, None);
+        assert_eq!(envelope.to_wire(), serde_json::to_string(&message).unwrap());
+        let received = Envelope::from_wire(&envelope.to_wire()).unwrap();
+        assert_eq!(received.message().unwrap(), message);
+        assert!(received.verify().is_err());
+
+        let envelope = Envelope::new(&message, Some(&TEST_KEYS.key[0]));
+        let received = Envelope::from_wire(&envelope.to_wire()).unwrap();
+        assert_eq!(received.verify().unwrap(), message);
+
+        // The message signed by other than the sender is invalid.
+        let forged = Envelope::new(&message, Some(&TEST_KEYS.key[1]));
+        assert!(forged.verify().is_err());
+    }
+
     #[test]
     fn test_sort_signer_id() {
         let alice = SignerID::new(
@@ -7,8 +7,19 @@ use log::Level::Trace;
 use log::{log_enabled, trace};
 use serde::Deserialize;
 
-use crate::blockdata::Block;
+use crate::blockdata::{Block, BlockHeader};
 use crate::errors::Error;
+use crate::http_client::{HttpClient, HttpError};
+use bitcoin::consensus::encode::deserialize;
+use http::Uri;
+use native_tls::Certificate;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct GetBlockchainInfoResult {
@@ -20,8 +31,63 @@ pub struct GetBlockchainInfoResult {
     pub initialblockdownload: bool,
 }
 
+/// How the client authenticates itself to Tapyrus Core.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcAuth {
+    None,
+    UserPass(String, Option<String>),
+    /// The `.cookie` file which tapyrus-core writes in its data directory. It is read on every
+    /// request because tapyrus-core renews the cookie when it restarts.
+    CookieFile(PathBuf),
+}
+
+impl RpcAuth {
+    /// Returns the value of the Authorization header.
+    fn header(&self) -> Result<Option<String>, Error> {
+        let credentials = match self {
+            RpcAuth::None => return Ok(None),
+            RpcAuth::UserPass(user, pass) => {
+                format!(
+                    "{}:{}",
+                    user,
+                    pass.as_ref().map(|s| s as &str).unwrap_or("")
+                )
+            }
+            RpcAuth::CookieFile(path) => std::fs::read_to_string(path)
+                .map_err(|e| {
+                    Error::RpcConnection(format!("can not read the cookie file {:?}. {}", path, e))
+                })?
+                .trim()
+                .to_string(),
+        };
+        Ok(Some(format!("Basic {}", base64::encode(&credentials))))
+    }
+}
+
+/// The idempotent calls are retried if Tapyrus Core can not be reached. The other errors, such as
+/// the authentication failure, are not retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub interval: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
 pub struct Rpc {
-    client: jsonrpc::client::Client,
+    client: HttpClient,
+    url: String,
+    auth: RpcAuth,
+    retry_policy: RetryPolicy,
+    /// The id of the next request.
+    next_id: AtomicUsize,
 }
 
 pub trait TapyrusApi {
@@ -33,47 +99,148 @@ pub trait TapyrusApi {
     fn submitblock(&self, block: &Block) -> Result<(), Error>;
     /// Get block chain info
     fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error>;
+    /// Get the hash of the block at the height in the best chain
+    fn getblockhash(&self, height: u64) -> Result<String, Error>;
+    /// Get the header of the block which has the hash
+    fn getblockheader(&self, hash: &str) -> Result<BlockHeader, Error>;
 }
 
 impl Rpc {
+    /// The url is `http://<host>:<port>` or `https://<host>:<port>`.
     pub fn new(url: String, user: Option<String>, pass: Option<String>) -> Self {
         // Check that if we have a password, we have a username; other way around is ok
         debug_assert!(pass.is_none() || user.is_some());
+        let auth = match user {
+            Some(user) => RpcAuth::UserPass(user, pass),
+            None => RpcAuth::None,
+        };
         Rpc {
-            client: jsonrpc::client::Client::new(url, user, pass),
+            client: HttpClient::new(
+                Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+                Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS),
+            ),
+            url,
+            auth,
+            retry_policy: RetryPolicy::default(),
+            next_id: AtomicUsize::new(1),
         }
     }
 
-    fn call<T>(&self, name: &str, params: &[serde_json::Value]) -> Result<T, Error>
+    /// Authenticates with the cookie file instead of the user name and the password.
+    pub fn with_cookie_file(mut self, path: PathBuf) -> Self {
+        self.auth = RpcAuth::CookieFile(path);
+        self
+    }
+
+    /// Trusts the CA certificate in the PEM file to connect over `https`.
+    pub fn with_ca_file(mut self, path: &Path) -> Result<Self, Error> {
+        let pem = std::fs::read(path)?;
+        let certificate = Certificate::from_pem(&pem)
+            .map_err(|e| Error::RpcConnection(format!("invalid CA certificate. {}", e)))?;
+        self.client = self.client.with_ca_certificate(certificate);
+        Ok(self)
+    }
+
+    /// `read_timeout` is the deadline of the whole request including the connection, so that a
+    /// slow response can't hold the signer longer than it.
+    pub fn with_timeouts(mut self, connect_timeout: Duration, read_timeout: Duration) -> Self {
+        self.client = self.client.with_timeouts(connect_timeout, read_timeout);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Calls the RPC. The call is retried by the retry policy if it is idempotent and Tapyrus Core
+    /// can not be reached.
+    fn call<T>(
+        &self,
+        name: &str,
+        params: &[serde_json::Value],
+        idempotent: bool,
+    ) -> Result<T, Error>
     where
         T: serde::de::DeserializeOwned,
     {
-        let req = self.client.build_request(name, params);
-
-        trace!("JSON-RPC request: {}", serde_json::to_string(&req).unwrap());
-
-        match self.client.send_request(&req) {
-            Ok(resp) => {
-                if log_enabled!(Trace) {
-                    trace!(
-                        "JSON-RPC response: {}: {}",
-                        name,
-                        serde_json::to_string(&resp).unwrap()
+        let req = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": name,
+            "params": params,
+            "id": self.next_id.fetch_add(1, Ordering::SeqCst),
+        });
+
+        trace!("JSON-RPC request: {}", req);
+
+        let body = serde_json::to_vec(&req)?;
+        let authorization = self.auth.header()?;
+        let max_retries = if idempotent {
+            self.retry_policy.max_retries
+        } else {
+            0
+        };
+        let mut retries = 0;
+        let raw_resp = loop {
+            match self.send(&body, &authorization) {
+                Ok(raw_resp) => break raw_resp,
+                Err(e) if e.is_transient() && retries < max_retries => {
+                    warn!(
+                        "RPC {} failed. retry after {:?}. {:?}",
+                        name, self.retry_policy.interval, e
                     );
+                    retries += 1;
+                    std::thread::sleep(self.retry_policy.interval);
                 }
+                Err(e) => return Err(Error::RpcConnection(e.to_string())),
+            }
+        };
+        let resp: jsonrpc::Response = serde_json::from_slice(&raw_resp)
+            .map_err(|e| Error::RpcConnection(format!("invalid JSON-RPC response. {}", e)))?;
+
+        if log_enabled!(Trace) {
+            trace!(
+                "JSON-RPC response: {}: {}",
+                name,
+                serde_json::to_string(&resp).unwrap()
+            );
+        }
 
-                if let Err(jsonrpc::Error::Rpc(e)) = resp.clone().check_error() {
-                    warn!("RPC Error: {:?}", e);
-                    return Err(Error::InvalidRequest(e));
-                }
+        if let Err(jsonrpc::Error::Rpc(e)) = resp.clone().check_error() {
+            warn!("RPC Error: {:?}", e);
+            return Err(Error::InvalidRequest(e));
+        }
 
-                match resp.result::<T>() {
-                    Ok(result) => Ok(result),
-                    Err(e) => Err(Error::JsonRpc(e)),
-                }
-            }
-            Err(e) => Err(Error::from(e)),
+        match resp.result::<T>() {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Error::JsonRpc(e)),
+        }
+    }
+
+    /// Posts the request body and returns the response body.
+    fn send(&self, body: &[u8], authorization: &Option<String>) -> Result<Vec<u8>, HttpError> {
+        let uri = self
+            .url
+            .parse::<Uri>()
+            .map_err(|e| HttpError::Invalid(format!("invalid url {}. {}", self.url, e)))?;
+        let headers: Vec<(&str, String)> = authorization
+            .iter()
+            .map(|authorization| ("Authorization", authorization.clone()))
+            .collect();
+        let response = self.client.post(&uri, &headers, body)?;
+
+        // Tapyrus Core responds the RPC errors with the status other than 200, so the body is
+        // returned if any.
+        if response.status == 401 {
+            return Err(HttpError::Invalid("unauthorized".to_string()));
+        }
+        if response.body.is_empty() {
+            return Err(HttpError::Invalid(format!(
+                "HTTP status is {}",
+                response.status
+            )));
         }
+        Ok(response.body)
     }
 
     pub fn test_connection(&self) -> Result<(), Error> {
@@ -88,12 +255,12 @@ impl TapyrusApi for Rpc {
     /// Call getnewblock rpc
     fn getnewblock(&self, address: &Address) -> Result<Block, Error> {
         let args = [address.to_string().into()];
-        let resp = self.call::<String>("getnewblock", &args);
+        let resp = self.call::<String>("getnewblock", &args, false);
 
         match resp {
             Ok(v) => {
-                let raw_block = hex::decode(v).expect("Decoding block hex failed");
-                Ok(Block::new(raw_block))
+                let raw_block = hex::decode(v).map_err(|_| Error::InvalidBlock)?;
+                Block::new(raw_block)
             }
             Err(e) => Err(e),
         }
@@ -101,23 +268,41 @@ impl TapyrusApi for Rpc {
 
     fn testproposedblock(&self, block: &Block) -> Result<bool, Error> {
         let blockhex = serde_json::Value::from(block.hex());
-        self.call::<bool>("testproposedblock", &[blockhex])
+        self.call::<bool>("testproposedblock", &[blockhex], true)
     }
 
     fn submitblock(&self, block: &Block) -> Result<(), Error> {
-        self.call::<()>("submitblock", &[block.hex().into()])
+        self.call::<()>("submitblock", &[block.hex().into()], false)
     }
 
     fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
-        self.call::<GetBlockchainInfoResult>("getblockchaininfo", &[])
+        self.call::<GetBlockchainInfoResult>("getblockchaininfo", &[], true)
+    }
+
+    fn getblockhash(&self, height: u64) -> Result<String, Error> {
+        self.call::<String>("getblockhash", &[height.into()], true)
+    }
+
+    fn getblockheader(&self, hash: &str) -> Result<BlockHeader, Error> {
+        // Get the serialized header with verbose = false.
+        let header_hex =
+            self.call::<String>("getblockheader", &[hash.into(), false.into()], true)?;
+        let raw_header = hex::decode(header_hex).map_err(|_| Error::InvalidBlock)?;
+        deserialize(&raw_header).map_err(|_| Error::InvalidBlock)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::blockdata::XField;
+    use crate::tests::helper::blocks::get_block;
     use crate::tests::helper::keys::TEST_KEYS;
     use secp256k1::Secp256k1;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Instant;
 
     pub fn get_rpc_client() -> Rpc {
         Rpc::new(
@@ -205,6 +390,20 @@ pub mod tests {
                 initialblockdownload: false,
             })
         }
+
+        fn getblockhash(&self, _height: u64) -> Result<String, Error> {
+            Ok("xxx".to_string())
+        }
+
+        /// Returns the header of the genesis block which has the aggregated public key of the
+        /// test federation.
+        fn getblockheader(&self, _hash: &str) -> Result<BlockHeader, Error> {
+            let block = self.result()?;
+            Ok(BlockHeader {
+                xfield: XField::AggregatePublicKey(TEST_KEYS.aggregated()),
+                ..block.header().clone()
+            })
+        }
     }
 
     /// TODO: use rpc mock. Now this test needs tapyrus node process.
@@ -227,4 +426,134 @@ pub mod tests {
 
         assert!(result.is_ok());
     }
+    const GETBLOCKCHAININFO_RESPONSE: &str = r#"{"result":{"chain":"prod","blocks":1,"headers":1,"bestblockhash":"xxx","mediantime":0,"initialblockdownload":false},"error":null,"id":1}"#;
+
+    /// Starts the stub of Tapyrus Core which responds to the requests in order with the status and
+    /// the body. The connection is closed without the response if the response is None. Returns
+    /// the url and the receiver of the request headers.
+    fn start_rpc_stub(
+        responses: Vec<Option<(&'static str, &'static str)>>,
+    ) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut head = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if line.to_ascii_lowercase().starts_with("content-length:") {
+                        content_length = line[15..].trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0u8; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let _ = sender.send(head);
+                if let Some((status, response)) = response {
+                    write!(
+                        &stream,
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        status,
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                }
+            }
+        });
+        (url, receiver)
+    }
+
+    fn no_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            interval: Duration::from_secs(0),
+        }
+    }
+
+    #[test]
+    fn test_cookie_file() {
+        let path = std::env::temp_dir().join("tapyrus_signer_test_rpc.cookie");
+        std::fs::write(&path, "__cookie__:secret\n").unwrap();
+        let (url, receiver) = start_rpc_stub(vec![Some(("200 OK", GETBLOCKCHAININFO_RESPONSE))]);
+        let rpc = Rpc::new(url, None, None).with_cookie_file(path);
+
+        assert_eq!(rpc.getblockchaininfo().unwrap().bestblockhash, "xxx");
+        let head = receiver.recv().unwrap();
+        assert!(head.contains(&format!(
+            "Authorization: Basic {}\r\n",
+            base64::encode("__cookie__:secret")
+        )));
+    }
+
+    #[test]
+    fn test_retry_idempotent_call() {
+        let (url, _receiver) =
+            start_rpc_stub(vec![None, Some(("200 OK", GETBLOCKCHAININFO_RESPONSE))]);
+        let rpc = Rpc::new(url, None, None).with_retry_policy(RetryPolicy {
+            max_retries: 1,
+            interval: Duration::from_secs(0),
+        });
+
+        assert!(rpc.getblockchaininfo().is_ok());
+    }
+
+    #[test]
+    fn test_no_retry_unauthorized() {
+        let (url, receiver) = start_rpc_stub(vec![
+            Some(("401 Unauthorized", "")),
+            Some(("200 OK", GETBLOCKCHAININFO_RESPONSE)),
+        ]);
+        let rpc = Rpc::new(url, None, None).with_retry_policy(RetryPolicy {
+            max_retries: 1,
+            interval: Duration::from_secs(0),
+        });
+
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcConnection(m)) => assert_eq!(m, "unauthorized"),
+            r => panic!("Should be RpcConnection error, but {:?}", r),
+        }
+        assert!(receiver.recv().is_ok());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_no_retry_non_idempotent_call() {
+        let (url, _receiver) = start_rpc_stub(vec![None]);
+        let rpc = Rpc::new(url, None, None);
+
+        match rpc.submitblock(&get_block(0)) {
+            Err(Error::RpcConnection(_)) => {}
+            r => panic!("Should be RpcConnection error, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        // The stub accepts the connection but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let _stream = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_secs(3));
+        });
+        let rpc = Rpc::new(url, None, None)
+            .with_timeouts(Duration::from_secs(1), Duration::from_secs(1))
+            .with_retry_policy(no_retry());
+
+        let started_at = Instant::now();
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcConnection(_)) => {}
+            r => panic!("Should be RpcConnection error, but {:?}", r),
+        }
+        assert!(started_at.elapsed() < Duration::from_secs(3));
+    }
 }
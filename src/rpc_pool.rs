@@ -0,0 +1,310 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! `TapyrusApi` over multiple Tapyrus Core nodes.
+//!
+//! The read calls are sent to the healthy nodes in turn. If a node can't be reached, it is marked
+//! unhealthy and the call fails over to the next node. An unhealthy node is tried again after
+//! `HEALTH_CHECK_INTERVAL` or when no healthy node is left. The errors which the node returns,
+//! like a rejected block, are not failed over.
+//!
+//! `submitblock` is sent to all healthy nodes so that the block is propagated even if some of the
+//! nodes are isolated from the Tapyrus network.
+
+use crate::blockdata::{Block, BlockHeader};
+use crate::errors::Error;
+use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
+use bitcoin::Address;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The interval to try an unhealthy node again.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+struct Endpoint<T: TapyrusApi> {
+    rpc: T,
+    /// The time when the node got unhealthy. None if the node is healthy.
+    unhealthy_since: Mutex<Option<Instant>>,
+}
+
+impl<T: TapyrusApi> Endpoint<T> {
+    /// Returns true if the node is healthy or it is time to try it again.
+    fn is_available(&self) -> bool {
+        match *self.unhealthy_since.lock().unwrap() {
+            None => true,
+            Some(since) => since.elapsed() >= HEALTH_CHECK_INTERVAL,
+        }
+    }
+
+    fn mark_healthy(&self) {
+        *self.unhealthy_since.lock().unwrap() = None;
+    }
+
+    fn mark_unhealthy(&self) {
+        *self.unhealthy_since.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Calls the RPC and updates the health of the node by the result.
+    fn call<R, F>(&self, index: usize, f: F) -> Result<R, Error>
+    where
+        F: Fn(&T) -> Result<R, Error>,
+    {
+        let result = f(&self.rpc);
+        match &result {
+            Err(e) if is_connection_error(e) => {
+                log::warn!(
+                    "Tapyrus Core node is unhealthy. endpoint index: {}, error: {:?}",
+                    index,
+                    e
+                );
+                self.mark_unhealthy();
+            }
+            _ => self.mark_healthy(),
+        }
+        result
+    }
+}
+
+/// Returns true if the error means that the node couldn't be reached or didn't respond properly,
+/// not that the node returned an error.
+fn is_connection_error(e: &Error) -> bool {
+    match e {
+        Error::JsonRpc(jsonrpc::error::Error::Rpc(_)) => false,
+        Error::JsonRpc(_) => true,
+        Error::RpcConnection(_) => true,
+        _ => false,
+    }
+}
+
+pub struct RpcPool<T: TapyrusApi> {
+    endpoints: Vec<Endpoint<T>>,
+    /// The index of the node to which the next read call is sent first.
+    next: AtomicUsize,
+}
+
+impl<T: TapyrusApi> RpcPool<T> {
+    pub fn new(rpcs: Vec<T>) -> Self {
+        assert!(!rpcs.is_empty(), "RpcPool needs one or more endpoints.");
+        RpcPool {
+            endpoints: rpcs
+                .into_iter()
+                .map(|rpc| Endpoint {
+                    rpc,
+                    unhealthy_since: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Checks all nodes with `getblockchaininfo` and returns the number of the healthy nodes.
+    pub fn check_health(&self) -> usize {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .filter(|(i, endpoint)| endpoint.call(*i, |rpc| rpc.getblockchaininfo()).is_ok())
+            .count()
+    }
+
+    /// Returns the nodes in the order to try. The available nodes come first in round-robin order
+    /// and the other unhealthy nodes come last.
+    fn ordered_endpoints(&self) -> Vec<(usize, &Endpoint<T>)> {
+        let len = self.endpoints.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let (available, unavailable): (Vec<_>, Vec<_>) = (0..len)
+            .map(|i| (start + i) % len)
+            .map(|i| (i, &self.endpoints[i]))
+            .partition(|(_, endpoint)| endpoint.is_available());
+        available.into_iter().chain(unavailable).collect()
+    }
+
+    /// Sends the read call to the nodes in order until a node responds.
+    fn read<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        F: Fn(&T) -> Result<R, Error>,
+    {
+        let mut last_error = None;
+        for (i, endpoint) in self.ordered_endpoints() {
+            match endpoint.call(i, &f) {
+                Err(e) if is_connection_error(&e) => last_error = Some(e),
+                result => return result,
+            }
+        }
+        Err(last_error.expect("RpcPool has one or more endpoints"))
+    }
+}
+
+impl<T: TapyrusApi> TapyrusApi for RpcPool<T> {
+    fn getnewblock(&self, address: &Address) -> Result<Block, Error> {
+        self.read(|rpc| rpc.getnewblock(address))
+    }
+
+    fn testproposedblock(&self, block: &Block) -> Result<bool, Error> {
+        self.read(|rpc| rpc.testproposedblock(block))
+    }
+
+    /// Submits the block to all healthy nodes, or to all nodes if no node is healthy. Succeeds if
+    /// any node accepts the block.
+    fn submitblock(&self, block: &Block) -> Result<(), Error> {
+        let mut endpoints: Vec<_> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, endpoint)| endpoint.is_available())
+            .collect();
+        if endpoints.is_empty() {
+            endpoints = self.endpoints.iter().enumerate().collect();
+        }
+
+        let mut accepted = false;
+        let mut rejected = None;
+        let mut unreachable = None;
+        for (i, endpoint) in endpoints {
+            match endpoint.call(i, |rpc| rpc.submitblock(block)) {
+                Ok(()) => accepted = true,
+                Err(e) if is_connection_error(&e) => unreachable = Some(e),
+                Err(e) => rejected = Some(e),
+            }
+        }
+        if accepted {
+            return Ok(());
+        }
+        Err(rejected
+            .or(unreachable)
+            .expect("RpcPool has one or more endpoints"))
+    }
+
+    fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
+        self.read(|rpc| rpc.getblockchaininfo())
+    }
+
+    fn getblockhash(&self, height: u64) -> Result<String, Error> {
+        self.read(|rpc| rpc.getblockhash(height))
+    }
+
+    fn getblockheader(&self, hash: &str) -> Result<BlockHeader, Error> {
+        self.read(|rpc| rpc.getblockheader(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RpcPool;
+    use crate::errors::Error;
+    use crate::rpc::TapyrusApi;
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::rpc::MockRpc;
+
+    fn connection_error() -> Error {
+        Error::JsonRpc(jsonrpc::error::Error::Json(
+            serde_json::from_str::<u64>("").unwrap_err(),
+        ))
+    }
+
+    fn rpc_error() -> Error {
+        Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+            code: -25,
+            message: "proposal was not based on our best chain".to_string(),
+            data: None,
+        }))
+    }
+
+    fn assert_all(pool: &RpcPool<MockRpc>) {
+        for endpoint in &pool.endpoints {
+            endpoint.rpc.assert();
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_getblockhash(Ok("hash0".to_string()));
+        let mut rpc1 = MockRpc::new();
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        let pool = RpcPool::new(vec![rpc0, rpc1]);
+
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash0");
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+        assert_all(&pool);
+    }
+
+    #[test]
+    fn test_failover() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_getblockhash(Err(connection_error()));
+        let mut rpc1 = MockRpc::new();
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        let pool = RpcPool::new(vec![rpc0, rpc1]);
+
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+        // The unhealthy node is skipped even in its turn.
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+        assert_all(&pool);
+    }
+
+    #[test]
+    fn test_all_nodes_unreachable() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_getblockhash(Err(connection_error()));
+        let mut rpc1 = MockRpc::new();
+        rpc1.should_call_getblockhash(Err(connection_error()));
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        let pool = RpcPool::new(vec![rpc0, rpc1]);
+
+        assert!(pool.getblockhash(1).is_err());
+        // The unhealthy nodes are tried if no healthy node is left.
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+        assert_all(&pool);
+    }
+
+    #[test]
+    fn test_rpc_error_is_not_failed_over() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_testproposedblock(Err(rpc_error()));
+        let rpc1 = MockRpc::new();
+        let pool = RpcPool::new(vec![rpc0, rpc1]);
+
+        match pool.testproposedblock(&get_block(0)) {
+            Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(_))) => {}
+            r => panic!("Should be the rpc error, but {:?}", r),
+        }
+        assert_all(&pool);
+    }
+
+    #[test]
+    fn test_submitblock() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_getblockhash(Err(connection_error()));
+        let mut rpc1 = MockRpc::new();
+        rpc1.should_call_getblockhash(Ok("hash1".to_string()));
+        rpc1.should_call_submitblock(Err(rpc_error()));
+        let mut rpc2 = MockRpc::new();
+        rpc2.should_call_submitblock(Ok(()));
+        let pool = RpcPool::new(vec![rpc0, rpc1, rpc2]);
+        assert_eq!(pool.getblockhash(1).unwrap(), "hash1");
+
+        // The block is submitted to all healthy nodes and accepted by one of them.
+        assert!(pool.submitblock(&get_block(0)).is_ok());
+        assert_all(&pool);
+    }
+
+    #[test]
+    fn test_submitblock_rejected() {
+        let mut rpc0 = MockRpc::new();
+        rpc0.should_call_submitblock(Err(connection_error()));
+        let mut rpc1 = MockRpc::new();
+        rpc1.should_call_submitblock(Err(rpc_error()));
+        let pool = RpcPool::new(vec![rpc0, rpc1]);
+
+        match pool.submitblock(&get_block(0)) {
+            Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(_))) => {}
+            r => panic!("Should be the rpc error, but {:?}", r),
+        }
+        assert_all(&pool);
+    }
+}
@@ -9,11 +9,14 @@ use curv::elliptic::curves::traits::*;
 use curv::{BigInt, FE, GE};
 
 use crate::blockdata::hash::SHA256Hash;
+use crate::blockdata::Block;
+use crate::crypto::backend::{validate_vss_share, DefaultBackend};
 use crate::errors::Error;
 use crate::signer_node::SharedSecretMap;
 use crate::signer_node::ToShares;
 use crate::signer_node::ToVerifiableSS;
 use crate::util::*;
+use bitcoin::PublicKey;
 use secp256k1::rand::thread_rng;
 
 pub struct Sign;
@@ -37,7 +40,7 @@ impl Sign {
         let y = &ECPoint::generator() * &u;
 
         Keys {
-            u_i: u,
+            u_i: u.into(),
             y_i: y,
             party_index: index.clone(),
         }
@@ -51,8 +54,7 @@ impl Sign {
     ) -> Result<SharedKeys, Error> {
         let correct_ss = secret_shares
             .values()
-            .map(|v| v.vss.validate_share(&v.secret_share, *index))
-            .all(|result| result.is_ok());
+            .all(|v| validate_vss_share::<DefaultBackend>(&v.vss, &v.secret_share, *index));
         let y_vec: Vec<GE> = secret_shares
             .to_vss()
             .iter()
@@ -98,6 +100,55 @@ impl Sign {
         let s_as_str = s_as_int.to_str_radix(16);
         format!("{:x}{:0>64}{:0>64}", 64, v_as_str, s_as_str)
     }
+
+    /// Parses the 64 bytes signature `r || s` in the proof of the block header, which is the
+    /// inverse of `format_signature` without the length prefix. `v` is the point whose x
+    /// coordinate is `r` and whose y coordinate has jacobi symbol 1, as the signers choose.
+    pub fn parse_signature(bytes: &[u8]) -> Result<Signature, Error> {
+        if bytes.len() != 64 {
+            return Err(Error::InvalidLength(64, bytes.len()));
+        }
+        let p = BigInt::from_str_radix(
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F",
+            16,
+        )
+        .unwrap();
+        let mut compressed = [0u8; 33];
+        compressed[1..].copy_from_slice(&bytes[..32]);
+        let mut v = None;
+        for prefix in &[2u8, 3u8] {
+            compressed[0] = *prefix;
+            let point = secp256k1::PublicKey::from_slice(&compressed)
+                .map_err(|_| Error::InvalidSig)
+                .and_then(|key| Self::to_point(&key))?;
+            if jacobi(&point.y_coor().unwrap(), &p) == 1 {
+                v = Some(point);
+            }
+        }
+        let v = v.ok_or(Error::InvalidSig)?;
+
+        // s must be in [1, q - 1], because the scalar can not be zero.
+        let s = BigInt::from(&bytes[32..]);
+        if s.is_zero() || s >= FE::q() {
+            return Err(Error::InvalidSig);
+        }
+        Ok(Signature {
+            sigma: ECScalar::from(&s),
+            v,
+        })
+    }
+
+    /// Verifies the proof of the block with the aggregated public key of the federation which
+    /// signed the block.
+    pub fn verify_block(block: &Block, aggregated_public_key: &PublicKey) -> Result<(), Error> {
+        let signature = Self::parse_signature(&block.header().proof)?;
+        let y = Self::to_point(&aggregated_public_key.key)?;
+        signature.verify(&block.sighash().into_inner(), &y)
+    }
+
+    fn to_point(key: &secp256k1::PublicKey) -> Result<GE, Error> {
+        GE::from_bytes(&key.serialize_uncompressed()[1..]).map_err(|_| Error::InvalidKey)
+    }
 }
 
 #[test]
@@ -128,7 +179,7 @@ fn test_create_key() {
     .unwrap();
     let key = Sign::create_key(0, Some(pk.clone()));
     assert_eq!(key.party_index, 0);
-    assert_eq!(key.u_i, ECScalar::from(&pk));
+    assert_eq!(*key.u_i, ECScalar::from(&pk));
     let x = BigInt::from_str(
         "59785365775367791548524849652375710528443431367690667459926784930515989662882",
     )
@@ -173,3 +224,49 @@ fn test_format_signature() {
     };
     assert_eq!(Sign::format_signature(&sig_0), "40c726149bfb2d4ab64823e0cfd8245645a7950e605ef9222735d821ae570b1e910000000000000000000000000000000000000000000000000000000000000001");
 }
+
+#[test]
+fn test_parse_signature() {
+    let proof = "c726149bfb2d4ab64823e0cfd8245645a7950e605ef9222735d821ae570b1e91f2b3080d94faf40969c08b663ff1556fe7fbbcfcb648ac2763c16a15a08676f3";
+    let sig = Sign::parse_signature(&hex::decode(proof).unwrap()).unwrap();
+    assert_eq!(Sign::format_signature(&sig), format!("40{}", proof));
+
+    match Sign::parse_signature(&hex::decode(&proof[..126]).unwrap()) {
+        Err(Error::InvalidLength(64, 63)) => {}
+        r => panic!(
+            "it should fail, but {:?}",
+            r.map(|s| Sign::format_signature(&s))
+        ),
+    }
+
+    // r is not the x coordinate of any point.
+    let not_on_curve = format!("{:0>64}{}", 0, &proof[64..]);
+    assert!(Sign::parse_signature(&hex::decode(not_on_curve).unwrap()).is_err());
+
+    // s is not less than the order of the curve.
+    let overflow = format!("{}{}", &proof[..64], "f".repeat(64));
+    assert!(Sign::parse_signature(&hex::decode(overflow).unwrap()).is_err());
+
+    // s is zero.
+    let zero = format!("{}{:0>64}", &proof[..64], 0);
+    match Sign::parse_signature(&hex::decode(zero).unwrap()) {
+        Err(Error::InvalidSig) => {}
+        r => panic!(
+            "it should fail, but {:?}",
+            r.map(|s| Sign::format_signature(&s))
+        ),
+    }
+}
+
+#[test]
+fn test_verify_block() {
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    // The block signed by the key of TEST_KEYS.key[0].
+    let block = Block::new(hex::decode("010000000000000000000000000000000000000000000000000000000000000000000000c1457ff3e5c527e69858108edf0ff1f49eea9c58d8d37300a164b3b4f8c8c7cef1a2e72770d547feae29f2dd40123a97c580d44fd4493de072416d53331997617b96f05d00403855a6ab34d740d8933b95a6ae020edbfd52b23e97ad9f1ca9f46169a473c6088ac16db0d07800341518586917e8933f2799ad032167d6d0c9ebfb9f7e406a2301010000000100000000000000000000000000000000000000000000000000000000000000000000000022210366262690cbdf648132ce0c088962c6361112582364ede120f3780ab73438fc4bffffffff0100f2052a010000002776a9226d70757956774d32596a454d755a4b72687463526b614a787062715447417346484688ac00000000").unwrap()).unwrap();
+    assert!(Sign::verify_block(&block, &TEST_KEYS.pubkeys()[0]).is_ok());
+    match Sign::verify_block(&block, &TEST_KEYS.pubkeys()[1]) {
+        Err(Error::InvalidSig) => {}
+        r => panic!("it should fail, but {:?}", r),
+    }
+}
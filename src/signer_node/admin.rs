@@ -0,0 +1,227 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Admin interface of the signer node.
+//!
+//! The operator talks to tapyrus-signerd over a Unix domain socket. The protocol is a request and
+//! a response per connection. Both are a line of JSON:
+//!
+//! ```text
+//! request:  {"method": "peer_scores"}
+//! response: {"PeerScores": [{"signer_id": ..., "score": 50.0, ...}]} or {"Error": "reason"}
+//! ```
+//!
+//! The response to `sign_attestation` is written when the federation completes the signature,
+//! so it takes up to 30 seconds.
+//!
+//! `AdminServer` passes the requests to the main loop of the node as `AdminCall`s, because the
+//! main loop owns the state of the node, and writes back the responses. The socket is created to
+//! be accessible only by the owner.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::errors::Error;
+use crate::signer_node::attestation::AttestationSignature;
+use crate::signer_node::peer_scores::PeerStatus;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Timeout to read the request and to write the response.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Timeout to wait for the main loop to process the request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum AdminRequest {
+    /// The misbehavior scores of the peers.
+    PeerScores,
+    /// Signs the attestation with the threshold key of the federation, coordinating the other
+    /// signers.
+    SignAttestation { domain: String, digest: SHA256Hash },
+    /// Allows this signer to sign the attestation which another signer coordinates.
+    ApproveAttestation { domain: String, digest: SHA256Hash },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AdminResponse {
+    PeerScores(Vec<PeerStatus>),
+    Attestation(AttestationSignature),
+    /// The message of the approved attestation.
+    Approved(SHA256Hash),
+    Error(String),
+}
+
+/// The request passed to the main loop with the channel to send back the response.
+pub struct AdminCall {
+    pub request: AdminRequest,
+    responder: Sender<AdminResponse>,
+}
+
+impl AdminCall {
+    /// Returns the call and the receiver of its response.
+    pub fn new(request: AdminRequest) -> (Self, Receiver<AdminResponse>) {
+        let (responder, response) = channel();
+        (AdminCall { request, responder }, response)
+    }
+
+    pub fn respond(self, response: AdminResponse) {
+        // The connection may have been closed by the timeout.
+        let _ = self.responder.send(response);
+    }
+}
+
+pub struct AdminServer {
+    listener: UnixListener,
+}
+
+impl AdminServer {
+    /// Listens on the socket at the path. The stale socket left at the path is removed, but the
+    /// other kinds of files are not.
+    pub fn bind(path: &Path) -> Result<Self, Error> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(Error::AdminInterfaceError(format!(
+                    "{:?} already exists and is not a socket.",
+                    path
+                )));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(AdminServer { listener })
+    }
+
+    /// Accepts the connections in a new thread, and returns the receiver of the requests.
+    pub fn start(self) -> Receiver<AdminCall> {
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let sender = sender.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = handle(stream, sender) {
+                                log::warn!("Failed to handle the admin request. {:?}", e);
+                            }
+                        });
+                    }
+                    Err(e) => log::warn!("Failed to accept the admin connection. {:?}", e),
+                }
+            }
+        });
+        receiver
+    }
+}
+
+fn handle(stream: UnixStream, sender: Sender<AdminCall>) -> Result<(), Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new((&stream).take(64 * 1024)).read_line(&mut line)?;
+    let response = match serde_json::from_str::<AdminRequest>(&line) {
+        Ok(request) => {
+            let (call, response) = AdminCall::new(request);
+            sender
+                .send(call)
+                .map_err(|_| Error::AdminInterfaceError("the node is stopped.".to_string()))?;
+            response
+                .recv_timeout(RESPONSE_TIMEOUT)
+                .unwrap_or_else(|_| AdminResponse::Error("timeout".to_string()))
+        }
+        Err(e) => AdminResponse::Error(format!("invalid request. {}", e)),
+    };
+
+    let mut line = serde_json::to_vec(&response)?;
+    line.push(b'\n');
+    (&stream).write_all(&line)?;
+    Ok(())
+}
+
+/// Sends the request to the admin interface at the path, and returns the response.
+pub fn request(path: &Path, request: &AdminRequest) -> Result<AdminResponse, Error> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_server(name: &str) -> (std::path::PathBuf, Receiver<AdminCall>) {
+        let path = std::env::temp_dir().join(name);
+        let server = AdminServer::bind(&path).unwrap();
+        (path, server.start())
+    }
+
+    #[test]
+    fn test_request() {
+        let (path, calls) = start_server("tapyrus_signer_test_admin.sock");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The main loop of the node.
+        std::thread::spawn(move || {
+            let call = calls.recv().unwrap();
+            assert_eq!(call.request, AdminRequest::PeerScores);
+            call.respond(AdminResponse::PeerScores(vec![]));
+        });
+
+        let response = request(&path, &AdminRequest::PeerScores).unwrap();
+        assert_eq!(response, AdminResponse::PeerScores(vec![]));
+    }
+
+    #[test]
+    fn test_attestation_request_format() {
+        let request: AdminRequest = serde_json::from_str(
+            r#"{"method": "sign_attestation", "domain": "checkpoint", "digest": "0101010101010101010101010101010101010101010101010101010101010101"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            AdminRequest::SignAttestation {
+                domain: "checkpoint".to_string(),
+                digest: SHA256Hash::from_slice(&[1u8; 32]).unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let (path, _calls) = start_server("tapyrus_signer_test_admin_invalid.sock");
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"{\"method\": \"unknown\"}\n").unwrap();
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        match serde_json::from_str(&response).unwrap() {
+            AdminResponse::Error(reason) => assert!(reason.starts_with("invalid request.")),
+            r => panic!("it should fail, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_bind_not_socket() {
+        let path = std::env::temp_dir().join("tapyrus_signer_test_admin_file");
+        std::fs::write(&path, "").unwrap();
+        match AdminServer::bind(&path) {
+            Err(Error::AdminInterfaceError(_)) => {}
+            _ => panic!("it should fail"),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
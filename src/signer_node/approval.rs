@@ -0,0 +1,296 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! The hook which asks an external approval system whether the signer may sign the candidate
+//! block.
+//!
+//! The request is sent to the endpoint with the candidate block, its height and sighash:
+//!
+//! ```text
+//! {"block": "<hex>", "block_height": 100, "sighash": "<hex>"}
+//! ```
+//!
+//! and the endpoint responds with the result:
+//!
+//! ```text
+//! {"approved": true}
+//! {"approved": false, "reason": "..."}
+//! ```
+//!
+//! The endpoint is either a local HTTP endpoint like `http://127.0.0.1:3000/approve`, to which the
+//! request is posted, or a Unix domain socket like `unix:/path/to/approval.sock`, to which the
+//! request is written as a line and from which the response is read as a line.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::blockdata::Block;
+use crate::errors::Error;
+use crate::http_client::HttpClient;
+use http::Uri;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_APPROVAL_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalEndpoint {
+    Http(Uri),
+    Unix(PathBuf),
+}
+
+impl ApprovalEndpoint {
+    pub fn parse(endpoint: &str) -> Result<Self, Error> {
+        if endpoint.starts_with("unix:") {
+            return Ok(ApprovalEndpoint::Unix(PathBuf::from(&endpoint[5..])));
+        }
+        let uri = endpoint
+            .parse::<Uri>()
+            .map_err(|e| Error::ApprovalError(format!("invalid endpoint. {}", e)))?;
+        if uri.scheme_str() != Some("http") || uri.host().is_none() {
+            return Err(Error::ApprovalError(
+                "endpoint should be http://<host>:<port>/<path> or unix:<path>".to_string(),
+            ));
+        }
+        Ok(ApprovalEndpoint::Http(uri))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApprovalHook {
+    endpoint: ApprovalEndpoint,
+    timeout: Duration,
+    /// Sign the block even if the approval system doesn't respond in time or fails.
+    fail_open: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApprovalRequest {
+    block: Block,
+    block_height: u64,
+    sighash: SHA256Hash,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApprovalResponse {
+    approved: bool,
+    reason: Option<String>,
+}
+
+impl ApprovalHook {
+    pub fn new(endpoint: ApprovalEndpoint, timeout: Duration, fail_open: bool) -> Self {
+        ApprovalHook {
+            endpoint,
+            timeout,
+            fail_open,
+        }
+    }
+
+    /// Returns true if the signer may take part in the round of the block at the height.
+    pub fn approve(&self, block: &Block, block_height: u64) -> bool {
+        let request = ApprovalRequest {
+            block: block.clone(),
+            block_height,
+            sighash: block.sighash(),
+        };
+        match self.request(&request) {
+            Ok(ApprovalResponse { approved: true, .. }) => true,
+            Ok(ApprovalResponse {
+                approved: false,
+                reason,
+            }) => {
+                log::warn!(
+                    "The block is not approved. block height: {}, reason: {}",
+                    block_height,
+                    reason.unwrap_or_default()
+                );
+                false
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to get the approval of the block. block height: {}, fail-open: {}, {:?}",
+                    block_height,
+                    self.fail_open,
+                    e
+                );
+                self.fail_open
+            }
+        }
+    }
+
+    fn request(&self, request: &ApprovalRequest) -> Result<ApprovalResponse, Error> {
+        let body = serde_json::to_vec(request)?;
+        let response = match &self.endpoint {
+            ApprovalEndpoint::Http(uri) => self.post(uri, &body)?,
+            ApprovalEndpoint::Unix(path) => self.send_line(path, &body)?,
+        };
+        Ok(serde_json::from_slice(&response)?)
+    }
+
+    fn post(&self, uri: &Uri, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = HttpClient::new(self.timeout, self.timeout)
+            .post(uri, &[], body)
+            .map_err(|e| Error::ApprovalError(e.to_string()))?;
+        if response.status != 200 {
+            return Err(Error::ApprovalError(format!(
+                "HTTP status is {}",
+                response.status
+            )));
+        }
+        Ok(response.body)
+    }
+
+    fn send_line(&self, path: &Path, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.write_all(body)?;
+        stream.write_all(b"\n")?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(line.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::blocks::get_block;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc::{channel, Receiver};
+
+    /// Starts the stub HTTP server which responds once with the status and the body, and returns
+    /// the endpoint and the receiver of the request body.
+    fn start_http_stub(status: &'static str, body: &'static str) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/approve", listener.local_addr().unwrap());
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if line.to_ascii_lowercase().starts_with("content-length:") {
+                    content_length = line[15..].trim().parse().unwrap();
+                }
+            }
+            let mut request = vec![0u8; content_length];
+            reader.read_exact(&mut request).unwrap();
+            sender.send(String::from_utf8(request).unwrap()).unwrap();
+            write!(
+                &stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        (endpoint, receiver)
+    }
+
+    fn hook(endpoint: &str, fail_open: bool) -> ApprovalHook {
+        ApprovalHook::new(
+            ApprovalEndpoint::parse(endpoint).unwrap(),
+            Duration::from_secs(1),
+            fail_open,
+        )
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            ApprovalEndpoint::parse("unix:/tmp/approval.sock").unwrap(),
+            ApprovalEndpoint::Unix(PathBuf::from("/tmp/approval.sock"))
+        );
+        match ApprovalEndpoint::parse("http://127.0.0.1:3000/approve").unwrap() {
+            ApprovalEndpoint::Http(uri) => assert_eq!(uri.port_u16(), Some(3000)),
+            e => panic!("unexpected endpoint {:?}", e),
+        }
+        assert!(ApprovalEndpoint::parse("https://127.0.0.1:3000/approve").is_err());
+        assert!(ApprovalEndpoint::parse("/approve").is_err());
+    }
+
+    #[test]
+    fn test_approve_over_http() {
+        let (endpoint, receiver) = start_http_stub("200 OK", "{\"approved\": true}");
+        let block = get_block(0);
+        assert!(hook(&endpoint, false).approve(&block, 100));
+
+        let request: serde_json::Value = serde_json::from_str(&receiver.recv().unwrap()).unwrap();
+        assert_eq!(request["block"], block.hex());
+        assert_eq!(request["block_height"], 100);
+        assert_eq!(
+            request["sighash"],
+            serde_json::to_value(&block.sighash()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reject_over_http() {
+        let (endpoint, _receiver) = start_http_stub(
+            "200 OK",
+            "{\"approved\": false, \"reason\": \"not allowed\"}",
+        );
+        // Rejection is respected even if fail-open is set.
+        assert!(!hook(&endpoint, true).approve(&get_block(0), 100));
+    }
+
+    #[test]
+    fn test_http_error() {
+        let (endpoint, _receiver) = start_http_stub("500 Internal Server Error", "");
+        assert!(!hook(&endpoint, false).approve(&get_block(0), 100));
+
+        let (endpoint, _receiver) = start_http_stub("500 Internal Server Error", "");
+        assert!(hook(&endpoint, true).approve(&get_block(0), 100));
+    }
+
+    #[test]
+    fn test_timeout() {
+        // The stub accepts the connection but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/approve", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let _stream = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_secs(3));
+        });
+        assert!(!hook(&endpoint, false).approve(&get_block(0), 100));
+    }
+
+    #[test]
+    fn test_approve_over_unix_socket() {
+        let path = std::env::temp_dir().join("tapyrus_signer_test_approval.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            for response in &["{\"approved\": true}\n", "{\"approved\": false}\n"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+                assert_eq!(request["block_height"], 100);
+                (&stream).write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let endpoint = format!("unix:{}", path.to_str().unwrap());
+        assert!(hook(&endpoint, false).approve(&get_block(0), 100));
+        assert!(!hook(&endpoint, true).approve(&get_block(0), 100));
+    }
+
+    #[test]
+    fn test_unix_socket_not_found() {
+        let endpoint = "unix:/tmp/tapyrus_signer_test_approval_none.sock";
+        assert!(!hook(endpoint, false).approve(&get_block(0), 100));
+        assert!(hook(endpoint, true).approve(&get_block(0), 100));
+    }
+}
@@ -0,0 +1,771 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Threshold signatures of the federation on attestations.
+//!
+//! Besides blocks, the federation signs off-chain statements such as checkpoint attestations,
+//! federation change announcements and proof-of-reserve messages. The operator asks a node to
+//! sign an attestation via the admin interface, and the node coordinates the signing like the
+//! master of a round:
+//!
+//! 1. The coordinator broadcasts `Attestationrequest`.
+//! 2. The signers whose operators approved the attestation send `Attestationvss` to each other.
+//! 3. The coordinator selects the participants from the signers whose VSS it received, and
+//!    broadcasts `Attestationparticipants`.
+//! 4. The participants send `Attestationsig` to the coordinator, and the coordinator aggregates
+//!    the local signatures into the signature.
+//!
+//! The signed message is the tagged hash of the digest, so that the signature on an attestation
+//! is never valid for a block or for an attestation in another domain.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::backend::{validate_vss_share, DefaultBackend};
+use crate::crypto::secret::Secret;
+use crate::crypto::vss::Vss;
+use crate::errors::Error;
+use crate::net::{ConnectionManager, Message, MessageType, SignerID};
+use crate::rpc::TapyrusApi;
+use crate::sign::Sign;
+use crate::signer_node::admin::{AdminCall, AdminResponse};
+use crate::signer_node::peer_scores::Misbehavior;
+use crate::signer_node::{BidirectionalSharedSecretMap, NodeParameters, SharedSecret};
+use curv::{FE, GE};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Seconds to wait for the signers to complete the signature.
+const SESSION_TIMEOUT_SECS: u64 = 30;
+/// Seconds to keep the approval of the operator.
+const APPROVAL_TIMEOUT_SECS: u64 = 600;
+const MAX_DOMAIN_LENGTH: usize = 64;
+
+/// The statement which the federation signs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// The kind of the statement, such as "checkpoint" or "proof-of-reserve". It consists of
+    /// lowercase letters, digits and hyphens.
+    pub domain: String,
+    /// The digest of the statement.
+    pub digest: SHA256Hash,
+    /// The block height to select the federation which signs the attestation.
+    pub block_height: u64,
+}
+
+impl Attestation {
+    pub fn new(domain: &str, digest: SHA256Hash, block_height: u64) -> Result<Self, Error> {
+        let attestation = Attestation {
+            domain: domain.to_string(),
+            digest,
+            block_height,
+        };
+        attestation.validate()?;
+        Ok(attestation)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_domain(&self.domain)
+    }
+
+    /// The message which the federation signs.
+    pub fn message(&self) -> SHA256Hash {
+        tagged_hash(&self.domain, &self.digest)
+    }
+}
+
+fn validate_domain(domain: &str) -> Result<(), Error> {
+    if domain.is_empty() || domain.len() > MAX_DOMAIN_LENGTH {
+        return Err(Error::InvalidAttestation(
+            "domain should have 1 to 64 characters",
+        ));
+    }
+    if !domain
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(Error::InvalidAttestation(
+            "domain should consist of lowercase letters, digits and hyphens",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns SHA256(SHA256(tag) || SHA256(tag) || digest), where the tag is
+/// "tapyrus-signer/<domain>". It never collides with the hash of a block for signing, because the
+/// length of the hashed data differs.
+pub fn tagged_hash(domain: &str, digest: &SHA256Hash) -> SHA256Hash {
+    let tag = Sha256::digest(format!("tapyrus-signer/{}", domain).as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.input(&tag);
+    hasher.input(&tag);
+    hasher.input(digest.borrow_inner());
+    SHA256Hash::from_slice(&hasher.result()[..]).expect("hash should be 32 bytes")
+}
+
+/// The signature on the attestation, which is returned to the operator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttestationSignature {
+    pub domain: String,
+    pub digest: SHA256Hash,
+    /// The tagged hash which the signature is for.
+    pub message: SHA256Hash,
+    /// The public key to verify the signature, as hex.
+    pub aggregated_public_key: String,
+    /// The 64 bytes Schnorr signature as hex.
+    pub signature: String,
+}
+
+struct Session {
+    attestation: Option<Attestation>,
+    coordinator: Option<SignerID>,
+    started_at: Instant,
+    /// The received VSSs which are not validated yet, because the request has not arrived.
+    pending: BidirectionalSharedSecretMap,
+    shared_block_secrets: BidirectionalSharedSecretMap,
+    participants: HashSet<SignerID>,
+    block_shared_keys: Option<(bool, Secret<FE>, GE)>,
+    signatures: BTreeMap<SignerID, (FE, FE)>,
+    /// The admin call which waits for the signature. Only the coordinator has it.
+    call: Option<AdminCall>,
+    is_done: bool,
+}
+
+impl Session {
+    fn new() -> Self {
+        Session {
+            attestation: None,
+            coordinator: None,
+            started_at: Instant::now(),
+            pending: BTreeMap::new(),
+            shared_block_secrets: BTreeMap::new(),
+            participants: HashSet::new(),
+            block_shared_keys: None,
+            signatures: BTreeMap::new(),
+            call: None,
+            is_done: false,
+        }
+    }
+
+    /// Sends the VSS of the ephemeral key to the other signers.
+    fn start<T, C>(
+        &mut self,
+        attestation: Attestation,
+        coordinator: SignerID,
+        params: &NodeParameters<T>,
+        conman: &C,
+    ) where
+        T: TapyrusApi,
+        C: ConnectionManager,
+    {
+        let message = attestation.message();
+        let block_height = attestation.block_height;
+        let sharing_params = params.sharing_params(block_height);
+        let self_node_index = params.self_node_index(block_height);
+        let (
+            _,
+            vss_scheme_for_positive,
+            secret_shares_for_positive,
+            vss_scheme_for_negative,
+            secret_shares_for_negative,
+        ) = Vss::create_block_shares(
+            self_node_index + 1,
+            sharing_params.threshold + 1,
+            sharing_params.share_count,
+        );
+
+        for (i, pubkey) in params.pubkey_list(block_height).into_iter().enumerate() {
+            if i == self_node_index {
+                continue;
+            }
+            conman.send_message(Message {
+                message_type: MessageType::Attestationvss(
+                    message,
+                    vss_scheme_for_positive.clone(),
+                    secret_shares_for_positive[i],
+                    vss_scheme_for_negative.clone(),
+                    secret_shares_for_negative[i],
+                ),
+                sender_id: params.signer_id,
+                receiver_id: Some(SignerID { pubkey }),
+            });
+        }
+
+        self.shared_block_secrets.insert(
+            params.signer_id,
+            (
+                SharedSecret {
+                    vss: vss_scheme_for_positive,
+                    secret_share: secret_shares_for_positive[self_node_index].into(),
+                },
+                SharedSecret {
+                    vss: vss_scheme_for_negative,
+                    secret_share: secret_shares_for_negative[self_node_index].into(),
+                },
+            ),
+        );
+        self.attestation = Some(attestation);
+        self.coordinator = Some(coordinator);
+        self.started_at = Instant::now();
+    }
+
+    fn is_coordinator(&self, signer_id: &SignerID) -> bool {
+        self.coordinator.as_ref() == Some(signer_id)
+    }
+
+    /// Moves the signing forward as far as the received messages allow.
+    fn progress<T, C>(&mut self, params: &NodeParameters<T>, conman: &C)
+    where
+        T: TapyrusApi,
+        C: ConnectionManager,
+    {
+        let attestation = match &self.attestation {
+            Some(attestation) => attestation.clone(),
+            None => return,
+        };
+        let message = attestation.message();
+        let block_height = attestation.block_height;
+        let index = params.self_node_index(block_height) + 1;
+        let threshold = params.threshold(block_height) as usize;
+        let is_coordinator = self.is_coordinator(&params.signer_id);
+
+        // The secret shares for this signer must match with the commitments of the VSSs.
+        let is_valid = |secret: &SharedSecret| {
+            validate_vss_share::<DefaultBackend>(&secret.vss, &secret.secret_share, index)
+        };
+        for (sender_id, (for_positive, for_negative)) in
+            std::mem::replace(&mut self.pending, BTreeMap::new())
+        {
+            if is_valid(&for_positive) && is_valid(&for_negative) {
+                self.shared_block_secrets
+                    .insert(sender_id, (for_positive, for_negative));
+            } else {
+                log::warn!("Invalid attestation VSS. sender: {}", sender_id);
+                params
+                    .peer_scores
+                    .report(&sender_id, Misbehavior::InvalidBlockVss);
+            }
+        }
+
+        // The coordinator selects itself and the first signers whose VSS it received.
+        if is_coordinator
+            && self.participants.is_empty()
+            && self.shared_block_secrets.len() >= threshold
+        {
+            let mut participants: HashSet<SignerID> = self
+                .shared_block_secrets
+                .keys()
+                .filter(|signer_id| **signer_id != params.signer_id)
+                .take(threshold - 1)
+                .cloned()
+                .collect();
+            participants.insert(params.signer_id);
+            conman.broadcast_message(Message {
+                message_type: MessageType::Attestationparticipants(message, participants.clone()),
+                sender_id: params.signer_id,
+                receiver_id: None,
+            });
+            self.participants = participants;
+        }
+
+        let shared_block_secrets_by_participants: BidirectionalSharedSecretMap = self
+            .shared_block_secrets
+            .clone()
+            .into_iter()
+            .filter(|(i, ..)| self.participants.contains(i))
+            .collect();
+
+        // Each participant signs once it has the VSSs of all participants.
+        if self.participants.contains(&params.signer_id)
+            && self.block_shared_keys.is_none()
+            && shared_block_secrets_by_participants.len() == self.participants.len()
+        {
+            let local_sig = match Vss::create_local_sig_from_shares(
+                &params
+                    .get_federation_by_block_height(block_height)
+                    .node_secret_share(),
+                index,
+                &shared_block_secrets_by_participants,
+                message,
+            ) {
+                Ok((is_positive, shared_keys, local_sig)) => {
+                    self.block_shared_keys = Some((is_positive, shared_keys.x_i, shared_keys.y));
+                    local_sig
+                }
+                Err(e) => {
+                    log::error!("Failed to sign the attestation. {:?}", e);
+                    return;
+                }
+            };
+            if is_coordinator {
+                self.signatures
+                    .insert(params.signer_id, (local_sig.gamma_i, local_sig.e));
+            } else {
+                conman.send_message(Message {
+                    message_type: MessageType::Attestationsig(
+                        message,
+                        local_sig.gamma_i,
+                        local_sig.e,
+                    ),
+                    sender_id: params.signer_id,
+                    receiver_id: self.coordinator,
+                });
+            }
+        }
+
+        if !is_coordinator || self.is_done || self.signatures.len() < threshold {
+            return;
+        }
+
+        self.is_done = true;
+        let federation = params.get_federation_by_block_height(block_height);
+        let response = match Vss::aggregate_and_verify_signature(
+            message,
+            self.signatures.clone(),
+            &params.pubkey_list(block_height),
+            &federation.node_shared_secrets(),
+            &self.block_shared_keys,
+            &shared_block_secrets_by_participants,
+            &federation.node_secret_share(),
+        ) {
+            Ok(signature) => {
+                log::info!(
+                    "The attestation is signed. domain: {}, message: {:?}",
+                    attestation.domain,
+                    message
+                );
+                AdminResponse::Attestation(AttestationSignature {
+                    domain: attestation.domain,
+                    digest: attestation.digest,
+                    message,
+                    aggregated_public_key: federation.aggregated_public_key().to_string(),
+                    // Drop the length prefix of the proof in the block header.
+                    signature: Sign::format_signature(&signature)[2..].to_string(),
+                })
+            }
+            Err(e) => {
+                log::error!("The aggregated signature is invalid. {:?}", e);
+                for sender_id in Vss::invalid_local_sig_senders(
+                    &params.signer_id,
+                    &self.signatures,
+                    &params.pubkey_list(block_height),
+                    &federation.node_shared_secrets(),
+                    &self.block_shared_keys,
+                    &shared_block_secrets_by_participants,
+                ) {
+                    log::warn!("Invalid local signature. sender: {}", sender_id);
+                    params
+                        .peer_scores
+                        .report(&sender_id, Misbehavior::InvalidLocalSig);
+                }
+                AdminResponse::Error(format!("the aggregated signature is invalid. {:?}", e))
+            }
+        };
+        if let Some(call) = self.call.take() {
+            call.respond(response);
+        }
+    }
+}
+
+/// The signing sessions of the attestations and the approvals of the operator.
+pub struct Attestations {
+    approvals: HashMap<SHA256Hash, Instant>,
+    sessions: HashMap<SHA256Hash, Session>,
+}
+
+impl Attestations {
+    pub fn new() -> Self {
+        Attestations {
+            approvals: HashMap::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Allows this signer to take part in the signing of the attestation which another signer
+    /// coordinates. Returns the message to sign.
+    pub fn approve(&mut self, domain: &str, digest: SHA256Hash) -> Result<SHA256Hash, Error> {
+        validate_domain(domain)?;
+        let message = tagged_hash(domain, &digest);
+        log::info!(
+            "The attestation is approved. domain: {}, message: {:?}",
+            domain,
+            message
+        );
+        self.approvals.insert(message, Instant::now());
+        Ok(message)
+    }
+
+    fn is_approved(&self, message: &SHA256Hash) -> bool {
+        match self.approvals.get(message) {
+            Some(approved_at) => approved_at.elapsed() < Duration::from_secs(APPROVAL_TIMEOUT_SECS),
+            None => false,
+        }
+    }
+
+    /// Starts the signing of the attestation as the coordinator. The call is responded when the
+    /// signature is completed or the session expires.
+    pub fn sign<T, C>(
+        &mut self,
+        call: AdminCall,
+        domain: &str,
+        digest: SHA256Hash,
+        block_height: u64,
+        params: &NodeParameters<T>,
+        conman: &C,
+    ) where
+        T: TapyrusApi,
+        C: ConnectionManager,
+    {
+        if !params
+            .get_federation_by_block_height(block_height)
+            .has_secret_shares()
+        {
+            call.respond(AdminResponse::Error(
+                "the attestations are not supported with the share signer.".to_string(),
+            ));
+            return;
+        }
+        let attestation = match Attestation::new(domain, digest, block_height) {
+            Ok(attestation) => attestation,
+            Err(e) => {
+                call.respond(AdminResponse::Error(format!("{:?}", e)));
+                return;
+            }
+        };
+        let message = attestation.message();
+        let session = self.sessions.entry(message).or_insert_with(Session::new);
+        if session.attestation.is_some() {
+            call.respond(AdminResponse::Error(
+                "the attestation is being signed.".to_string(),
+            ));
+            return;
+        }
+
+        log::info!(
+            "Start to sign the attestation. domain: {}, message: {:?}",
+            domain,
+            message
+        );
+        conman.broadcast_message(Message {
+            message_type: MessageType::Attestationrequest(attestation.clone()),
+            sender_id: params.signer_id,
+            receiver_id: None,
+        });
+        session.call = Some(call);
+        session.start(attestation, params.signer_id, params, conman);
+        session.progress(params, conman);
+    }
+
+    /// Processes the messages of the attestations from the other signers. `block_height` is the
+    /// height of the tip, because only the current federation signs the attestations.
+    pub fn process_message<T, C>(
+        &mut self,
+        sender_id: &SignerID,
+        message: MessageType,
+        block_height: u64,
+        params: &NodeParameters<T>,
+        conman: &C,
+    ) where
+        T: TapyrusApi,
+        C: ConnectionManager,
+    {
+        // Ignore the message when the sender is myself.
+        if *sender_id == params.signer_id {
+            return;
+        }
+        // The attestations are signed with the node secret share, which is kept by the share
+        // signer if it is used.
+        if !params
+            .get_federation_by_block_height(block_height)
+            .has_secret_shares()
+        {
+            log::warn!(
+                "Ignore the attestation message from {}, because the attestations are not supported with the share signer.",
+                sender_id
+            );
+            return;
+        }
+        match message {
+            MessageType::Attestationrequest(attestation) => {
+                if let Err(e) = attestation.validate() {
+                    log::warn!("Invalid attestation request from {}. {:?}", sender_id, e);
+                    return;
+                }
+                let current = params.get_federation_by_block_height(block_height);
+                let federation = params.get_federation_by_block_height(attestation.block_height);
+                if federation.aggregated_public_key() != current.aggregated_public_key() {
+                    log::warn!(
+                        "The attestation request from {} is not for the current federation. block height: {}",
+                        sender_id,
+                        attestation.block_height
+                    );
+                    return;
+                }
+                let hash = attestation.message();
+                if !self.is_approved(&hash) {
+                    log::info!(
+                        "The attestation is not approved. coordinator: {}, domain: {}, digest: {:?}",
+                        sender_id,
+                        attestation.domain,
+                        attestation.digest
+                    );
+                    return;
+                }
+                let session = self.sessions.entry(hash).or_insert_with(Session::new);
+                if session.attestation.is_some() {
+                    return;
+                }
+                log::info!(
+                    "Start to sign the attestation. coordinator: {}, domain: {}, message: {:?}",
+                    sender_id,
+                    attestation.domain,
+                    hash
+                );
+                session.start(attestation, *sender_id, params, conman);
+                session.progress(params, conman);
+            }
+            MessageType::Attestationvss(
+                hash,
+                vss_for_positive,
+                secret_share_for_positive,
+                vss_for_negative,
+                secret_share_for_negative,
+            ) => {
+                // The VSS may arrive before the request, so the session is created to keep it.
+                let session = self.sessions.entry(hash).or_insert_with(Session::new);
+                if session.shared_block_secrets.contains_key(sender_id) {
+                    return;
+                }
+                session.pending.insert(
+                    *sender_id,
+                    (
+                        SharedSecret {
+                            vss: vss_for_positive,
+                            secret_share: secret_share_for_positive.into(),
+                        },
+                        SharedSecret {
+                            vss: vss_for_negative,
+                            secret_share: secret_share_for_negative.into(),
+                        },
+                    ),
+                );
+                session.progress(params, conman);
+            }
+            MessageType::Attestationparticipants(hash, participants) => {
+                if let Some(session) = self.sessions.get_mut(&hash) {
+                    if session.is_coordinator(sender_id) && session.participants.is_empty() {
+                        session.participants = participants;
+                        session.progress(params, conman);
+                    }
+                }
+            }
+            MessageType::Attestationsig(hash, gamma_i, e) => {
+                if let Some(session) = self.sessions.get_mut(&hash) {
+                    if session.is_coordinator(&params.signer_id)
+                        && session.participants.contains(sender_id)
+                    {
+                        session.signatures.insert(*sender_id, (gamma_i, e));
+                        session.progress(params, conman);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops the completed and the expired sessions and the approvals. The coordinator responds
+    /// to the call of the expired session with the error.
+    pub fn expire(&mut self) {
+        self.approvals.retain(|_, approved_at| {
+            approved_at.elapsed() < Duration::from_secs(APPROVAL_TIMEOUT_SECS)
+        });
+        let expired: Vec<SHA256Hash> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                session.is_done
+                    || session.started_at.elapsed() >= Duration::from_secs(SESSION_TIMEOUT_SECS)
+            })
+            .map(|(hash, _)| *hash)
+            .collect();
+        for hash in expired {
+            let session = self.sessions.remove(&hash).expect("session should exist");
+            if let Some(call) = session.call {
+                log::warn!(
+                    "Signing the attestation timed out. message: {:?}, signatures: {}",
+                    hash,
+                    session.signatures.len()
+                );
+                call.respond(AdminResponse::Error(format!(
+                    "timeout. {} local signatures are received.",
+                    session.signatures.len()
+                )));
+            }
+        }
+    }
+}
+
+impl Default for Attestations {
+    fn default() -> Self {
+        Attestations::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::federation::{Federation, Federations};
+    use crate::signer_node::admin::AdminRequest;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::net::TestConnectionManager;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::node_vss::node_vss;
+    use crate::tests::helper::rpc::MockRpc;
+    use curv::elliptic::curves::traits::ECPoint;
+
+    fn digest() -> SHA256Hash {
+        SHA256Hash::from_slice(&[1u8; 32]).unwrap()
+    }
+
+    /// Returns the parameters of the signer at the index of the federation whose threshold is 3.
+    fn params(index: usize) -> NodeParameters<MockRpc> {
+        NodeParametersBuilder::new()
+            .public_key(TEST_KEYS.pubkeys()[index])
+            .federations(Federations::new(vec![Federation::new(
+                TEST_KEYS.pubkeys()[index],
+                0,
+                Some(3),
+                node_vss(index),
+                TEST_KEYS.aggregated(),
+            )]))
+            .build()
+    }
+
+    #[test]
+    fn test_tagged_hash() {
+        let attestation = Attestation::new("checkpoint", digest(), 0).unwrap();
+        assert_eq!(
+            hex::encode(attestation.message().into_inner()),
+            "ef30fd863e6a839afdfed836c327cef3700205e55da438fa52a4881eec31f066"
+        );
+        assert_ne!(
+            attestation.message(),
+            tagged_hash("proof-of-reserve", &digest())
+        );
+    }
+
+    #[test]
+    fn test_invalid_domain() {
+        for domain in &["", "Checkpoint", "check point", "tapyrus/checkpoint"] {
+            match Attestation::new(domain, digest(), 0) {
+                Err(Error::InvalidAttestation(_)) => {}
+                r => panic!("{:?} should be invalid, but {:?}", domain, r),
+            }
+        }
+        assert!(Attestation::new(&"a".repeat(64), digest(), 0).is_ok());
+        assert!(Attestation::new(&"a".repeat(65), digest(), 0).is_err());
+    }
+
+    #[test]
+    fn test_request_without_approval() {
+        let params = NodeParametersBuilder::new().build();
+        let conman = TestConnectionManager::new();
+        let coordinator = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        let request =
+            MessageType::Attestationrequest(Attestation::new("checkpoint", digest(), 0).unwrap());
+
+        let mut attestations = Attestations::new();
+        attestations.process_message(&coordinator, request.clone(), 0, &params, &conman);
+        assert!(attestations.sessions.is_empty());
+
+        // The signer sends its VSS to the others after the approval.
+        attestations.approve("checkpoint", digest()).unwrap();
+        attestations.process_message(&coordinator, request, 0, &params, &conman);
+        assert_eq!(attestations.sessions.len(), 1);
+        assert_eq!(conman.sent.borrow().len(), params.pubkey_list(0).len() - 1);
+    }
+
+    #[test]
+    fn test_sign_with_signers() {
+        let params: Vec<NodeParameters<MockRpc>> = (0..5).map(params).collect();
+        let conmans: Vec<TestConnectionManager> =
+            (0..5).map(|_| TestConnectionManager::new()).collect();
+        let mut attestations: Vec<Attestations> = (0..5).map(|_| Attestations::new()).collect();
+
+        // The operator of the signer 4 doesn't approve the attestation.
+        for signer in &mut attestations[1..4] {
+            signer.approve("checkpoint", digest()).unwrap();
+        }
+        let (call, response) = AdminCall::new(AdminRequest::SignAttestation {
+            domain: "checkpoint".to_string(),
+            digest: digest(),
+        });
+        attestations[0].sign(call, "checkpoint", digest(), 0, &params[0], &conmans[0]);
+
+        // Deliver the messages to the receivers until no signer sends a message.
+        let mut participants = HashSet::new();
+        let mut signers = HashSet::new();
+        loop {
+            let mut messages = vec![];
+            for conman in &conmans {
+                messages.extend(conman.broadcasted.borrow_mut().drain(..));
+                messages.extend(conman.sent.borrow_mut().drain(..));
+            }
+            if messages.is_empty() {
+                break;
+            }
+            for message in messages {
+                match &message.message_type {
+                    MessageType::Attestationparticipants(_, selected) => {
+                        assert_eq!(message.sender_id, params[0].signer_id);
+                        participants = selected.clone();
+                    }
+                    MessageType::Attestationsig(..) => {
+                        assert_eq!(message.receiver_id, Some(params[0].signer_id));
+                        signers.insert(message.sender_id);
+                    }
+                    _ => {}
+                }
+                for i in 0..5 {
+                    if message
+                        .receiver_id
+                        .map_or(true, |receiver_id| receiver_id == params[i].signer_id)
+                    {
+                        attestations[i].process_message(
+                            &message.sender_id,
+                            message.message_type.clone(),
+                            0,
+                            &params[i],
+                            &conmans[i],
+                        );
+                    }
+                }
+            }
+        }
+
+        // The coordinator selects itself and the approved signers as many as the threshold.
+        assert_eq!(participants.len(), 3);
+        assert!(participants.contains(&params[0].signer_id));
+        assert!(!participants.contains(&params[4].signer_id));
+        // Only the participants other than the coordinator send the local signatures.
+        participants.remove(&params[0].signer_id);
+        assert_eq!(signers, participants);
+
+        let signature = match response.try_recv().unwrap() {
+            AdminResponse::Attestation(signature) => signature,
+            r => panic!("Should be signed, but {:?}", r),
+        };
+        let message = tagged_hash("checkpoint", &digest());
+        assert_eq!(signature.message, message);
+        assert_eq!(
+            signature.aggregated_public_key,
+            TEST_KEYS.aggregated().to_string()
+        );
+        let y = GE::from_bytes(&TEST_KEYS.aggregated().key.serialize_uncompressed()[1..]).unwrap();
+        let parsed = Sign::parse_signature(&hex::decode(&signature.signature).unwrap()).unwrap();
+        assert!(parsed.verify(&message.into_inner(), &y).is_ok());
+        // The signature on the attestation is not valid for the other domain.
+        let other = tagged_hash("proof-of-reserve", &digest());
+        assert!(parsed.verify(&other.into_inner(), &y).is_err());
+    }
+}
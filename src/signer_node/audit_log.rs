@@ -0,0 +1,303 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Audit log of the blocks which the signer signed.
+//!
+//! A record is appended when the signer creates its local signature or signature share for a
+//! block, when it submits a completed block, and when the completed block doesn't become the tip
+//! of the chain. The log is a file of JSON lines. Each record has
+//! the hash of the previous record and is signed with the private key of the signer, so that
+//! removing, reordering or modifying the records is detected by `verify`.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::errors::Error;
+use crate::net::SignerID;
+use bitcoin::{PrivateKey, PublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The signer created its local signature or signature share for the block.
+    LocalSig,
+    /// The signer submitted the block with the proof to Tapyrus Core.
+    CompletedBlock,
+    /// The block with the proof which the signer submitted didn't become the tip of the chain.
+    NotAccepted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub event: AuditEvent,
+    /// Unix time when the record was appended.
+    pub time: u64,
+    /// The height of the signed block.
+    pub block_height: u64,
+    pub sighash: SHA256Hash,
+    pub master: SignerID,
+    pub participants: Vec<SignerID>,
+    pub aggregated_public_key: PublicKey,
+    /// SHA256 of the local signature or signature share of the signer for `LocalSig`, and SHA256
+    /// of the proof for `CompletedBlock` and `NotAccepted`.
+    pub commitment: SHA256Hash,
+}
+
+impl AuditEntry {
+    pub fn new(
+        event: AuditEvent,
+        block_height: u64,
+        sighash: SHA256Hash,
+        master: SignerID,
+        participants: Vec<SignerID>,
+        aggregated_public_key: PublicKey,
+        commitment: &[u8],
+    ) -> Self {
+        let mut participants = participants;
+        participants.sort();
+        AuditEntry {
+            event,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            block_height,
+            sighash,
+            master,
+            participants,
+            aggregated_public_key,
+            commitment: sha256(&[commitment]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AuditRecord {
+    #[serde(flatten)]
+    entry: AuditEntry,
+    /// The hash of the previous record. All zero for the first record.
+    prev_hash: SHA256Hash,
+    /// DER encoded ECDSA signature in hex of the hash of this record.
+    signature: String,
+}
+
+impl AuditRecord {
+    fn hash(&self) -> Result<SHA256Hash, Error> {
+        record_hash(&self.entry, &self.prev_hash)
+    }
+}
+
+fn record_hash(entry: &AuditEntry, prev_hash: &SHA256Hash) -> Result<SHA256Hash, Error> {
+    let entry = serde_json::to_vec(entry)?;
+    Ok(sha256(&[&entry[..], &prev_hash.borrow_inner()[..]]))
+}
+
+fn sha256(inputs: &[&[u8]]) -> SHA256Hash {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.input(input);
+    }
+    SHA256Hash::from_slice(&hasher.result()[..]).expect("sha256 digest should be 32 bytes")
+}
+
+fn to_secp_message(hash: &SHA256Hash) -> secp256k1::Message {
+    secp256k1::Message::from_slice(hash.borrow_inner()).expect("hash should be 32 bytes")
+}
+
+fn zero_hash() -> SHA256Hash {
+    SHA256Hash::from_slice(&[0u8; 32]).expect("should be 32 bytes")
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    private_key: PrivateKey,
+    /// The hash of the last record in the file.
+    last_hash: Mutex<SHA256Hash>,
+}
+
+impl AuditLog {
+    /// Opens the log file. The records are appended following the last record if the file exists.
+    pub fn open(path: PathBuf, private_key: PrivateKey) -> Result<Self, Error> {
+        let last_hash = if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+            match lines.last() {
+                Some(line) => {
+                    let record: AuditRecord = serde_json::from_str(line)
+                        .map_err(|_| Error::InvalidAuditLog(lines.len(), "invalid record"))?;
+                    record.hash()?
+                }
+                None => zero_hash(),
+            }
+        } else {
+            zero_hash()
+        };
+        Ok(AuditLog {
+            path,
+            private_key,
+            last_hash: Mutex::new(last_hash),
+        })
+    }
+
+    /// Returns the public key to verify the records.
+    pub fn public_key(&self) -> PublicKey {
+        let secp = secp256k1::Secp256k1::signing_only();
+        PublicKey::from_private_key(&secp, &self.private_key)
+    }
+
+    pub fn append(&self, entry: AuditEntry) -> Result<(), Error> {
+        let mut last_hash = self.last_hash.lock().unwrap();
+        let hash = record_hash(&entry, &last_hash)?;
+        let secp = secp256k1::Secp256k1::signing_only();
+        let signature = secp.sign(&to_secp_message(&hash), &self.private_key.key);
+        let record = AuditRecord {
+            entry,
+            prev_hash: *last_hash,
+            signature: hex::encode(&signature.serialize_der()[..]),
+        };
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o600)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n", serde_json::to_string(&record)?).as_bytes())?;
+        file.sync_all()?;
+        *last_hash = hash;
+        Ok(())
+    }
+}
+
+/// Verifies that the records in the log file are chained and signed by the public key. Returns
+/// the number of the records.
+pub fn verify(path: &Path, public_key: &PublicKey) -> Result<usize, Error> {
+    let secp = secp256k1::Secp256k1::verification_only();
+    let contents = std::fs::read_to_string(path)?;
+    let mut prev_hash = zero_hash();
+    let mut count = 0;
+    for (i, line) in contents.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let line_number = i + 1;
+        let record: AuditRecord = serde_json::from_str(line)
+            .map_err(|_| Error::InvalidAuditLog(line_number, "invalid record"))?;
+        if record.prev_hash != prev_hash {
+            return Err(Error::InvalidAuditLog(
+                line_number,
+                "the previous hash doesn't match",
+            ));
+        }
+        let hash = record.hash()?;
+        let signature = hex::decode(&record.signature)
+            .ok()
+            .and_then(|der| secp256k1::Signature::from_der(&der).ok())
+            .ok_or(Error::InvalidAuditLog(line_number, "invalid signature"))?;
+        if secp
+            .verify(&to_secp_message(&hash), &signature, &public_key.key)
+            .is_err()
+        {
+            return Err(Error::InvalidAuditLog(line_number, "invalid signature"));
+        }
+        prev_hash = hash;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "tapyrus-signer-{}-{}.log",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn entry(event: AuditEvent, block_height: u64) -> AuditEntry {
+        AuditEntry::new(
+            event,
+            block_height,
+            get_block(0).sighash(),
+            SignerID::new(TEST_KEYS.pubkeys()[0]),
+            vec![
+                SignerID::new(TEST_KEYS.pubkeys()[2]),
+                SignerID::new(TEST_KEYS.pubkeys()[0]),
+                SignerID::new(TEST_KEYS.pubkeys()[1]),
+            ],
+            TEST_KEYS.aggregated(),
+            &[1u8; 32],
+        )
+    }
+
+    #[test]
+    fn test_append_and_verify() {
+        let path = temp_path("audit-log");
+        let log = AuditLog::open(path.clone(), TEST_KEYS.key[0]).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 1)).unwrap();
+        log.append(entry(AuditEvent::CompletedBlock, 1)).unwrap();
+        log.append(entry(AuditEvent::NotAccepted, 1)).unwrap();
+
+        assert_eq!(verify(&path, &TEST_KEYS.pubkeys()[0]).unwrap(), 3);
+        match verify(&path, &TEST_KEYS.pubkeys()[1]) {
+            Err(Error::InvalidAuditLog(1, "invalid signature")) => {}
+            r => panic!("Should be invalid signature, but {:?}", r),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = temp_path("audit-log-reopen");
+        let log = AuditLog::open(path.clone(), TEST_KEYS.key[0]).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 1)).unwrap();
+
+        // The records appended after the restart are chained to the records before it.
+        let log = AuditLog::open(path.clone(), TEST_KEYS.key[0]).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 2)).unwrap();
+        assert_eq!(verify(&path, &TEST_KEYS.pubkeys()[0]).unwrap(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_tampered() {
+        let path = temp_path("audit-log-tampered");
+        let log = AuditLog::open(path.clone(), TEST_KEYS.key[0]).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 1)).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 2)).unwrap();
+        log.append(entry(AuditEvent::LocalSig, 3)).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        // Modify the block height of the second record.
+        let modified = lines[1].replace("\"block_height\":2", "\"block_height\":4");
+        std::fs::write(&path, [lines[0], &modified, lines[2]].join("\n")).unwrap();
+        match verify(&path, &TEST_KEYS.pubkeys()[0]) {
+            Err(Error::InvalidAuditLog(2, "invalid signature")) => {}
+            r => panic!("Should be invalid signature, but {:?}", r),
+        }
+
+        // Remove the second record.
+        std::fs::write(&path, [lines[0], lines[2]].join("\n")).unwrap();
+        match verify(&path, &TEST_KEYS.pubkeys()[0]) {
+            Err(Error::InvalidAuditLog(2, "the previous hash doesn't match")) => {}
+            r => panic!("Should be the broken chain, but {:?}", r),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
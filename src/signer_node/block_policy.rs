@@ -0,0 +1,283 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! The policy which the members apply to the candidate blocks in addition to the consensus rules.
+//!
+//! A member doesn't take part in the round if the candidate block which the master proposes
+//! violates the policy. All rules are disabled by default.
+
+use crate::blockdata::Block;
+use crate::errors::Error;
+use bitcoin::{Script, Transaction};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockPolicy {
+    /// Upper limit of the block time minus the local time in seconds.
+    pub max_time_drift: Option<i64>,
+    /// Lower limit of the block time minus the local time in seconds. It is usually negative.
+    pub min_time_drift: Option<i64>,
+    /// The script which the coinbase transaction must pay to.
+    pub coinbase_script: Option<Script>,
+    /// Upper limit of the number of the transactions including the coinbase transaction.
+    pub max_tx_count: Option<usize>,
+    /// Upper limit of the size of the block in bytes.
+    pub max_block_size: Option<usize>,
+    /// The scripts which no transaction in the block may pay to.
+    pub denylisted_scripts: Vec<Script>,
+}
+
+impl BlockPolicy {
+    /// Returns `Error::PolicyViolation` with the reason if the block violates the policy. `now` is
+    /// the local time in UNIX time.
+    pub fn check(&self, block: &Block, now: u64) -> Result<(), Error> {
+        let drift = block.get_time() as i64 - now as i64;
+        if let Some(max) = self.max_time_drift {
+            if drift > max {
+                return Err(Error::PolicyViolation(format!(
+                    "block time is {} seconds ahead of the local time. max-time-drift: {}",
+                    drift, max
+                )));
+            }
+        }
+        if let Some(min) = self.min_time_drift {
+            if drift < min {
+                return Err(Error::PolicyViolation(format!(
+                    "block time is {} seconds behind the local time. min-time-drift: {}",
+                    -drift, min
+                )));
+            }
+        }
+
+        if let Some(max) = self.max_block_size {
+            let size = block.payload().len();
+            if size > max {
+                return Err(Error::PolicyViolation(format!(
+                    "block size {} exceeds max-block-size {}",
+                    size, max
+                )));
+            }
+        }
+
+        // Parse the transactions only if any rule needs them.
+        if self.coinbase_script.is_none()
+            && self.max_tx_count.is_none()
+            && self.denylisted_scripts.is_empty()
+        {
+            return Ok(());
+        }
+        let transactions = block
+            .get_transactions()
+            .map_err(|_| Error::PolicyViolation("transactions can not be parsed".to_string()))?;
+
+        if let Some(max) = self.max_tx_count {
+            if transactions.len() > max {
+                return Err(Error::PolicyViolation(format!(
+                    "transaction count {} exceeds max-tx-count {}",
+                    transactions.len(),
+                    max
+                )));
+            }
+        }
+
+        if let Some(script) = &self.coinbase_script {
+            match transactions.first() {
+                Some(coinbase) if coinbase.is_coin_base() && pays_to(coinbase, script) => {}
+                _ => {
+                    return Err(Error::PolicyViolation(format!(
+                        "coinbase transaction doesn't pay to coinbase-script {}",
+                        hex::encode(script.as_bytes())
+                    )));
+                }
+            }
+        }
+
+        for tx in &transactions {
+            if let Some(script) = self
+                .denylisted_scripts
+                .iter()
+                .find(|script| pays_to(tx, script))
+            {
+                return Err(Error::PolicyViolation(format!(
+                    "transaction {} pays to denylisted script {}",
+                    tx.txid(),
+                    hex::encode(script.as_bytes())
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn pays_to(tx: &Transaction, script: &Script) -> bool {
+    tx.output
+        .iter()
+        .any(|output| output.script_pubkey == *script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::{OutPoint, TxIn, TxOut};
+
+    const TIME: u32 = 1576048251;
+
+    fn script(hex: &str) -> Script {
+        Script::from(hex::decode(hex).unwrap())
+    }
+
+    fn tx(input: OutPoint, scripts: &[Script]) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: input,
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            output: scripts
+                .iter()
+                .map(|script| TxOut {
+                    value: 5_000_000_000,
+                    script_pubkey: script.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the block which has the coinbase transaction paying to `76a914...88ac` and a
+    /// transaction paying to `a914...87`.
+    fn block() -> Block {
+        let coinbase = tx(
+            OutPoint::null(),
+            &[script("76a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac")],
+        );
+        let spending = tx(
+            OutPoint {
+                txid: coinbase.txid(),
+                vout: 0,
+            },
+            &[script("a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b287")],
+        );
+        let mut header = vec![0u8; 105];
+        header[0] = 1;
+        header[100..104].copy_from_slice(&TIME.to_le_bytes());
+        let proof = serialize(&Vec::<u8>::new());
+        let transactions = serialize(&vec![coinbase, spending]);
+        Block::new([&header[..], &proof[..], &transactions[..]].concat()).unwrap()
+    }
+
+    fn assert_violation(policy: &BlockPolicy, now: u64, reason: &str) {
+        match policy.check(&block(), now) {
+            Err(Error::PolicyViolation(r)) => assert!(r.starts_with(reason), "{}", r),
+            r => panic!("it should violate the policy, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_default_policy() {
+        assert!(BlockPolicy::default().check(&block(), 0).is_ok());
+    }
+
+    #[test]
+    fn test_time_drift() {
+        let policy = BlockPolicy {
+            max_time_drift: Some(60),
+            min_time_drift: Some(-600),
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block(), TIME as u64 - 60).is_ok());
+        assert!(policy.check(&block(), TIME as u64 + 600).is_ok());
+        assert_violation(
+            &policy,
+            TIME as u64 - 61,
+            "block time is 61 seconds ahead of the local time.",
+        );
+        assert_violation(
+            &policy,
+            TIME as u64 + 601,
+            "block time is 601 seconds behind the local time.",
+        );
+    }
+
+    #[test]
+    fn test_coinbase_script() {
+        let policy = BlockPolicy {
+            coinbase_script: Some(script("76a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac")),
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block(), TIME as u64).is_ok());
+
+        // The output of the other transaction doesn't count.
+        let policy = BlockPolicy {
+            coinbase_script: Some(script("a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b287")),
+            ..BlockPolicy::default()
+        };
+        assert_violation(
+            &policy,
+            TIME as u64,
+            "coinbase transaction doesn't pay to coinbase-script",
+        );
+    }
+
+    #[test]
+    fn test_max_tx_count_and_block_size() {
+        let size = block().payload().len();
+        let policy = BlockPolicy {
+            max_tx_count: Some(2),
+            max_block_size: Some(size),
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block(), TIME as u64).is_ok());
+
+        let policy = BlockPolicy {
+            max_tx_count: Some(1),
+            ..BlockPolicy::default()
+        };
+        assert_violation(
+            &policy,
+            TIME as u64,
+            "transaction count 2 exceeds max-tx-count 1",
+        );
+
+        let policy = BlockPolicy {
+            max_block_size: Some(size - 1),
+            ..BlockPolicy::default()
+        };
+        assert_violation(&policy, TIME as u64, "block size");
+    }
+
+    #[test]
+    fn test_denylisted_scripts() {
+        let policy = BlockPolicy {
+            denylisted_scripts: vec![script("6a")],
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block(), TIME as u64).is_ok());
+
+        let policy = BlockPolicy {
+            denylisted_scripts: vec![
+                script("6a"),
+                script("a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b287"),
+            ],
+            ..BlockPolicy::default()
+        };
+        assert_violation(&policy, TIME as u64, "transaction");
+    }
+
+    #[test]
+    fn test_unparsable_transactions() {
+        let policy = BlockPolicy {
+            max_tx_count: Some(10),
+            ..BlockPolicy::default()
+        };
+        let block = Block::new(block().payload()[..120].to_vec()).unwrap();
+        match policy.check(&block, TIME as u64) {
+            Err(Error::PolicyViolation(r)) => assert_eq!(r, "transactions can not be parsed"),
+            r => panic!("it should violate the policy, but {:?}", r),
+        }
+    }
+}
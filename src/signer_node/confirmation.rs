@@ -0,0 +1,180 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! The confirmation that the signed block became the tip of the chain.
+//!
+//! `submitblock` succeeding doesn't mean that the block was connected to the chain, and failing
+//! doesn't mean that it wasn't, because the block might have been relayed already via the Tapyrus
+//! network. So after submitting the block, the signer polls the best block hash until the tip is
+//! the signed block. If the tip doesn't change in time, the block is submitted again.
+//!
+//! The block is submitted and the tip is polled via `NodeParameters::rpc`, which is the pool of
+//! the Tapyrus Core nodes in the signer. So the block is submitted to all healthy nodes, and the
+//! polling fails over to another node if a node can't be reached.
+//!
+//! The signer doesn't process the messages while it waits, so the wait is capped at half of the
+//! round duration. The tip is checked at least once after the block is submitted.
+
+use crate::blockdata::Block;
+use crate::errors::Error;
+use crate::rpc::TapyrusApi;
+use crate::signer_node::NodeParameters;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CONFIRMATION_TIMEOUT_SECS: u64 = 10;
+
+/// The interval to poll the best block hash.
+const POLLING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Submits the block and waits until it becomes the tip of the chain. Returns
+/// `Error::BlockNotAccepted` if the block doesn't become the tip in time.
+pub fn submit_and_confirm<T>(block: &Block, params: &NodeParameters<T>) -> Result<(), Error>
+where
+    T: TapyrusApi,
+{
+    let deadline = Instant::now() + Duration::from_secs(params.round_duration) / 2;
+    for attempt in 1.. {
+        if let Err(e) = params.rpc.submitblock(block) {
+            log::warn!(
+                "submitblock failed. attempt: {}, rpc error: {:?}",
+                attempt,
+                e
+            );
+        }
+
+        let timeout = std::cmp::min(
+            params.confirmation_timeout,
+            deadline.saturating_duration_since(Instant::now()),
+        );
+        if wait_for_tip(block, params.rpc.as_ref(), timeout) {
+            log::info!(
+                "The block became the tip of the chain. attempt: {}",
+                attempt
+            );
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    log::error!(
+        "Signed but not accepted. The block didn't become the tip of the chain. block: {:?}",
+        block
+    );
+    Err(Error::BlockNotAccepted)
+}
+
+/// Polls the best block hash until the tip is the block. Returns false if the timeout passes.
+/// The tip is checked at least once even if the timeout is zero.
+fn wait_for_tip<T>(block: &Block, rpc: &T, timeout: Duration) -> bool
+where
+    T: TapyrusApi,
+{
+    let started_at = Instant::now();
+    loop {
+        match is_tip(block, rpc) {
+            Ok(true) => return true,
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to get the tip of the chain. rpc error: {:?}", e),
+        }
+
+        if started_at.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(POLLING_INTERVAL);
+    }
+}
+
+fn is_tip<T>(block: &Block, rpc: &T) -> Result<bool, Error>
+where
+    T: TapyrusApi,
+{
+    let info = rpc.getblockchaininfo()?;
+    let tip = rpc.getblockheader(&info.bestblockhash)?;
+    Ok(tip == *block.header())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::submit_and_confirm;
+    use crate::errors::Error;
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::rpc::MockRpc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_submit_and_confirm() {
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&block);
+        let params = NodeParametersBuilder::new().rpc(rpc).build();
+
+        assert!(submit_and_confirm(&block, &params).is_ok());
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_submit_and_confirm_already_relayed() {
+        // The block is confirmed even if submitblock fails because it was relayed already.
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(
+            jsonrpc::error::RpcError {
+                code: -27,
+                message: "block already in chain".to_string(),
+                data: None,
+            },
+        ))));
+        rpc.should_return_tip(&block);
+        let params = NodeParametersBuilder::new().rpc(rpc).build();
+
+        assert!(submit_and_confirm(&block, &params).is_ok());
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_submit_and_confirm_again() {
+        // The block is submitted again if it doesn't become the tip in the confirmation timeout.
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&get_block(1));
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&block);
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .round_duration(2)
+            .build()
+            .with_confirmation_timeout(Duration::from_secs(0));
+
+        assert!(submit_and_confirm(&block, &params).is_ok());
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_submit_and_confirm_not_accepted() {
+        // The tip is checked only once, because the round duration is zero. The mock panics if
+        // it is checked again.
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&get_block(1));
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .round_duration(0)
+            .build()
+            .with_confirmation_timeout(Duration::from_secs(10));
+
+        let started_at = Instant::now();
+        match submit_and_confirm(&block, &params) {
+            Err(Error::BlockNotAccepted) => {}
+            r => panic!("Should be BlockNotAccepted, but {:?}", r),
+        }
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+        params.rpc.assert();
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Detection of the equivocation in the rounds for a block.
+//!
+//! The signer keeps the signed messages of the rounds for the current block, and detects that the
+//! master sends different candidate blocks to different members, or that a participant sends
+//! different signatures with the same nonce. The two messages are the evidence, which anyone can
+//! verify with only the public key of the offender, because they are signed by the offender.
+//!
+//! The messages are compared by their subjects, which are taken from the messages themselves and
+//! for which the honest signer signs only one message: the previous block of the candidate block,
+//! because the master proposes the same candidate block again in the later rounds on the same
+//! previous block, and the nonce of the signature, because a nonce is never used twice. The
+//! members relay the candidate block signed by the master to each other, so that the master
+//! sending different candidate blocks to different members is detected.
+
+use crate::blockdata::hash::SHA256Hash;
+use crate::errors::Error;
+use crate::net::{Envelope, MessageType, SignerID};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EquivocationKind {
+    /// Two candidate blocks on the same previous block.
+    ConflictingCandidates,
+    /// Two different local signatures or signature shares with the same nonce.
+    ConflictingSignatures,
+}
+
+/// Returns the kind of the equivocation and the subject of the message. The honest signer signs
+/// only one message for a subject.
+fn subject(message_type: &MessageType) -> Option<(EquivocationKind, [u8; 32])> {
+    match message_type {
+        MessageType::Candidateblock(block) => Some((
+            EquivocationKind::ConflictingCandidates,
+            block.header().prev_blockhash,
+        )),
+        // The challenge is determined by the nonce of the round, which is new in every round.
+        MessageType::Blocksig(hash, _, e) => {
+            let mut hasher = Sha256::new();
+            hasher.input(&hash.borrow_inner()[..]);
+            hasher.input(&e.get_element()[..]);
+            Some((EquivocationKind::ConflictingSignatures, digest(hasher)))
+        }
+        MessageType::Blocksigshare(_, (hiding, binding), _) => {
+            let mut hasher = Sha256::new();
+            hasher.input(&hiding.get_element().serialize()[..]);
+            hasher.input(&binding.get_element().serialize()[..]);
+            Some((EquivocationKind::ConflictingSignatures, digest(hasher)))
+        }
+        _ => None,
+    }
+}
+
+fn digest(hasher: Sha256) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    result.copy_from_slice(&hasher.result()[..]);
+    result
+}
+
+/// Returns true if the messages of the same sender conflict with each other.
+fn is_conflicting(a: &MessageType, b: &MessageType) -> bool {
+    match (a, b) {
+        (MessageType::Candidateblock(a), MessageType::Candidateblock(b)) => {
+            a.header().prev_blockhash == b.header().prev_blockhash && a.sighash() != b.sighash()
+        }
+        (
+            MessageType::Blocksig(hash_a, gamma_a, e_a),
+            MessageType::Blocksig(hash_b, gamma_b, e_b),
+        ) => hash_a == hash_b && e_a == e_b && gamma_a != gamma_b,
+        // A nonce used for two different blocks also leaks the node secret share.
+        (
+            MessageType::Blocksigshare(hash_a, commitment_a, a),
+            MessageType::Blocksigshare(hash_b, commitment_b, b),
+        ) => commitment_a == commitment_b && (hash_a != hash_b || a != b),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub kind: EquivocationKind,
+    /// The height of the block of the round when the equivocation was detected. This is for the
+    /// information and is not verified.
+    pub block_height: u64,
+    pub offender: SignerID,
+    /// The two conflicting messages signed by the offender.
+    pub messages: Vec<Envelope>,
+}
+
+impl Evidence {
+    /// Verifies that the messages are signed by the offender and conflict with each other.
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.messages.len() != 2 {
+            return Err(Error::InvalidEvidence("evidence should have two messages"));
+        }
+        let messages = self
+            .messages
+            .iter()
+            .map(|envelope| {
+                envelope
+                    .verify()
+                    .map_err(|_| Error::InvalidEvidence("message is not signed by the sender"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if messages.iter().any(|m| m.sender_id != self.offender) {
+            return Err(Error::InvalidEvidence(
+                "message is not sent by the offender",
+            ));
+        }
+        if messages
+            .iter()
+            .any(|m| subject(&m.message_type).map(|(kind, _)| kind) != Some(self.kind))
+        {
+            return Err(Error::InvalidEvidence("message is not of the kind"));
+        }
+        if !is_conflicting(&messages[0].message_type, &messages[1].message_type) {
+            return Err(Error::InvalidEvidence("messages don't conflict"));
+        }
+        Ok(())
+    }
+
+    /// Writes the evidence to the directory and returns the path of the file.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Error> {
+        let kind = match self.kind {
+            EquivocationKind::ConflictingCandidates => "candidates",
+            EquivocationKind::ConflictingSignatures => "signatures",
+        };
+        let path = dir.join(format!(
+            "equivocation-{}-{}-{}.json",
+            self.block_height, self.offender, kind
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Keeps the first signed message of each sender, kind and subject in the rounds for the current
+/// block.
+#[derive(Default)]
+pub struct EquivocationDetector {
+    block_height: u64,
+    first_messages: BTreeMap<(SignerID, EquivocationKind, [u8; 32]), Envelope>,
+    /// The offenders who have been reported for the current block.
+    reported: BTreeSet<(SignerID, EquivocationKind)>,
+}
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the messages if the round is for a new block. The messages of the previous rounds
+    /// for the same block are kept, because the honest signer doesn't sign the conflicting
+    /// messages in different rounds either.
+    pub fn start_round(&mut self, block_height: u64) {
+        if self.block_height != block_height {
+            self.block_height = block_height;
+            self.first_messages.clear();
+            self.reported.clear();
+        }
+    }
+
+    /// Returns the evidence if the message conflicts with the message which the sender signed
+    /// before for the same subject. The messages without the valid signature are ignored, because
+    /// they can't be the evidence.
+    pub fn observe(&mut self, envelope: &Envelope, block_height: u64) -> Option<Evidence> {
+        let message = envelope.verify().ok()?;
+        let (kind, subject) = subject(&message.message_type)?;
+
+        let first = match self.first_messages.get(&(message.sender_id, kind, subject)) {
+            Some(first) => first,
+            None => {
+                self.first_messages
+                    .insert((message.sender_id, kind, subject), envelope.clone());
+                return None;
+            }
+        };
+        if self.reported.contains(&(message.sender_id, kind)) {
+            return None;
+        }
+        let first_message = first.message().ok()?;
+        if !is_conflicting(&first_message.message_type, &message.message_type) {
+            return None;
+        }
+
+        self.reported.insert((message.sender_id, kind));
+        Some(Evidence {
+            kind,
+            block_height,
+            offender: message.sender_id,
+            messages: vec![first.clone(), envelope.clone()],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockdata::Block;
+    use crate::crypto::frost::create_nonce;
+    use crate::net::Message;
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use curv::FE;
+
+    fn signed(message_type: MessageType, index: usize) -> Envelope {
+        let message = Message {
+            message_type,
+            sender_id: SignerID::new(TEST_KEYS.pubkeys()[index]),
+            receiver_id: None,
+        };
+        Envelope::new(&message, Some(&TEST_KEYS.key[index]))
+    }
+
+    /// Returns the block which has the same previous block as `get_block(0)` but differs from it.
+    fn other_candidate() -> Block {
+        let mut header = get_block(0).header().clone();
+        header.time += 1;
+        Block::from_parts(header, get_block(0).get_transactions().unwrap())
+    }
+
+    #[test]
+    fn test_conflicting_candidates() {
+        let mut detector = EquivocationDetector::new();
+        detector.start_round(10);
+        let first = signed(MessageType::Candidateblock(get_block(0)), 0);
+        assert_eq!(detector.observe(&first, 11), None);
+
+        // The same candidate is not the equivocation, even if it is proposed in a later round.
+        assert_eq!(detector.observe(&first, 11), None);
+        detector.start_round(10);
+        assert_eq!(detector.observe(&first, 11), None);
+
+        let second = signed(MessageType::Candidateblock(other_candidate()), 0);
+        let evidence = detector.observe(&second, 11).unwrap();
+        assert_eq!(evidence.kind, EquivocationKind::ConflictingCandidates);
+        assert_eq!(evidence.offender, SignerID::new(TEST_KEYS.pubkeys()[0]));
+        assert_eq!(evidence.messages, vec![first, second.clone()]);
+        assert!(evidence.verify().is_ok());
+
+        // The offender is reported once for a block.
+        assert_eq!(detector.observe(&second, 11), None);
+
+        // The evidence is portable.
+        let json = serde_json::to_string(&evidence).unwrap();
+        let evidence: Evidence = serde_json::from_str(&json).unwrap();
+        assert!(evidence.verify().is_ok());
+    }
+
+    #[test]
+    fn test_conflicting_signatures() {
+        let mut detector = EquivocationDetector::new();
+        detector.start_round(10);
+        let sighash = get_block(0).sighash();
+        let e = FE::new_random();
+        let first = signed(MessageType::Blocksig(sighash, FE::new_random(), e), 1);
+        assert_eq!(detector.observe(&first, 11), None);
+
+        // Signatures from other signers are not the equivocation.
+        let other = signed(MessageType::Blocksig(sighash, FE::new_random(), e), 2);
+        assert_eq!(detector.observe(&other, 11), None);
+
+        // The signature with the nonce of another round is not the equivocation.
+        let next_round = signed(
+            MessageType::Blocksig(sighash, FE::new_random(), FE::new_random()),
+            1,
+        );
+        assert_eq!(detector.observe(&next_round, 11), None);
+
+        let second = signed(MessageType::Blocksig(sighash, FE::new_random(), e), 1);
+        let evidence = detector.observe(&second, 11).unwrap();
+        assert_eq!(evidence.kind, EquivocationKind::ConflictingSignatures);
+        assert!(evidence.verify().is_ok());
+
+        // The messages for the previous block are forgotten.
+        detector.start_round(11);
+        assert_eq!(detector.observe(&first, 12), None);
+    }
+
+    #[test]
+    fn test_conflicting_signature_shares() {
+        let mut detector = EquivocationDetector::new();
+        detector.start_round(10);
+        let (_, commitment) = create_nonce();
+        let first = signed(
+            MessageType::Blocksigshare(get_block(0).sighash(), commitment, FE::new_random()),
+            1,
+        );
+        assert_eq!(detector.observe(&first, 11), None);
+
+        // The share with another nonce is not the equivocation.
+        let (_, other_commitment) = create_nonce();
+        let next_round = signed(
+            MessageType::Blocksigshare(get_block(0).sighash(), other_commitment, FE::new_random()),
+            1,
+        );
+        assert_eq!(detector.observe(&next_round, 11), None);
+
+        // The nonce is used for another block.
+        let second = signed(
+            MessageType::Blocksigshare(other_candidate().sighash(), commitment, FE::new_random()),
+            1,
+        );
+        let evidence = detector.observe(&second, 11).unwrap();
+        assert_eq!(evidence.kind, EquivocationKind::ConflictingSignatures);
+        assert!(evidence.verify().is_ok());
+    }
+
+    #[test]
+    fn test_unsigned_messages_are_ignored() {
+        let mut detector = EquivocationDetector::new();
+        let message = |block: Block| Message {
+            message_type: MessageType::Candidateblock(block),
+            sender_id: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            receiver_id: None,
+        };
+        let first = Envelope::new(&message(get_block(0)), None);
+        assert_eq!(detector.observe(&first, 11), None);
+        let second = Envelope::new(&message(other_candidate()), None);
+        assert_eq!(detector.observe(&second, 11), None);
+
+        // The message signed by other than the sender is ignored too.
+        let forged = Envelope::new(&message(other_candidate()), Some(&TEST_KEYS.key[1]));
+        assert_eq!(detector.observe(&forged, 11), None);
+    }
+
+    #[test]
+    fn test_verify_forged_evidence() {
+        let first = signed(MessageType::Candidateblock(get_block(0)), 0);
+        let second = signed(MessageType::Candidateblock(other_candidate()), 1);
+        let evidence = Evidence {
+            kind: EquivocationKind::ConflictingCandidates,
+            block_height: 10,
+            offender: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            messages: vec![first.clone(), second],
+        };
+        match evidence.verify() {
+            Err(Error::InvalidEvidence("message is not sent by the offender")) => {}
+            r => panic!("Should be invalid, but {:?}", r),
+        }
+
+        let evidence = Evidence {
+            kind: EquivocationKind::ConflictingCandidates,
+            block_height: 10,
+            offender: SignerID::new(TEST_KEYS.pubkeys()[0]),
+            messages: vec![first.clone(), first],
+        };
+        match evidence.verify() {
+            Err(Error::InvalidEvidence("messages don't conflict")) => {}
+            r => panic!("Should be invalid, but {:?}", r),
+        }
+    }
+}
@@ -1,16 +1,24 @@
+mod process_blockcommitments;
+mod process_blocknonces;
 mod process_blockparticipants;
 mod process_blocksig;
+mod process_blocksigshare;
 mod process_blockvss;
 mod process_candidateblock;
 mod process_completedblock;
+pub use process_blockcommitments::{process_blockcommitments, select_participants};
+pub use process_blocknonces::process_blocknonces;
 pub use process_blockparticipants::process_blockparticipants;
 pub use process_blocksig::process_blocksig;
+pub use process_blocksigshare::process_blocksigshare;
 pub use process_blockvss::process_blockvss;
 pub use process_candidateblock::process_candidateblock;
 pub use process_completedblock::process_completedblock;
 
 use crate::blockdata::hash::SHA256Hash;
 use crate::blockdata::Block;
+use crate::crypto::block_key::BlockKeyDerivation;
+use crate::crypto::frost::{NonceCommitments, SigningPackage};
 use crate::crypto::multi_party_schnorr::Keys;
 use crate::crypto::multi_party_schnorr::{LocalSig, SharedKeys};
 use crate::crypto::vss::Vss;
@@ -20,8 +28,12 @@ use crate::net::Message;
 use crate::net::MessageType;
 use crate::net::SignerID;
 use crate::rpc::TapyrusApi;
+use crate::signer_node::audit_log::{AuditEntry, AuditEvent};
 use crate::signer_node::SharedSecret;
-use crate::signer_node::{BidirectionalSharedSecretMap, NodeParameters, NodeState};
+use crate::signer_node::{master_index, BidirectionalSharedSecretMap, NodeParameters, NodeState};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{FE, GE};
+use std::collections::HashSet;
 
 fn get_valid_block(state: &NodeState, blockhash: SHA256Hash) -> Result<&Block, Error> {
     let block_opt = match state {
@@ -68,17 +80,33 @@ where
 
     let self_node_index = params.self_node_index(block_height);
 
+    // Use the block key in the nonce pool if it has, so that the round doesn't wait for the
+    // generation of it. The pooled key is hedged with the block in the same way as below.
     let (
         key,
         vss_scheme_for_positive,
         secret_shares_for_positive,
         vss_scheme_for_negative,
         secret_shares_for_negative,
-    ) = Vss::create_block_shares(
-        self_node_index + 1,
-        sharing_params.threshold + 1,
-        sharing_params.share_count,
-    );
+    ) = params
+        .take_block_shares(block_height, &block.sighash())
+        .unwrap_or_else(|| {
+            // The block key is derived from the node secret share and the block, so that it is not
+            // predictable even if the random number generator is weak. The block height is used as
+            // the round id.
+            let federation = params.get_federation_by_block_height(block_height);
+            let derivation = BlockKeyDerivation::for_block(
+                &federation.node_secret_share().x_i,
+                &block.sighash(),
+                block_height,
+            );
+            Vss::create_block_shares_with(
+                &derivation,
+                self_node_index + 1,
+                sharing_params.threshold + 1,
+                sharing_params.share_count,
+            )
+        });
 
     for i in 0..params.pubkey_list(block_height).len() {
         // Skip broadcasting if it is vss for myself. Just return this.
@@ -105,11 +133,11 @@ where
         key,
         SharedSecret {
             vss: vss_scheme_for_positive.clone(),
-            secret_share: secret_shares_for_positive[self_node_index],
+            secret_share: secret_shares_for_positive[self_node_index].into(),
         },
         SharedSecret {
             vss: vss_scheme_for_negative.clone(),
-            secret_share: secret_shares_for_negative[self_node_index],
+            secret_share: secret_shares_for_negative[self_node_index].into(),
         },
     )
 }
@@ -129,16 +157,36 @@ where
     );
     let block = get_valid_block(prev_state, blockhash)?;
     let block_height = prev_state.block_height();
-    let federation = params.get_federation_by_block_height(block_height);
 
     Vss::create_local_sig_from_shares(
-        &federation.node_secret_share(),
+        &params
+            .get_federation_by_block_height(block_height)
+            .node_secret_share(),
         params.self_node_index(block_height) + 1,
         shared_block_secrets,
-        &block,
+        block.sighash(),
     )
 }
 
+/// Returns the signing package of FROST signing for the block.
+fn signing_package<T>(
+    blockhash: SHA256Hash,
+    commitments: NonceCommitments,
+    block_height: u64,
+    params: &NodeParameters<T>,
+) -> Result<SigningPackage, Error>
+where
+    T: TapyrusApi,
+{
+    // The aggregated public key is taken from the federation, because the node secret share
+    // may be kept by the share signer.
+    let aggregated_public_key = params.aggregated_public_key(block_height);
+    let y = GE::from_bytes(&aggregated_public_key.key.serialize_uncompressed()[1..])
+        .map_err(|_| Error::InvalidKey)?;
+    let federation = params.get_federation_by_block_height(block_height);
+    SigningPackage::new(blockhash, commitments, &federation.signers(), y)
+}
+
 fn broadcast_localsig<C: ConnectionManager>(
     sighash: SHA256Hash,
     local_sig: &LocalSig,
@@ -156,6 +204,109 @@ fn broadcast_localsig<C: ConnectionManager>(
     });
 }
 
+/// Records the local signature or the signature share of this signer to the audit log. The
+/// signer must not send the local signature if it fails, so that no signature is left unrecorded.
+fn audit_localsig<T>(
+    sighash: SHA256Hash,
+    share: &FE,
+    participants: &HashSet<SignerID>,
+    state: &NodeState,
+    params: &NodeParameters<T>,
+) -> Result<(), Error>
+where
+    T: TapyrusApi,
+{
+    audit(
+        AuditEvent::LocalSig,
+        sighash,
+        &share.get_element()[..],
+        participants,
+        state,
+        params,
+    )
+}
+
+/// Records the completed block which this signer submits to the audit log.
+fn audit_completedblock<T>(block: &Block, state: &NodeState, params: &NodeParameters<T>)
+where
+    T: TapyrusApi,
+{
+    audit_block(AuditEvent::CompletedBlock, block, state, params);
+}
+
+/// Records the completed block which didn't become the tip of the chain to the audit log.
+fn audit_not_accepted<T>(block: &Block, state: &NodeState, params: &NodeParameters<T>)
+where
+    T: TapyrusApi,
+{
+    audit_block(AuditEvent::NotAccepted, block, state, params);
+}
+
+fn audit_block<T>(event: AuditEvent, block: &Block, state: &NodeState, params: &NodeParameters<T>)
+where
+    T: TapyrusApi,
+{
+    let participants = match state {
+        NodeState::Master { participants, .. } => participants.clone(),
+        NodeState::Member { participants, .. } => participants.clone(),
+        _ => HashSet::new(),
+    };
+    // The block is already signed by the federation, so the failure is only logged.
+    let _ = audit(
+        event,
+        block.sighash(),
+        &block.header().proof,
+        &participants,
+        state,
+        params,
+    );
+}
+
+/// Appends the record to the audit log if it is enabled. The failure is logged and returned.
+fn audit<T>(
+    event: AuditEvent,
+    sighash: SHA256Hash,
+    commitment: &[u8],
+    participants: &HashSet<SignerID>,
+    state: &NodeState,
+    params: &NodeParameters<T>,
+) -> Result<(), Error>
+where
+    T: TapyrusApi,
+{
+    let audit_log = match &params.audit_log {
+        Some(audit_log) => audit_log,
+        None => return Ok(()),
+    };
+    let block_height = state.block_height();
+    let master = match master_index(state, params) {
+        Some(index) => params.get_signer_id_by_index(block_height, index),
+        None => {
+            log::error!(
+                "Failed to append the record to the audit log, because the master of the round is unknown. event: {:?}, state: {:?}",
+                event,
+                state
+            );
+            return Err(Error::InvalidNodeState);
+        }
+    };
+    let entry = AuditEntry::new(
+        event,
+        block_height + 1,
+        sighash,
+        master,
+        participants.iter().cloned().collect(),
+        params
+            .get_federation_by_block_height(block_height)
+            .aggregated_public_key(),
+        commitment,
+    );
+    audit_log.append(entry).map_err(|e| {
+        log::error!("Failed to append the record to the audit log. {:?}", e);
+        e
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,7 +319,7 @@ mod tests {
 
     #[test]
     fn test_get_valid_block_valid_for_master() {
-        let block = Some(Block::new(hex::decode(BLOCK).unwrap()));
+        let block = Some(Block::new(hex::decode(BLOCK).unwrap()).unwrap());
         let state = Master::for_test().candidate_block(block.clone()).build();
         let blockhash = SHA256Hash::from_slice(&hex::decode(HASH).unwrap()[..]).unwrap();
         assert_eq!(*get_valid_block(&state, blockhash).unwrap(), block.unwrap());
@@ -176,7 +327,7 @@ mod tests {
 
     #[test]
     fn test_get_valid_block_valid_for_member() {
-        let block = Some(Block::new(hex::decode(BLOCK).unwrap()));
+        let block = Some(Block::new(hex::decode(BLOCK).unwrap()).unwrap());
         let state = Member::for_test().candidate_block(block.clone()).build();
         let blockhash = SHA256Hash::from_slice(&hex::decode(HASH).unwrap()[..]).unwrap();
         assert_eq!(*get_valid_block(&state, blockhash).unwrap(), block.unwrap());
@@ -191,7 +342,7 @@ mod tests {
 
     #[test]
     fn test_get_valid_block_invalid_blockhash_for_master() {
-        let block = Some(Block::new(hex::decode(BLOCK).unwrap()));
+        let block = Some(Block::new(hex::decode(BLOCK).unwrap()).unwrap());
         let state = Master::for_test().candidate_block(block.clone()).build();
         let blockhash = SHA256Hash::from_slice(&hex::decode(INVALID_HASH).unwrap()[..]).unwrap();
         assert!(get_valid_block(&state, blockhash).is_err());
@@ -199,7 +350,7 @@ mod tests {
 
     #[test]
     fn test_get_valid_block_invalid_blockhash_for_member() {
-        let block = Some(Block::new(hex::decode(BLOCK).unwrap()));
+        let block = Some(Block::new(hex::decode(BLOCK).unwrap()).unwrap());
         let state = Member::for_test().candidate_block(block.clone()).build();
         let blockhash = SHA256Hash::from_slice(&hex::decode(INVALID_HASH).unwrap()[..]).unwrap();
         assert!(get_valid_block(&state, blockhash).is_err());
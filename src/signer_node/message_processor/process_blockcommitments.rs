@@ -0,0 +1,319 @@
+use crate::federation::SigningProtocol;
+use crate::net::{ConnectionManager, Message, MessageType, SignerID};
+use crate::rpc::TapyrusApi;
+use crate::signer_node::message_processor::audit_localsig;
+use crate::signer_node::node_state::builder::{Builder, Master};
+use crate::signer_node::{NodeParameters, NodeState};
+use curv::GE;
+
+/// Update the nonce commitments which the sender published for the future rounds of FROST
+/// signing. The master which still waits for the commitments tries to select the participants.
+pub fn process_blockcommitments<T, C>(
+    sender_id: &SignerID,
+    commitments: Vec<(GE, GE)>,
+    prev_state: &NodeState,
+    conman: &C,
+    params: &NodeParameters<T>,
+) -> NodeState
+where
+    T: TapyrusApi,
+    C: ConnectionManager,
+{
+    // The nonces of this signer are kept by itself.
+    if *sender_id == params.signer_id {
+        return prev_state.clone();
+    }
+    params.update_published_commitments(*sender_id, commitments);
+
+    match prev_state {
+        NodeState::Master {
+            candidate_block: Some(_),
+            participants,
+            round_is_done: false,
+            block_height,
+            ..
+        } if participants.is_empty()
+            && params.signing_protocol(*block_height) == SigningProtocol::Frost =>
+        {
+            select_participants(prev_state, conman, params)
+        }
+        _ => prev_state.clone(),
+    }
+}
+
+/// Select the participants of FROST signing with the nonce commitments which they published ahead
+/// of the round, and broadcast the commitments of the participants after signing the candidate
+/// block with the own nonce. The master keeps waiting without the participants if the other
+/// signers have not published enough commitments yet.
+pub fn select_participants<T, C>(
+    prev_state: &NodeState,
+    conman: &C,
+    params: &NodeParameters<T>,
+) -> NodeState
+where
+    T: TapyrusApi,
+    C: ConnectionManager,
+{
+    let blockhash = match prev_state {
+        NodeState::Master {
+            candidate_block: Some(block),
+            ..
+        } => block.sighash(),
+        _ => return prev_state.clone(),
+    };
+
+    let block_height = prev_state.block_height();
+    let threshold = params.threshold(block_height) as usize;
+    let signers = params
+        .get_federation_by_block_height(block_height)
+        .signers();
+
+    let own_commitment = match params.nonce_commitments() {
+        Ok(commitments) => commitments[0],
+        Err(e) => {
+            log::error!("Failed to get the own nonce commitment. {:?}", e);
+            return prev_state.clone();
+        }
+    };
+
+    // The other signers are selected in the round-robin order from the master, so that the
+    // rounds don't always depend on the same signers.
+    let candidates: Vec<SignerID> = signers
+        .iter()
+        .cycle()
+        .skip(params.self_node_index(block_height) + 1)
+        .take(signers.len() - 1)
+        .filter(|id| !params.peer_scores.is_banned(id))
+        .cloned()
+        .collect();
+    let mut selected = match params.select_published_commitments(&candidates, threshold - 1) {
+        Some(selected) => selected,
+        None => {
+            log::info!("Wait for the nonce commitments of the other signers.");
+            return prev_state.clone();
+        }
+    };
+    selected.insert(params.signer_id, own_commitment);
+
+    let share = match params.sign_share(block_height, blockhash, &selected) {
+        Ok(share) => share,
+        Err(e) => {
+            log::error!("Failed to create the signature share. {:?}", e);
+            return prev_state.clone();
+        }
+    };
+
+    if audit_localsig(
+        blockhash,
+        &share,
+        &selected.keys().cloned().collect(),
+        prev_state,
+        params,
+    )
+    .is_err()
+    {
+        return prev_state.clone();
+    }
+
+    log::info!(
+        "Broadcast nonce commitments of the participants: {:?}",
+        selected.keys()
+    );
+    conman.broadcast_message(Message {
+        message_type: MessageType::Blocknonces(blockhash, selected.clone()),
+        sender_id: params.signer_id,
+        receiver_id: None,
+    });
+
+    Master::from_node_state(prev_state.clone())
+        .participants(selected.keys().cloned().collect())
+        .nonce_commitments(selected)
+        .insert_signature_share(params.signer_id, share)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{process_blockcommitments, select_participants};
+    use crate::crypto::frost::{public_share, Nonces, NONCE_BATCH_SIZE};
+    use crate::federation::{Federation, Federations, SigningProtocol};
+    use crate::net::{Message, MessageType, SignerID};
+    use crate::signer_node::message_processor::signing_package;
+    use crate::signer_node::node_state::builder::{Builder, Master, Member};
+    use crate::signer_node::{NodeParameters, NodeState};
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::net::TestConnectionManager;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::node_state_builder::BuilderForTest;
+    use crate::tests::helper::node_vss::node_vss;
+    use crate::tests::helper::rpc::MockRpc;
+    use curv::GE;
+    use std::collections::HashSet;
+
+    fn params() -> NodeParameters<MockRpc> {
+        let federation = Federation::new(
+            TEST_KEYS.pubkeys()[0],
+            0,
+            Some(3),
+            node_vss(0),
+            TEST_KEYS.aggregated(),
+        )
+        .with_signing_protocol(SigningProtocol::Frost);
+        NodeParametersBuilder::new()
+            .public_key(TEST_KEYS.pubkeys()[0])
+            .federations(Federations::new(vec![federation]))
+            .build()
+    }
+
+    fn commitments() -> Vec<(GE, GE)> {
+        Nonces::new().commitments(NONCE_BATCH_SIZE)
+    }
+
+    #[test]
+    fn test_process_blockcommitments_selects_participants() {
+        let params = params();
+        let mut conman = TestConnectionManager::new();
+        let prev_state = Master::for_test().build();
+        let own_commitment = params.nonce_commitments().unwrap()[0];
+        let commitments_3 = commitments();
+        let commitments_1 = commitments();
+
+        // The master waits for the commitments of the threshold signers.
+        let state = process_blockcommitments(
+            &TEST_KEYS.signer_ids()[3],
+            commitments_3.clone(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+        assert_eq!(state, prev_state);
+
+        let state = process_blockcommitments(
+            &TEST_KEYS.signer_ids()[1],
+            commitments_1.clone(),
+            &state,
+            &conman,
+            &params,
+        );
+
+        let (participants, nonce_commitments, signature_shares) = match state {
+            NodeState::Master {
+                participants,
+                nonce_commitments,
+                signature_shares,
+                ..
+            } => (participants, nonce_commitments, signature_shares),
+            _ => panic!("NodeState should be Master"),
+        };
+        let expected: HashSet<SignerID> = [0, 1, 3]
+            .iter()
+            .map(|i| TEST_KEYS.signer_ids()[*i])
+            .collect();
+        assert_eq!(participants, expected);
+        assert_eq!(nonce_commitments[&params.signer_id], own_commitment);
+        assert_eq!(
+            nonce_commitments[&TEST_KEYS.signer_ids()[1]],
+            commitments_1[0]
+        );
+        assert_eq!(
+            nonce_commitments[&TEST_KEYS.signer_ids()[3]],
+            commitments_3[0]
+        );
+
+        // The share of the master is valid, and its nonce is never used again.
+        let package = signing_package(
+            get_block(0).sighash(),
+            nonce_commitments.clone(),
+            0,
+            &params,
+        )
+        .unwrap();
+        let federation = params.get_federation_by_block_height(0);
+        let share = signature_shares[&params.signer_id];
+        assert!(package
+            .verify_share(
+                &params.signer_id,
+                &share,
+                &public_share(&federation.node_shared_secrets(), 1)
+            )
+            .is_ok());
+        assert!(params
+            .sign_share(0, get_block(0).sighash(), &nonce_commitments)
+            .is_err());
+
+        conman.should_broadcast(Message {
+            message_type: MessageType::Blocknonces(get_block(0).sighash(), nonce_commitments),
+            sender_id: params.signer_id,
+            receiver_id: None,
+        });
+        conman.assert();
+    }
+
+    #[test]
+    fn test_selected_commitments_are_consumed() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let prev_state = Master::for_test().build();
+        for i in &[1, 2] {
+            params.update_published_commitments(
+                TEST_KEYS.signer_ids()[*i],
+                commitments()[..1].to_vec(),
+            );
+        }
+
+        let state = select_participants(&prev_state, &conman, &params);
+        assert_ne!(state, prev_state);
+
+        // The next round must wait for the new commitments.
+        let state = select_participants(&prev_state, &conman, &params);
+        assert_eq!(state, prev_state);
+        assert_eq!(conman.broadcasted.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_process_blockcommitments_as_member() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let prev_state = Member::for_test()
+            .candidate_block(Some(get_block(0)))
+            .build();
+        let commitments = commitments();
+
+        let next = process_blockcommitments(
+            &TEST_KEYS.signer_ids()[1],
+            commitments.clone(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        let selected = params
+            .select_published_commitments(&TEST_KEYS.signer_ids()[1..2], 1)
+            .unwrap();
+        assert_eq!(selected[&TEST_KEYS.signer_ids()[1]], commitments[0]);
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blockcommitments_from_itself() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let prev_state = Master::for_test().build();
+
+        let next = process_blockcommitments(
+            &params.signer_id,
+            commitments(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        assert!(params
+            .select_published_commitments(&[params.signer_id], 1)
+            .is_none());
+        conman.assert();
+    }
+}
@@ -0,0 +1,340 @@
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::frost::NonceCommitments;
+use crate::net::{ConnectionManager, Message, MessageType, SignerID};
+use crate::rpc::TapyrusApi;
+use crate::signer_node::message_processor::{audit_localsig, get_valid_block};
+use crate::signer_node::node_state::builder::{Builder, Member};
+use crate::signer_node::{NodeParameters, NodeState};
+
+/// Create the signature share of FROST signing with the nonce commitments of the participants
+/// which the master selected, and send it to the master. The nonce of the commitment is removed
+/// when it is used, so the master can't make this signer use it twice.
+pub fn process_blocknonces<T, C>(
+    sender_id: &SignerID,
+    blockhash: SHA256Hash,
+    commitments: NonceCommitments,
+    prev_state: &NodeState,
+    conman: &C,
+    params: &NodeParameters<T>,
+) -> NodeState
+where
+    T: TapyrusApi,
+    C: ConnectionManager,
+{
+    let (nonce_commitments, master_index, block_height) = match prev_state {
+        NodeState::Member {
+            nonce_commitments,
+            master_index,
+            block_height,
+            ..
+        } => (nonce_commitments, master_index, block_height),
+        _ => return prev_state.clone(),
+    };
+
+    let master_id = params.get_signer_id_by_index(*block_height, *master_index);
+    if master_id != *sender_id {
+        return prev_state.clone();
+    }
+
+    // The master selects the participants only once in a round.
+    if !nonce_commitments.is_empty() {
+        return prev_state.clone();
+    }
+
+    if let Err(e) = get_valid_block(prev_state, blockhash) {
+        log::warn!("{:?}", e);
+        return prev_state.clone();
+    }
+
+    // The selected commitments of the other signers are never selected again by this signer.
+    params.remove_published_commitments(&commitments);
+
+    let mut state_builder = Member::from_node_state(prev_state.clone());
+    state_builder
+        .participants(commitments.keys().cloned().collect())
+        .nonce_commitments(commitments.clone());
+
+    // Do nothing if the node is not included in participants.
+    let own_commitment = match commitments.get(&params.signer_id) {
+        Some(commitment) => *commitment,
+        None => return state_builder.build(),
+    };
+
+    // The nonce of the own commitment is used only once, so that the commitments of a replayed
+    // message are not signed again.
+    let share = match params.sign_share(*block_height, blockhash, &commitments) {
+        Ok(share) => share,
+        Err(e) => {
+            log::warn!(
+                "Failed to create the signature share. sender: {}, {:?}",
+                sender_id,
+                e
+            );
+            return state_builder.build();
+        }
+    };
+
+    if audit_localsig(
+        blockhash,
+        &share,
+        &commitments.keys().cloned().collect(),
+        prev_state,
+        params,
+    )
+    .is_err()
+    {
+        return state_builder.build();
+    }
+    conman.send_message(Message {
+        message_type: MessageType::Blocksigshare(blockhash, own_commitment, share),
+        sender_id: params.signer_id,
+        receiver_id: Some(master_id),
+    });
+
+    state_builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::process_blocknonces;
+    use crate::blockdata::hash::SHA256Hash;
+    use crate::crypto::frost::{create_nonce, public_share, NonceCommitments, SigningPackage};
+    use crate::federation::{Federation, Federations, SigningProtocol};
+    use crate::net::{Message, MessageType};
+    use crate::signer_node::audit_log::AuditLog;
+    use crate::signer_node::node_state::builder::{Builder, Member};
+    use crate::signer_node::{NodeParameters, NodeState};
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::net::TestConnectionManager;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::node_state_builder::BuilderForTest;
+    use crate::tests::helper::node_vss::node_vss;
+    use crate::tests::helper::rpc::MockRpc;
+
+    fn federation() -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[1],
+            0,
+            Some(3),
+            node_vss(1),
+            TEST_KEYS.aggregated(),
+        )
+        .with_signing_protocol(SigningProtocol::Frost)
+    }
+
+    fn params() -> NodeParameters<MockRpc> {
+        NodeParametersBuilder::new()
+            .public_key(TEST_KEYS.pubkeys()[1])
+            .federations(Federations::new(vec![federation()]))
+            .build()
+    }
+
+    fn blockhash() -> SHA256Hash {
+        get_block(0).sighash()
+    }
+
+    /// Returns the state of the signer 1 and the commitments of the signer 0, 1 and 3. The
+    /// commitment of the signer 1 is the one it published.
+    fn setup(params: &NodeParameters<MockRpc>) -> (NodeState, NonceCommitments) {
+        let state = Member::for_test()
+            .candidate_block(Some(get_block(0)))
+            .master_index(0)
+            .build();
+        let mut commitments = NonceCommitments::new();
+        commitments.insert(
+            TEST_KEYS.signer_ids()[1],
+            params.nonce_commitments().unwrap()[0],
+        );
+        for i in &[0, 3] {
+            let (_, commitment) = create_nonce();
+            commitments.insert(TEST_KEYS.signer_ids()[*i], commitment);
+        }
+        (state, commitments)
+    }
+
+    #[test]
+    fn test_process_blocknonces_as_participant() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let (prev_state, commitments) = setup(&params);
+
+        let next = process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            blockhash(),
+            commitments.clone(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        match next {
+            NodeState::Member {
+                nonce_commitments,
+                participants,
+                ..
+            } => {
+                assert_eq!(nonce_commitments, commitments);
+                assert_eq!(participants.len(), 3);
+            }
+            _ => panic!("NodeState should be Member"),
+        }
+
+        // It should send the valid signature share to the master.
+        let share = match &conman.sent.borrow()[..] {
+            [Message {
+                message_type: MessageType::Blocksigshare(hash, commitment, share),
+                receiver_id: Some(receiver_id),
+                ..
+            }] if *hash == blockhash()
+                && *commitment == commitments[&params.signer_id]
+                && *receiver_id == TEST_KEYS.signer_ids()[0] =>
+            {
+                *share
+            }
+            sent => panic!("Sent unexpected messages {:?}", sent),
+        };
+        let package = SigningPackage::new(
+            blockhash(),
+            commitments.clone(),
+            &TEST_KEYS.signer_ids(),
+            federation().node_secret_share().y,
+        )
+        .unwrap();
+        let public_share = public_share(&federation().node_shared_secrets(), 2);
+        assert!(package
+            .verify_share(&params.signer_id, &share, &public_share)
+            .is_ok());
+
+        // The nonce is discarded not to be used again.
+        assert!(params.sign_share(0, blockhash(), &commitments).is_err());
+    }
+
+    #[test]
+    fn test_process_blocknonces_with_audit_log_failure() {
+        // The directory of the audit log doesn't exist, so the record can't be appended.
+        let path = std::env::temp_dir()
+            .join("tapyrus-signer-no-such-dir")
+            .join("audit.log");
+        let params = params().with_audit_log(Some(AuditLog::open(path, TEST_KEYS.key[1]).unwrap()));
+        let conman = TestConnectionManager::new();
+        let (prev_state, commitments) = setup(&params);
+
+        process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            blockhash(),
+            commitments,
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        // The signature share is not sent without the record.
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocknonces_not_include_the_node() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let (prev_state, mut commitments) = setup(&params);
+        commitments.remove(&TEST_KEYS.signer_ids()[1]);
+        let (_, commitment) = create_nonce();
+        commitments.insert(TEST_KEYS.signer_ids()[4], commitment);
+
+        let next = process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            blockhash(),
+            commitments.clone(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        match next {
+            NodeState::Member {
+                nonce_commitments,
+                participants,
+                ..
+            } => {
+                assert_eq!(nonce_commitments, commitments);
+                assert!(!participants.contains(&params.signer_id));
+            }
+            _ => panic!("NodeState should be Member"),
+        }
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocknonces_from_fake_master() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let (prev_state, commitments) = setup(&params);
+
+        let next = process_blocknonces(
+            &TEST_KEYS.signer_ids()[3],
+            blockhash(),
+            commitments,
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocknonces_with_unknown_commitment() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let (prev_state, mut commitments) = setup(&params);
+        let (_, commitment) = create_nonce();
+        commitments.insert(TEST_KEYS.signer_ids()[1], commitment);
+
+        process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            blockhash(),
+            commitments,
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocknonces_with_used_commitment() {
+        let params = params();
+        let conman = TestConnectionManager::new();
+        let (prev_state, commitments) = setup(&params);
+        process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            blockhash(),
+            commitments.clone(),
+            &prev_state,
+            &conman,
+            &params,
+        );
+        assert_eq!(conman.sent.borrow().len(), 1);
+
+        // The master selects the same commitment again in the next round.
+        let next_block = get_block(1);
+        let prev_state = Member::for_test()
+            .candidate_block(Some(next_block.clone()))
+            .master_index(0)
+            .build();
+        process_blocknonces(
+            &TEST_KEYS.signer_ids()[0],
+            next_block.sighash(),
+            commitments,
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        // It must not sign with the same nonce twice.
+        assert_eq!(conman.sent.borrow().len(), 1);
+    }
+}
@@ -1,9 +1,10 @@
 use crate::blockdata::hash::SHA256Hash;
 use crate::crypto::multi_party_schnorr::LocalSig;
+use crate::crypto::secret::Secret;
 use crate::net::{ConnectionManager, SignerID};
 use crate::rpc::TapyrusApi;
 use crate::signer_node::message_processor::{
-    broadcast_localsig, generate_local_sig, get_valid_block,
+    audit_localsig, broadcast_localsig, generate_local_sig, get_valid_block,
 };
 use crate::signer_node::node_state::builder::{Builder, Master, Member};
 use crate::signer_node::{NodeParameters, NodeState};
@@ -92,6 +93,17 @@ where
         }
     };
 
+    if audit_localsig(
+        block.sighash(),
+        &local_sig.gamma_i,
+        &participants,
+        prev_state,
+        params,
+    )
+    .is_err()
+    {
+        return prev_state.clone();
+    }
     broadcast_localsig(block.sighash(), &local_sig, conman, &params.signer_id);
 
     create_next_state(
@@ -108,7 +120,7 @@ fn create_next_state(
     prev_state: &NodeState,
     participants: HashSet<SignerID>,
     local_sig: Option<LocalSig>,
-    block_shared_keys: Option<(bool, FE, GE)>,
+    block_shared_keys: Option<(bool, Secret<FE>, GE)>,
 ) -> NodeState {
     match prev_state {
         NodeState::Master { .. } => {
@@ -137,6 +149,7 @@ mod tests {
     use super::process_blockparticipants;
     use crate::blockdata::hash::SHA256Hash;
     use crate::crypto::multi_party_schnorr::LocalSig;
+    use crate::crypto::secret::Secret;
     use crate::net::SignerID;
     use crate::signer_node::node_state::builder::{Builder, Master, Member};
     use crate::signer_node::*;
@@ -402,13 +415,13 @@ mod tests {
         NodeState,
         NodeParameters<MockRpc>,
         Option<LocalSig>,
-        Option<(bool, FE, GE)>,
+        Option<(bool, Secret<FE>, GE)>,
     ) {
         let v = &contents["cases"][case];
 
         let params = to_node_parameters(&v, rpc);
 
-        let block_key: Option<FE> = serde_json::from_value(v["block_key"].clone()).unwrap();
+        let block_key: Option<Secret<FE>> = serde_json::from_value(v["block_key"].clone()).unwrap();
         let block = to_block(&v["candidate_block"]);
 
         let sender = to_signer_id(&v["received"]["sender"].as_str().unwrap().to_string());
@@ -1,13 +1,18 @@
 use crate::blockdata::hash::SHA256Hash;
 use crate::blockdata::Block;
 use crate::crypto::multi_party_schnorr::Signature;
+use crate::crypto::secret::{self, Secret};
 use crate::crypto::vss::Vss;
 use crate::errors::Error;
 use crate::net::{ConnectionManager, Message, MessageType, SignerID};
 use crate::rpc::TapyrusApi;
 use crate::sign::Sign;
-use crate::signer_node::message_processor::get_valid_block;
+use crate::signer_node::confirmation::submit_and_confirm;
+use crate::signer_node::message_processor::{
+    audit_completedblock, audit_not_accepted, get_valid_block,
+};
 use crate::signer_node::node_state::builder::{Builder, Master};
+use crate::signer_node::peer_scores::Misbehavior;
 use crate::signer_node::NodeParameters;
 use crate::signer_node::NodeState;
 use bitcoin::PublicKey;
@@ -41,7 +46,7 @@ where
             .received(Received {
                 sender: sender_id.clone(),
                 block_hash: blockhash,
-                gamma_i,
+                gamma_i: gamma_i.into(),
                 e,
             })
             .public_keys(params.pubkey_list(block_height).clone())
@@ -85,9 +90,9 @@ where
     let mut state_builder = Master::from_node_state(prev_state.clone());
 
     log::debug!(
-        "Store local sig, sender: {:?}, gamma_i: {:?}, e: {:?}",
+        "Store local sig, sender: {:?}, gamma_i: {}, e: {:?}",
         sender_id,
-        gamma_i,
+        Secret::new(gamma_i),
         e
     );
     let new_signatures = store_received_local_sig(sender_id, signatures, gamma_i, e);
@@ -130,8 +135,8 @@ where
 
     let federation = params.get_federation_by_block_height(block_height);
     let signature = match Vss::aggregate_and_verify_signature(
-        candidate_block,
-        new_signatures,
+        candidate_block.sighash(),
+        new_signatures.clone(),
         &params.pubkey_list(block_height),
         &federation.node_shared_secrets(),
         &block_shared_keys,
@@ -141,16 +146,29 @@ where
         Ok(sig) => sig,
         Err(e) => {
             log::error!("aggregated signature is invalid. e: {:?}", e);
+            for sender_id in Vss::invalid_local_sig_senders(
+                &params.signer_id,
+                &new_signatures,
+                &params.pubkey_list(block_height),
+                &federation.node_shared_secrets(),
+                &block_shared_keys,
+                &shared_block_secrets_by_participants,
+            ) {
+                log::warn!("Invalid local signature. sender: {}", sender_id);
+                params
+                    .peer_scores
+                    .report(&sender_id, Misbehavior::InvalidLocalSig);
+            }
             #[cfg(feature = "dump")]
             dump_builder.build().unwrap().log();
             return prev_state.clone();
         }
     };
 
-    let completed_block = match submitblock(candidate_block, &signature, &params.rpc) {
+    let completed_block = match submitblock(candidate_block, &signature, prev_state, params) {
         Ok(block) => block,
         Err(e) => {
-            log::error!("block rejected by Tapyrus Core: {:?}", e);
+            log::error!("block was not accepted by Tapyrus Core: {:?}", e);
 
             #[cfg(feature = "dump")]
             dump_builder.build().unwrap().log();
@@ -188,19 +206,28 @@ fn store_received_local_sig(
     result
 }
 
-fn submitblock<T>(block: &Block, sig: &Signature, rpc: &std::sync::Arc<T>) -> Result<Block, Error>
+/// Adds the proof to the block and submits it. Returns the completed block only if it became the
+/// tip of the chain.
+pub(super) fn submitblock<T>(
+    block: &Block,
+    sig: &Signature,
+    prev_state: &NodeState,
+    params: &NodeParameters<T>,
+) -> Result<Block, Error>
 where
     T: TapyrusApi,
 {
     let sig_hex = Sign::format_signature(sig);
-    let new_block: Block = block.add_proof(hex::decode(sig_hex).unwrap());
-    match rpc.submitblock(&new_block) {
-        Ok(_) => Ok(new_block),
-        Err(e) => Err(e),
+    let new_block: Block = block.add_proof(hex::decode(sig_hex).unwrap())?;
+    audit_completedblock(&new_block, prev_state, params);
+    if let Err(e) = submit_and_confirm(&new_block, params) {
+        audit_not_accepted(&new_block, prev_state, params);
+        return Err(e);
     }
+    Ok(new_block)
 }
 
-fn broadcast_completedblock<C>(block: Block, own_id: &SignerID, conman: &C)
+pub(super) fn broadcast_completedblock<C>(block: Block, own_id: &SignerID, conman: &C)
 where
     C: ConnectionManager,
 {
@@ -227,9 +254,15 @@ pub struct Dump {
 }
 
 impl Dump {
+    /// Logs the dump as JSON which can be loaded in the tests. The JSON contains the secrets, so
+    /// it is logged only if the secrets are revealed. Otherwise the dump is logged redacted.
     #[allow(dead_code)]
     fn log(&self) {
-        log::debug!("Dump: {}", serde_json::to_string(self).unwrap());
+        if secret::is_revealed() {
+            log::debug!("Dump: {}", serde_json::to_string(self).unwrap());
+        } else {
+            log::debug!("Dump: {:?}", self);
+        }
     }
 }
 
@@ -237,7 +270,7 @@ impl Dump {
 pub struct Received {
     sender: SignerID,
     block_hash: SHA256Hash,
-    gamma_i: FE,
+    gamma_i: Secret<FE>,
     e: FE,
 }
 
@@ -248,6 +281,7 @@ mod tests {
     use crate::net::Message;
     use crate::signer_node::message_processor::process_blocksig::Dump;
     use crate::signer_node::*;
+    use crate::tests::helper::blocks::get_block;
     use crate::tests::helper::net::TestConnectionManager;
     use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
     use crate::tests::helper::rpc::MockRpc;
@@ -269,7 +303,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -298,7 +332,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -329,7 +363,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -381,7 +415,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -423,7 +457,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -467,7 +501,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -487,6 +521,7 @@ mod tests {
         //  - has block key,
         // then node should
         //  - call rpc submitblock
+        //  - confirm the block became the tip of the chain
         //  - send message `Completedblock`
         //  - return Master
 
@@ -494,8 +529,10 @@ mod tests {
         let dump: Dump =
             serde_json::from_value(contents["cases"]["process_blocksig_successfully"].clone())
                 .unwrap();
+        let completed_block = dump.completed_block.clone().unwrap();
         let mut rpc = MockRpc::new();
         rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&completed_block);
         let federations = vec![Federation::new(
             dump.public_key,
             0,
@@ -512,7 +549,7 @@ mod tests {
 
         let mut conman = TestConnectionManager::new();
         conman.should_broadcast(Message {
-            message_type: MessageType::Completedblock(dump.completed_block.unwrap().clone()),
+            message_type: MessageType::Completedblock(completed_block),
             sender_id: params.signer_id,
             receiver_id: None,
         });
@@ -520,7 +557,7 @@ mod tests {
         let next = process_blocksig(
             &dump.received.sender,
             dump.received.block_hash.clone(),
-            dump.received.gamma_i.clone(),
+            *dump.received.gamma_i,
             dump.received.e.clone(),
             &dump.prev_state,
             &conman,
@@ -541,4 +578,47 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_process_blocksig_not_accepted() {
+        // when the signed block doesn't become the tip of the chain, node should
+        //  - not send message `Completedblock`
+        //  - return Master whose round is not done
+        let contents = load_test_vector("./tests/resources/process_blocksig.json").unwrap();
+        let dump: Dump =
+            serde_json::from_value(contents["cases"]["process_blocksig_successfully"].clone())
+                .unwrap();
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&get_block(0));
+        let federations = Federations::new(vec![Federation::new(
+            dump.public_key,
+            0,
+            Some(dump.threshold as u8),
+            dump.node_vss.clone(),
+            dump.aggregated_public_key,
+        )]);
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .public_key(dump.public_key)
+            .federations(federations)
+            .build();
+        let conman = TestConnectionManager::new();
+
+        let next = process_blocksig(
+            &dump.received.sender,
+            dump.received.block_hash.clone(),
+            *dump.received.gamma_i,
+            dump.received.e.clone(),
+            &dump.prev_state,
+            &conman,
+            &params,
+        );
+        match next {
+            NodeState::Master { round_is_done, .. } => assert_eq!(round_is_done, false),
+            _ => panic!("NodeState should be Master"),
+        }
+        params.rpc.assert();
+        conman.assert();
+    }
 }
@@ -0,0 +1,356 @@
+use crate::blockdata::hash::SHA256Hash;
+use crate::crypto::frost::public_share;
+use crate::net::{ConnectionManager, SignerID};
+use crate::rpc::TapyrusApi;
+use crate::signer_node::message_processor::process_blocksig::{
+    broadcast_completedblock, submitblock,
+};
+use crate::signer_node::message_processor::{get_valid_block, signing_package};
+use crate::signer_node::node_state::builder::{Builder, Master};
+use crate::signer_node::peer_scores::Misbehavior;
+use crate::signer_node::utils::sender_index;
+use crate::signer_node::{NodeParameters, NodeState};
+use curv::{FE, GE};
+
+/// Collect the signature shares of FROST signing. When the master has the shares of all
+/// participants, it aggregates them and submits the block.
+pub fn process_blocksigshare<T, C>(
+    sender_id: &SignerID,
+    blockhash: SHA256Hash,
+    commitment: (GE, GE),
+    share: FE,
+    prev_state: &NodeState,
+    conman: &C,
+    params: &NodeParameters<T>,
+) -> NodeState
+where
+    T: TapyrusApi,
+    C: ConnectionManager,
+{
+    let (nonce_commitments, signature_shares, participants) = match prev_state {
+        NodeState::Master {
+            nonce_commitments,
+            signature_shares,
+            participants,
+            round_is_done: false,
+            ..
+        } => (nonce_commitments, signature_shares, participants),
+        _ => return prev_state.clone(),
+    };
+
+    // Ignore the message if the sender is not contained in the participants.
+    if !participants.contains(sender_id) {
+        return prev_state.clone();
+    }
+
+    // The share must be signed with the nonce which the master selected for the sender.
+    if nonce_commitments.get(sender_id) != Some(&commitment) {
+        log::warn!(
+            "Signature share is not for the selected nonce commitments. sender: {}",
+            sender_id
+        );
+        return prev_state.clone();
+    }
+
+    let candidate_block = match get_valid_block(prev_state, blockhash) {
+        Ok(block) => block,
+        Err(e) => {
+            log::warn!("{:?}", e);
+            return prev_state.clone();
+        }
+    };
+
+    let block_height = prev_state.block_height();
+    let package = match signing_package(blockhash, nonce_commitments.clone(), block_height, params)
+    {
+        Ok(package) => package,
+        Err(e) => {
+            log::error!("Invalid nonce commitments. {:?}", e);
+            return prev_state.clone();
+        }
+    };
+
+    let federation = params.get_federation_by_block_height(block_height);
+    let index = sender_index(sender_id, &params.pubkey_list(block_height));
+    let public_share = public_share(&federation.node_shared_secrets(), index + 1);
+    if let Err(e) = package.verify_share(sender_id, &share, &public_share) {
+        log::warn!("Invalid signature share. sender: {}, {:?}", sender_id, e);
+        params
+            .peer_scores
+            .report(sender_id, Misbehavior::InvalidLocalSig);
+        return prev_state.clone();
+    }
+
+    let mut state_builder = Master::from_node_state(prev_state.clone());
+    state_builder.insert_signature_share(*sender_id, share);
+
+    let mut shares = signature_shares.clone();
+    shares.insert(*sender_id, share);
+    log::trace!(
+        "number of signature shares: {:?} (participants: {:?})",
+        shares.len(),
+        participants.len()
+    );
+    if shares.len() < participants.len() {
+        return state_builder.build();
+    }
+
+    let signature = match package.aggregate(&shares) {
+        Ok(sig) => sig,
+        Err(e) => {
+            log::error!("aggregated signature is invalid. e: {:?}", e);
+            return prev_state.clone();
+        }
+    };
+
+    let completed_block = match submitblock(candidate_block, &signature, prev_state, params) {
+        Ok(block) => block,
+        Err(e) => {
+            log::error!("block was not accepted by Tapyrus Core: {:?}", e);
+            return state_builder.build();
+        }
+    };
+
+    log::info!(
+        "Round Success. candidateblock(block hash for sign)={:?}",
+        candidate_block.sighash(),
+    );
+
+    // send completeblock message
+    broadcast_completedblock(completed_block, &params.signer_id, conman);
+
+    state_builder.round_is_done(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::process_blocksigshare;
+    use crate::blockdata::hash::SHA256Hash;
+    use crate::crypto::frost::{create_nonce, NonceCommitments, SigningPackage};
+    use crate::federation::{Federation, Federations, SigningProtocol};
+    use crate::net::{Message, MessageType, SignerID};
+    use crate::sign::Sign;
+    use crate::signer_node::node_state::builder::{Builder, Master};
+    use crate::signer_node::{NodeParameters, NodeState};
+    use crate::tests::helper::blocks::get_block;
+    use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::net::TestConnectionManager;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::node_state_builder::BuilderForTest;
+    use crate::tests::helper::node_vss::node_vss;
+    use crate::tests::helper::rpc::MockRpc;
+    use curv::elliptic::curves::traits::ECScalar;
+    use curv::{BigInt, FE, GE};
+    use std::collections::BTreeMap;
+
+    const PARTICIPANTS: [usize; 3] = [0, 1, 3];
+
+    fn federation(index: usize) -> Federation {
+        Federation::new(
+            TEST_KEYS.pubkeys()[index],
+            0,
+            Some(3),
+            node_vss(index),
+            TEST_KEYS.aggregated(),
+        )
+        .with_signing_protocol(SigningProtocol::Frost)
+    }
+
+    fn params(rpc: MockRpc) -> NodeParameters<MockRpc> {
+        NodeParametersBuilder::new()
+            .public_key(TEST_KEYS.pubkeys()[0])
+            .federations(Federations::new(vec![federation(0)]))
+            .rpc(rpc)
+            .build()
+    }
+
+    fn blockhash() -> SHA256Hash {
+        get_block(0).sighash()
+    }
+
+    /// Returns the state of the master which has its own share, the signing package and the
+    /// shares of all participants.
+    fn setup() -> (NodeState, SigningPackage, BTreeMap<SignerID, FE>) {
+        let mut nonces = BTreeMap::new();
+        let mut commitments = NonceCommitments::new();
+        for i in PARTICIPANTS.iter() {
+            let (nonce, commitment) = create_nonce();
+            nonces.insert(TEST_KEYS.signer_ids()[*i], nonce);
+            commitments.insert(TEST_KEYS.signer_ids()[*i], commitment);
+        }
+        let package = SigningPackage::new(
+            blockhash(),
+            commitments.clone(),
+            &TEST_KEYS.signer_ids(),
+            federation(0).node_secret_share().y,
+        )
+        .unwrap();
+        let mut shares = BTreeMap::new();
+        for i in PARTICIPANTS.iter() {
+            let signer_id = TEST_KEYS.signer_ids()[*i];
+            let share = package
+                .sign(
+                    &signer_id,
+                    &nonces[&signer_id],
+                    &federation(*i).node_secret_share().x_i,
+                )
+                .unwrap();
+            shares.insert(signer_id, share);
+        }
+
+        let master_id = TEST_KEYS.signer_ids()[0];
+        let state = Master::for_test()
+            .participants(commitments.keys().cloned().collect())
+            .nonce_commitments(commitments)
+            .insert_signature_share(master_id, shares[&master_id])
+            .build();
+        (state, package, shares)
+    }
+
+    fn commitment(state: &NodeState, signer_id: &SignerID) -> (GE, GE) {
+        match state {
+            NodeState::Master {
+                nonce_commitments, ..
+            } => nonce_commitments[signer_id],
+            _ => panic!("NodeState should be Master"),
+        }
+    }
+
+    #[test]
+    fn test_process_blocksigshare_below_participants() {
+        let params = params(MockRpc::new());
+        let conman = TestConnectionManager::new();
+        let (prev_state, _, shares) = setup();
+        let sender_id = TEST_KEYS.signer_ids()[1];
+
+        let next = process_blocksigshare(
+            &sender_id,
+            blockhash(),
+            commitment(&prev_state, &sender_id),
+            shares[&sender_id],
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        match next {
+            NodeState::Master {
+                signature_shares,
+                round_is_done,
+                ..
+            } => {
+                assert_eq!(signature_shares.len(), 2);
+                assert!(!round_is_done);
+            }
+            _ => panic!("NodeState should be Master"),
+        }
+        params.rpc.assert();
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocksigshare_completes_round() {
+        let (mut state, package, shares) = setup();
+        let signature = package.aggregate(&shares).unwrap();
+        let proof = hex::decode(Sign::format_signature(&signature)).unwrap();
+        let completed_block = get_block(0).add_proof(proof).unwrap();
+
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&completed_block);
+        let params = params(rpc);
+        let mut conman = TestConnectionManager::new();
+        conman.should_broadcast(Message {
+            message_type: MessageType::Completedblock(completed_block),
+            sender_id: params.signer_id,
+            receiver_id: None,
+        });
+
+        for i in &[1, 3] {
+            let sender_id = TEST_KEYS.signer_ids()[*i];
+            state = process_blocksigshare(
+                &sender_id,
+                blockhash(),
+                commitment(&state, &sender_id),
+                shares[&sender_id],
+                &state,
+                &conman,
+                &params,
+            );
+        }
+
+        match state {
+            NodeState::Master { round_is_done, .. } => assert!(round_is_done),
+            _ => panic!("NodeState should be Master"),
+        }
+        params.rpc.assert();
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocksigshare_invalid_share() {
+        let params = params(MockRpc::new());
+        let conman = TestConnectionManager::new();
+        let (prev_state, _, shares) = setup();
+        let sender_id = TEST_KEYS.signer_ids()[1];
+        let one: FE = ECScalar::from(&BigInt::from(1));
+
+        let next = process_blocksigshare(
+            &sender_id,
+            blockhash(),
+            commitment(&prev_state, &sender_id),
+            shares[&sender_id] + one,
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        params.rpc.assert();
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocksigshare_with_other_nonce() {
+        let params = params(MockRpc::new());
+        let conman = TestConnectionManager::new();
+        let (prev_state, _, shares) = setup();
+        let sender_id = TEST_KEYS.signer_ids()[1];
+        let (_, other_commitment) = create_nonce();
+
+        let next = process_blocksigshare(
+            &sender_id,
+            blockhash(),
+            other_commitment,
+            shares[&sender_id],
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        params.rpc.assert();
+        conman.assert();
+    }
+
+    #[test]
+    fn test_process_blocksigshare_from_non_participant() {
+        let params = params(MockRpc::new());
+        let conman = TestConnectionManager::new();
+        let (prev_state, _, shares) = setup();
+
+        let next = process_blocksigshare(
+            &TEST_KEYS.signer_ids()[2],
+            blockhash(),
+            commitment(&prev_state, &TEST_KEYS.signer_ids()[1]),
+            shares[&TEST_KEYS.signer_ids()[1]],
+            &prev_state,
+            &conman,
+            &params,
+        );
+
+        assert_eq!(next, prev_state);
+        params.rpc.assert();
+        conman.assert();
+    }
+}
@@ -1,12 +1,14 @@
 use crate::blockdata::hash::SHA256Hash;
 use crate::blockdata::Block;
+use crate::crypto::backend::{validate_vss_share, DefaultBackend};
 use crate::errors::Error;
 use crate::net::{ConnectionManager, Message, MessageType, SignerID};
 use crate::rpc::TapyrusApi;
 use crate::signer_node::message_processor::{
-    broadcast_localsig, generate_local_sig, get_valid_block,
+    audit_localsig, broadcast_localsig, generate_local_sig, get_valid_block,
 };
 use crate::signer_node::node_state::builder::{Builder, Master, Member};
+use crate::signer_node::peer_scores::Misbehavior;
 use crate::signer_node::NodeParameters;
 use crate::signer_node::{BidirectionalSharedSecretMap, NodeState, SharedSecret};
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
@@ -56,6 +58,20 @@ where
         }
     };
 
+    // The secret shares for this signer must match with the commitments of the VSSs.
+    let index = params.self_node_index(prev_state.block_height()) + 1;
+    let is_valid = |secret: &SharedSecret| {
+        validate_vss_share::<DefaultBackend>(&secret.vss, &secret.secret_share, index)
+    };
+    let (for_positive, for_negative) = &new_shared_block_secrets[sender_id];
+    if !is_valid(for_positive) || !is_valid(for_negative) {
+        log::warn!("Invalid block VSS. sender: {}", sender_id);
+        params
+            .peer_scores
+            .report(sender_id, Misbehavior::InvalidBlockVss);
+        return prev_state.clone();
+    }
+
     match prev_state {
         NodeState::Master {
             participants,
@@ -92,13 +108,24 @@ where
                     }
                 };
 
+                // The round is abandoned if the local signature can't be recorded.
+                if audit_localsig(
+                    candidate_block.sighash(),
+                    &local_sig.gamma_i,
+                    &participants,
+                    prev_state,
+                    params,
+                )
+                .is_err()
+                {
+                    return prev_state.clone();
+                }
                 broadcast_blockparticipants(
                     &participants,
                     candidate_block,
                     conman,
                     &params.signer_id,
                 );
-
                 broadcast_localsig(
                     candidate_block.sighash(),
                     &local_sig,
@@ -138,6 +165,17 @@ where
                     }
                 };
 
+                if audit_localsig(
+                    candidate_block.sighash(),
+                    &local_sig.gamma_i,
+                    participants,
+                    prev_state,
+                    params,
+                )
+                .is_err()
+                {
+                    return prev_state.clone();
+                }
                 broadcast_localsig(
                     candidate_block.sighash(),
                     &local_sig,
@@ -215,11 +253,11 @@ fn store_received_vss(
         (
             SharedSecret {
                 vss: vss_for_positive.clone(),
-                secret_share: secret_share_for_positive,
+                secret_share: secret_share_for_positive.into(),
             },
             SharedSecret {
                 vss: vss_for_negative.clone(),
-                secret_share: secret_share_for_negative,
+                secret_share: secret_share_for_negative.into(),
             },
         ),
     );
@@ -232,6 +270,7 @@ mod tests {
     use super::process_blockvss;
     use crate::blockdata::hash::SHA256Hash;
     use crate::crypto::multi_party_schnorr::LocalSig;
+    use crate::crypto::secret::Secret;
     use crate::net::SignerID;
     use crate::signer_node::node_state::builder::{Builder, Master, Member};
     use crate::signer_node::*;
@@ -630,13 +669,13 @@ mod tests {
         NodeParameters<MockRpc>,
         HashSet<SignerID>,
         Option<LocalSig>,
-        Option<(bool, FE, GE)>,
+        Option<(bool, Secret<FE>, GE)>,
     ) {
         let v = &contents["cases"][case];
 
         let params = to_node_parameters(&v, rpc);
 
-        let block_key: Option<FE> = serde_json::from_value(v["block_key"].clone()).unwrap();
+        let block_key: Option<Secret<FE>> = serde_json::from_value(v["block_key"].clone()).unwrap();
         let block = to_block(&v["candidate_block"]);
 
         let sender = to_signer_id(&v["received"]["sender"].as_str().unwrap().to_string());
@@ -1,11 +1,14 @@
 use crate::blockdata::Block;
 use crate::errors::Error;
+use crate::federation::SigningProtocol;
 use crate::net::{ConnectionManager, SignerID};
 use crate::rpc::TapyrusApi;
 use crate::signer_node::message_processor::create_block_vss;
 use crate::signer_node::node_state::builder::{Builder, Member};
+use crate::signer_node::peer_scores::Misbehavior;
 use crate::signer_node::utils::sender_index;
 use crate::signer_node::{NodeParameters, NodeState};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn process_candidateblock<T, C>(
     sender_id: &SignerID,
@@ -31,6 +34,9 @@ where
                     sender_id,
                     block,
                 );
+                params
+                    .peer_scores
+                    .report(sender_id, Misbehavior::InvalidCandidate);
                 return prev_state.clone();
             }
 
@@ -45,16 +51,56 @@ where
                     sender_id,
                     e
                 );
+                // The failure to reach Tapyrus Core is not the fault of the master.
+                if let Error::JsonRpc(jsonrpc::error::Error::Rpc(_)) = e {
+                    params
+                        .peer_scores
+                        .report(sender_id, Misbehavior::InvalidCandidate);
+                }
                 return prev_state.clone();
             }
 
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time should be after the UNIX epoch")
+                .as_secs();
+            if let Err(e) = params.block_policy.check(block, now) {
+                log::warn!(
+                    "Decline the candidate block which violates the block policy. sender: {}, {:?}",
+                    sender_id,
+                    e
+                );
+                return prev_state.clone();
+            }
+
+            if let Some(approval_hook) = &params.approval_hook {
+                if !approval_hook.approve(block, *block_height + 1) {
+                    log::warn!(
+                        "Decline the candidate block which is not approved. sender: {}",
+                        sender_id
+                    );
+                    return prev_state.clone();
+                }
+            }
+
+            let master_index = sender_index(sender_id, &params.pubkey_list(*block_height));
+
+            // In FROST signing, the nonce commitments are already published, so the member waits
+            // for the master to select the participants instead of sending the VSSs.
+            if params.signing_protocol(*block_height) == SigningProtocol::Frost {
+                return Member::from_node_state(prev_state.clone())
+                    .candidate_block(Some(block.clone()))
+                    .master_index(master_index)
+                    .build();
+            }
+
             let (key, shared_secret_for_positive, shared_secret_for_negative) =
                 create_block_vss(block.clone(), params, conman, *block_height);
 
             Member::from_node_state(prev_state.clone())
                 .block_key(Some(key.u_i))
                 .candidate_block(Some(block.clone()))
-                .master_index(sender_index(sender_id, &params.pubkey_list(*block_height)))
+                .master_index(master_index)
                 .insert_shared_block_secrets(
                     params.signer_id.clone(),
                     shared_secret_for_positive,
@@ -75,14 +121,12 @@ where
     T: TapyrusApi,
 {
     match block.get_xfield_type() {
-        0 | 1 => {}
+        0 | 1 | 2 => {}
         _ => return Err(Error::UnsupportedXField),
     }
 
-    // validate length of xfield
-    block.get_xfield_length()?;
-
-    verify_aggregated_public_key(block, block_height, params)
+    verify_aggregated_public_key(block, block_height, params)?;
+    verify_max_block_size(block, block_height, params)
 }
 
 fn verify_aggregated_public_key<T>(
@@ -104,7 +148,7 @@ where
             Err(Error::InvalidAggregatedPublicKey)
         }
     } else {
-        if next_block_height == federation.block_height() {
+        if params.is_aggregated_public_key_changed_at(next_block_height) {
             Err(Error::InvalidAggregatedPublicKey)
         } else {
             Ok(())
@@ -112,12 +156,36 @@ where
     }
 }
 
+fn verify_max_block_size<T>(
+    block: &Block,
+    block_height: u64,
+    params: &NodeParameters<T>,
+) -> Result<(), Error>
+where
+    T: TapyrusApi,
+{
+    let next_block_height = block_height + 1;
+    let is_changed = params.is_max_block_size_changed_at(next_block_height);
+    match block.get_max_block_size() {
+        Some(max_block_size)
+            if is_changed && params.max_block_size(next_block_height) == Some(max_block_size) =>
+        {
+            Ok(())
+        }
+        Some(_) => Err(Error::InvalidMaxBlockSize),
+        None if is_changed => Err(Error::InvalidMaxBlockSize),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::blockdata::Block;
-    use crate::federation::{Federation, Federations};
+    use crate::federation::{Federation, Federations, SigningProtocol};
     use crate::net::{Message, MessageType, SignerID};
+    use crate::signer_node::approval::{ApprovalEndpoint, ApprovalHook};
+    use crate::signer_node::block_policy::BlockPolicy;
     use crate::signer_node::node_state::builder::{Builder, Master, Member};
     use crate::signer_node::{master_index, NodeState};
     use crate::tests::helper::blocks::get_block;
@@ -129,6 +197,7 @@ mod tests {
     use crate::tests::helper::rpc::MockRpc;
     use bitcoin::PublicKey;
     use std::str::FromStr;
+    use std::time::Duration;
 
     fn sender_id() -> SignerID {
         TEST_KEYS.signer_ids()[2]
@@ -174,6 +243,105 @@ mod tests {
         params.rpc.assert();
     }
 
+    #[test]
+    fn test_as_member_with_frost() {
+        let sender_id = sender_id();
+        let candidate_block = get_block(0);
+        let prev_state = Member::for_test().build();
+        let conman = TestConnectionManager::new();
+        let mut rpc = MockRpc::new();
+        rpc.should_call_testproposedblock(Ok(true));
+        let federation = Federation::new(
+            TEST_KEYS.pubkeys()[4],
+            0,
+            Some(3),
+            node_vss(4),
+            TEST_KEYS.aggregated(),
+        )
+        .with_signing_protocol(SigningProtocol::Frost);
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .federations(Federations::new(vec![federation]))
+            .build();
+
+        let next_state =
+            process_candidateblock(&sender_id, &candidate_block, &prev_state, &conman, &params);
+
+        // It should set candidate_block into return state.
+        match next_state {
+            NodeState::Member {
+                candidate_block: Some(block),
+                nonce_commitments,
+                ..
+            } => {
+                assert_eq!(candidate_block, block);
+                assert!(nonce_commitments.is_empty());
+            }
+            _ => panic!("NodeState should be Member with the candidate block"),
+        }
+
+        // It should not send any message, because the nonce commitments are already published.
+        assert_eq!(conman.sent.borrow().len(), 0);
+        assert_eq!(conman.broadcasted.borrow().len(), 0);
+
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_as_member_with_policy_violation() {
+        let sender_id = sender_id();
+        let candidate_block = get_block(0);
+        let prev_state = Member::for_test().build();
+        let conman = TestConnectionManager::new();
+        let mut rpc = MockRpc::new();
+        rpc.should_call_testproposedblock(Ok(true));
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .build()
+            .with_block_policy(BlockPolicy {
+                max_block_size: Some(100),
+                ..BlockPolicy::default()
+            });
+
+        let next_state =
+            process_candidateblock(&sender_id, &candidate_block, &prev_state, &conman, &params);
+
+        // It should decline to take part in the round.
+        assert_eq!(prev_state, next_state);
+        assert_eq!(conman.sent.borrow().len(), 0);
+
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_as_member_without_approval() {
+        let sender_id = sender_id();
+        let candidate_block = get_block(0);
+        let prev_state = Member::for_test().build();
+        let conman = TestConnectionManager::new();
+        let mut rpc = MockRpc::new();
+        rpc.should_call_testproposedblock(Ok(true));
+        // The approval system is not running, and the hook is fail-closed.
+        let approval_hook = ApprovalHook::new(
+            ApprovalEndpoint::parse("unix:/tmp/tapyrus_signer_test_no_approval.sock").unwrap(),
+            Duration::from_secs(1),
+            false,
+        );
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .build()
+            .with_approval_hook(Some(approval_hook));
+
+        let next_state =
+            process_candidateblock(&sender_id, &candidate_block, &prev_state, &conman, &params);
+
+        // It should decline to take part in the round.
+        assert_eq!(prev_state, next_state);
+        assert_eq!(conman.sent.borrow().len(), 0);
+
+        params.rpc.assert();
+    }
+
     /// This is a case of receiving own candidateblock message.
     /// It should ignore the message because it is from myself and already have.
     #[test]
@@ -198,7 +366,7 @@ mod tests {
     fn test_as_master_with_invalid_block() {
         let sender_id = sender_id();
         // invalid block
-        let candidate_block = Block::new(hex::decode("00000020ed658cc40670cceda23bb0b614821fe6d48a41d107d19f3f3a5608ad3d483092b151160ab71133b428e1f62eaeb598ae858ff66017c99601f29088b7c64a481d6284e145d29b70bf54392d29701031d2af9fed5f9bb21fbb284fa71ceb238f69a6d4095d00010200000000010100000000000000000000000000000000000000000000000000000000000000000c000000035c0101ffffffff0200f2052a010000001976a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac0000000000000000266a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf90120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap());
+        let candidate_block = Block::new(hex::decode("00000020ed658cc40670cceda23bb0b614821fe6d48a41d107d19f3f3a5608ad3d483092b151160ab71133b428e1f62eaeb598ae858ff66017c99601f29088b7c64a481d6284e145d29b70bf54392d29701031d2af9fed5f9bb21fbb284fa71ceb238f69a6d4095d00010200000000010100000000000000000000000000000000000000000000000000000000000000000c000000035c0101ffffffff0200f2052a010000001976a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac0000000000000000266a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf90120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap()).unwrap();
         let prev_state = Master::for_test().build();
         let conman = TestConnectionManager::new();
         let rpc = MockRpc::new();
@@ -221,7 +389,7 @@ mod tests {
     fn test_as_member_with_invalid_block() {
         let sender_id = sender_id();
         // invalid block
-        let candidate_block = Block::new(hex::decode("00000020ed658cc40670cceda23bb0b614821fe6d48a41d107d19f3f3a5608ad3d483092b151160ab71133b428e1f62eaeb598ae858ff66017c99601f29088b7c64a481d6284e145d29b70bf54392d29701031d2af9fed5f9bb21fbb284fa71ceb238f69a6d4095d00010200000000010100000000000000000000000000000000000000000000000000000000000000000c000000035c0101ffffffff0200f2052a010000001976a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac0000000000000000266a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf90120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap());
+        let candidate_block = Block::new(hex::decode("00000020ed658cc40670cceda23bb0b614821fe6d48a41d107d19f3f3a5608ad3d483092b151160ab71133b428e1f62eaeb598ae858ff66017c99601f29088b7c64a481d6284e145d29b70bf54392d29701031d2af9fed5f9bb21fbb284fa71ceb238f69a6d4095d00010200000000010100000000000000000000000000000000000000000000000000000000000000000c000000035c0101ffffffff0200f2052a010000001976a914cf12dbc04bb0de6fb6a87a5aeb4b2e74c97006b288ac0000000000000000266a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf90120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap()).unwrap();
         let prev_state = Member::for_test().build();
         let conman = TestConnectionManager::new();
         let mut rpc = MockRpc::new();
@@ -291,12 +459,12 @@ mod tests {
 
     fn test_block_with_public_key() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITH_PUBKEY).unwrap();
-        Block::new(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     fn test_block_without_public_key() -> Block {
         let raw_block = hex::decode(TEST_BLOCK_WITHOUT_PUBKEY).unwrap();
-        Block::new(raw_block)
+        Block::new(raw_block).unwrap()
     }
     #[test]
     fn test_verify_aggregated_public_key() {
@@ -345,10 +513,52 @@ mod tests {
         let block = test_block_with_public_key();
         assert!(verify_aggregated_public_key(&block, 199, &params).is_err());
 
+        // federation100 has the same aggregated public key as federation0.
         let block = test_block_without_public_key();
-        assert!(verify_aggregated_public_key(&block, 99, &params).is_err());
+        assert!(verify_aggregated_public_key(&block, 99, &params).is_ok());
 
         let block = test_block_without_public_key();
         assert!(verify_aggregated_public_key(&block, 100, &params).is_ok());
+
+        let block = test_block_without_public_key();
+        assert!(verify_aggregated_public_key(&block, 199, &params).is_err());
+    }
+
+    #[test]
+    fn test_verify_max_block_size() {
+        let federation = |block_height: u64, max_block_size: Option<u32>| {
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                block_height,
+                Some(3),
+                node_vss(0),
+                TEST_KEYS.aggregated(),
+            )
+            .with_max_block_size(max_block_size)
+        };
+        let federations = Federations::new(vec![
+            federation(0, None),
+            federation(100, Some(2_000_000)),
+            federation(200, Some(2_000_000)),
+        ]);
+        let params = NodeParametersBuilder::new()
+            .public_key(TEST_KEYS.pubkeys()[2])
+            .rpc(MockRpc::new())
+            .federations(federations)
+            .build();
+
+        let block = test_block_without_public_key().add_max_block_size(2_000_000);
+        assert!(verify_max_block_size(&block, 99, &params).is_ok());
+        assert!(verify_max_block_size(&block, 100, &params).is_err());
+        // federation200 has the same max block size as federation100.
+        assert!(verify_max_block_size(&block, 199, &params).is_err());
+
+        let block = test_block_without_public_key().add_max_block_size(4_000_000);
+        assert!(verify_max_block_size(&block, 99, &params).is_err());
+
+        let block = test_block_without_public_key();
+        assert!(verify_max_block_size(&block, 99, &params).is_err());
+        assert!(verify_max_block_size(&block, 100, &params).is_ok());
+        assert!(verify_max_block_size(&block, 199, &params).is_ok());
     }
 }
@@ -1,6 +1,9 @@
 use crate::blockdata::Block;
 use crate::net::SignerID;
 use crate::rpc::TapyrusApi;
+use crate::signer_node::confirmation::submit_and_confirm;
+use crate::signer_node::message_processor::{audit_completedblock, audit_not_accepted};
+use crate::signer_node::peer_scores::Misbehavior;
 use crate::signer_node::{is_master, master_index, next_master_index, NodeParameters, NodeState};
 
 pub fn process_completedblock<T>(
@@ -14,12 +17,24 @@ where
 {
     if !is_master(sender_id, prev_state, params) {
         log::warn!("Peer {} may be malicious node. It might impersonate as master or your node might be behind from others.", sender_id);
+        // The node doesn't know the master unless a round is in progress.
+        match prev_state {
+            NodeState::Master { .. } | NodeState::Member { .. } => params
+                .peer_scores
+                .report(sender_id, Misbehavior::ImpersonateMaster),
+            _ => {}
+        }
         return prev_state.clone(); // Ignore message
     }
 
-    if let Err(e) = params.rpc.submitblock(block) {
+    audit_completedblock(block, prev_state, params);
+
+    // The block might have been relayed already via Tapyrus network, so the failure of
+    // submitblock is not an error as long as the block becomes the tip of the chain.
+    if let Err(e) = submit_and_confirm(block, params) {
+        audit_not_accepted(block, prev_state, params);
         log::warn!(
-            "The node got invalid completed block or it was already relayed via Tapyrus network. from-peer: {}, block: {:?}, rpc error: {:?}",
+            "The node got invalid completed block. from-peer: {}, block: {:?}, error: {:?}",
             sender_id,
             block,
             e
@@ -39,6 +54,7 @@ mod tests {
     use super::process_completedblock;
     use crate::errors::Error;
     use crate::net::SignerID;
+    use crate::signer_node::audit_log::{verify, AuditLog};
     use crate::signer_node::node_state::builder::{Builder, Member};
     use crate::signer_node::{master_index, NodeState};
     use crate::tests::helper::blocks::get_block;
@@ -52,6 +68,7 @@ mod tests {
         let block = get_block(0);
         let mut rpc = MockRpc::new();
         rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&block);
         let params = NodeParametersBuilder::new().rpc(rpc).build();
 
         // check 1, next_master_index should be incremented after process completeblock message.
@@ -71,6 +88,7 @@ mod tests {
         // check 2, next master index should be back to 0 if the previous master index is the last number.
         let mut rpc = MockRpc::new();
         rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&block);
         let params = NodeParametersBuilder::new().rpc(rpc).build();
         let prev_state = Member::for_test().master_index(4).build();
         let sender_id = SignerID::new(TEST_KEYS.pubkeys()[4]);
@@ -97,6 +115,7 @@ mod tests {
                 data: None,
             },
         ))));
+        rpc.should_return_tip(&get_block(1));
         let params = NodeParametersBuilder::new().rpc(rpc).build();
 
         let prev_state = Member::for_test().master_index(0).build();
@@ -113,6 +132,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_process_completedblock_with_audit_log() {
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-signer-completedblock-audit-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&block);
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .build()
+            .with_audit_log(Some(
+                AuditLog::open(path.clone(), TEST_KEYS.key[4]).unwrap(),
+            ));
+
+        let prev_state = Member::for_test().master_index(0).build();
+        let sender_id = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        process_completedblock(&sender_id, &block, &prev_state, &params);
+
+        params.rpc.assert();
+        assert_eq!(verify(&path, &TEST_KEYS.pubkeys()[4]).unwrap(), 1);
+        let record: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(record["event"], "completed_block");
+        assert_eq!(record["master"], TEST_KEYS.pubkeys()[0].to_string());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_process_completedblock_not_accepted_with_audit_log() {
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-signer-not-accepted-audit-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let block = get_block(0);
+        let mut rpc = MockRpc::new();
+        rpc.should_call_submitblock(Ok(()));
+        rpc.should_return_tip(&get_block(1));
+        let params = NodeParametersBuilder::new()
+            .rpc(rpc)
+            .build()
+            .with_audit_log(Some(
+                AuditLog::open(path.clone(), TEST_KEYS.key[4]).unwrap(),
+            ));
+
+        let prev_state = Member::for_test().master_index(0).build();
+        let sender_id = SignerID::new(TEST_KEYS.pubkeys()[0]);
+        process_completedblock(&sender_id, &block, &prev_state, &params);
+
+        params.rpc.assert();
+        assert_eq!(verify(&path, &TEST_KEYS.pubkeys()[4]).unwrap(), 2);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let record: serde_json::Value =
+            serde_json::from_str(contents.lines().last().unwrap()).unwrap();
+        assert_eq!(record["event"], "not_accepted");
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_process_completedblock_ignore_different_master() {
         let block = get_block(0);
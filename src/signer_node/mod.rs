@@ -2,25 +2,47 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
+pub mod admin;
+pub mod approval;
+pub mod attestation;
+pub mod audit_log;
+pub mod block_policy;
+pub mod confirmation;
+pub mod equivocation;
 mod message_processor;
 pub mod node_parameters;
 pub mod node_state;
+pub mod nonce_pool;
+pub mod peer_scores;
 pub mod utils;
 
 pub use crate::signer_node::node_parameters::NodeParameters;
 pub use crate::signer_node::node_state::NodeState;
 
+use crate::blockdata::hash::SHA256Hash;
 use crate::blockdata::Block;
+use crate::crypto::secret::Secret;
 use crate::errors::Error;
-use crate::net::{ConnectionManager, Message, MessageType, SignerID};
+use crate::federation::SigningProtocol;
+use crate::keygen::repair::{RepairHelper, RepairPieces};
+use crate::keygen::{send_all, RESEND_INTERVAL_SECS};
+use crate::net::{ConnectionManager, Envelope, Message, MessageType, Signature, SignerID};
 use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
+use crate::signer_node::admin::{AdminCall, AdminRequest, AdminResponse};
+use crate::signer_node::attestation::Attestations;
+use crate::signer_node::equivocation::EquivocationDetector;
 use crate::signer_node::message_processor::create_block_vss;
+use crate::signer_node::message_processor::process_blockcommitments;
+use crate::signer_node::message_processor::process_blocknonces;
 use crate::signer_node::message_processor::process_blockparticipants;
 use crate::signer_node::message_processor::process_blocksig;
+use crate::signer_node::message_processor::process_blocksigshare;
 use crate::signer_node::message_processor::process_blockvss;
 use crate::signer_node::message_processor::process_candidateblock;
 use crate::signer_node::message_processor::process_completedblock;
+use crate::signer_node::message_processor::select_participants;
 use crate::signer_node::node_state::builder::{Builder, Master, Member};
+use crate::signer_node::peer_scores::Misbehavior;
 use crate::timer::RoundTimeOutObserver;
 
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
@@ -29,12 +51,16 @@ use redis::ControlFlow;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Round interval.
 pub static ROUND_INTERVAL_DEFAULT_SECS: u64 = 60;
 /// Round time limit delta. Round timeout timer should be little longer than `ROUND_INTERVAL_DEFAULT_SECS`.
 static ROUND_TIMELIMIT_DELTA: u64 = 10;
+/// Seconds to keep a session to help the repair of other signer's node VSS. It is longer than
+/// `REPAIR_REQUEST_TIMEOUT_SECS`, so that the request is already expired when it is replayed after
+/// the session is dropped.
+static REPAIR_SESSION_TIMEOUT_SECS: u64 = 600;
 
 pub struct SignerNode<T: TapyrusApi, C: ConnectionManager> {
     connection_manager: C,
@@ -49,12 +75,24 @@ pub struct SignerNode<T: TapyrusApi, C: ConnectionManager> {
     /// * New round is started on only receiving completedblock message
     ///   or previous round is timeout.
     round_timer: RoundTimeOutObserver,
+    /// Sessions to help other signers to repair their node VSS, for each target signer. A session
+    /// is kept until it expires, because the target may not receive the messages at first.
+    repair_helpers: BTreeMap<SignerID, (Instant, RepairHelper)>,
+    repair_resent_at: Instant,
+    equivocation_detector: EquivocationDetector,
+    /// The candidate block which this signer proposed as the master. It is proposed again in the
+    /// later rounds on the same previous block, because the honest master never signs two
+    /// different candidate blocks on a previous block.
+    proposed_candidate: Option<Block>,
+    /// The requests from the admin interface.
+    admin_calls: Option<Receiver<AdminCall>>,
+    attestations: Attestations,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SharedSecret {
     pub vss: VerifiableSS,
-    pub secret_share: FE,
+    pub secret_share: Secret<FE>,
 }
 
 pub type SharedSecretMap = BTreeMap<SignerID, SharedSecret>;
@@ -98,7 +136,7 @@ pub trait ToShares {
 
 impl ToShares for SharedSecretMap {
     fn to_shares(&self) -> Vec<FE> {
-        self.values().map(|i| i.secret_share).collect()
+        self.values().map(|i| *i.secret_share).collect()
     }
 }
 
@@ -116,6 +154,12 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
             current_state: NodeState::Joining,
             stop_signal: None,
             round_timer: RoundTimeOutObserver::new("round_timer", timer_limit),
+            repair_helpers: BTreeMap::new(),
+            repair_resent_at: Instant::now(),
+            equivocation_detector: EquivocationDetector::new(),
+            proposed_candidate: None,
+            admin_calls: None,
+            attestations: Attestations::new(),
         }
     }
 
@@ -123,16 +167,23 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         self.stop_signal = Some(receiver);
     }
 
-    pub fn start(&mut self) {
+    pub fn admin_handler(&mut self, receiver: Receiver<AdminCall>) {
+        self.admin_calls = Some(receiver);
+    }
+
+    /// Runs the rounds until the stop signal. Returns the error if the federations don't match
+    /// with the chain.
+    pub fn start(&mut self) -> Result<(), Error> {
         if !self.params.skip_waiting_ibd {
             self.wait_for_ibd_finish(std::time::Duration::from_secs(10));
         } else {
             log::info!("Skip waiting for ibd finish.")
         }
+        self.verify_activated_federations(Duration::from_secs(10))?;
 
         log::info!("Start thread for redis subscription");
-        let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
-        let closure = move |message: Message| match sender.send(message) {
+        let (sender, receiver): (Sender<Envelope>, Receiver<Envelope>) = channel();
+        let closure = move |envelope: Envelope| match sender.send(envelope) {
             Ok(_) => ControlFlow::Continue,
             Err(error) => {
                 log::warn!("Happened error!: {:?}", error);
@@ -150,7 +201,7 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
 
         // Start First Round
         log::info!("Start block creation rounds.");
-        self.start_next_round(INITIAL_MASTER_INDEX);
+        self.start_next_round(INITIAL_MASTER_INDEX)?;
 
         // get error_handler that is for catch error within connection_manager.
         let connection_manager_error_handler = self.connection_manager.error_handler();
@@ -179,12 +230,15 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
             }
 
             // Receiving message.
-            match receiver.try_recv() {
-                Ok(Message {
+            match receiver
+                .try_recv()
+                .map(|envelope| self.check_equivocation(envelope))
+            {
+                Ok(Some(Message {
                     message_type,
                     sender_id,
                     ..
-                }) => {
+                })) => {
                     log::debug!(
                         "Got {} message from {:?}. MessageType: {:?}",
                         message_type,
@@ -200,11 +254,14 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                     } = &self.current_state
                     {
                         let v = *next_master_index;
-                        self.start_next_round(v)
+                        self.start_next_round(v)?;
                     }
 
                     log::debug!("Current state updated as {:?}", self.current_state);
                 }
+                Ok(None) => {
+                    // Invalid message. Do nothing.
+                }
                 Err(TryRecvError::Empty) => {
                     // No new messages. Do nothing.
                 }
@@ -216,7 +273,7 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 Ok(_) => {
                     // Round duration is timeout. Starting next round.
                     let next_master_index = next_master_index(&self.current_state, &self.params);
-                    self.start_next_round(next_master_index);
+                    self.start_next_round(next_master_index)?;
                     log::debug!("Current state updated as {:?}", self.current_state);
                 }
                 Err(TryRecvError::Empty) => {
@@ -244,9 +301,112 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 }
             }
 
+            self.resend_repair_messages();
+            self.process_admin_calls();
+            self.attestations.expire();
+
             // Wait for next loop 300 ms.
             std::thread::sleep(Duration::from_millis(300));
         }
+        Ok(())
+    }
+
+    /// Returns the message in the envelope, after recording the evidence if the sender equivocates.
+    /// It also records whether the message is signed by the sender, so that the faults are scored
+    /// only for the signed messages. The signed candidate block from the master is relayed to the
+    /// other signers, and the relayed one is only checked for the equivocation.
+    fn check_equivocation(&mut self, envelope: Envelope) -> Option<Message> {
+        let message = match envelope.message() {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("Invalid message is received. {:?}", e);
+                return None;
+            }
+        };
+        let signed = envelope.verify().is_ok();
+        self.params
+            .peer_scores
+            .observe_message(&message.sender_id, signed);
+
+        match &message.message_type {
+            MessageType::Candidaterelay(relayed) => {
+                if let Ok(relayed_message) = relayed.verify() {
+                    self.params
+                        .peer_scores
+                        .observe_message(&relayed_message.sender_id, true);
+                    self.observe_for_equivocation(relayed);
+                }
+                return None;
+            }
+            MessageType::Candidateblock(_)
+                if signed && message.sender_id != self.params.signer_id =>
+            {
+                self.connection_manager.broadcast_message(Message {
+                    message_type: MessageType::Candidaterelay(envelope.clone()),
+                    sender_id: self.params.signer_id,
+                    receiver_id: None,
+                });
+            }
+            _ => {}
+        }
+        self.observe_for_equivocation(&envelope);
+        Some(message)
+    }
+
+    /// Records the evidence if the sender of the signed message equivocates.
+    fn observe_for_equivocation(&mut self, envelope: &Envelope) {
+        let block_height = self.current_state.block_height() + 1;
+        if let Some(evidence) = self.equivocation_detector.observe(envelope, block_height) {
+            log::error!(
+                "Equivocation is detected. offender: {}, kind: {:?}, block height: {}",
+                evidence.offender,
+                evidence.kind,
+                block_height
+            );
+            self.params
+                .peer_scores
+                .report(&evidence.offender, Misbehavior::Equivocation);
+            if let Some(dir) = &self.params.evidence_dir {
+                match evidence.write(dir) {
+                    Ok(path) => log::error!("The evidence is written to {:?}", path),
+                    Err(e) => log::error!("Failed to write the evidence. {:?}", e),
+                }
+            }
+        }
+    }
+
+    /// Responds to the requests from the admin interface.
+    fn process_admin_calls(&mut self) {
+        let calls: Vec<AdminCall> = match &self.admin_calls {
+            Some(receiver) => receiver.try_iter().collect(),
+            None => return,
+        };
+        for call in calls {
+            let response = match call.request.clone() {
+                AdminRequest::PeerScores => {
+                    AdminResponse::PeerScores(self.params.peer_scores.status())
+                }
+                AdminRequest::ApproveAttestation { domain, digest } => {
+                    match self.attestations.approve(&domain, digest) {
+                        Ok(message) => AdminResponse::Approved(message),
+                        Err(e) => AdminResponse::Error(format!("{:?}", e)),
+                    }
+                }
+                AdminRequest::SignAttestation { domain, digest } => {
+                    // The call is responded when the signature is completed.
+                    self.attestations.sign(
+                        call,
+                        &domain,
+                        digest,
+                        self.current_state.block_height(),
+                        &self.params,
+                        &self.connection_manager,
+                    );
+                    continue;
+                }
+            };
+            call.respond(response);
+        }
     }
 
     /// Signer Node waits for connected Tapyrus Core Node complete IBD(Initial Block Download).
@@ -280,6 +440,34 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         }
     }
 
+    /// Signer Node refuses to run if the federations which have already been activated in the
+    /// chain don't match with the chain. It retries at the interval while Tapyrus Core can't be
+    /// reached.
+    fn verify_activated_federations(&self, interval: Duration) -> Result<(), Error> {
+        loop {
+            let result = self
+                .params
+                .rpc
+                .getblockchaininfo()
+                .and_then(|info| self.params.verify_activated_federations(info.blocks));
+            match result {
+                Err(Error::RpcConnection(e)) => {
+                    log::warn!(
+                        "Failed to verify the federations with Tapyrus Core. Retry in {} secs. {}",
+                        interval.as_secs(),
+                        e
+                    );
+                    std::thread::sleep(interval);
+                }
+                Err(e) => {
+                    log::error!("The federations don't match with the chain. {:?}", e);
+                    return Err(e);
+                }
+                Ok(()) => return Ok(()),
+            }
+        }
+    }
+
     pub fn start_new_round(&mut self, block_height: u64) -> NodeState {
         std::thread::sleep(Duration::from_secs(self.params.round_duration));
 
@@ -299,6 +487,14 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         }
 
         let block = self.add_aggregated_public_key_if_needed(block_height, block);
+        let block = self.add_max_block_size_if_needed(block_height, block);
+        let block = match self.proposed_candidate.take() {
+            Some(proposed) if proposed.header().prev_blockhash == block.header().prev_blockhash => {
+                proposed
+            }
+            _ => block,
+        };
+        self.proposed_candidate = Some(block.clone());
         log::info!(
             "Broadcast candidate block. block hash for signing: {:?}",
             block.sighash()
@@ -309,6 +505,16 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
             receiver_id: None,
         });
 
+        // In FROST signing, the master selects the participants from the nonce commitments
+        // which the signers published ahead of the round.
+        if self.params.signing_protocol(block_height) == SigningProtocol::Frost {
+            let state = Master::default()
+                .candidate_block(Some(block))
+                .block_height(block_height)
+                .build();
+            return select_participants(&state, &self.connection_manager, &self.params);
+        }
+
         let (keys, shared_secret_for_positive, shared_secret_for_negative) = create_block_vss(
             block.clone(),
             &self.params,
@@ -336,10 +542,12 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
 
     fn add_aggregated_public_key_if_needed(&self, block_height: u64, block: Block) -> Block {
         let next_block_height = block_height + 1;
-        let federation = self
+        // The federation which only refreshed the node secret shares keeps the same aggregated
+        // public key, so the block doesn't need to have it.
+        if self
             .params
-            .get_federation_by_block_height(next_block_height);
-        if federation.block_height() == next_block_height {
+            .is_aggregated_public_key_changed_at(next_block_height)
+        {
             let aggregated_public_key = self.params.aggregated_public_key(next_block_height);
             block.add_aggregated_public_key(aggregated_public_key)
         } else {
@@ -347,6 +555,16 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         }
     }
 
+    fn add_max_block_size_if_needed(&self, block_height: u64, block: Block) -> Block {
+        let next_block_height = block_height + 1;
+        match self.params.max_block_size(next_block_height) {
+            Some(max_block_size) if self.params.is_max_block_size_changed_at(next_block_height) => {
+                block.add_max_block_size(max_block_size)
+            }
+            _ => block,
+        }
+    }
+
     pub fn process_round_message(
         &mut self,
         sender_id: &SignerID,
@@ -355,6 +573,10 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         if !self.is_federation_member(sender_id) {
             return self.current_state.clone();
         }
+        if self.params.peer_scores.is_banned(sender_id) {
+            log::debug!("Ignore the message from the banned peer {}.", sender_id);
+            return self.current_state.clone();
+        }
         match message {
             MessageType::Candidateblock(block) => process_candidateblock(
                 &sender_id,
@@ -400,19 +622,175 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 &self.connection_manager,
                 &self.params,
             ),
+            MessageType::Blockcommitments(commitments) => process_blockcommitments(
+                &sender_id,
+                commitments,
+                &self.current_state,
+                &self.connection_manager,
+                &self.params,
+            ),
+            MessageType::Blocknonces(blockhash, commitments) => process_blocknonces(
+                &sender_id,
+                blockhash,
+                commitments,
+                &self.current_state,
+                &self.connection_manager,
+                &self.params,
+            ),
+            MessageType::Blocksigshare(blockhash, commitment, share) => process_blocksigshare(
+                &sender_id,
+                blockhash,
+                commitment,
+                share,
+                &self.current_state,
+                &self.connection_manager,
+                &self.params,
+            ),
+            // Key generation messages are processed by `tapyrus-setup`, not in block rounds.
+            MessageType::Nodevss(_)
+            | MessageType::Nodevsscomplaints(_, _)
+            | MessageType::Nodevssreveal(_)
+            | MessageType::Refreshvss(_)
+            | MessageType::Refreshvssreveal(_)
+            | MessageType::Resharepiece(_, _) => self.current_state.clone(),
+            MessageType::Repairrequest(block_height, helpers, nonce, requested_at, signature) => {
+                self.process_repairrequest(
+                    sender_id,
+                    block_height,
+                    helpers,
+                    nonce,
+                    requested_at,
+                    &signature,
+                );
+                self.current_state.clone()
+            }
+            MessageType::Repairpiece(hash, pieces) => {
+                self.process_repairpiece(sender_id, hash, pieces);
+                self.current_state.clone()
+            }
+            // The relayed candidate blocks are only checked for the equivocation.
+            MessageType::Candidaterelay(_) => self.current_state.clone(),
+            // Repaired node VSS is received by `tapyrus-setup repair`.
+            MessageType::Repairshare(_, _, _) => self.current_state.clone(),
+            // The attestations are signed independently of the rounds.
+            MessageType::Attestationrequest(_)
+            | MessageType::Attestationvss(_, _, _, _, _)
+            | MessageType::Attestationparticipants(_, _)
+            | MessageType::Attestationsig(_, _, _) => {
+                self.attestations.process_message(
+                    sender_id,
+                    message,
+                    self.current_state.block_height(),
+                    &self.params,
+                    &self.connection_manager,
+                );
+                self.current_state.clone()
+            }
+        }
+    }
+
+    /// Start a session to help the repair if this signer is one of the helpers.
+    fn process_repairrequest(
+        &mut self,
+        target: &SignerID,
+        block_height: u64,
+        helpers: Vec<SignerID>,
+        nonce: u64,
+        requested_at: u64,
+        signature: &Signature,
+    ) {
+        if !helpers.contains(&self.params.signer_id) {
+            return;
+        }
+        let federation = self.params.get_federation_by_block_height(block_height);
+        // The repair pieces are created from the node secret share, which is kept by the share
+        // signer if it is used.
+        if !federation.has_secret_shares() {
+            log::warn!(
+                "Ignore the repair request from {}, because the repair is not supported with the share signer.",
+                target
+            );
+            return;
+        }
+        match RepairHelper::new(
+            federation,
+            *target,
+            block_height,
+            helpers,
+            nonce,
+            requested_at,
+            signature,
+        ) {
+            Ok(helper) => {
+                // The target sends the same request repeatedly. An older request is a replay.
+                if let Some((_, current)) = self.repair_helpers.get(target) {
+                    if current.hash() == helper.hash()
+                        || current.requested_at() > helper.requested_at()
+                    {
+                        return;
+                    }
+                }
+                log::info!(
+                    "Start to help the repair of node VSS. target: {}, block height: {}",
+                    target,
+                    block_height
+                );
+                send_all(&self.connection_manager, &helper.outgoing());
+                self.repair_helpers
+                    .insert(*target, (Instant::now(), helper));
+            }
+            Err(e) => log::warn!("Invalid repair request from {}. {:?}", target, e),
+        }
+    }
+
+    fn process_repairpiece(
+        &mut self,
+        sender_id: &SignerID,
+        hash: SHA256Hash,
+        pieces: RepairPieces,
+    ) {
+        let connection_manager = &self.connection_manager;
+        if let Some((_, helper)) = self
+            .repair_helpers
+            .values_mut()
+            .find(|(_, helper)| helper.hash() == hash)
+        {
+            let was_completed = helper.is_completed();
+            helper.receive_pieces(*sender_id, pieces);
+            if !was_completed && helper.is_completed() {
+                send_all(connection_manager, &helper.outgoing());
+            }
+        }
+    }
+
+    /// Drop the expired repair sessions and send the messages of the others again periodically.
+    fn resend_repair_messages(&mut self) {
+        if self.repair_resent_at.elapsed() < Duration::from_secs(RESEND_INTERVAL_SECS) {
+            return;
+        }
+        self.repair_resent_at = Instant::now();
+        self.repair_helpers.retain(|_, (started_at, _)| {
+            started_at.elapsed() < Duration::from_secs(REPAIR_SESSION_TIMEOUT_SECS)
+        });
+        for (_, helper) in self.repair_helpers.values() {
+            send_all(&self.connection_manager, &helper.outgoing());
         }
     }
 
     /// Start next round.
     /// decide master of next round according to Round-robin.
-    fn start_next_round(&mut self, next_master_index: usize) {
+    fn start_next_round(&mut self, next_master_index: usize) -> Result<(), Error> {
         self.round_timer.restart().unwrap();
+        self.params.peer_scores.new_round();
 
         let block_height = match self.params.rpc.getblockchaininfo() {
             Ok(GetBlockchainInfoResult {
                 blocks: block_height,
                 ..
-            }) => block_height,
+            }) => {
+                self.verify_federation_activated_at(block_height)?;
+                block_height
+            }
             _ => match self.current_state {
                 NodeState::Member { block_height, .. } => block_height + 1,
                 NodeState::Master { block_height, .. } => block_height + 1,
@@ -420,6 +798,10 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 _ => panic!("current_state is invalid"),
             },
         };
+        self.equivocation_detector.start_round(block_height);
+        if self.params.signing_protocol(block_height) == SigningProtocol::Frost {
+            self.publish_nonce_commitments();
+        }
         log::info!(
             "Start next round: self_index={}, master_index={}",
             self.params.self_node_index(block_height),
@@ -434,6 +816,46 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 .block_height(block_height)
                 .build();
         }
+        Ok(())
+    }
+
+    /// Broadcast the commitments of the unused nonces for the future rounds of FROST signing. They
+    /// are published at every round, because the nonces are used in the rounds.
+    fn publish_nonce_commitments(&self) {
+        let commitments = match self.params.nonce_commitments() {
+            Ok(commitments) => commitments,
+            Err(e) => {
+                log::error!("Failed to get the nonce commitments. {:?}", e);
+                return;
+            }
+        };
+        self.connection_manager.broadcast_message(Message {
+            message_type: MessageType::Blockcommitments(commitments),
+            sender_id: self.params.signer_id,
+            receiver_id: None,
+        });
+    }
+
+    /// Returns the error to stop the node if the federation whose activation block is the tip
+    /// doesn't match with it. The failure of the RPC is only logged, so that the node keeps
+    /// running and verifies the federation in the next round.
+    fn verify_federation_activated_at(&mut self, tip_height: u64) -> Result<(), Error> {
+        match self.params.verify_federation_activated_at(tip_height) {
+            Ok(()) => Ok(()),
+            Err(e @ Error::InvalidFederation(..)) => {
+                self.round_timer.stop();
+                log::error!("The federation doesn't match with the chain. {:?}", e);
+                Err(e)
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to verify the federation activated at {} height. {:?}",
+                    tip_height,
+                    e
+                );
+                Ok(())
+            }
+        }
     }
 
     fn verify_block(&self, block: &Block) -> Result<(), Error> {
@@ -496,7 +918,7 @@ where
 mod tests {
     use crate::blockdata::Block;
     use crate::federation::{Federation, Federations};
-    use crate::net::{ConnectionManager, ConnectionManagerError, Message, SignerID};
+    use crate::net::{ConnectionManager, ConnectionManagerError, Envelope, Message, SignerID};
     use crate::rpc::tests::{safety, MockRpc};
     use crate::rpc::TapyrusApi;
     use crate::signer_node::{
@@ -509,7 +931,7 @@ mod tests {
     use crate::tests::helper::{address, enable_log};
     use bitcoin::PublicKey;
     use redis::ControlFlow;
-    use std::collections::HashSet;
+    use std::collections::{BTreeMap, HashSet};
     use std::str::FromStr;
     use std::sync::mpsc::{channel, Receiver, Sender};
     use std::sync::Arc;
@@ -557,14 +979,14 @@ mod tests {
 
         fn start(
             &self,
-            mut message_processor: impl FnMut(Message) -> ControlFlow<()> + Send + 'static,
+            mut message_processor: impl FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
             _id: SignerID,
         ) -> JoinHandle<()> {
             for _count in 0..self.receive_count {
                 match self.receiver.recv() {
                     Ok(message) => {
                         log::debug!("Test message receiving!! {:?}", message.message_type);
-                        message_processor(message);
+                        message_processor(Envelope::new(&message, None));
                     }
                     Err(e) => log::warn!("happend receiver error: {:?}", e),
                 }
@@ -625,7 +1047,7 @@ mod tests {
     fn get_invalid_block() -> Block {
         const TEST_BLOCK_WITH_UNKNOWN_XFIELD: &str = "010000000000000000000000000000000000000000000000000000000000000000000000e7c526d0125538b13a50b06465fb8b72120be13fb1142e93aba2aabb2a4f369826c18219f76e4d0ebddbaa9b744837c2ac65b347673695a23c3cc1a2be4141e1427d735efffd2602ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff000101000000010000000000000000000000000000000000000000000000000000000000000000000000002221025700236c2890233592fcef262f4520d22af9160e3d9705855140eb2aa06c35d3ffffffff0100f2052a010000001976a914834e0737cdb9008db614cd95ec98824e952e3dc588ac00000000";
         let raw_block = hex::decode(TEST_BLOCK_WITH_UNKNOWN_XFIELD).unwrap();
-        Block::new(raw_block)
+        Block::new(raw_block).unwrap()
     }
 
     #[test]
@@ -644,6 +1066,7 @@ mod tests {
                 participants: HashSet::new(),
                 master_index: 0,
                 block_height: 0,
+                nonce_commitments: BTreeMap::new(),
             },
             rpc,
         );
@@ -659,6 +1082,138 @@ mod tests {
         assert!(!result);
     }
 
+    #[test]
+    fn test_process_repairrequest() {
+        use crate::keygen::repair::Repair;
+        use crate::keygen::Protocol;
+        use crate::net::MessageType;
+
+        let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
+        let closure: SpyMethod = Box::new(move |message: Arc<Message>| {
+            sender.send((*message).clone()).unwrap();
+        });
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let (mut node, _broadcaster) =
+            create_node_with_closure_and_publish_count(NodeState::Joining, rpc, closure, 0);
+
+        // The node runs with TEST_KEYS.pubkeys()[4], so request the repair of another signer.
+        let secp = secp256k1::Secp256k1::new();
+        let target_key = TEST_KEYS
+            .key
+            .iter()
+            .find(|key| PublicKey::from_private_key(&secp, key) != TEST_KEYS.pubkeys()[4])
+            .unwrap();
+        let target_id = SignerID::new(PublicKey::from_private_key(&secp, target_key));
+        let helpers: Vec<PublicKey> = TEST_KEYS
+            .pubkeys()
+            .into_iter()
+            .filter(|pk| *pk != target_id.pubkey)
+            .collect();
+        let repair = Repair::new(target_key, 0, helpers).unwrap();
+        let request = repair.outgoing().remove(0);
+
+        node.process_round_message(&target_id, request.message_type.clone());
+        // The same request is ignored.
+        node.process_round_message(&target_id, request.message_type);
+
+        let sent: Vec<Message> = receiver.try_iter().collect();
+        assert_eq!(sent.len(), 3);
+        for message in sent {
+            match message.message_type {
+                MessageType::Repairpiece(..) => assert!(message.receiver_id.is_some()),
+                m => assert!(false, "Sent unexpected message {:?}", m),
+            }
+        }
+        assert_eq!(node.repair_helpers.len(), 1);
+    }
+
+    #[test]
+    fn test_master_sends_different_candidates_to_members() {
+        use crate::net::MessageType;
+        use crate::signer_node::peer_scores::{PeerScores, ScoringConfig};
+
+        let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
+        let closure: SpyMethod = Box::new(move |message: Arc<Message>| {
+            sender.send((*message).clone()).unwrap();
+        });
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let (mut node, _broadcaster) =
+            create_node_with_closure_and_publish_count(NodeState::Joining, rpc, closure, 0);
+        node.params.peer_scores = PeerScores::new(ScoringConfig::default());
+
+        let master_id = TEST_KEYS.signer_ids()[0];
+        let candidate = |block: Block| {
+            let message = Message {
+                message_type: MessageType::Candidateblock(block),
+                sender_id: master_id,
+                receiver_id: None,
+            };
+            Envelope::new(&message, Some(&TEST_KEYS.key[0]))
+        };
+
+        // The candidate block which this node received is relayed to the other members.
+        let received = candidate(get_block(0));
+        assert!(node.check_equivocation(received.clone()).is_some());
+        let relayed: Vec<Message> = receiver.try_iter().collect();
+        assert_eq!(relayed.len(), 1);
+        assert_eq!(
+            relayed[0].message_type,
+            MessageType::Candidaterelay(received)
+        );
+
+        // Another member relays the different candidate block which the master sent to it.
+        let mut header = get_block(0).header().clone();
+        header.time += 1;
+        let other = Block::from_parts(header, get_block(0).get_transactions().unwrap());
+        let relay = Message {
+            message_type: MessageType::Candidaterelay(candidate(other)),
+            sender_id: TEST_KEYS.signer_ids()[1],
+            receiver_id: None,
+        };
+        let relay = Envelope::new(&relay, Some(&TEST_KEYS.key[1]));
+        assert!(node.check_equivocation(relay).is_none());
+        assert!(node.params.peer_scores.is_banned(&master_id));
+
+        // The relayed candidate block is not relayed again.
+        assert_eq!(receiver.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_propose_same_candidate_on_previous_block() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(NodeState::Joining, rpc);
+        let candidate = |state: NodeState| match state {
+            NodeState::Master {
+                candidate_block: Some(block),
+                ..
+            } => block,
+            s => panic!("Should be Master with the candidate block, but {:?}", s),
+        };
+        let new_candidate = candidate(node.start_new_round(0));
+
+        // The candidate block proposed in the previous round is proposed again.
+        let mut header = new_candidate.header().clone();
+        header.time += 1;
+        let proposed = Block::from_parts(header.clone(), new_candidate.get_transactions().unwrap());
+        node.proposed_candidate = Some(proposed.clone());
+        assert_eq!(candidate(node.start_new_round(0)), proposed);
+
+        // The candidate block on another previous block is not.
+        header.prev_blockhash = [1u8; 32];
+        let stale = Block::from_parts(header, new_candidate.get_transactions().unwrap());
+        node.proposed_candidate = Some(stale);
+        assert_eq!(candidate(node.start_new_round(0)), new_candidate);
+    }
+
     #[test]
     fn test_timeout_roundrobin() {
         enable_log(None);
@@ -679,7 +1234,7 @@ mod tests {
             thread::sleep(Duration::from_secs(16)); // 16s = 1 round (10s) + idle time(5s) + 1s
             ss.send(1).unwrap();
         });
-        node.start();
+        node.start().unwrap();
 
         assert_eq!(master_index(&node.current_state, &node.params).unwrap(), 1);
     }
@@ -699,19 +1254,22 @@ mod tests {
                 participants: HashSet::new(),
                 master_index: 0,
                 block_height: 0,
+                nonce_commitments: BTreeMap::new(),
             },
             rpc,
         );
 
         assert_eq!(master_index(&node.current_state, &node.params).unwrap(), 0);
 
-        node.start_next_round(next_master_index(&node.current_state, &node.params));
+        node.start_next_round(next_master_index(&node.current_state, &node.params))
+            .unwrap();
         assert_eq!(master_index(&node.current_state, &node.params).unwrap(), 1);
 
         // When the state is Joining, next round should be started as first round, so that,
         // the master index is 0.
         node.current_state = NodeState::Joining;
-        node.start_next_round(next_master_index(&node.current_state, &node.params));
+        node.start_next_round(next_master_index(&node.current_state, &node.params))
+            .unwrap();
         assert_eq!(master_index(&node.current_state, &node.params).unwrap(), 0);
     }
 
@@ -730,6 +1288,7 @@ mod tests {
                 participants: HashSet::new(),
                 master_index: 0,
                 block_height: 0,
+                nonce_commitments: BTreeMap::new(),
             },
             rpc,
         );
@@ -739,7 +1298,7 @@ mod tests {
     }
 
     mod test_for_waiting_ibd_finish {
-        use crate::blockdata::Block;
+        use crate::blockdata::{Block, BlockHeader};
         use crate::errors::Error;
         use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
         use crate::signer_node::tests::create_node;
@@ -772,6 +1331,14 @@ mod tests {
 
                 Ok(result)
             }
+
+            fn getblockhash(&self, _height: u64) -> Result<String, Error> {
+                unimplemented!()
+            }
+
+            fn getblockheader(&self, _hash: &str) -> Result<BlockHeader, Error> {
+                unimplemented!()
+            }
         }
 
         #[test]
@@ -796,6 +1363,7 @@ mod tests {
                     participants: HashSet::new(),
                     master_index: 0,
                     block_height: 0,
+                    nonce_commitments: BTreeMap::new(),
                 },
                 rpc,
             );
@@ -1,10 +1,26 @@
+use crate::blockdata::hash::SHA256Hash;
+use crate::blockdata::XField;
+use crate::crypto::block_key::BlockKeyDerivation;
+use crate::crypto::frost::{NonceCommitments, PublishedCommitments, NONCE_BATCH_SIZE};
 use crate::crypto::multi_party_schnorr::Parameters;
-use crate::federation::{Federation, Federations};
+use crate::crypto::share_signer::{LocalShareSigner, ShareSigner, UnixSocketShareSigner};
+use crate::errors::Error;
+use crate::federation::{Federation, Federations, SigningProtocol};
 use crate::net::SignerID;
 use crate::rpc::TapyrusApi;
+use crate::signer_node::approval::ApprovalHook;
+use crate::signer_node::audit_log::AuditLog;
+use crate::signer_node::block_policy::BlockPolicy;
+use crate::signer_node::confirmation::DEFAULT_CONFIRMATION_TIMEOUT_SECS;
+use crate::signer_node::nonce_pool::{BlockShares, NoncePool, NONCE_POOL_SIZE};
+use crate::signer_node::peer_scores::PeerScores;
 use bitcoin::{Address, PublicKey};
+use curv::{FE, GE};
 use std::convert::TryInto;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub struct NodeParameters<T: TapyrusApi> {
     pub rpc: std::sync::Arc<T>,
@@ -14,6 +30,28 @@ pub struct NodeParameters<T: TapyrusApi> {
     pub round_duration: u64,
     pub skip_waiting_ibd: bool,
     federations: Federations,
+    nonce_pool: Arc<Mutex<NoncePool>>,
+    /// Whether the background thread which refills the nonce pool is running.
+    refilling_nonce_pool: Arc<AtomicBool>,
+    /// The nonce commitments which the other signers published for FROST signing.
+    published_commitments: Mutex<PublishedCommitments>,
+    /// The signer which keeps the nonces of FROST signing and creates the signature shares. The
+    /// nonces are kept only in memory, so that a nonce is never used again after a restart.
+    share_signer: Box<dyn ShareSigner + Send + Sync>,
+    /// The policy which the candidate blocks must satisfy for the node to take part in the round.
+    pub block_policy: BlockPolicy,
+    /// The hook to ask the external approval system before taking part in the round.
+    pub approval_hook: Option<ApprovalHook>,
+    /// How long to wait for the submitted block to become the tip of the chain before submitting
+    /// it again.
+    pub confirmation_timeout: Duration,
+    /// The log to which the signed blocks are recorded. Nothing is recorded if it is None.
+    pub audit_log: Option<AuditLog>,
+    /// The directory where the evidence of the equivocation is written. The evidence is only
+    /// logged if it is None.
+    pub evidence_dir: Option<PathBuf>,
+    /// The scores of the misbehavior of the other signers. It is disabled by default.
+    pub peer_scores: PeerScores,
 }
 
 impl<T: TapyrusApi> NodeParameters<T> {
@@ -33,10 +71,169 @@ impl<T: TapyrusApi> NodeParameters<T> {
             signer_id,
             round_duration,
             skip_waiting_ibd,
+            share_signer: Box::new(LocalShareSigner::new(federations.clone())),
             federations,
+            nonce_pool: Arc::new(Mutex::new(NoncePool::new(NONCE_POOL_SIZE))),
+            refilling_nonce_pool: Arc::new(AtomicBool::new(false)),
+            published_commitments: Mutex::new(PublishedCommitments::new()),
+            block_policy: BlockPolicy::default(),
+            approval_hook: None,
+            confirmation_timeout: Duration::from_secs(DEFAULT_CONFIRMATION_TIMEOUT_SECS),
+            audit_log: None,
+            evidence_dir: None,
+            peer_scores: PeerScores::disabled(),
         }
     }
 
+    /// Creates the signature shares with the share signer process listening on the socket
+    /// instead of this process, if it is specified.
+    pub fn with_share_signer_socket(mut self, share_signer_socket: Option<PathBuf>) -> Self {
+        if let Some(path) = share_signer_socket {
+            self.share_signer = Box::new(UnixSocketShareSigner::new(path));
+        }
+        self
+    }
+
+    pub fn with_block_policy(mut self, block_policy: BlockPolicy) -> Self {
+        self.block_policy = block_policy;
+        self
+    }
+
+    pub fn with_approval_hook(mut self, approval_hook: Option<ApprovalHook>) -> Self {
+        self.approval_hook = approval_hook;
+        self
+    }
+
+    pub fn with_confirmation_timeout(mut self, confirmation_timeout: Duration) -> Self {
+        self.confirmation_timeout = confirmation_timeout;
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    pub fn with_evidence_dir(mut self, evidence_dir: Option<PathBuf>) -> Self {
+        self.evidence_dir = evidence_dir;
+        self
+    }
+
+    pub fn with_peer_scores(mut self, peer_scores: PeerScores) -> Self {
+        self.peer_scores = peer_scores;
+        self
+    }
+
+    /// Returns a block key from the nonce pool for the federation at the block height. The key is
+    /// hedged with the derivation from the node secret share and the sighash of the block, so
+    /// that it is bound to the block as same as the key which is generated in the round.
+    ///
+    /// The pool is refilled in the background after every call, including when it is empty.
+    pub fn take_block_shares(
+        &self,
+        block_height: u64,
+        sighash: &SHA256Hash,
+    ) -> Option<BlockShares> {
+        let sharing_params = self.sharing_params(block_height);
+        let shares = self.nonce_pool.lock().unwrap().take(
+            self.self_node_index(block_height) + 1,
+            sharing_params.threshold + 1,
+            sharing_params.share_count,
+        );
+        self.refill_nonce_pool(block_height);
+        let shares = shares?;
+        let derivation = BlockKeyDerivation::for_block(
+            &self
+                .get_federation_by_block_height(block_height)
+                .node_secret_share()
+                .x_i,
+            sighash,
+            block_height,
+        );
+        Some(NoncePool::hedge(shares, &derivation))
+    }
+
+    /// Start a background thread which generates block keys for the federation at the block
+    /// height until the nonce pool is full. It returns immediately, so that the round doesn't wait
+    /// for the generation, and does nothing if the thread is already running.
+    fn refill_nonce_pool(&self, block_height: u64) {
+        let federation = self.get_federation_by_block_height(block_height);
+        if federation.threshold().is_none()
+            || federation.signing_protocol() != SigningProtocol::Vss
+            || !federation.has_secret_shares()
+        {
+            return;
+        }
+        if self.nonce_pool.lock().unwrap().is_full()
+            || self.refilling_nonce_pool.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+        let node_secret_share = federation.node_secret_share();
+        let index = self.self_node_index(block_height) + 1;
+        let sharing_params = self.sharing_params(block_height);
+        let nonce_pool = Arc::clone(&self.nonce_pool);
+        let refilling_nonce_pool = Arc::clone(&self.refilling_nonce_pool);
+        std::thread::spawn(move || {
+            while !nonce_pool.lock().unwrap().is_full() {
+                // The key is generated without the lock, so that the round can take a key from
+                // the pool meanwhile.
+                let entry = NoncePool::generate(
+                    &node_secret_share.x_i,
+                    index,
+                    sharing_params.threshold + 1,
+                    sharing_params.share_count,
+                );
+                nonce_pool.lock().unwrap().push(entry);
+            }
+            refilling_nonce_pool.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Returns the commitments of the unused nonces of this signer for FROST signing, after
+    /// creating new nonces up to the batch size.
+    pub fn nonce_commitments(&self) -> Result<Vec<(GE, GE)>, Error> {
+        self.share_signer.nonce_commitments(NONCE_BATCH_SIZE)
+    }
+
+    /// Returns the signature share of this signer for FROST signing. The nonce of the own
+    /// commitment is used only once.
+    pub fn sign_share(
+        &self,
+        block_height: u64,
+        message: SHA256Hash,
+        commitments: &NonceCommitments,
+    ) -> Result<FE, Error> {
+        self.share_signer
+            .sign_share(block_height, message, commitments)
+    }
+
+    /// Replaces the nonce commitments which the signer published.
+    pub fn update_published_commitments(&self, signer_id: SignerID, commitments: Vec<(GE, GE)>) {
+        self.published_commitments
+            .lock()
+            .unwrap()
+            .update(signer_id, commitments);
+    }
+
+    /// Removes the published nonce commitments which are selected for a round.
+    pub fn remove_published_commitments(&self, selected: &NonceCommitments) {
+        self.published_commitments.lock().unwrap().remove(selected);
+    }
+
+    /// Takes a published nonce commitment of each of the first `count` candidates, or returns
+    /// None if there are not enough commitments.
+    pub fn select_published_commitments(
+        &self,
+        candidates: &[SignerID],
+        count: usize,
+    ) -> Option<NonceCommitments> {
+        self.published_commitments
+            .lock()
+            .unwrap()
+            .select(candidates, count)
+    }
+
     pub fn get_federation_by_block_height(&self, block_height: u64) -> &Federation {
         self.federations.get_by_block_height(block_height)
     }
@@ -73,6 +270,11 @@ impl<T: TapyrusApi> NodeParameters<T> {
             .expect("threshold should not be None")
     }
 
+    pub fn signing_protocol(&self, block_height: u64) -> SigningProtocol {
+        let federation = self.get_federation_by_block_height(block_height);
+        federation.signing_protocol()
+    }
+
     pub fn self_node_index(&self, block_height: u64) -> usize {
         let federation = self.get_federation_by_block_height(block_height);
         federation.node_index()
@@ -87,14 +289,98 @@ impl<T: TapyrusApi> NodeParameters<T> {
         let federation = self.get_federation_by_block_height(block_height);
         federation.aggregated_public_key()
     }
+
+    pub fn is_aggregated_public_key_changed_at(&self, block_height: u64) -> bool {
+        self.federations
+            .is_aggregated_public_key_changed_at(block_height)
+    }
+
+    pub fn max_block_size(&self, block_height: u64) -> Option<u32> {
+        let federation = self.get_federation_by_block_height(block_height);
+        federation.max_block_size()
+    }
+
+    pub fn is_max_block_size_changed_at(&self, block_height: u64) -> bool {
+        self.federations.is_max_block_size_changed_at(block_height)
+    }
+
+    /// Verifies the federations whose activation blocks are already in the chain whose tip is at
+    /// `tip_height`.
+    pub fn verify_activated_federations(&self, tip_height: u64) -> Result<(), Error> {
+        for federation in self.federations.iter() {
+            if activation_block_height(federation) <= tip_height {
+                self.verify_activation_block(federation)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the federation whose activation block is the tip at `tip_height`, if any. The
+    /// federation from the genesis block is verified only by `verify_activated_federations`.
+    pub fn verify_federation_activated_at(&self, tip_height: u64) -> Result<(), Error> {
+        match self
+            .federations
+            .iter()
+            .find(|f| f.block_height() > 0 && activation_block_height(f) == tip_height)
+        {
+            Some(federation) => self.verify_activation_block(federation),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `Error::InvalidFederation` if the activation block of the federation doesn't have
+    /// the xfield which the federation schedules.
+    fn verify_activation_block(&self, federation: &Federation) -> Result<(), Error> {
+        let block_height = federation.block_height();
+        let expected = if self.is_aggregated_public_key_changed_at(block_height) {
+            XField::AggregatePublicKey(federation.aggregated_public_key())
+        } else {
+            match federation.max_block_size() {
+                Some(max_block_size) if self.is_max_block_size_changed_at(block_height) => {
+                    XField::MaxBlockSize(max_block_size)
+                }
+                _ => return Ok(()),
+            }
+        };
+
+        let hash = self.rpc.getblockhash(activation_block_height(federation))?;
+        let header = self.rpc.getblockheader(&hash)?;
+        if header.xfield != expected {
+            log::error!(
+                "The activation block of the federation at {} height has unexpected xfield. block hash: {}, expected: {:?}, actual: {:?}",
+                block_height,
+                hash,
+                expected,
+                header.xfield
+            );
+            return Err(Error::InvalidFederation(
+                Some(block_height),
+                "The activation block of the federation doesn't have the xfield of the federation.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the height of the block which has the xfield of the federation. It is the previous
+/// block of the federation, except the genesis block which has the first aggregated public key.
+fn activation_block_height(federation: &Federation) -> u64 {
+    federation.block_height().saturating_sub(1)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::blockdata::{BlockHeader, XField};
+    use crate::errors::Error;
+    use crate::federation::{Federation, Federations};
     use crate::signer_node::NodeParameters;
+    use crate::tests::helper::blocks::get_block;
     use crate::tests::helper::keys::TEST_KEYS;
+    use crate::tests::helper::node_parameters_builder::NodeParametersBuilder;
+    use crate::tests::helper::node_vss::node_vss;
     use crate::tests::helper::rpc::MockRpc;
     use bitcoin::PublicKey;
+    use std::str::FromStr;
 
     #[test]
     fn test_sort_publickey() {
@@ -102,4 +388,103 @@ mod tests {
         NodeParameters::<MockRpc>::sort_publickey(&mut pubkeys);
         assert_eq!(pubkeys, TEST_KEYS.pubkeys());
     }
+
+    fn another_key() -> PublicKey {
+        PublicKey::from_str("030acd6af981c498ebf2ffd9a341d2a96bde5832c150e7d300fa3583eee0f964fe")
+            .unwrap()
+    }
+
+    /// Returns the parameters with the federations which change the aggregated public key at 100
+    /// height, and the max block size at 200 height.
+    fn params(rpc: MockRpc) -> NodeParameters<MockRpc> {
+        let federation = |block_height: u64, aggregated_public_key: PublicKey| {
+            Federation::new(
+                TEST_KEYS.pubkeys()[4],
+                block_height,
+                Some(3),
+                node_vss(0),
+                aggregated_public_key,
+            )
+        };
+        let federations = Federations::new(vec![
+            federation(0, TEST_KEYS.aggregated()),
+            federation(100, another_key()),
+            federation(200, another_key()).with_max_block_size(Some(2_000_000)),
+        ]);
+        NodeParametersBuilder::new()
+            .rpc(rpc)
+            .federations(federations)
+            .build()
+    }
+
+    fn header(xfield: XField) -> BlockHeader {
+        BlockHeader {
+            xfield,
+            ..get_block(0).header().clone()
+        }
+    }
+
+    #[test]
+    fn test_verify_activated_federations() {
+        let mut rpc = MockRpc::new();
+        rpc.should_call_getblockhash(Ok("hash0".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(
+            TEST_KEYS.aggregated(),
+        ))));
+        rpc.should_call_getblockhash(Ok("hash99".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(another_key()))));
+        rpc.should_call_getblockhash(Ok("hash199".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::MaxBlockSize(2_000_000))));
+        let params = params(rpc);
+        assert!(params.verify_activated_federations(300).is_ok());
+        params.rpc.assert();
+
+        // The federation at 200 height is not activated yet.
+        let mut rpc = MockRpc::new();
+        rpc.should_call_getblockhash(Ok("hash0".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(
+            TEST_KEYS.aggregated(),
+        ))));
+        rpc.should_call_getblockhash(Ok("hash99".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(another_key()))));
+        let params = params(rpc);
+        assert!(params.verify_activated_federations(198).is_ok());
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_verify_activated_federations_mismatch() {
+        let mut rpc = MockRpc::new();
+        rpc.should_call_getblockhash(Ok("hash0".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(
+            TEST_KEYS.aggregated(),
+        ))));
+        rpc.should_call_getblockhash(Ok("hash99".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::AggregatePublicKey(
+            TEST_KEYS.aggregated(),
+        ))));
+        let params = params(rpc);
+        match params.verify_activated_federations(150) {
+            Err(Error::InvalidFederation(Some(100), _)) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+        params.rpc.assert();
+    }
+
+    #[test]
+    fn test_verify_federation_activated_at() {
+        let mut rpc = MockRpc::new();
+        rpc.should_call_getblockhash(Ok("hash199".to_string()));
+        rpc.should_call_getblockheader(Ok(header(XField::None)));
+        let params = params(rpc);
+
+        // Only the federation whose activation block is the tip is verified.
+        assert!(params.verify_federation_activated_at(0).is_ok());
+        assert!(params.verify_federation_activated_at(150).is_ok());
+        match params.verify_federation_activated_at(199) {
+            Err(Error::InvalidFederation(Some(200), _)) => {}
+            r => panic!("it should fail, but {:?}", r),
+        }
+        params.rpc.assert();
+    }
 }
@@ -1,4 +1,6 @@
 use crate::blockdata::Block;
+use crate::crypto::frost::NonceCommitments;
+use crate::crypto::secret::Secret;
 use crate::net::SignerID;
 use crate::signer_node::BidirectionalSharedSecretMap;
 use curv::{FE, GE};
@@ -11,14 +13,14 @@ pub enum NodeState {
         /// *block_key* is random value for using int the Signature Issuing Protocol.
         /// VSS which is distributed to each other signer is generated by this key. All signers in
         /// all block generation rounds has each own block_key.
-        block_key: Option<FE>,
+        block_key: Option<Secret<FE>>,
         /// Map of VSSs and commitment in Signature Issuing Protocol. A Signer broadcasts this value
         /// on blockvss message and collected by all signers who include oneself.
         shared_block_secrets: BidirectionalSharedSecretMap,
         /// Share in which is generated from above shared_block_secrets. It is produced by
         /// aggregating VSSs and first element of the commitments which are come from the
         /// participants who are selected by round master.
-        block_shared_keys: Option<(bool, FE, GE)>,
+        block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         /// Candidate block of a round.
         /// It is broadcasted by master node of a round. The goal of rounds are generating signature
         /// for this candidate block.
@@ -32,19 +34,26 @@ pub enum NodeState {
         /// Set true when the round is done.
         round_is_done: bool,
         block_height: u64,
+        /// Nonce commitments of the participants for FROST signing, which the master selects
+        /// from the commitments published ahead of the rounds.
+        #[serde(default)]
+        nonce_commitments: NonceCommitments,
+        /// Signature shares of the participants for FROST signing.
+        #[serde(default)]
+        signature_shares: BTreeMap<SignerID, FE>,
     },
     Member {
         /// *block_key* is random value for using int the Signature Issuing Protocol.
         /// VSS which is distributed to each other signer is generated by this key. All signers in
         /// all block generation rounds has each own block_key.
-        block_key: Option<FE>,
+        block_key: Option<Secret<FE>>,
         /// Map of VSSs and commitment in Signature Issuing Protocol. A Signer broadcasts this value
         /// on blockvss message and collected by all signers who include oneself.
         shared_block_secrets: BidirectionalSharedSecretMap,
         /// Share in which is generated from above shared_block_secrets. It is produced by
         /// aggregating VSSs and first element of the commitments which are come from the
         /// participants who are selected by round master.
-        block_shared_keys: Option<(bool, FE, GE)>,
+        block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         /// Candidate block of a round.
         /// It is broadcasted by master node of a round. The goal of rounds are generating signature
         /// for this candidate block.
@@ -54,6 +63,10 @@ pub enum NodeState {
         participants: HashSet<SignerID>,
         master_index: usize,
         block_height: u64,
+        /// Nonce commitments of the participants for FROST signing, which the master selects
+        /// from the commitments published ahead of the rounds.
+        #[serde(default)]
+        nonce_commitments: NonceCommitments,
     },
     RoundComplete {
         master_index: usize,
@@ -75,7 +88,9 @@ impl NodeState {
 
 pub mod builder {
     use crate::blockdata::Block;
+    use crate::crypto::frost::NonceCommitments;
     use crate::crypto::multi_party_schnorr::LocalSig;
+    use crate::crypto::secret::Secret;
     use crate::net::SignerID;
     use crate::signer_node::{
         BidirectionalSharedSecretMap, NodeState, SharedSecret, INITIAL_MASTER_INDEX,
@@ -90,14 +105,16 @@ pub mod builder {
     }
 
     pub struct Master {
-        block_key: Option<FE>,
+        block_key: Option<Secret<FE>>,
         shared_block_secrets: BidirectionalSharedSecretMap,
-        block_shared_keys: Option<(bool, FE, GE)>,
+        block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         candidate_block: Option<Block>,
         signatures: BTreeMap<SignerID, (FE, FE)>,
         participants: HashSet<SignerID>,
         round_is_done: bool,
         block_height: u64,
+        nonce_commitments: NonceCommitments,
+        signature_shares: BTreeMap<SignerID, FE>,
     }
 
     impl Builder for Master {
@@ -111,6 +128,8 @@ pub mod builder {
                 participants: self.participants.clone(),
                 round_is_done: self.round_is_done,
                 block_height: 0,
+                nonce_commitments: self.nonce_commitments.clone(),
+                signature_shares: self.signature_shares.clone(),
             }
         }
 
@@ -124,6 +143,8 @@ pub mod builder {
                 participants,
                 round_is_done,
                 block_height,
+                nonce_commitments,
+                signature_shares,
             } = state
             {
                 Self {
@@ -135,6 +156,8 @@ pub mod builder {
                     participants,
                     round_is_done,
                     block_height,
+                    nonce_commitments,
+                    signature_shares,
                 }
             } else {
                 unreachable!(
@@ -155,20 +178,24 @@ pub mod builder {
                 participants: HashSet::new(),
                 round_is_done: false,
                 block_height: 0,
+                nonce_commitments: NonceCommitments::new(),
+                signature_shares: BTreeMap::new(),
             }
         }
     }
 
     impl Master {
         pub fn new(
-            block_key: Option<FE>,
+            block_key: Option<Secret<FE>>,
             shared_block_secrets: BidirectionalSharedSecretMap,
-            block_shared_keys: Option<(bool, FE, GE)>,
+            block_shared_keys: Option<(bool, Secret<FE>, GE)>,
             candidate_block: Option<Block>,
             signatures: BTreeMap<SignerID, (FE, FE)>,
             participants: HashSet<SignerID>,
             round_is_done: bool,
             block_height: u64,
+            nonce_commitments: NonceCommitments,
+            signature_shares: BTreeMap<SignerID, FE>,
         ) -> Self {
             Self {
                 block_key,
@@ -179,10 +206,12 @@ pub mod builder {
                 participants,
                 round_is_done,
                 block_height,
+                nonce_commitments,
+                signature_shares,
             }
         }
 
-        pub fn block_key(&mut self, block_key: Option<FE>) -> &mut Self {
+        pub fn block_key(&mut self, block_key: Option<Secret<FE>>) -> &mut Self {
             self.block_key = block_key;
             self
         }
@@ -210,7 +239,7 @@ pub mod builder {
 
         pub fn block_shared_keys(
             &mut self,
-            block_shared_keys: Option<(bool, FE, GE)>,
+            block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         ) -> &mut Self {
             self.block_shared_keys = block_shared_keys;
             self
@@ -250,16 +279,36 @@ pub mod builder {
             self.block_height = block_height;
             self
         }
+
+        pub fn insert_nonce_commitment(
+            &mut self,
+            signer_id: SignerID,
+            commitment: (GE, GE),
+        ) -> &mut Self {
+            self.nonce_commitments.insert(signer_id, commitment);
+            self
+        }
+
+        pub fn nonce_commitments(&mut self, nonce_commitments: NonceCommitments) -> &mut Self {
+            self.nonce_commitments = nonce_commitments;
+            self
+        }
+
+        pub fn insert_signature_share(&mut self, signer_id: SignerID, share: FE) -> &mut Self {
+            self.signature_shares.insert(signer_id, share);
+            self
+        }
     }
 
     pub struct Member {
-        block_key: Option<FE>,
+        block_key: Option<Secret<FE>>,
         shared_block_secrets: BidirectionalSharedSecretMap,
-        block_shared_keys: Option<(bool, FE, GE)>,
+        block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         candidate_block: Option<Block>,
         participants: HashSet<SignerID>,
         master_index: usize,
         block_height: u64,
+        nonce_commitments: NonceCommitments,
     }
 
     impl Default for Member {
@@ -272,6 +321,7 @@ pub mod builder {
                 participants: HashSet::new(),
                 master_index: INITIAL_MASTER_INDEX,
                 block_height: 0,
+                nonce_commitments: NonceCommitments::new(),
             }
         }
     }
@@ -286,6 +336,7 @@ pub mod builder {
                 participants: self.participants.clone(),
                 master_index: self.master_index,
                 block_height: self.block_height,
+                nonce_commitments: self.nonce_commitments.clone(),
             }
         }
 
@@ -298,6 +349,7 @@ pub mod builder {
                 participants,
                 master_index,
                 block_height,
+                nonce_commitments,
             } = state
             {
                 Self {
@@ -308,6 +360,7 @@ pub mod builder {
                     participants,
                     master_index,
                     block_height,
+                    nonce_commitments,
                 }
             } else {
                 unreachable!(
@@ -319,13 +372,14 @@ pub mod builder {
 
     impl Member {
         pub fn new(
-            block_key: Option<FE>,
+            block_key: Option<Secret<FE>>,
             shared_block_secrets: BidirectionalSharedSecretMap,
-            block_shared_keys: Option<(bool, FE, GE)>,
+            block_shared_keys: Option<(bool, Secret<FE>, GE)>,
             candidate_block: Option<Block>,
             participants: HashSet<SignerID>,
             master_index: usize,
             block_height: u64,
+            nonce_commitments: NonceCommitments,
         ) -> Self {
             Self {
                 block_key,
@@ -335,10 +389,11 @@ pub mod builder {
                 participants,
                 master_index,
                 block_height,
+                nonce_commitments,
             }
         }
 
-        pub fn block_key(&mut self, block_key: Option<FE>) -> &mut Self {
+        pub fn block_key(&mut self, block_key: Option<Secret<FE>>) -> &mut Self {
             self.block_key = block_key;
             self
         }
@@ -366,7 +421,7 @@ pub mod builder {
 
         pub fn block_shared_keys(
             &mut self,
-            block_shared_keys: Option<(bool, FE, GE)>,
+            block_shared_keys: Option<(bool, Secret<FE>, GE)>,
         ) -> &mut Self {
             self.block_shared_keys = block_shared_keys;
             self
@@ -391,5 +446,19 @@ pub mod builder {
             self.block_height = block_height;
             self
         }
+
+        pub fn insert_nonce_commitment(
+            &mut self,
+            signer_id: SignerID,
+            commitment: (GE, GE),
+        ) -> &mut Self {
+            self.nonce_commitments.insert(signer_id, commitment);
+            self
+        }
+
+        pub fn nonce_commitments(&mut self, nonce_commitments: NonceCommitments) -> &mut Self {
+            self.nonce_commitments = nonce_commitments;
+            self
+        }
     }
 }
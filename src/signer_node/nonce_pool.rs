@@ -0,0 +1,297 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Pool of block keys which are generated ahead of the rounds.
+//!
+//! Generating a block key and its VSSs is elliptic curve work, so the signer does it in a
+//! background thread between the rounds and only sends the VSSs with the block hash when the round
+//! runs. The pooled keys are generated before the block is known, so that they are not bound to
+//! any block. When a key is taken for the round, it is hedged with the derivation from the sighash
+//! of the block (see `NoncePool::hedge`).
+//!
+//! A block key must never be used twice. The pool removes the key before returning it, and the
+//! pool is kept only in memory, so the keys are discarded at the restart of the signer and never
+//! loaded again.
+
+use crate::crypto::block_key::BlockKeyDerivation;
+use crate::crypto::multi_party_schnorr::Keys;
+use crate::crypto::secret::Secret;
+use crate::crypto::vss::Vss;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{FE, GE};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of the block keys which the pool keeps.
+pub const NONCE_POOL_SIZE: usize = 4;
+
+/// The block key and the VSSs of it for both positive and negative.
+pub type BlockShares = (Keys, VerifiableSS, Vec<FE>, VerifiableSS, Vec<FE>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PooledShares {
+    index: usize,
+    threshold: usize,
+    share_count: usize,
+    u_i: Secret<FE>,
+    vss_for_positive: VerifiableSS,
+    secret_shares_for_positive: Vec<FE>,
+    vss_for_negative: VerifiableSS,
+    secret_shares_for_negative: Vec<FE>,
+}
+
+impl PooledShares {
+    fn matches(&self, index: usize, threshold: usize, share_count: usize) -> bool {
+        self.index == index && self.threshold == threshold && self.share_count == share_count
+    }
+
+    fn into_block_shares(self) -> BlockShares {
+        let g: GE = ECPoint::generator();
+        let y_i = g * &*self.u_i;
+        let key = Keys {
+            u_i: self.u_i,
+            y_i,
+            party_index: self.index,
+        };
+        (
+            key,
+            self.vss_for_positive,
+            self.secret_shares_for_positive,
+            self.vss_for_negative,
+            self.secret_shares_for_negative,
+        )
+    }
+}
+
+pub struct NoncePool {
+    entries: VecDeque<PooledShares>,
+    capacity: usize,
+}
+
+impl NoncePool {
+    pub fn new(capacity: usize) -> Self {
+        NoncePool {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.capacity
+    }
+
+    /// Generate a block key for the signer at `index` if the pool is not full.
+    pub fn refill(
+        &mut self,
+        node_secret_share: &FE,
+        index: usize,
+        threshold: usize,
+        share_count: usize,
+    ) {
+        if self.is_full() {
+            return;
+        }
+        self.push(Self::generate(
+            node_secret_share,
+            index,
+            threshold,
+            share_count,
+        ))
+    }
+
+    /// Returns a new block key for the signer at `index`. It doesn't touch the pool, so that the
+    /// caller can generate the key without locking the pool and `push` it later.
+    ///
+    /// The key is derived from `node_secret_share` without the block, because the block is not
+    /// known yet.
+    pub fn generate(
+        node_secret_share: &FE,
+        index: usize,
+        threshold: usize,
+        share_count: usize,
+    ) -> PooledShares {
+        // The time is used as the round id, so that the keys are different even if the random
+        // number generator is weak.
+        let round_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let derivation = BlockKeyDerivation::without_block(node_secret_share, round_id);
+        let (
+            key,
+            vss_for_positive,
+            secret_shares_for_positive,
+            vss_for_negative,
+            secret_shares_for_negative,
+        ) = Vss::create_block_shares_with(&derivation, index, threshold, share_count);
+        PooledShares {
+            index,
+            threshold,
+            share_count,
+            u_i: key.u_i,
+            vss_for_positive,
+            secret_shares_for_positive,
+            vss_for_negative,
+            secret_shares_for_negative,
+        }
+    }
+
+    /// Add the block key to the pool. The key is dropped if the pool is full.
+    pub fn push(&mut self, entry: PooledShares) {
+        if !self.is_full() {
+            self.entries.push_back(entry);
+        }
+    }
+
+    /// Returns a block key for the signer at `index`, which is removed from the pool. The keys for
+    /// other parameters are discarded because the federation has been changed.
+    ///
+    /// Returns None if the pool has no key. The caller should create a new block key in that case.
+    pub fn take(
+        &mut self,
+        index: usize,
+        threshold: usize,
+        share_count: usize,
+    ) -> Option<BlockShares> {
+        self.entries
+            .retain(|entry| entry.matches(index, threshold, share_count));
+        self.entries
+            .pop_front()
+            .map(PooledShares::into_block_shares)
+    }
+
+    /// Returns the block key hedged with `derivation` of the block, so that the key is bound to
+    /// the block when the round runs. The block key becomes `u_i + d` where `d` is the scalar at 0
+    /// of the derivation. The constant terms of the VSSs and all the shares are shifted by `d` and
+    /// `-d` for positive and negative respectively, so the VSSs are still valid.
+    pub fn hedge(shares: BlockShares, derivation: &BlockKeyDerivation) -> BlockShares {
+        let (
+            key,
+            mut vss_for_positive,
+            secret_shares_for_positive,
+            mut vss_for_negative,
+            secret_shares_for_negative,
+        ) = shares;
+        let g: GE = ECPoint::generator();
+        let d = derivation.scalar(0);
+        let neg_d: FE = ECScalar::from(&(FE::q() - d.to_big_int()));
+
+        let key = Keys {
+            u_i: Secret::new(*key.u_i + &d),
+            y_i: key.y_i + &(g * &d),
+            party_index: key.party_index,
+        };
+        vss_for_positive.commitments[0] = vss_for_positive.commitments[0] + &(g * &d);
+        vss_for_negative.commitments[0] = vss_for_negative.commitments[0] + &(g * &neg_d);
+        let secret_shares_for_positive = secret_shares_for_positive
+            .into_iter()
+            .map(|share| share + &d)
+            .collect();
+        let secret_shares_for_negative = secret_shares_for_negative
+            .into_iter()
+            .map(|share| share + &neg_d)
+            .collect();
+        (
+            key,
+            vss_for_positive,
+            secret_shares_for_positive,
+            vss_for_negative,
+            secret_shares_for_negative,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockdata::hash::SHA256Hash;
+
+    fn secret() -> FE {
+        ECScalar::new_random()
+    }
+
+    #[test]
+    fn test_refill_and_take() {
+        let mut pool = NoncePool::new(2);
+        for _ in 0..3 {
+            pool.refill(&secret(), 1, 3, 5);
+        }
+        assert_eq!(pool.len(), 2);
+
+        let (key, vss, shares, _, _) = pool.take(1, 3, 5).unwrap();
+        assert_eq!(key.party_index, 1);
+        assert_eq!(shares.len(), 5);
+        assert!(vss.validate_share(&shares[0], 1).is_ok());
+        assert_eq!(pool.len(), 1);
+
+        // The keys for other parameters are discarded.
+        assert!(pool.take(1, 2, 5).is_none());
+        assert_eq!(pool.len(), 0);
+    }
+
+    #[test]
+    fn test_generate_and_push() {
+        let mut pool = NoncePool::new(1);
+        let entry = NoncePool::generate(&secret(), 1, 3, 5);
+        pool.push(entry.clone());
+        assert!(pool.is_full());
+
+        // The key is dropped if the pool is full.
+        pool.push(NoncePool::generate(&secret(), 1, 3, 5));
+        assert_eq!(pool.len(), 1);
+
+        let (key, ..) = pool.take(1, 3, 5).unwrap();
+        assert_eq!(key.u_i, entry.u_i);
+    }
+
+    #[test]
+    fn test_hedge() {
+        let mut pool = NoncePool::new(1);
+        pool.refill(&secret(), 1, 3, 5);
+        let shares = pool.take(1, 3, 5).unwrap();
+        let u_i = *shares.0.u_i;
+
+        let sighash = SHA256Hash::from_slice(&[1u8; 32][..]).unwrap();
+        let derivation = BlockKeyDerivation::for_block(&secret(), &sighash, 10);
+        let d = derivation.scalar(0);
+        let (key, vss_for_positive, shares_for_positive, vss_for_negative, shares_for_negative) =
+            NoncePool::hedge(shares, &derivation);
+
+        let g: GE = ECPoint::generator();
+        assert_eq!(*key.u_i, u_i + &d);
+        assert_eq!(key.y_i, g * &*key.u_i);
+        assert_eq!(vss_for_positive.commitments[0], key.y_i);
+        let negative: FE = ECScalar::from(&(FE::q() - key.u_i.to_big_int()));
+        assert_eq!(vss_for_negative.commitments[0], g * &negative);
+        for i in 0..5 {
+            assert!(vss_for_positive
+                .validate_share(&shares_for_positive[i], i + 1)
+                .is_ok());
+            assert!(vss_for_negative
+                .validate_share(&shares_for_negative[i], i + 1)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_take_is_single_use() {
+        let mut pool = NoncePool::new(2);
+        pool.refill(&secret(), 1, 3, 5);
+        pool.refill(&secret(), 1, 3, 5);
+
+        let (first, ..) = pool.take(1, 3, 5).unwrap();
+        let (second, ..) = pool.take(1, 3, 5).unwrap();
+        assert_ne!(first.u_i, second.u_i);
+        assert!(pool.take(1, 3, 5).is_none());
+    }
+}
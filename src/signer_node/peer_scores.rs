@@ -0,0 +1,332 @@
+// Copyright (c) 2020 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Scores of the misbehavior of the other signers.
+//!
+//! Each fault of a peer raises its score by the penalty of the kind of the fault, and the score
+//! decays by half in every half-life. When the score reaches the threshold, the peer is banned
+//! for a number of rounds: the node ignores all messages from it, so the master doesn't choose it
+//! as a participant either.
+//!
+//! The sender of a message which is not signed can't be told, so that anyone could make the node
+//! ban a peer by sending invalid messages in its name. So the faults of a peer are scored only if
+//! all the messages from it in the round are signed by it.
+
+use crate::net::SignerID;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BAN_THRESHOLD: f64 = 100.0;
+pub const DEFAULT_BAN_ROUNDS: u64 = 10;
+pub const DEFAULT_DECAY_HALF_LIFE_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Misbehavior {
+    /// The round master proposed a candidate block which is invalid or rejected by Tapyrus Core.
+    InvalidCandidate,
+    /// The peer sent a completed block although it is not the round master. It may also happen
+    /// when this node is behind the others, so the penalty is small.
+    ImpersonateMaster,
+    /// The secret share of the block VSS doesn't match with the commitments.
+    InvalidBlockVss,
+    /// The local signature or the signature share is invalid.
+    InvalidLocalSig,
+    /// The peer sent conflicting messages in a round.
+    Equivocation,
+}
+
+impl Misbehavior {
+    pub fn penalty(&self) -> f64 {
+        match self {
+            Misbehavior::InvalidCandidate => 50.0,
+            Misbehavior::ImpersonateMaster => 10.0,
+            Misbehavior::InvalidBlockVss => 50.0,
+            Misbehavior::InvalidLocalSig => 50.0,
+            Misbehavior::Equivocation => 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoringConfig {
+    /// The peer is banned when its score reaches this.
+    pub ban_threshold: f64,
+    /// How many rounds the banned peer is ignored, following the current round.
+    pub ban_rounds: u64,
+    /// The score decays by half in this duration.
+    pub decay_half_life: Duration,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            ban_threshold: DEFAULT_BAN_THRESHOLD,
+            ban_rounds: DEFAULT_BAN_ROUNDS,
+            decay_half_life: Duration::from_secs(DEFAULT_DECAY_HALF_LIFE_SECS),
+        }
+    }
+}
+
+/// The score of a peer to show to the operator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerStatus {
+    pub signer_id: SignerID,
+    pub score: f64,
+    /// The number of the remaining rounds of the ban. 0 if the peer is not banned.
+    pub banned_rounds: u64,
+    /// The number of the faults of each kind since the node started.
+    pub faults: BTreeMap<Misbehavior, u64>,
+}
+
+struct PeerScore {
+    score: f64,
+    updated_at: Instant,
+    /// The last round in which the peer is banned.
+    banned_until: Option<u64>,
+    faults: BTreeMap<Misbehavior, u64>,
+}
+
+impl PeerScore {
+    fn score_at(&self, now: Instant, half_life: Duration) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        self.score * 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+    }
+}
+
+#[derive(Default)]
+struct Scores {
+    round: u64,
+    peers: BTreeMap<SignerID, PeerScore>,
+    /// The senders of the messages in the current round, and whether all of the messages are
+    /// signed by the sender.
+    senders: BTreeMap<SignerID, bool>,
+}
+
+pub struct PeerScores {
+    config: ScoringConfig,
+    /// Nothing is scored and no peer is banned if it is false.
+    enabled: bool,
+    scores: Mutex<Scores>,
+}
+
+impl PeerScores {
+    pub fn new(config: ScoringConfig) -> Self {
+        PeerScores {
+            config,
+            enabled: true,
+            scores: Mutex::new(Scores::default()),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        PeerScores {
+            enabled: false,
+            ..PeerScores::new(ScoringConfig::default())
+        }
+    }
+
+    /// Records the message which the peer sent in the current round, and whether it is signed by
+    /// the peer. This is called before the message is processed.
+    pub fn observe_message(&self, peer: &SignerID, signed: bool) {
+        let mut scores = self.scores.lock().unwrap();
+        let all_signed = scores.senders.entry(*peer).or_insert(true);
+        *all_signed = *all_signed && signed;
+    }
+
+    /// Raises the score of the peer, and bans it if the score reaches the threshold. The fault is
+    /// ignored if any message from the peer in the current round is not signed.
+    pub fn report(&self, peer: &SignerID, misbehavior: Misbehavior) {
+        self.report_at(peer, misbehavior, Instant::now());
+    }
+
+    fn report_at(&self, peer: &SignerID, misbehavior: Misbehavior, now: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let mut scores = self.scores.lock().unwrap();
+        if scores.senders.get(peer) != Some(&true) {
+            log::warn!(
+                "Peer {} misbehaved: {:?}. It is not scored because the messages are not signed.",
+                peer,
+                misbehavior
+            );
+            return;
+        }
+        let round = scores.round;
+        let peer_score = scores.peers.entry(*peer).or_insert(PeerScore {
+            score: 0.0,
+            updated_at: now,
+            banned_until: None,
+            faults: BTreeMap::new(),
+        });
+        peer_score.score =
+            peer_score.score_at(now, self.config.decay_half_life) + misbehavior.penalty();
+        peer_score.updated_at = now;
+        *peer_score.faults.entry(misbehavior).or_insert(0) += 1;
+        log::warn!(
+            "Peer {} misbehaved: {:?}. score: {:.1}",
+            peer,
+            misbehavior,
+            peer_score.score
+        );
+
+        if peer_score.score >= self.config.ban_threshold {
+            // The score starts from zero after the ban, not to ban the peer again at once.
+            peer_score.score = 0.0;
+            peer_score.banned_until = Some(round + self.config.ban_rounds);
+            log::warn!(
+                "Peer {} is banned for {} rounds.",
+                peer,
+                self.config.ban_rounds
+            );
+        }
+    }
+
+    pub fn is_banned(&self, peer: &SignerID) -> bool {
+        let scores = self.scores.lock().unwrap();
+        match scores.peers.get(peer).and_then(|p| p.banned_until) {
+            Some(banned_until) => scores.round <= banned_until,
+            None => false,
+        }
+    }
+
+    /// Counts the rounds for the ban. This is called at the start of each round.
+    pub fn new_round(&self) {
+        let mut scores = self.scores.lock().unwrap();
+        scores.round += 1;
+        scores.senders.clear();
+    }
+
+    pub fn status(&self) -> Vec<PeerStatus> {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> Vec<PeerStatus> {
+        let scores = self.scores.lock().unwrap();
+        scores
+            .peers
+            .iter()
+            .map(|(signer_id, peer_score)| PeerStatus {
+                signer_id: *signer_id,
+                score: peer_score.score_at(now, self.config.decay_half_life),
+                banned_rounds: match peer_score.banned_until {
+                    Some(banned_until) if scores.round <= banned_until => {
+                        banned_until - scores.round + 1
+                    }
+                    _ => 0,
+                },
+                faults: peer_score.faults.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper::keys::TEST_KEYS;
+
+    fn peer(index: usize) -> SignerID {
+        SignerID::new(TEST_KEYS.pubkeys()[index])
+    }
+
+    fn scores(ban_rounds: u64) -> PeerScores {
+        PeerScores::new(ScoringConfig {
+            ban_threshold: 100.0,
+            ban_rounds,
+            decay_half_life: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn test_decay() {
+        let scores = scores(2);
+        scores.observe_message(&peer(0), true);
+        let now = Instant::now();
+        scores.report_at(&peer(0), Misbehavior::InvalidCandidate, now);
+
+        let status = scores.status_at(now + Duration::from_secs(60));
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].signer_id, peer(0));
+        assert!((status[0].score - 25.0).abs() < 1e-6);
+        assert_eq!(status[0].banned_rounds, 0);
+        assert_eq!(
+            status[0].faults.get(&Misbehavior::InvalidCandidate),
+            Some(&1)
+        );
+
+        // The decayed score is not enough to ban the peer.
+        scores.report_at(
+            &peer(0),
+            Misbehavior::InvalidCandidate,
+            now + Duration::from_secs(60),
+        );
+        assert!(!scores.is_banned(&peer(0)));
+    }
+
+    #[test]
+    fn test_ban() {
+        let scores = scores(2);
+        scores.observe_message(&peer(0), true);
+        let now = Instant::now();
+        scores.report_at(&peer(0), Misbehavior::InvalidLocalSig, now);
+        assert!(!scores.is_banned(&peer(0)));
+        scores.report_at(&peer(0), Misbehavior::InvalidBlockVss, now);
+        assert!(scores.is_banned(&peer(0)));
+        assert!(!scores.is_banned(&peer(1)));
+        assert_eq!(scores.status_at(now)[0].banned_rounds, 3);
+
+        // The peer is banned in the current round and the following 2 rounds.
+        scores.new_round();
+        scores.new_round();
+        assert!(scores.is_banned(&peer(0)));
+        assert_eq!(scores.status_at(now)[0].banned_rounds, 1);
+        scores.new_round();
+        assert!(!scores.is_banned(&peer(0)));
+        assert_eq!(scores.status_at(now)[0].banned_rounds, 0);
+        assert_eq!(scores.status_at(now)[0].score, 0.0);
+    }
+
+    #[test]
+    fn test_equivocation_bans_at_once() {
+        let scores = scores(0);
+        scores.observe_message(&peer(1), true);
+        scores.report(&peer(1), Misbehavior::Equivocation);
+        assert!(scores.is_banned(&peer(1)));
+        scores.new_round();
+        assert!(!scores.is_banned(&peer(1)));
+    }
+
+    #[test]
+    fn test_not_score_unsigned_messages() {
+        let scores = scores(2);
+        // The fault of the peer who didn't send any message in the round is not scored either.
+        scores.report(&peer(0), Misbehavior::Equivocation);
+        assert!(scores.status().is_empty());
+
+        scores.observe_message(&peer(0), true);
+        scores.observe_message(&peer(0), false);
+        scores.report(&peer(0), Misbehavior::Equivocation);
+        assert!(!scores.is_banned(&peer(0)));
+        assert!(scores.status().is_empty());
+
+        // The next round starts without the unsigned message.
+        scores.new_round();
+        scores.observe_message(&peer(0), true);
+        scores.report(&peer(0), Misbehavior::Equivocation);
+        assert!(scores.is_banned(&peer(0)));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let scores = PeerScores::disabled();
+        scores.observe_message(&peer(0), true);
+        scores.report(&peer(0), Misbehavior::Equivocation);
+        assert!(!scores.is_banned(&peer(0)));
+        assert!(scores.status().is_empty());
+    }
+}
@@ -78,5 +78,5 @@ pub fn get_block(index: u8) -> Block {
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
         ],
     };
-    Block::new(bytes)
+    Block::new(bytes).unwrap()
 }
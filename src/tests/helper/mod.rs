@@ -40,6 +40,7 @@ pub fn address(private_key: &PrivateKey) -> Address {
 pub mod test_vectors {
     use crate::blockdata::Block;
     use crate::crypto::multi_party_schnorr::LocalSig;
+    use crate::crypto::secret::Secret;
     use crate::crypto::vss::Vss;
     use crate::federation::{Federation, Federations};
     use crate::net::SignerID;
@@ -115,14 +116,14 @@ pub mod test_vectors {
     ///     "y": "eb2ee580a7a6c3abda257d16adac311a7d6fda959026423225493d663fd14cb3"
     ///   }
     /// }
-    pub fn to_block_shared_keys(v: &Value) -> Option<(bool, FE, GE)> {
+    pub fn to_block_shared_keys(v: &Value) -> Option<(bool, Secret<FE>, GE)> {
         if v.is_null() {
             None
         } else {
             let is_positive = v["positive"].as_bool().unwrap();
             let x_i = to_fe(&v["x_i"]);
             let y = to_point(&v["y"]);
-            Some((is_positive, x_i, y))
+            Some((is_positive, x_i.into(), y))
         }
     }
 
@@ -131,7 +132,7 @@ pub mod test_vectors {
             None
         } else {
             let hex = hex::decode(block.as_str().unwrap()).unwrap();
-            let block = Block::new(hex);
+            let block = Block::new(hex).unwrap();
             Some(block)
         }
     }
@@ -1,4 +1,4 @@
-use crate::net::{ConnectionManager, ConnectionManagerError, Message, SignerID};
+use crate::net::{ConnectionManager, ConnectionManagerError, Envelope, Message, SignerID};
 use redis::ControlFlow;
 use std::cell::RefCell;
 use std::sync::mpsc::Receiver;
@@ -59,7 +59,7 @@ impl ConnectionManager for TestConnectionManager {
 
     fn start(
         &self,
-        _message_processor: impl FnMut(Message) -> ControlFlow<()> + Send + 'static,
+        _message_processor: impl FnMut(Envelope) -> ControlFlow<()> + Send + 'static,
         _id: SignerID,
     ) -> JoinHandle<()> {
         // do nothing.
@@ -5,6 +5,7 @@ use crate::tests::helper::keys::TEST_KEYS;
 use crate::tests::helper::node_vss::node_vss;
 use crate::tests::helper::rpc::MockRpc;
 use bitcoin::{Address, PublicKey};
+use std::time::Duration;
 
 pub struct NodeParametersBuilder {
     rpc: Option<MockRpc>,
@@ -43,6 +44,8 @@ impl NodeParametersBuilder {
             self.skip_waiting_ibd,
             self.federations.clone(),
         )
+        // Check the tip only once not to wait in the tests.
+        .with_confirmation_timeout(Duration::from_secs(0))
     }
 
     pub fn public_key(&mut self, public_key: PublicKey) -> &mut Self {
@@ -18,6 +18,8 @@ impl BuilderForTest for Master {
             HashSet::new(),
             false,
             0,
+            BTreeMap::new(),
+            BTreeMap::new(),
         )
     }
 }
@@ -32,6 +34,7 @@ impl BuilderForTest for Member {
             HashSet::new(),
             0,
             0,
+            BTreeMap::new(),
         )
     }
 }
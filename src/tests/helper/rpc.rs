@@ -1,4 +1,4 @@
-use crate::blockdata::Block;
+use crate::blockdata::{Block, BlockHeader};
 use crate::errors::Error;
 use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
 use bitcoin::Address;
@@ -38,6 +38,8 @@ pub struct MockRpc {
     getblockchaininfo_results: RefCell<VecDeque<GetBlockchainInfoResult>>,
     testproposedblock_results: RefCell<VecDeque<Result<bool, Error>>>,
     submitblock_results: RefCell<VecDeque<Result<(), Error>>>,
+    getblockhash_results: RefCell<VecDeque<Result<String, Error>>>,
+    getblockheader_results: RefCell<VecDeque<Result<BlockHeader, Error>>>,
 }
 
 impl MockRpc {
@@ -47,6 +49,8 @@ impl MockRpc {
             getblockchaininfo_results: RefCell::new(VecDeque::new()),
             testproposedblock_results: RefCell::new(VecDeque::new()),
             submitblock_results: RefCell::new(VecDeque::new()),
+            getblockhash_results: RefCell::new(VecDeque::new()),
+            getblockheader_results: RefCell::new(VecDeque::new()),
         }
     }
 
@@ -67,6 +71,14 @@ impl MockRpc {
             self.submitblock_results.borrow().is_empty(),
             "submitblock RPC should be called once or more, but not."
         );
+        assert!(
+            self.getblockhash_results.borrow().is_empty(),
+            "getblockhash RPC should be called once or more, but not."
+        );
+        assert!(
+            self.getblockheader_results.borrow().is_empty(),
+            "getblockheader RPC should be called once or more, but not."
+        );
     }
 
     pub fn should_call_getnewblock(&mut self, result: Result<Block, Error>) {
@@ -98,6 +110,29 @@ impl MockRpc {
         list.push_front(result);
     }
 
+    pub fn should_call_getblockhash(&mut self, result: Result<String, Error>) {
+        let mut list = self.getblockhash_results.borrow_mut();
+        list.push_front(result);
+    }
+
+    pub fn should_call_getblockheader(&mut self, result: Result<BlockHeader, Error>) {
+        let mut list = self.getblockheader_results.borrow_mut();
+        list.push_front(result);
+    }
+
+    /// Set stubs which return the block as the tip of the chain.
+    pub fn should_return_tip(&mut self, block: &Block) {
+        self.should_call_getblockchaininfo(Ok(GetBlockchainInfoResult {
+            chain: "".to_string(),
+            blocks: 0,
+            headers: 0,
+            bestblockhash: "".to_string(),
+            mediantime: 0,
+            initialblockdownload: false,
+        }));
+        self.should_call_getblockheader(Ok(block.header().clone()));
+    }
+
     pub fn should_call_testproposedblock_and_returns_invalid_block_error(&mut self) {
         let err = Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
             code: -25,
@@ -141,4 +176,20 @@ impl TapyrusApi for MockRpc {
             .expect("Unexpected RPC call method=getblockchaininfo");
         Ok(result)
     }
+
+    fn getblockhash(&self, height: u64) -> Result<String, Error> {
+        let mut list = self.getblockhash_results.borrow_mut();
+        list.pop_back().expect(&format!(
+            "Unexpected RPC call method=getblockhash, args(height={})",
+            height
+        ))
+    }
+
+    fn getblockheader(&self, hash: &str) -> Result<BlockHeader, Error> {
+        let mut list = self.getblockheader_results.borrow_mut();
+        list.pop_back().expect(&format!(
+            "Unexpected RPC call method=getblockheader, args(hash={})",
+            hash
+        ))
+    }
 }
@@ -1,4 +1,5 @@
-use curv::{BigInt, GE};
+use curv::elliptic::curves::traits::ECScalar;
+use curv::{BigInt, FE, GE};
 use std::convert::TryFrom;
 use std::os::raw::c_int;
 use std::sync::atomic::AtomicUsize;
@@ -11,6 +12,40 @@ pub fn sum_point(points: &Vec<GE>) -> GE {
     tail.fold(head.clone(), |acc, x| acc + x)
 }
 
+/// Returns the Lagrange coefficient of the party at `index` in `indices` to interpolate the value
+/// at `x`. Indices are x coordinates of the shares, and `x` must not be in `indices`.
+pub fn lagrange_coefficient(index: usize, indices: &[usize], x: usize) -> FE {
+    let one: FE = ECScalar::from(&BigInt::from(1));
+    let (numerator, denominator) = indices
+        .iter()
+        .filter(|j| **j != index)
+        .fold((one, one), |(num, den), j| {
+            (num * scalar_of_diff(x, *j), den * scalar_of_diff(index, *j))
+        });
+    numerator * denominator.invert()
+}
+
+/// Split the secret into `count` random pieces whose sum is the secret.
+pub fn split_secret(secret: FE, count: usize) -> Vec<FE> {
+    let mut pieces: Vec<FE> = (1..count).map(|_| ECScalar::new_random()).collect();
+    let last = pieces
+        .iter()
+        .fold(secret, |acc, piece| acc.sub(&piece.get_element()));
+    pieces.push(last);
+    pieces
+}
+
+/// Returns `a - b` as a scalar. `a` and `b` must be different.
+fn scalar_of_diff(a: usize, b: usize) -> FE {
+    assert_ne!(a, b);
+    let diff = if a > b {
+        BigInt::from((a - b) as u64)
+    } else {
+        FE::q() - BigInt::from((b - a) as u64)
+    };
+    ECScalar::from(&diff)
+}
+
 pub fn jacobi(a: &BigInt, n: &BigInt) -> i8 {
     assert!(*n >= BigInt::from(3));
     assert!(a < n);
@@ -117,6 +152,22 @@ mod tests {
         assert_eq!(sum, p6);
     }
 
+    #[test]
+    fn test_lagrange_coefficient() {
+        use curv::elliptic::curves::traits::ECScalar;
+
+        // f(x) = 3 + 2x. f(1) = 5, f(2) = 7, f(4) = 11
+        let f1: FE = ECScalar::from(&BigInt::from(5));
+        let f2: FE = ECScalar::from(&BigInt::from(7));
+        let f4 =
+            f1 * lagrange_coefficient(1, &[1, 2], 4) + &(f2 * lagrange_coefficient(2, &[1, 2], 4));
+        assert_eq!(f4.to_big_int(), BigInt::from(11));
+
+        let f0 =
+            f1 * lagrange_coefficient(1, &[1, 2], 0) + &(f2 * lagrange_coefficient(2, &[1, 2], 0));
+        assert_eq!(f0.to_big_int(), BigInt::from(3));
+    }
+
     #[test]
     fn test_jacobi() {
         assert_eq!(jacobi(&BigInt::from(158), &BigInt::from(235)), -1);